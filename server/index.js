@@ -59,6 +59,14 @@ const cos = new COS({
   SecretKey: ossConfig.secretKey,
 });
 
+// 服务端版本号（打包后读取不到 package.json 时为 null）
+let SERVER_VERSION = null;
+try {
+  SERVER_VERSION = JSON.parse(fs.readFileSync(new URL('./package.json', import.meta.url), 'utf8')).version;
+} catch {
+  SERVER_VERSION = null;
+}
+
 /**
  * 健康检查接口
  */
@@ -66,10 +74,23 @@ app.get('/api/health', (req, res) => {
   res.json({
     status: 'ok',
     message: 'AIYOU Backend Server is running',
+    version: SERVER_VERSION,
     timestamp: new Date().toISOString()
   });
 });
 
+/**
+ * 版本信息接口（桌面端据此识别并接管上次遗留的服务进程）
+ */
+app.get('/api/version', (req, res) => {
+  res.set('X-Aiyou-Server', SERVER_VERSION || 'unknown');
+  res.json({
+    name: 'aiyou-server',
+    version: SERVER_VERSION,
+    pid: process.pid
+  });
+});
+
 /**
  * OSS 文件上传接口
  * POST /api/upload-oss
@@ -1779,9 +1800,9 @@ app.get('/api/logs/stats', async (req, res) => {
 const __filename = fileURLToPath(import.meta.url);
 const __dirname = path.dirname(__filename);
 
-// 视频存储目录
-const VIDEOS_DIR = path.join(__dirname, '../videos');
-const VIDEO_DB_FILE = path.join(__dirname, '../videos/database.json');
+// 视频存储目录（桌面端通过 AIYOU_VIDEOS_DIR 指定）
+const VIDEOS_DIR = process.env.AIYOU_VIDEOS_DIR || path.join(__dirname, '../videos');
+const VIDEO_DB_FILE = path.join(VIDEOS_DIR, 'database.json');
 
 // 确保目录存在
 if (!fs.existsSync(VIDEOS_DIR)) {
@@ -0,0 +1,524 @@
+//! Thin proxies for sidecar endpoints, so endpoint paths and response
+//! shapes live in one place on the Rust side.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::error::ServerError;
+use crate::server::{self, ServerState, ServerStatus, ServerSubcommand};
+use crate::subtitles::TimedLine;
+
+pub(crate) const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Providers can take a while to accept a generation.
+pub(crate) const GENERATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+async fn get_json<T: DeserializeOwned>(state: &ServerState, path: &str) -> Result<T, ServerError> {
+    server::ensure_unfrozen(state)?;
+    let resp = state
+        .client()
+        .get(state.url(path))
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(ServerError::BadStatus(resp.status().as_u16()));
+    }
+    Ok(resp.json().await?)
+}
+
+async fn post(state: &ServerState, path: &str) -> Result<(), ServerError> {
+    server::ensure_unfrozen(state)?;
+    let resp = state
+        .client()
+        .post(state.url(path))
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(ServerError::BadStatus(resp.status().as_u16()));
+    }
+    Ok(())
+}
+
+async fn post_json<B: Serialize, T: DeserializeOwned>(
+    state: &ServerState,
+    path: &str,
+    body: &B,
+) -> Result<T, ServerError> {
+    server::ensure_unfrozen(state)?;
+    let resp = state
+        .client()
+        .post(state.url(path))
+        .json(body)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(ServerError::BadStatus(resp.status().as_u16()));
+    }
+    Ok(resp.json().await?)
+}
+
+/// Any request, for `proxy::api_request`. An empty body comes back as
+/// `null`.
+pub async fn request(
+    state: &ServerState,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&serde_json::Value>,
+    timeout: Duration,
+) -> Result<serde_json::Value, ServerError> {
+    server::ensure_unfrozen(state)?;
+    let mut request = state.client().request(method, state.url(path)).timeout(timeout);
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    let resp = request.send().await?;
+    if !resp.status().is_success() {
+        return Err(ServerError::BadStatus(resp.status().as_u16()));
+    }
+    let bytes = resp.bytes().await?;
+    if bytes.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+    serde_json::from_slice(&bytes).map_err(|e| ServerError::InvalidResponse(e.to_string()))
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HealthInfo {
+    /// Sidecar version, if it reports one.
+    pub version: Option<String>,
+    /// Whether the server dedupes requests carrying the same
+    /// `Idempotency-Key`.
+    pub idempotency_keys: bool,
+}
+
+pub async fn get_health(state: &ServerState) -> Result<HealthInfo, ServerError> {
+    get_json(state, "/api/health").await
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct QueueStatus {
+    /// Jobs currently generating.
+    pub active: u32,
+    /// Jobs waiting for a slot.
+    pub queued: u32,
+    /// Server's estimate until a newly submitted job would start.
+    pub estimated_wait_secs: Option<u64>,
+}
+
+pub async fn queue_status(state: &ServerState) -> Result<QueueStatus, ServerError> {
+    get_json(state, "/api/queue").await
+}
+
+#[tauri::command]
+pub async fn get_queue_status(
+    state: tauri::State<'_, ServerState>,
+) -> Result<QueueStatus, ServerError> {
+    queue_status(&state).await
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CacheClearResult {
+    /// `None` if the server didn't say.
+    pub freed_bytes: Option<u64>,
+}
+
+/// Clears the server's generation cache: through the running server if it
+/// is up, otherwise with the `--clear-cache` subcommand. Refuses with
+/// `Busy` while jobs are running or queued.
+#[tauri::command]
+pub async fn clear_server_cache(app: AppHandle) -> Result<CacheClearResult, ServerError> {
+    let state = app.state::<ServerState>();
+    match state.status() {
+        ServerStatus::Ready => {
+            let queue = queue_status(&state).await?;
+            if queue.active > 0 || queue.queued > 0 {
+                return Err(ServerError::Busy);
+            }
+            post_json(&state, "/api/cache/clear", &serde_json::json!({})).await
+        }
+        ServerStatus::Stopped | ServerStatus::Failed | ServerStatus::Crashed => {
+            let output = server::run_subcommand(&app, ServerSubcommand::ClearCache).await?;
+            if output.exit_code != Some(0) {
+                return Err(ServerError::InvalidResponse(output.stderr.trim().to_owned()));
+            }
+            // The subcommand reports its result as a JSON line on stdout.
+            let result = output
+                .stdout
+                .lines()
+                .rev()
+                .find_map(|line| serde_json::from_str(line).ok())
+                .unwrap_or_default();
+            Ok(result)
+        }
+        // Mid-start, mid-stop or unresponsive: the server may still be
+        // touching the cache.
+        ServerStatus::Starting
+        | ServerStatus::Stopping
+        | ServerStatus::Unreachable
+        | ServerStatus::Loading
+        | ServerStatus::Unhealthy => Err(ServerError::Busy),
+    }
+}
+
+/// Pauses or resumes the sidecar's render queue.
+pub async fn set_queue_paused(state: &ServerState, paused: bool) -> Result<(), ServerError> {
+    let path = if paused {
+        "/api/queue/pause"
+    } else {
+        "/api/queue/resume"
+    };
+    post(state, path).await
+}
+
+/// Levels the sidecar's logger knows.
+const SERVER_LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+#[derive(Deserialize)]
+struct ServerLogLevel {
+    level: String,
+}
+
+/// A 404 from `/api/loglevel` means a server build that can't change its
+/// level at runtime.
+fn log_level_error(e: ServerError) -> ServerError {
+    match e {
+        ServerError::BadStatus(404) => {
+            ServerError::Unsupported("changing its log level at runtime".into())
+        }
+        e => e,
+    }
+}
+
+/// The sidecar's current log level.
+#[tauri::command]
+pub async fn get_server_log_level(
+    state: tauri::State<'_, ServerState>,
+) -> Result<String, ServerError> {
+    let current: ServerLogLevel = get_json(&state, "/api/loglevel")
+        .await
+        .map_err(log_level_error)?;
+    Ok(current.level)
+}
+
+/// Changes the sidecar's log level without a restart. `level` is one of
+/// `SERVER_LOG_LEVELS`.
+#[tauri::command]
+pub async fn set_server_log_level(
+    state: tauri::State<'_, ServerState>,
+    level: String,
+) -> Result<(), ServerError> {
+    let level = level.trim().to_ascii_lowercase();
+    if !SERVER_LOG_LEVELS.contains(&level.as_str()) {
+        return Err(ServerError::Logging(format!(
+            "{} (expected one of {})",
+            level,
+            SERVER_LOG_LEVELS.join(", ")
+        )));
+    }
+    let body = serde_json::json!({ "level": level });
+    request(&state, reqwest::Method::POST, "/api/loglevel", Some(&body), REQUEST_TIMEOUT)
+        .await
+        .map_err(log_level_error)?;
+    tracing::info!(%level, "Server log level changed");
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderRequest {
+    /// Absolute path to the `.aiyou` project file.
+    pub project_path: String,
+    /// Directory the finished episodes are written to.
+    pub out_dir: String,
+    /// Render every episode rather than only the ones marked for export.
+    pub all: bool,
+    /// Set to force the watermark; the server decides otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RenderSubmission {
+    /// Ids of the jobs the request was split into.
+    pub jobs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobState {
+    #[default]
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RenderJob {
+    pub id: String,
+    pub status: JobState,
+    /// Percent complete, 0 to 100.
+    pub progress: Option<f64>,
+    /// Output file once the job is done.
+    pub output: Option<String>,
+    pub error: Option<String>,
+    /// What its generations used, once done, for servers that report it.
+    pub usage: Option<serde_json::Value>,
+}
+
+/// Submits a render. Takes any body so queued job specs from the frontend
+/// can be passed through as-is.
+pub async fn submit_render<B: Serialize>(
+    state: &ServerState,
+    request: &B,
+) -> Result<RenderSubmission, ServerError> {
+    post_json(state, "/api/render", request).await
+}
+
+/// A generation request that failed, and whether it can have reached the
+/// server and created a job there.
+#[derive(Debug)]
+pub struct GenerationFailed {
+    pub error: ServerError,
+    /// False only when no connection was made.
+    pub sent: bool,
+}
+
+/// Submits a generation to one of the endpoints in `generation_schema`,
+/// passing the provider key the way the sidecar expects it.
+/// `idempotency_key` marks retries of the same generation. Returns the
+/// provider's response as the sidecar relays it.
+pub async fn create_generation(
+    state: &ServerState,
+    endpoint: &str,
+    api_key: Option<&str>,
+    idempotency_key: &str,
+    body: &serde_json::Value,
+) -> Result<serde_json::Value, GenerationFailed> {
+    let sent = |error: ServerError| GenerationFailed { error, sent: true };
+    server::ensure_unfrozen(state).map_err(|error| GenerationFailed { error, sent: false })?;
+    let mut request = state
+        .client()
+        .post(state.url(endpoint))
+        .header("Idempotency-Key", idempotency_key)
+        .json(body)
+        .timeout(GENERATION_TIMEOUT);
+    if let Some(key) = api_key {
+        request = request.header("X-API-Key", key);
+    }
+    let resp = request.send().await.map_err(|e| GenerationFailed {
+        sent: !e.is_connect(),
+        error: e.into(),
+    })?;
+    if !resp.status().is_success() {
+        return Err(sent(ServerError::BadStatus(resp.status().as_u16())));
+    }
+    resp.json().await.map_err(|e| sent(e.into()))
+}
+
+/// `/api/jobs/<job_id><rest>`, with the id percent-encoded so it can't
+/// reach another route. Ids come from the frontend as well as the server.
+fn job_path(job_id: &str, rest: &str) -> Result<String, ServerError> {
+    if matches!(job_id, "" | "." | "..") {
+        return Err(ServerError::InvalidArgument(format!("invalid job id: {:?}", job_id)));
+    }
+    let mut path = String::from("/api/jobs/");
+    for byte in job_id.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                path.push(byte as char)
+            }
+            _ => path.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    path.push_str(rest);
+    Ok(path)
+}
+
+pub async fn get_job(state: &ServerState, job_id: &str) -> Result<RenderJob, ServerError> {
+    get_json(state, &job_path(job_id, "")?).await
+}
+
+pub async fn cancel_job(state: &ServerState, job_id: &str) -> Result<(), ServerError> {
+    post(state, &job_path(job_id, "/cancel")?).await
+}
+
+#[derive(Deserialize)]
+struct JobScript {
+    script: Option<String>,
+}
+
+/// Drama script a job generated alongside its video, if it has one.
+pub async fn get_job_script(
+    state: &ServerState,
+    job_id: &str,
+) -> Result<Option<String>, ServerError> {
+    let job: JobScript = get_json(state, &job_path(job_id, "/script")?).await?;
+    Ok(job.script)
+}
+
+/// `{ "success": true, "data": ... }` wrapper used by the project routes.
+#[derive(Deserialize)]
+struct Envelope<T> {
+    data: T,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectSummary {
+    pub id: String,
+}
+
+pub async fn list_projects(state: &ServerState) -> Result<Vec<ProjectSummary>, ServerError> {
+    let envelope: Envelope<_> = get_json(state, "/api/projects").await?;
+    Ok(envelope.data)
+}
+
+/// Full project export: the project row plus its nodes, connections and
+/// groups, with media records but not the media itself.
+pub async fn get_project(
+    state: &ServerState,
+    project_id: &str,
+) -> Result<serde_json::Value, ServerError> {
+    let envelope: Envelope<_> = get_json(state, &format!("/api/projects/{}", project_id)).await?;
+    Ok(envelope.data)
+}
+
+/// Every dialogue line of a project with its aligned TTS timing.
+pub async fn get_timed_lines(
+    state: &ServerState,
+    project_id: &str,
+) -> Result<Vec<TimedLine>, ServerError> {
+    let path = format!("/api/projects/{}/timeline", project_id);
+    let envelope: Envelope<_> = get_json(state, &path).await?;
+    Ok(envelope.data)
+}
+
+/// Content and usage figures the sidecar keeps for a project.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ServerProjectStats {
+    pub scene_count: u32,
+    pub rendered_duration_secs: f64,
+    pub dialogue_words: u64,
+    /// API calls per provider, from the usage log.
+    pub api_calls: BTreeMap<String, u64>,
+    /// Estimated tokens per provider.
+    pub estimated_tokens: BTreeMap<String, u64>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// Absolute directory holding the project's media, if it has one.
+    pub media_dir: Option<String>,
+}
+
+pub async fn get_project_stats(
+    state: &ServerState,
+    project_id: &str,
+) -> Result<ServerProjectStats, ServerError> {
+    let path = format!("/api/projects/{}/stats", project_id);
+    let envelope: Envelope<_> = get_json(state, &path).await?;
+    Ok(envelope.data)
+}
+
+/// Where the sidecar keeps synthesized speech, and which of those files
+/// current scenes use.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TtsCacheInfo {
+    /// Absolute directory, laid out as
+    /// `<provider>/<voice>/<project>/<line>/<file>`.
+    pub dir: String,
+    /// Referenced files, relative to `dir` with `/` separators.
+    #[serde(default)]
+    pub referenced: Vec<String>,
+}
+
+pub async fn get_tts_cache(state: &ServerState) -> Result<TtsCacheInfo, ServerError> {
+    let envelope: Envelope<_> = get_json(state, "/api/tts/cache").await?;
+    Ok(envelope.data)
+}
+
+/// An AI provider configured in the sidecar.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderInfo {
+    pub name: String,
+    pub base_url: Option<String>,
+}
+
+pub async fn get_providers(state: &ServerState) -> Result<Vec<ProviderInfo>, ServerError> {
+    let envelope: Envelope<_> = get_json(state, "/api/providers").await?;
+    Ok(envelope.data)
+}
+
+/// Providers are probed one after another and each may take its own
+/// timeout to answer.
+const PROVIDER_CHECK_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Whether a provider accepted the key the sidecar has for it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthStatus {
+    Valid,
+    Invalid,
+    /// No key is configured.
+    Missing,
+    /// Not checked, or the provider couldn't be reached to check.
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+/// One provider as the sidecar found it. The key itself is never sent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderCheck {
+    pub name: String,
+    pub reachable: bool,
+    #[serde(default)]
+    pub auth: AuthStatus,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Has the sidecar call each configured provider with its key. Older
+/// server builds answer 404.
+pub async fn check_providers(state: &ServerState) -> Result<Vec<ProviderCheck>, ServerError> {
+    let body = serde_json::json!({});
+    let resp = request(
+        state,
+        reqwest::Method::POST,
+        "/api/providers/check",
+        Some(&body),
+        PROVIDER_CHECK_TIMEOUT,
+    )
+    .await?;
+    let envelope: Envelope<_> =
+        serde_json::from_value(resp).map_err(|e| ServerError::InvalidResponse(e.to_string()))?;
+    Ok(envelope.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_ids_stay_in_their_path_segment() {
+        assert_eq!(job_path("job-42_a", "/cancel").unwrap(), "/api/jobs/job-42_a/cancel");
+        assert_eq!(job_path("../projects", "").unwrap(), "/api/jobs/..%2Fprojects");
+        assert_eq!(job_path("a?b#c", "/script").unwrap(), "/api/jobs/a%3Fb%23c/script");
+        assert_eq!(job_path("镜头", "").unwrap(), "/api/jobs/%E9%95%9C%E5%A4%B4");
+        for id in ["", ".", ".."] {
+            assert!(matches!(job_path(id, ""), Err(ServerError::InvalidArgument(_))));
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Version and environment details for the About dialog and bug reports.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Url};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::api;
+use crate::assets::{AssetsState, VerifySummary};
+use crate::config::ServerMode;
+use crate::error::ServerError;
+use crate::hardware::{self, HardwareReport};
+use crate::redact;
+use crate::server::{ServerState, ServerStatus};
+
+/// Variables the sidecar and its providers read secrets from. Always
+/// listed, so a missing one shows up as `false`.
+const KNOWN_SECRET_VARS: &[&str] = &[
+    "API_KEY",
+    "GEMINI_API_KEY",
+    "OSS_SECRET_ID",
+    "OSS_SECRET_KEY",
+    "DB_PASSWORD",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppInfo {
+    pub app_version: String,
+    pub tauri_version: String,
+    /// Reported by the sidecar's health endpoint; `None` while it's down.
+    pub sidecar_version: Option<String>,
+    pub ffmpeg_version: Option<String>,
+    pub os_name: String,
+    pub os_version: String,
+    pub arch: String,
+    pub data_dir: Option<String>,
+    pub config_dir: Option<String>,
+    pub log_dir: Option<String>,
+    /// Port of the running server; `None` while it's down.
+    pub server_port: Option<u16>,
+    pub server_mode: ServerMode,
+    /// Set by `AIYOU_SERVER_MODE` or a flag rather than the config.
+    pub server_mode_overridden: bool,
+    /// `"dev"` or `"release"`.
+    pub build: String,
+    /// Result of the last `verify_all_assets` this session.
+    pub last_verification: Option<VerifySummary>,
+    /// `None` until the startup probe has finished.
+    pub hardware: Option<HardwareReport>,
+}
+
+/// What support needs to triage a report. Secrets appear only as whether
+/// they are set, never by value.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentInfo {
+    pub app_version: String,
+    pub os_name: String,
+    pub os_version: String,
+    pub arch: String,
+    pub server_mode: ServerMode,
+    /// Executable the server is started from; `None` in external mode.
+    pub server_program: Option<String>,
+    pub server_program_found: bool,
+    pub server_host: Option<String>,
+    pub server_port: u16,
+    pub profile: String,
+    /// Secret-looking environment variables and whether each is set.
+    pub secrets_present: BTreeMap<String, bool>,
+}
+
+/// Versions that take a request or a process to find out, looked up on
+/// first use.
+#[derive(Default)]
+pub struct AppInfoState {
+    sidecar_version: Mutex<Option<String>>,
+    ffmpeg_version: OnceCell<Option<String>>,
+}
+
+/// First line of `ffmpeg -version` is `ffmpeg version <version> Copyright …`.
+async fn ffmpeg_version() -> Option<String> {
+    let output = tokio::process::Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .await
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .next()?
+        .strip_prefix("ffmpeg version ")?
+        .split_whitespace()
+        .next()
+        .map(str::to_owned)
+}
+
+async fn sidecar_version(app: &AppHandle) -> Option<String> {
+    let state = app.state::<AppInfoState>();
+    let mut cached = state.sidecar_version.lock().await;
+    if cached.is_none() {
+        let server = app.state::<ServerState>();
+        if server.status() == ServerStatus::Ready {
+            *cached = api::get_health(&server).await.ok().and_then(|h| h.version);
+        }
+    }
+    cached.clone()
+}
+
+async fn collect(app: &AppHandle) -> AppInfo {
+    let path_string = |p: tauri::Result<std::path::PathBuf>| {
+        p.ok().map(|p| p.to_string_lossy().into_owned())
+    };
+    let server = app.state::<ServerState>();
+    let running = matches!(
+        server.status(),
+        ServerStatus::Ready
+            | ServerStatus::Unreachable
+            | ServerStatus::Loading
+            | ServerStatus::Unhealthy
+    );
+    let os = os_info::get();
+
+    AppInfo {
+        app_version: app.package_info().version.to_string(),
+        tauri_version: tauri::VERSION.to_owned(),
+        sidecar_version: sidecar_version(app).await,
+        ffmpeg_version: app
+            .state::<AppInfoState>()
+            .ffmpeg_version
+            .get_or_init(ffmpeg_version)
+            .await
+            .clone(),
+        os_name: os.os_type().to_string(),
+        os_version: os.version().to_string(),
+        arch: std::env::consts::ARCH.to_owned(),
+        data_dir: path_string(app.path().app_data_dir()),
+        config_dir: path_string(app.path().app_config_dir()),
+        log_dir: path_string(app.path().app_log_dir()),
+        server_port: running.then(|| server.port()),
+        server_mode: server.mode(),
+        server_mode_overridden: server.mode_overridden(),
+        build: if cfg!(debug_assertions) { "dev" } else { "release" }.to_owned(),
+        last_verification: app.state::<AssetsState>().last_summary(),
+        hardware: hardware::cached(app),
+    }
+}
+
+/// `program` as found on `PATH`, unless it is already a path.
+fn which(program: &str) -> PathBuf {
+    let given = PathBuf::from(program);
+    if given.components().count() > 1 {
+        return given;
+    }
+    let exe = format!("{}{}", program, std::env::consts::EXE_SUFFIX);
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join(&exe))
+        .find(|p| p.is_file())
+        .unwrap_or(given)
+}
+
+/// Where the server in `mode` is started from. The bundled sidecar sits
+/// next to the app's executable.
+fn server_program(mode: &ServerMode) -> Option<PathBuf> {
+    match mode {
+        ServerMode::Bundled => {
+            let exe = tauri::utils::platform::current_exe().ok()?;
+            let name = format!("aiyou-server{}", std::env::consts::EXE_SUFFIX);
+            Some(exe.parent()?.join(name))
+        }
+        ServerMode::CustomCommand { program, .. } => Some(which(program)),
+        ServerMode::External { .. } => None,
+    }
+}
+
+/// Presence of the known secrets and of any other set variable whose name
+/// looks secret.
+fn secrets_present() -> BTreeMap<String, bool> {
+    let is_set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+    let mut present: BTreeMap<String, bool> = KNOWN_SECRET_VARS
+        .iter()
+        .map(|name| ((*name).to_owned(), is_set(name)))
+        .collect();
+    for (name, value) in std::env::vars_os() {
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if redact::is_secret_name(name) && !value.is_empty() {
+            present.insert(name.to_owned(), true);
+        }
+    }
+    present
+}
+
+fn environment(app: &AppHandle) -> EnvironmentInfo {
+    let server = app.state::<ServerState>();
+    let mode = server.mode();
+    let program = server_program(&mode);
+    let os = os_info::get();
+    EnvironmentInfo {
+        app_version: app.package_info().version.to_string(),
+        os_name: os.os_type().to_string(),
+        os_version: os.version().to_string(),
+        arch: std::env::consts::ARCH.to_owned(),
+        server_program_found: program.as_ref().is_some_and(|p| p.is_file()),
+        server_program: program.map(|p| p.to_string_lossy().into_owned()),
+        server_mode: mode,
+        server_host: Url::parse(&server.url("/"))
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_owned)),
+        server_port: server.port(),
+        profile: server.config().profile,
+        secrets_present: secrets_present(),
+    }
+}
+
+fn hardware_rows(hw: &HardwareReport) -> Vec<(&'static str, String)> {
+    let gib = |bytes: u64| format!("{:.1} GiB", bytes as f64 / (1u64 << 30) as f64);
+    let list = |items: Vec<String>| {
+        if items.is_empty() {
+            "none found".to_owned()
+        } else {
+            items.join(", ")
+        }
+    };
+    vec![
+        (
+            "GPUs",
+            list(hw
+                .gpus
+                .iter()
+                .map(|g| match g.vram_bytes {
+                    Some(vram) => format!("{} ({})", g.name, gib(vram)),
+                    None => g.name.clone(),
+                })
+                .collect()),
+        ),
+        (
+            "Displays",
+            list(hw
+                .displays
+                .iter()
+                .map(|d| {
+                    let mut s = format!("{}x{}", d.width, d.height);
+                    if let Some(hz) = d.refresh_hz {
+                        s.push_str(&format!("@{}Hz", hz));
+                    }
+                    if d.hdr == Some(true) {
+                        s.push_str(" HDR");
+                    }
+                    s
+                })
+                .collect()),
+        ),
+        (
+            "Hardware encoders",
+            list(hw
+                .encoders
+                .iter()
+                .filter(|e| e.hardware)
+                .map(|e| e.name.clone())
+                .collect()),
+        ),
+        ("CPU cores", hw.cpu_cores.to_string()),
+        (
+            "Memory",
+            hw.total_memory_bytes.map(gib).unwrap_or_else(|| "unknown".into()),
+        ),
+    ]
+}
+
+/// Custom command arguments are left out; they may carry secrets.
+fn server_mode_row(info: &AppInfo) -> String {
+    let mode = match &info.server_mode {
+        ServerMode::Bundled => "bundled".to_owned(),
+        ServerMode::External { base_url } => format!("external ({})", base_url),
+        ServerMode::CustomCommand { program, .. } => format!("custom command ({})", program),
+    };
+    if info.server_mode_overridden {
+        format!("{}, overridden", mode)
+    } else {
+        mode
+    }
+}
+
+fn to_markdown(info: &AppInfo) -> String {
+    let or_unknown = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown".into());
+    let rows = [
+        ("App version", info.app_version.clone()),
+        ("Tauri version", info.tauri_version.clone()),
+        ("Sidecar version", or_unknown(&info.sidecar_version)),
+        ("FFmpeg version", or_unknown(&info.ffmpeg_version)),
+        ("OS", format!("{} {} ({})", info.os_name, info.os_version, info.arch)),
+        ("Data dir", or_unknown(&info.data_dir)),
+        ("Config dir", or_unknown(&info.config_dir)),
+        ("Log dir", or_unknown(&info.log_dir)),
+        (
+            "Server port",
+            info.server_port
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "not running".into()),
+        ),
+        ("Server mode", server_mode_row(info)),
+        ("Build", info.build.clone()),
+        (
+            "Model verification",
+            info.last_verification
+                .as_ref()
+                .map(|s| {
+                    format!(
+                        "{}/{} ok, {} missing, {} bad",
+                        s.ok,
+                        s.total,
+                        s.missing,
+                        s.failed.len()
+                    )
+                })
+                .unwrap_or_else(|| "not run".into()),
+        ),
+    ];
+    let hardware_rows = info.hardware.as_ref().map(hardware_rows).unwrap_or_default();
+
+    let mut out = String::from("### AIYOU diagnostics\n\n| | |\n|---|---|\n");
+    for (label, value) in rows.into_iter().chain(hardware_rows) {
+        out.push_str(&format!("| {} | `{}` |\n", label, value));
+    }
+    out
+}
+
+#[tauri::command]
+pub async fn get_app_info(app: AppHandle) -> AppInfo {
+    collect(&app).await
+}
+
+#[tauri::command]
+pub fn get_environment_info(app: AppHandle) -> EnvironmentInfo {
+    environment(&app)
+}
+
+/// Puts the same details as `get_app_info` on the clipboard as a Markdown
+/// table, ready to paste into an issue.
+#[tauri::command]
+pub async fn copy_diagnostics_to_clipboard(app: AppHandle) -> Result<(), ServerError> {
+    let markdown = to_markdown(&collect(&app).await);
+    app.clipboard()
+        .write_text(markdown)
+        .map_err(|e| ServerError::Io(e.to_string()))
+}
@@ -0,0 +1,319 @@
+//! SHA-256 verification of downloaded model files (voices and the like).
+//!
+//! Known models and their hashes are listed in the bundled
+//! `resources/models.json`, with paths relative to `app_data_dir()/models`.
+//! Files that don't match are moved to `app_data_dir()/quarantine` so they
+//! can't be loaded, with the report saying what was wrong.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::blocking;
+use crate::error::ServerError;
+use crate::time::now_ms;
+
+const MANIFEST: &str = "resources/models.json";
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Deserialize)]
+struct Manifest {
+    models: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ManifestEntry {
+    /// Relative to the models directory.
+    path: String,
+    sha256: String,
+    size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VerifyStatus {
+    Ok,
+    Missing,
+    SizeMismatch,
+    HashMismatch,
+    /// The file couldn't be read.
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResult {
+    pub path: String,
+    pub status: VerifyStatus,
+    pub expected_sha256: String,
+    pub actual_sha256: Option<String>,
+    pub expected_size: Option<u64>,
+    pub actual_size: Option<u64>,
+    /// Where the file was moved, if it was quarantined.
+    pub quarantined_to: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifySummary {
+    /// Unix time in milliseconds.
+    pub checked_at: u64,
+    pub total: usize,
+    pub ok: usize,
+    pub missing: usize,
+    /// Results other than ok and missing.
+    pub failed: Vec<VerifyResult>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyProgress<'a> {
+    path: &'a str,
+    hashed: u64,
+    size: u64,
+    /// Position of this file in a bulk check, from 1.
+    index: usize,
+    count: usize,
+}
+
+#[derive(Default)]
+pub struct AssetsState {
+    last_summary: Mutex<Option<VerifySummary>>,
+    busy: tokio::sync::Mutex<()>,
+}
+
+impl AssetsState {
+    pub fn last_summary(&self) -> Option<VerifySummary> {
+        self.last_summary.lock().unwrap().clone()
+    }
+}
+
+/// Hashes `path`, calling `progress` with the bytes hashed so far every
+/// `PROGRESS_INTERVAL`. Blocking; run it on the blocking pool.
+fn hash_file(path: &Path, mut progress: impl FnMut(u64)) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut hashed = 0;
+    let mut last_emit = Instant::now();
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        hashed += n as u64;
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            last_emit = Instant::now();
+            progress(hashed);
+        }
+    }
+    progress(hashed);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Checks one file against its expected hash (and size, which is cheaper
+/// and checked first). Doesn't move anything.
+async fn check(
+    app: &AppHandle,
+    path: &Path,
+    expected_sha256: &str,
+    expected_size: Option<u64>,
+    (index, count): (usize, usize),
+) -> VerifyResult {
+    let display = path.to_string_lossy().into_owned();
+    let mut result = VerifyResult {
+        path: display.clone(),
+        status: VerifyStatus::Ok,
+        expected_sha256: expected_sha256.trim().to_ascii_lowercase(),
+        actual_sha256: None,
+        expected_size,
+        actual_size: None,
+        quarantined_to: None,
+        error: None,
+    };
+    let size = match tokio::fs::metadata(path).await {
+        Ok(meta) => meta.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            result.status = VerifyStatus::Missing;
+            return result;
+        }
+        Err(e) => {
+            result.status = VerifyStatus::Error;
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    result.actual_size = Some(size);
+    if expected_size.is_some_and(|expected| expected != size) {
+        result.status = VerifyStatus::SizeMismatch;
+        return result;
+    }
+
+    let handle = app.clone();
+    let owned = path.to_owned();
+    let hashed = blocking::run(move || {
+        hash_file(&owned, |hashed| {
+            let _ = handle.emit(
+                "asset-verify-progress",
+                VerifyProgress {
+                    path: &display,
+                    hashed,
+                    size,
+                    index,
+                    count,
+                },
+            );
+        })
+    })
+    .await;
+    match hashed {
+        Ok(Ok(actual)) => {
+            if actual != result.expected_sha256 {
+                result.status = VerifyStatus::HashMismatch;
+            }
+            result.actual_sha256 = Some(actual);
+        }
+        Ok(Err(e)) => {
+            result.status = VerifyStatus::Error;
+            result.error = Some(e.to_string());
+        }
+        Err(e) => {
+            result.status = VerifyStatus::Error;
+            result.error = Some(e);
+        }
+    }
+    result
+}
+
+/// Moves a bad file into the quarantine directory, named so repeated
+/// quarantines of the same file don't collide.
+async fn quarantine(app: &AppHandle, result: &mut VerifyResult) {
+    let path = PathBuf::from(&result.path);
+    let moved = async {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .join("quarantine");
+        tokio::fs::create_dir_all(&dir).await?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let dest = dir.join(format!("{}-{}", now_ms(), name));
+        // A plain rename fails across filesystems; copy instead then.
+        if tokio::fs::rename(&path, &dest).await.is_err() {
+            tokio::fs::copy(&path, &dest).await?;
+            tokio::fs::remove_file(&path).await?;
+        }
+        Ok::<_, std::io::Error>(dest)
+    };
+    match moved.await {
+        Ok(dest) => {
+            warn!(
+                path = %result.path,
+                status = ?result.status,
+                dest = %dest.display(),
+                "Quarantined model file"
+            );
+            result.quarantined_to = Some(dest.to_string_lossy().into_owned());
+        }
+        Err(e) => {
+            warn!(path = %result.path, error = %e, "Failed to quarantine model file");
+            result.error = Some(format!("quarantine failed: {}", e));
+        }
+    }
+}
+
+fn needs_quarantine(status: VerifyStatus) -> bool {
+    matches!(
+        status,
+        VerifyStatus::SizeMismatch | VerifyStatus::HashMismatch
+    )
+}
+
+/// Verifies a completed download and quarantines it on a mismatch. Used by
+/// `download_file` when it is given a hash.
+pub async fn verify_download(app: &AppHandle, path: &Path, expected_sha256: &str) -> VerifyResult {
+    let mut result = check(app, path, expected_sha256, None, (1, 1)).await;
+    if needs_quarantine(result.status) {
+        quarantine(app, &mut result).await;
+    }
+    result
+}
+
+fn load_manifest(app: &AppHandle) -> Result<Manifest, ServerError> {
+    let path = app
+        .path()
+        .resolve(MANIFEST, BaseDirectory::Resource)
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    let data = std::fs::read_to_string(&path)?;
+    serde_json::from_str(&data)
+        .map_err(|e| ServerError::InvalidConfig(format!("{}: {}", path.display(), e)))
+}
+
+/// Checks one file. Mismatches are reported, not quarantined.
+#[tauri::command]
+pub async fn verify_asset(app: AppHandle, path: String, expected_sha256: String) -> VerifyResult {
+    blocking::watched("verify_asset", async move {
+        check(&app, Path::new(&path), &expected_sha256, None, (1, 1)).await
+    })
+    .await
+}
+
+/// Checks every model in the manifest, one at a time since they are large,
+/// and quarantines the ones that don't match.
+#[tauri::command]
+pub async fn verify_all_assets(app: AppHandle) -> Result<VerifySummary, ServerError> {
+    blocking::watched("verify_all_assets", async move {
+        let state = app.state::<AssetsState>();
+        let Ok(_busy) = state.busy.try_lock() else {
+            return Err(ServerError::Busy);
+        };
+        let manifest = load_manifest(&app)?;
+        let models_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| ServerError::Io(e.to_string()))?
+            .join("models");
+
+        let count = manifest.models.len();
+        let mut summary = VerifySummary {
+            checked_at: 0,
+            total: count,
+            ok: 0,
+            missing: 0,
+            failed: Vec::new(),
+        };
+        for (i, entry) in manifest.models.iter().enumerate() {
+            let path = models_dir.join(&entry.path);
+            let mut result = check(&app, &path, &entry.sha256, entry.size, (i + 1, count)).await;
+            match result.status {
+                VerifyStatus::Ok => summary.ok += 1,
+                VerifyStatus::Missing => summary.missing += 1,
+                status => {
+                    if needs_quarantine(status) {
+                        quarantine(&app, &mut result).await;
+                    }
+                    summary.failed.push(result);
+                }
+            }
+        }
+        summary.checked_at = now_ms();
+        info!(
+            total = summary.total,
+            ok = summary.ok,
+            missing = summary.missing,
+            bad = summary.failed.len(),
+            "Verified models"
+        );
+        *state.last_summary.lock().unwrap() = Some(summary.clone());
+        Ok(summary)
+    })
+    .await
+}
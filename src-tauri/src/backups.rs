@@ -0,0 +1,349 @@
+//! Scheduled project backups to `app_data_dir()/backups/<project>/<time>.zip`.
+//!
+//! Each backup holds the project export from the sidecar (`project.json`)
+//! and a copy of its SQLite database. Media files are left out.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::warn;
+
+use crate::api;
+use crate::blocking;
+use crate::crash;
+use crate::error::ServerError;
+use crate::server::{self, RestartReason, ServerState, ServerStatus};
+use crate::settings::SettingsState;
+use crate::tempfiles;
+use crate::time::now_ms;
+
+/// The sidecar's database and its WAL files, in the app data dir.
+const DB_FILES: &[&str] = &["aiyou.db", "aiyou.db-wal", "aiyou.db-shm"];
+/// Graceful-shutdown backups give up after this.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub path: String,
+    /// Unix time in milliseconds.
+    pub created_at: u64,
+    pub size: u64,
+}
+
+#[derive(Default)]
+pub struct BackupState {
+    /// Running jobs and the project they belong to, if the server said.
+    active_jobs: Mutex<HashMap<String, Option<String>>>,
+    /// Serializes backups and restores.
+    busy: tokio::sync::Mutex<()>,
+    shutdown_done: AtomicBool,
+}
+
+impl BackupState {
+    /// A job with no known project counts for every project, to be safe.
+    pub(crate) fn rendering(&self, project_id: &str) -> bool {
+        self.active_jobs
+            .lock()
+            .unwrap()
+            .values()
+            .any(|p| p.as_deref().is_none_or(|p| p == project_id))
+    }
+}
+
+pub(crate) fn valid_project_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn project_dir(app: &AppHandle, project_id: &str) -> Result<PathBuf, ServerError> {
+    if !valid_project_id(project_id) {
+        return Err(ServerError::InvalidArgument(format!("invalid project id: {}", project_id)));
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("backups").join(project_id))
+}
+
+fn list(dir: &Path) -> Vec<BackupInfo> {
+    let mut backups: Vec<BackupInfo> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let created_at = path
+                .file_name()?
+                .to_str()?
+                .strip_suffix(".zip")?
+                .parse()
+                .ok()?;
+            Some(BackupInfo {
+                size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                path: path.to_string_lossy().into_owned(),
+                created_at,
+            })
+        })
+        .collect();
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+    backups
+}
+
+fn write_zip(dest: &Path, project: &[u8], data_dir: &Path) -> Result<(), ServerError> {
+    let written = (|| -> zip::result::ZipResult<()> {
+        let mut zip = zip::ZipWriter::new(File::create(dest)?);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("project.json", options)?;
+        zip.write_all(project)?;
+        for name in DB_FILES {
+            let Ok(mut file) = File::open(data_dir.join(name)) else {
+                continue;
+            };
+            zip.start_file(*name, options)?;
+            std::io::copy(&mut file, &mut zip)?;
+        }
+        zip.finish()?;
+        Ok(())
+    })();
+    // Disk and permission problems keep their own kinds.
+    written.map_err(|e| match e {
+        zip::result::ZipError::Io(e) => e.into(),
+        e => ServerError::Backup(e.to_string()),
+    })
+}
+
+async fn backup(app: &AppHandle, project_id: &str) -> Result<BackupInfo, ServerError> {
+    let state = app.state::<BackupState>();
+    if state.rendering(project_id) {
+        return Err(ServerError::Backup("project has an active render".into()));
+    }
+    let dir = project_dir(app, project_id)?;
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    let project = api::get_project(&app.state::<ServerState>(), project_id).await?;
+    let project =
+        serde_json::to_vec_pretty(&project).map_err(|e| ServerError::Backup(e.to_string()))?;
+    let keep = app.state::<SettingsState>().get().backups_to_keep.max(1);
+
+    let created_at = now_ms();
+    let dest = dir.join(format!("{}.zip", created_at));
+    let path = dest.to_string_lossy().into_owned();
+    let part = tempfiles::create_temp_path(app, "backups", "zip")?;
+    let zipped = part.clone();
+    let written = blocking::run(move || write_zip(&zipped, &project, &data_dir))
+        .await
+        .map_err(ServerError::Backup)?;
+    if let Err(e) = written {
+        tempfiles::discard(app, &part).await;
+        return Err(e);
+    }
+    tokio::fs::create_dir_all(&dir).await?;
+    tempfiles::commit(app, &part, &dest).await?;
+    let size = blocking::run(move || -> Result<u64, ServerError> {
+        for old in list(&dir).into_iter().skip(keep) {
+            let _ = std::fs::remove_file(old.path);
+        }
+        Ok(std::fs::metadata(&dest)?.len())
+    })
+    .await
+    .map_err(ServerError::Backup)??;
+
+    Ok(BackupInfo {
+        path,
+        created_at,
+        size,
+    })
+}
+
+/// Backs up every project the server knows about, skipping ones mid-render.
+async fn backup_all(app: &AppHandle) {
+    if app.state::<ServerState>().status() != ServerStatus::Ready {
+        return;
+    }
+    let state = app.state::<BackupState>();
+    let _busy = state.busy.lock().await;
+    let projects = match api::list_projects(&app.state::<ServerState>()).await {
+        Ok(projects) => projects,
+        Err(e) => {
+            warn!(error = %e, "Backup skipped, couldn't list projects");
+            return;
+        }
+    };
+    for project in projects {
+        if let Err(e) = backup(app, &project.id).await {
+            warn!(project_id = %project.id, error = %e, "Backup skipped");
+        }
+    }
+}
+
+/// Starts the schedule once the server is first ready.
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("backups", async move {
+        let mut rx = handle.state::<ServerState>().subscribe();
+        if rx.wait_for(|s| *s == ServerStatus::Ready).await.is_err() {
+            return;
+        }
+        loop {
+            let hours = handle.state::<SettingsState>().get().backup_interval_hours;
+            if hours == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs(u64::from(hours) * 60 * 60)).await;
+            backup_all(&handle).await;
+        }
+    });
+}
+
+/// Final backup on the way out. Runs at most once and never holds up exit
+/// for long.
+pub fn on_exit(app: &AppHandle) {
+    let state = app.state::<BackupState>();
+    if state.shutdown_done.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    if app.state::<SettingsState>().get().backup_interval_hours == 0 {
+        return;
+    }
+    let finished = tauri::async_runtime::block_on(async {
+        tokio::time::timeout(SHUTDOWN_TIMEOUT, backup_all(app)).await
+    });
+    if finished.is_err() {
+        warn!("Shutdown backup timed out");
+    }
+}
+
+/// Called by the event bridge to keep track of which projects are rendering.
+pub fn on_server_event(app: &AppHandle, event: &str, payload: &serde_json::Value) {
+    let Some(job_id) = payload
+        .get("jobId")
+        .or_else(|| payload.get("id"))
+        .and_then(|v| v.as_str())
+    else {
+        return;
+    };
+    let state = app.state::<BackupState>();
+    let mut active = state.active_jobs.lock().unwrap();
+    match event {
+        "generation-progress" | "render-progress" => {
+            let project = payload
+                .get("projectId")
+                .and_then(|v| v.as_str())
+                .map(str::to_owned);
+            active.insert(job_id.to_owned(), project);
+        }
+        "generation-complete" | "generation-error" | "job-complete" | "export-finished" => {
+            active.remove(job_id);
+        }
+        _ => {}
+    }
+}
+
+#[tauri::command]
+pub fn list_backups(app: AppHandle, project_id: String) -> Result<Vec<BackupInfo>, ServerError> {
+    Ok(list(&project_dir(&app, &project_id)?))
+}
+
+#[tauri::command]
+pub async fn create_backup_now(
+    app: AppHandle,
+    project_id: String,
+) -> Result<BackupInfo, ServerError> {
+    blocking::watched("create_backup_now", async move {
+        let state = app.state::<BackupState>();
+        let _busy = state.busy.lock().await;
+        backup(&app, &project_id).await
+    })
+    .await
+}
+
+/// Restores the database from a backup. The sidecar is stopped while the
+/// files are replaced and started again afterwards; the current database
+/// is backed up first. Emits `backup-restored` when the server is back.
+#[tauri::command]
+pub async fn restore_backup(
+    app: AppHandle,
+    project_id: String,
+    backup_path: String,
+) -> Result<(), ServerError> {
+    blocking::watched("restore_backup", async move {
+        let dir = project_dir(&app, &project_id)?;
+        let backup_path = PathBuf::from(backup_path);
+        // Only backups we made for this project.
+        let inside = backup_path
+            .canonicalize()
+            .ok()
+            .zip(dir.canonicalize().ok())
+            .is_some_and(|(p, d)| p.parent() == Some(d.as_path()));
+        if !inside {
+            return Err(ServerError::Backup("not a backup of this project".into()));
+        }
+
+        let state = app.state::<BackupState>();
+        let _busy = state.busy.lock().await;
+        if app.state::<ServerState>().status() == ServerStatus::Ready {
+            if let Err(e) = backup(&app, &project_id).await {
+                warn!(error = %e, "Pre-restore backup skipped");
+            }
+        }
+
+        match server::stop(&app).await {
+            Ok(_) | Err(ServerError::NotRunning) => {}
+            Err(e) => return Err(e),
+        }
+        app.state::<ServerState>().note_restart(RestartReason::Restore);
+
+        let data_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| ServerError::Io(e.to_string()))?;
+        let restored = blocking::run(move || -> Result<(), ServerError> {
+            let mut archive = File::open(&backup_path)
+                .map_err(ServerError::from)
+                .and_then(|f| {
+                    zip::ZipArchive::new(f).map_err(|e| ServerError::Backup(e.to_string()))
+                })?;
+            if archive.by_name(DB_FILES[0]).is_err() {
+                return Err(ServerError::Backup("backup has no database".into()));
+            }
+            for name in DB_FILES {
+                let target = data_dir.join(name);
+                match archive.by_name(name) {
+                    Ok(mut entry) => {
+                        let mut out = File::create(&target)?;
+                        std::io::copy(&mut entry, &mut out)?;
+                    }
+                    // Stale WAL files from the current database must not be
+                    // replayed over the restored one.
+                    Err(_) => {
+                        let _ = std::fs::remove_file(&target);
+                    }
+                }
+            }
+            Ok(())
+        })
+        .await
+        .map_err(ServerError::Backup)?;
+
+        // Bring the server back either way.
+        server::start(&app).await?;
+        restored?;
+        let _ = app.emit("backup-restored", &project_id);
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,227 @@
+//! Caps on upload and download speed, shared by every transfer.
+//!
+//! Each direction is one token bucket: a transfer takes tokens for each
+//! chunk it moves and, once the bucket is in debt, sleeps until the debt
+//! would be paid off. Concurrent transfers draw from the same bucket, so
+//! together they stay under the cap. Nothing polls; a transfer that isn't
+//! moving data costs nothing.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+use tracing::info;
+
+use crate::error::ServerError;
+use crate::settings::SettingsState;
+
+/// Tokens an idle bucket saves up, in seconds of transfer at the cap.
+const BURST_SECS: f64 = 1.0;
+/// Span `get_transfer_stats` averages throughput over.
+const STATS_WINDOW: Duration = Duration::from_secs(2);
+/// Chunks this close together are counted as one sample.
+const SAMPLE_GRANULARITY: Duration = Duration::from_millis(100);
+
+/// Speed caps in KB/s; 0 is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BandwidthLimit {
+    pub up_kbps: u32,
+    pub down_kbps: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectionStats {
+    /// 0 is unlimited.
+    pub limit_kbps: u32,
+    /// Averaged over the last `STATS_WINDOW`, across all transfers.
+    pub bytes_per_sec: u64,
+    /// Since launch.
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferStats {
+    pub up: DirectionStats,
+    pub down: DirectionStats,
+}
+
+struct Bucket {
+    /// Bytes per second; 0 is unlimited.
+    rate: u64,
+    /// Negative while in debt.
+    tokens: f64,
+    refilled: Instant,
+    samples: VecDeque<(Instant, u64)>,
+    total: u64,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            rate: 0,
+            tokens: 0.0,
+            refilled: Instant::now(),
+            samples: VecDeque::new(),
+            total: 0,
+        }
+    }
+}
+
+impl Bucket {
+    /// Takes `bytes` worth of tokens and returns how long to wait before
+    /// moving them, if at all.
+    fn take(&mut self, bytes: u64, now: Instant) -> Option<Duration> {
+        if self.rate == 0 {
+            return None;
+        }
+        let rate = self.rate as f64;
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+        self.tokens = (self.tokens + elapsed * rate).min(rate * BURST_SECS);
+        self.tokens -= bytes as f64;
+        (self.tokens < 0.0).then(|| Duration::from_secs_f64(-self.tokens / rate))
+    }
+
+    fn record(&mut self, bytes: u64, now: Instant) {
+        self.total += bytes;
+        match self.samples.back_mut() {
+            Some((at, sum)) if now.saturating_duration_since(*at) < SAMPLE_GRANULARITY => {
+                *sum += bytes;
+            }
+            _ => self.samples.push_back((now, bytes)),
+        }
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((at, _)) = self.samples.front() {
+            if now.saturating_duration_since(*at) <= STATS_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    fn stats(&mut self, now: Instant) -> DirectionStats {
+        self.prune(now);
+        let recent: u64 = self.samples.iter().map(|(_, bytes)| bytes).sum();
+        DirectionStats {
+            limit_kbps: (self.rate / 1024) as u32,
+            bytes_per_sec: (recent as f64 / STATS_WINDOW.as_secs_f64()) as u64,
+            total_bytes: self.total,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Limiter {
+    bucket: Mutex<Bucket>,
+    /// Wakes sleeping transfers when the cap changes, so a wait computed
+    /// for the old one doesn't hold them.
+    changed: Notify,
+}
+
+impl Limiter {
+    /// Counts `bytes` against the cap, waiting if the transfers together
+    /// are going faster than it allows.
+    pub async fn throttle(&self, bytes: usize) {
+        let (wait, changed) = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            bucket.record(bytes as u64, now);
+            (bucket.take(bytes as u64, now), self.changed.notified())
+        };
+        if let Some(wait) = wait {
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = changed => {}
+            }
+        }
+    }
+
+    fn set_kbps(&self, kbps: u32) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.rate = u64::from(kbps) * 1024;
+        // Debt run up under the old cap is forgiven.
+        bucket.tokens = 0.0;
+        bucket.refilled = Instant::now();
+        drop(bucket);
+        self.changed.notify_waiters();
+    }
+
+    fn stats(&self) -> DirectionStats {
+        self.bucket.lock().unwrap().stats(Instant::now())
+    }
+}
+
+#[derive(Default)]
+pub struct BandwidthState {
+    pub up: Limiter,
+    pub down: Limiter,
+}
+
+impl BandwidthState {
+    fn apply(&self, limit: BandwidthLimit) {
+        self.up.set_kbps(limit.up_kbps);
+        self.down.set_kbps(limit.down_kbps);
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    let limit = app.state::<SettingsState>().get().bandwidth_limit;
+    app.state::<BandwidthState>().apply(limit);
+}
+
+/// Caps upload and download speed in KB/s, 0 for unlimited. Applies to
+/// transfers already running as well as later ones.
+#[tauri::command]
+pub fn set_bandwidth_limit(
+    app: AppHandle,
+    up_kbps: u32,
+    down_kbps: u32,
+) -> Result<(), ServerError> {
+    let limit = BandwidthLimit { up_kbps, down_kbps };
+    app.state::<SettingsState>()
+        .update(|s| s.bandwidth_limit = limit)?;
+    app.state::<BandwidthState>().apply(limit);
+    info!(up_kbps, down_kbps, "Bandwidth limit changed");
+    Ok(())
+}
+
+/// Current throughput of all transfers together, per direction.
+#[tauri::command]
+pub fn get_transfer_stats(state: tauri::State<'_, BandwidthState>) -> TransferStats {
+    TransferStats {
+        up: state.up.stats(),
+        down: state.down.stats(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfers_share_one_bucket() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            rate: 1000,
+            refilled: start,
+            ..Bucket::default()
+        };
+        // Two transfers taking 500 bytes each at once wait in turn.
+        assert_eq!(bucket.take(500, start), Some(Duration::from_millis(500)));
+        assert_eq!(bucket.take(500, start), Some(Duration::from_secs(1)));
+        // Paid off a second later.
+        assert_eq!(bucket.take(0, start + Duration::from_secs(1)), None);
+        // An idle bucket saves up no more than `BURST_SECS`.
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.take(1000, later), None);
+        assert!(bucket.take(1, later).is_some());
+    }
+}
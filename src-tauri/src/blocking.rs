@@ -0,0 +1,92 @@
+//! Blocking work from async commands.
+//!
+//! Async commands share the tauri runtime with the health monitor, the
+//! event bridge and every other command, so a `std::fs` loop or a hash in
+//! one of them stalls all of those. Such work goes through [`run`], which
+//! moves it to the blocking pool. Commands are wrapped in [`watched`] so
+//! that, in debug builds, one that still holds a runtime thread too long
+//! says so in the log.
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+
+/// Longest a single poll of a command may take before it is reported.
+#[cfg(debug_assertions)]
+const SLOW_POLL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Runs `f` on the blocking pool. A panic in `f` is returned as its
+/// message instead of taking the command down with it.
+pub async fn run<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let joined = tauri::async_runtime::spawn_blocking(move || {
+        std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+            payload
+                .downcast_ref::<&str>()
+                .map(|s| (*s).to_owned())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "blocking task panicked".to_owned())
+        })
+    })
+    .await;
+    match joined {
+        Ok(result) => result,
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `command`'s future, logging a warning whenever one poll of it takes
+/// longer than [`SLOW_POLL`]. Only checks in debug builds.
+#[cfg(debug_assertions)]
+pub fn watched<F: Future>(command: &'static str, fut: F) -> impl Future<Output = F::Output> {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::time::Instant;
+
+    struct Watched<F> {
+        command: &'static str,
+        fut: Pin<Box<F>>,
+    }
+
+    impl<F: Future> Future for Watched<F> {
+        type Output = F::Output;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+            let started = Instant::now();
+            let poll = self.fut.as_mut().poll(cx);
+            let held = started.elapsed();
+            if held > SLOW_POLL {
+                tracing::warn!(
+                    command = self.command,
+                    held_ms = held.as_millis() as u64,
+                    "Command blocked the async runtime; move the work into blocking::run"
+                );
+            }
+            poll
+        }
+    }
+
+    Watched {
+        command,
+        fut: Box::pin(fut),
+    }
+}
+
+#[cfg(not(debug_assertions))]
+pub fn watched<F: Future>(_command: &'static str, fut: F) -> impl Future<Output = F::Output> {
+    fut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn panics_come_back_as_errors() {
+        assert_eq!(run(|| 2 + 2).await, Ok(4));
+        let panicked = run(|| -> u32 { panic!("disk on fire") }).await;
+        assert_eq!(panicked, Err("disk on fire".to_owned()));
+    }
+}
@@ -0,0 +1,116 @@
+//! Relays the sidecar's server-sent events to the webview as Tauri events.
+
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::error::ServerError;
+use crate::{backups, crash, generation, job_poll, notify, power, render_queue, taskbar};
+use crate::server::{ServerState, ServerStatus};
+
+/// Spawns the relay task. Connects whenever the server is ready and
+/// reconnects after the stream drops. A server without an event stream is
+/// probed again the next time it becomes ready; job polling covers it
+/// meanwhile.
+pub fn spawn(app: AppHandle) {
+    crash::spawn("bridge", async move {
+        let mut status = app.state::<ServerState>().subscribe();
+        loop {
+            if status.wait_for(|s| *s == ServerStatus::Ready).await.is_err() {
+                return;
+            }
+            match relay(&app).await {
+                Ok(true) => info!("Event stream closed, reconnecting"),
+                Ok(false) => {
+                    info!("Server has no event stream, falling back to polling");
+                    if status.wait_for(|s| *s != ServerStatus::Ready).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Err(e) => warn!(error = %e, "Event stream failed"),
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+    });
+}
+
+/// Streams events until the connection ends. Returns `Ok(false)` if the
+/// endpoint doesn't exist.
+async fn relay(app: &AppHandle) -> Result<bool, ServerError> {
+    let state = app.state::<ServerState>();
+    let mut resp = state
+        .client()
+        .get(state.url("/api/events"))
+        .header(reqwest::header::ACCEPT, "text/event-stream")
+        .send()
+        .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        job_poll::set_sse_available(app, false);
+        return Ok(false);
+    }
+    job_poll::set_sse_available(app, true);
+
+    let mut parser = SseParser::default();
+    while let Some(chunk) = resp.chunk().await? {
+        for (event, data) in parser.feed(&chunk) {
+            let payload = serde_json::from_str(&data)
+                .unwrap_or(serde_json::Value::String(data));
+            dispatch(app, &event, payload);
+        }
+    }
+    Ok(true)
+}
+
+/// Hands a server event to the modules that follow jobs, then to the
+/// webview. Also used for events synthesized from polling.
+pub fn dispatch(app: &AppHandle, event: &str, payload: serde_json::Value) {
+    notify::on_server_event(app, event, &payload);
+    power::on_server_event(app, event, &payload);
+    taskbar::on_server_event(app, event, &payload);
+    backups::on_server_event(app, event, &payload);
+    render_queue::on_server_event(app, event, &payload);
+    generation::on_server_event(app, event, &payload);
+    if let Err(e) = app.emit(event, payload) {
+        warn!(%event, error = %e, "Failed to emit server event");
+    }
+}
+
+/// Minimal `text/event-stream` parser; handles events split across chunks.
+#[derive(Default)]
+struct SseParser {
+    buf: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn feed(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let raw: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&raw);
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    let event = self.event.take().unwrap_or_else(|| "server-message".into());
+                    events.push((event, self.data.join("\n")));
+                    self.data.clear();
+                }
+                self.event = None;
+                continue;
+            }
+
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.event = Some(value.to_owned()),
+                "data" => self.data.push(value.to_owned()),
+                _ => {}
+            }
+        }
+        events
+    }
+}
@@ -0,0 +1,173 @@
+//! Copying generated text out of the app, and images in and out of it.
+
+use image::imageops::FilterType;
+use image::RgbaImage;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::image::Image;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tracing::{debug, info};
+
+use crate::api;
+use crate::backups::valid_project_id;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::server::ServerState;
+use crate::settings::SettingsState;
+use crate::tempfiles;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedImage {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    /// Set when the image was scaled down to the pixel cap.
+    pub downscaled: bool,
+}
+
+#[tauri::command]
+pub fn copy_to_clipboard(app: AppHandle, text: String) -> Result<(), ServerError> {
+    app.clipboard()
+        .write_text(text)
+        .map_err(|e| ServerError::Clipboard(e.to_string()))
+}
+
+/// Fetches the script a job generated and copies it. Fails with `noScript`
+/// rather than copying nothing if the job has none.
+#[tauri::command]
+pub async fn copy_generated_script(app: AppHandle, job_id: String) -> Result<(), ServerError> {
+    let script = match api::get_job_script(&app.state::<ServerState>(), &job_id).await {
+        Ok(script) => script,
+        Err(ServerError::BadStatus(404)) => None,
+        Err(e) => return Err(e),
+    };
+    match script.filter(|s| !s.trim().is_empty()) {
+        Some(script) => copy_to_clipboard(app, script),
+        None => Err(ServerError::NoScript(job_id)),
+    }
+}
+
+/// The largest size with the same aspect ratio and at most `max_pixels`
+/// pixels; the size itself if it is already within.
+fn fit_within(width: u32, height: u32, max_pixels: u64) -> (u32, u32) {
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels <= max_pixels.max(1) {
+        return (width, height);
+    }
+    let scale = (max_pixels.max(1) as f64 / pixels as f64).sqrt();
+    let fit = |side: u32| ((f64::from(side) * scale).floor() as u32).max(1);
+    (fit(width), fit(height))
+}
+
+fn assets_dir(app: &AppHandle, project_id: &str) -> Result<PathBuf, ServerError> {
+    if !valid_project_id(project_id) {
+        return Err(ServerError::Clipboard(format!(
+            "invalid project id: {}",
+            project_id
+        )));
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("projects").join(project_id).join("assets"))
+}
+
+/// Saves the image on the clipboard to the project's `assets` folder as a
+/// PNG, scaled down first if it has more pixels than the
+/// `clipboardImageMaxPixels` setting. `None` if the clipboard holds no
+/// image.
+#[tauri::command]
+pub async fn read_clipboard_image(
+    app: AppHandle,
+    project_id: String,
+) -> Result<Option<SavedImage>, ServerError> {
+    let dir = assets_dir(&app, &project_id)?;
+    // The plugin converts whichever format the OS offers (PNG, DIB or
+    // TIFF) to RGBA. It reports an empty clipboard and one holding only
+    // text with the same error, so any failure counts as no image.
+    let (rgba, width, height) = match app.clipboard().read_image() {
+        Ok(image) => (image.rgba().to_vec(), image.width(), image.height()),
+        Err(e) => {
+            debug!(error = %e, "No image on the clipboard");
+            return Ok(None);
+        }
+    };
+    let Some(pixels) = RgbaImage::from_raw(width, height, rgba) else {
+        return Ok(None);
+    };
+    let max_pixels = app.state::<SettingsState>().get().clipboard_image_max_pixels;
+
+    let tmp = tempfiles::create_temp_path(&app, "clipboard", "png")?;
+    let encoded = tmp.clone();
+    let saved = blocking::run(move || -> Result<(u32, u32), ServerError> {
+        let (w, h) = fit_within(width, height, max_pixels);
+        let pixels = if (w, h) == (width, height) {
+            pixels
+        } else {
+            image::imageops::resize(&pixels, w, h, FilterType::Lanczos3)
+        };
+        pixels
+            .save_with_format(&encoded, image::ImageFormat::Png)
+            .map_err(|e| ServerError::Clipboard(e.to_string()))?;
+        Ok((w, h))
+    })
+    .await
+    .map_err(ServerError::Clipboard);
+    let (w, h) = match saved {
+        Ok(Ok(size)) => size,
+        Ok(Err(e)) | Err(e) => {
+            tempfiles::discard(&app, &tmp).await;
+            return Err(e);
+        }
+    };
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    let dest = dir.join(format!("clipboard-{}.png", millis));
+    tokio::fs::create_dir_all(&dir).await?;
+    tempfiles::commit(&app, &tmp, &dest).await?;
+    let downscaled = (w, h) != (width, height);
+    info!(project_id, width = w, height = h, downscaled, "Saved clipboard image");
+    Ok(Some(SavedImage {
+        path: dest.to_string_lossy().into_owned(),
+        width: w,
+        height: h,
+        downscaled,
+    }))
+}
+
+/// Puts the PNG or JPEG at `path` on the clipboard, e.g. a frame from
+/// `capture_frame`.
+#[tauri::command]
+pub async fn write_image_to_clipboard(app: AppHandle, path: String) -> Result<(), ServerError> {
+    let decoded = blocking::run(move || image::open(&path).map(|opened| opened.into_rgba8()))
+        .await
+        .map_err(ServerError::Clipboard)?
+        .map_err(|e| match e {
+            image::ImageError::IoError(e) => e.into(),
+            e => ServerError::Clipboard(e.to_string()),
+        })?;
+    let (width, height) = decoded.dimensions();
+    app.clipboard()
+        .write_image(&Image::new_owned(decoded.into_raw(), width, height))
+        .map_err(|e| ServerError::Clipboard(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_images_fit_the_pixel_cap() {
+        assert_eq!(fit_within(1920, 1080, 4096 * 4096), (1920, 1080));
+        let (w, h) = fit_within(8000, 6000, 4096 * 4096);
+        assert!(u64::from(w) * u64::from(h) <= 4096 * 4096);
+        assert_eq!((w, h), (4729, 3547));
+        assert_eq!(fit_within(4000, 2000, 2_000_000), (2000, 1000));
+    }
+}
@@ -0,0 +1,479 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Url};
+use tracing::warn;
+
+use crate::error::ServerError;
+use crate::logging;
+use crate::redact::{self, Redactor};
+use crate::server::{self, ServerState};
+use crate::settings::SettingsState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    #[default]
+    Http,
+    /// For a sidecar behind a local TLS proxy.
+    Https,
+}
+
+impl Scheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+}
+
+/// How the server is run.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ServerMode {
+    /// The sidecar shipped with the app.
+    #[default]
+    Bundled,
+    /// A server started some other way, such as `npm run dev`. Nothing is
+    /// spawned, and the app won't stop or restart it.
+    External { base_url: String },
+    /// A server run from a source checkout, e.g. `node server/index.js`.
+    /// Its output is logged like the sidecar's. Only for development and
+    /// CI, through `AIYOU_SERVER_MODE` or `--server-mode`; it is never
+    /// saved in the config.
+    CustomCommand { program: String, args: Vec<String> },
+}
+
+impl ServerMode {
+    /// Parses the `AIYOU_SERVER_MODE` / `--server-mode` form: `bundled`,
+    /// `external:<url>` or `custom:<program> [args...]`, the command line
+    /// split on whitespace.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let (kind, rest) = value.split_once(':').unwrap_or((value, ""));
+        let mode = match kind {
+            "bundled" if rest.is_empty() => ServerMode::Bundled,
+            "external" => ServerMode::External {
+                base_url: rest.trim().to_owned(),
+            },
+            "custom" => {
+                let mut words = rest.split_whitespace().map(str::to_owned);
+                ServerMode::CustomCommand {
+                    program: words.next().unwrap_or_default(),
+                    args: words.collect(),
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "{}: expected bundled, external:<url> or custom:<command>",
+                    value
+                ))
+            }
+        };
+        mode.validate()?;
+        Ok(mode)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ServerMode::Bundled => Ok(()),
+            ServerMode::External { base_url } => parse_server_url(base_url).map(|_| ()),
+            ServerMode::CustomCommand { program, .. } if program.trim().is_empty() => {
+                Err("custom command has no program".into())
+            }
+            ServerMode::CustomCommand { .. } => Ok(()),
+        }
+    }
+
+    /// The server URL in external mode.
+    pub fn external_url(&self) -> Option<Url> {
+        match self {
+            ServerMode::External { base_url } => parse_server_url(base_url).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Checks a server base URL: absolute http(s) with a host, optionally with
+/// a base path.
+pub fn parse_server_url(value: &str) -> Result<Url, String> {
+    let url = Url::parse(value).map_err(|e| format!("{}: {}", value, e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(format!("{}: must be an http(s) URL with a host", value));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("{}: no query or fragment allowed", value));
+    }
+    Ok(url)
+}
+
+/// Sidecar configuration persisted to `config.json` in the app config dir.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ServerConfig {
+    /// Port the sidecar listens on, passed to it as `PORT`. 0 picks a free
+    /// port on each start.
+    pub port: u16,
+    /// Data profile, passed to the sidecar as `AIYOU_PROFILE`.
+    pub profile: String,
+    /// How long to wait for the first successful health check.
+    pub startup_timeout_secs: u64,
+    /// Spawns tried before giving up on starting the sidecar, each with
+    /// its own `startup_timeout_secs`.
+    pub startup_max_attempts: u32,
+    /// Seconds between health checks once the server is ready. Longer is
+    /// quieter and cheaper on battery; shorter notices a dead server sooner.
+    pub heartbeat_interval_secs: u64,
+    /// Limit for a health request until the server first reports ready.
+    pub startup_health_timeout_ms: u64,
+    /// Limit for a health request once it has, so a latency spike during
+    /// heavy generation isn't taken for a lost server.
+    pub steady_health_timeout_ms: u64,
+    /// How long a draining restart waits for generations in flight before
+    /// restarting anyway.
+    pub drain_timeout_secs: u64,
+    pub scheme: Scheme,
+    /// Accept self-signed or otherwise invalid certificates over HTTPS.
+    /// Only meant for local proxies; off by default.
+    pub danger_accept_invalid_certs: bool,
+    /// Regexes for secrets masked in sidecar output. A `secret` group
+    /// limits masking to that part of the match.
+    pub redact_patterns: Vec<String>,
+    /// Overridden by `AIYOU_SERVER_MODE` or `--server-mode`.
+    pub server_mode: ServerMode,
+    /// Localhost port for the app's own health endpoint; 0, the default,
+    /// leaves it closed. Read at launch.
+    pub health_port: u16,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 3001,
+            profile: "default".into(),
+            startup_timeout_secs: 30,
+            startup_max_attempts: 2,
+            heartbeat_interval_secs: 10,
+            startup_health_timeout_ms: 2000,
+            steady_health_timeout_ms: 5000,
+            drain_timeout_secs: 300,
+            scheme: Scheme::default(),
+            danger_accept_invalid_certs: false,
+            redact_patterns: redact::DEFAULT_PATTERNS
+                .iter()
+                .map(|p| (*p).to_owned())
+                .collect(),
+            server_mode: ServerMode::default(),
+            health_port: 0,
+        }
+    }
+}
+
+impl ServerConfig {
+    fn validate(&self) -> Result<(), ServerError> {
+        if self.port != 0 && self.port < 1024 {
+            return Err(ServerError::InvalidConfig(format!(
+                "port must be 0 or between 1024 and 65535, got {}",
+                self.port
+            )));
+        }
+        let valid_profile = !self.profile.is_empty()
+            && self.profile.len() <= 32
+            && self
+                .profile
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_profile {
+            return Err(ServerError::InvalidConfig(format!(
+                "invalid profile name: {:?}",
+                self.profile
+            )));
+        }
+        if self.health_port != 0 && (self.health_port < 1024 || self.health_port == self.port) {
+            return Err(ServerError::InvalidConfig(format!(
+                "health port must be 0 or between 1024 and 65535 and differ from the server \
+                 port, got {}",
+                self.health_port
+            )));
+        }
+        if !(5..=600).contains(&self.startup_timeout_secs) {
+            return Err(ServerError::InvalidConfig(format!(
+                "startup timeout must be between 5 and 600 seconds, got {}",
+                self.startup_timeout_secs
+            )));
+        }
+        if !(1..=5).contains(&self.startup_max_attempts) {
+            return Err(ServerError::InvalidConfig(format!(
+                "startup attempts must be between 1 and 5, got {}",
+                self.startup_max_attempts
+            )));
+        }
+        if !(1..=300).contains(&self.heartbeat_interval_secs) {
+            return Err(ServerError::InvalidConfig(format!(
+                "heartbeat interval must be between 1 and 300 seconds, got {}",
+                self.heartbeat_interval_secs
+            )));
+        }
+        for (name, ms) in [
+            ("startup", self.startup_health_timeout_ms),
+            ("steady", self.steady_health_timeout_ms),
+        ] {
+            if !(100..=60_000).contains(&ms) {
+                return Err(ServerError::InvalidConfig(format!(
+                    "{} health timeout must be between 100 and 60000 ms, got {}",
+                    name, ms
+                )));
+            }
+        }
+        if !(1..=3600).contains(&self.drain_timeout_secs) {
+            return Err(ServerError::InvalidConfig(format!(
+                "drain timeout must be between 1 and 3600 seconds, got {}",
+                self.drain_timeout_secs
+            )));
+        }
+        if let Err(e) = self.server_mode.validate() {
+            return Err(ServerError::InvalidConfig(format!("invalid server mode: {}", e)));
+        }
+        if let ServerMode::CustomCommand { .. } = self.server_mode {
+            return Err(ServerError::InvalidConfig(
+                "custom commands can only be set with AIYOU_SERVER_MODE or --server-mode".into(),
+            ));
+        }
+        if let Err(e) = Redactor::new(&self.redact_patterns, []) {
+            return Err(ServerError::InvalidConfig(format!(
+                "invalid redaction pattern: {}",
+                e
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn path(app: &AppHandle) -> Option<PathBuf> {
+    app.path()
+        .app_config_dir()
+        .ok()
+        .map(|dir| dir.join("config.json"))
+}
+
+/// Reads the config file, falling back to defaults if it is missing or
+/// invalid.
+pub fn load(app: &AppHandle) -> ServerConfig {
+    let Some(contents) = path(app).and_then(|p| std::fs::read_to_string(p).ok()) else {
+        return ServerConfig::default();
+    };
+    match serde_json::from_str::<ServerConfig>(&contents) {
+        Ok(mut config) => {
+            // Saved by an older version; the rest of the config still holds.
+            if let ServerMode::CustomCommand { .. } = config.server_mode {
+                warn!("Ignoring saved custom server command; use AIYOU_SERVER_MODE");
+                config.server_mode = ServerMode::Bundled;
+            }
+            match config.validate() {
+                Ok(()) => config,
+                Err(e) => {
+                    warn!(error = %e, "Ignoring invalid server config");
+                    ServerConfig::default()
+                }
+            }
+        }
+        Err(e) => {
+            warn!(error = %e, "Ignoring invalid server config file");
+            ServerConfig::default()
+        }
+    }
+}
+
+fn save(app: &AppHandle, config: &ServerConfig) -> Result<(), ServerError> {
+    let Some(path) = path(app) else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json =
+        serde_json::to_string_pretty(config).map_err(|e| ServerError::Io(e.to_string()))?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_server_config(state: tauri::State<'_, ServerState>) -> ServerConfig {
+    state.config()
+}
+
+/// Validates and saves the config. The heartbeat interval applies from the
+/// next beat; port, profile and server mode only take effect on the next
+/// start, so pass `restart` to restart a running server right away. A
+/// custom command is refused; it can only come from the environment or
+/// the command line.
+#[tauri::command]
+pub async fn set_server_config(
+    app: AppHandle,
+    config: ServerConfig,
+    restart: Option<bool>,
+) -> Result<(), ServerError> {
+    config.validate()?;
+    save(&app, &config)?;
+
+    let state = app.state::<ServerState>();
+    let previous = state.config();
+    state.set_config(config.clone());
+
+    let needs_restart = previous.port != config.port
+        || previous.profile != config.profile
+        || previous.server_mode != config.server_mode;
+    if restart.unwrap_or(false) && needs_restart && state.is_running() {
+        server::restart(&app, server::RestartReason::ConfigChange).await?;
+    }
+    Ok(())
+}
+
+/// `config` with anything that could be a secret taken out: the password
+/// of an external server URL, and whatever the redaction patterns or our
+/// secret env values match in custom command args.
+fn elide_secrets(mut config: ServerConfig) -> ServerConfig {
+    match &mut config.server_mode {
+        ServerMode::External { base_url } => {
+            if let Ok(mut url) = Url::parse(base_url) {
+                if url.password().is_some() && url.set_password(None).is_ok() {
+                    *base_url = url.to_string();
+                }
+            }
+        }
+        ServerMode::CustomCommand { args, .. } => {
+            let literals = std::env::vars()
+                .filter(|(name, _)| redact::is_secret_name(name))
+                .map(|(_, value)| value);
+            let redactor = Redactor::new(&config.redact_patterns, literals).unwrap_or_default();
+            for arg in args.iter_mut() {
+                *arg = redactor.redact(arg).into_owned();
+            }
+        }
+        ServerMode::Bundled => {}
+    }
+    config
+}
+
+/// What `export_config` writes: the server config with the app's log
+/// level alongside.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SharedConfig {
+    #[serde(flatten)]
+    server: ServerConfig,
+    log_level: String,
+}
+
+/// The effective server config and log level as pretty JSON for sharing,
+/// secrets elided. The server mode is the one in use, even if overridden
+/// for this launch. There is no host: the sidecar only listens on
+/// localhost.
+#[tauri::command]
+pub fn export_config(
+    state: tauri::State<'_, ServerState>,
+    settings: tauri::State<'_, SettingsState>,
+) -> Result<String, ServerError> {
+    let config = ServerConfig {
+        server_mode: state.mode(),
+        ..state.config()
+    };
+    let shared = SharedConfig {
+        server: elide_secrets(config),
+        log_level: settings.get().log_level,
+    };
+    serde_json::to_string_pretty(&shared).map_err(|e| ServerError::Io(e.to_string()))
+}
+
+/// The server config and log level in a shared config. Fields left out get
+/// their defaults, except the log level, which is `None` then. Unknown
+/// fields are rejected, and so is any server mode but bundled: a shared
+/// config mustn't be able to point the app at another server or have it
+/// run a command.
+fn parse_shared(json: &str) -> Result<(ServerConfig, Option<String>), ServerError> {
+    let invalid = |e: serde_json::Error| ServerError::InvalidConfig(e.to_string());
+    let mut value: serde_json::Value = serde_json::from_str(json).map_err(invalid)?;
+    let Some(fields) = value.as_object_mut() else {
+        return Err(ServerError::InvalidConfig("expected a JSON object".into()));
+    };
+    let log_level = match fields.remove("logLevel") {
+        None => None,
+        Some(serde_json::Value::String(level)) => {
+            logging::parse_filter(&level)?;
+            Some(level)
+        }
+        Some(_) => return Err(ServerError::InvalidConfig("logLevel: expected a string".into())),
+    };
+    let known = serde_json::to_value(ServerConfig::default()).map_err(invalid)?;
+    if let Some(unknown) = fields.keys().find(|k| known.get(k.as_str()).is_none()) {
+        return Err(ServerError::InvalidConfig(format!("unknown field: {}", unknown)));
+    }
+    let config: ServerConfig = serde_json::from_value(value).map_err(invalid)?;
+    if config.server_mode != ServerMode::Bundled {
+        return Err(ServerError::InvalidConfig(
+            "only the bundled server mode can be imported".into(),
+        ));
+    }
+    Ok((config, log_level))
+}
+
+/// Validates and applies a config from `export_config`, like
+/// `set_server_config` and `set_log_level`; see `parse_shared` for what is
+/// accepted.
+#[tauri::command]
+pub async fn import_config(
+    app: AppHandle,
+    json: String,
+    restart: Option<bool>,
+) -> Result<(), ServerError> {
+    let (config, log_level) = parse_shared(&json)?;
+    set_server_config(app.clone(), config, restart).await?;
+    match log_level {
+        Some(level) => logging::set_log_level(app, level),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_configs_only_import_the_bundled_server() {
+        let (config, log_level) =
+            parse_shared(r#"{ "port": 3100, "profile": "qa", "logLevel": "debug" }"#).unwrap();
+        assert_eq!(config.port, 3100);
+        assert_eq!(config.profile, "qa");
+        assert_eq!(config.server_mode, ServerMode::Bundled);
+        assert_eq!(log_level.as_deref(), Some("debug"));
+
+        let custom = r#"{
+            "serverMode": { "kind": "customCommand", "program": "sh", "args": ["-c", "id"] }
+        }"#;
+        assert!(matches!(parse_shared(custom), Err(ServerError::InvalidConfig(_))));
+        let external = r#"{ "serverMode": { "kind": "external", "baseUrl": "http://x.test" } }"#;
+        assert!(matches!(parse_shared(external), Err(ServerError::InvalidConfig(_))));
+        let host = r#"{ "host": "0.0.0.0" }"#;
+        assert!(matches!(parse_shared(host), Err(ServerError::InvalidConfig(_))));
+        assert!(parse_shared(r#"{ "logLevel": 3 }"#).is_err());
+    }
+
+    #[test]
+    fn custom_commands_are_never_saved() {
+        let config = ServerConfig {
+            server_mode: ServerMode::CustomCommand {
+                program: "node".into(),
+                args: vec!["server/index.js".into()],
+            },
+            ..ServerConfig::default()
+        };
+        assert!(matches!(config.validate(), Err(ServerError::InvalidConfig(_))));
+        let external = ServerConfig {
+            server_mode: ServerMode::External {
+                base_url: "http://localhost:5174".into(),
+            },
+            ..ServerConfig::default()
+        };
+        assert!(external.validate().is_ok());
+    }
+}
@@ -0,0 +1,573 @@
+//! Dry-run cost estimates for generation jobs, worked out here without
+//! calling any provider.
+//!
+//! A job spec says what it will ask for: `prompts` for `textModel`, and a
+//! `scenes` list whose entries take images from `imageModel`, a video from
+//! `videoModel` and dialogue `lines` spoken by `ttsModel`. Prompts are
+//! counted with tiktoken's encodings, which are exact for OpenAI models and
+//! close for the rest; speech is counted in characters per voice.
+//!
+//! Prices come from `resources/prices.json`, or from the copy fetched daily
+//! from the CDN into `app_cache_dir()/prices.json` when that one's
+//! `version` is newer. Without a CDN URL in the build the shipped table is
+//! all there is.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Manager};
+use tiktoken_rs::CoreBPE;
+use tracing::{info, warn};
+
+use crate::blocking;
+use crate::crash;
+use crate::error::ServerError;
+use crate::network::NetworkState;
+use crate::server::ServerState;
+
+/// Where the current price table is published, set at build time.
+const PRICES_URL: Option<&str> = option_env!("AIYOU_PRICES_URL");
+const BUNDLED_PRICES: &str = "resources/prices.json";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// Reply length assumed per prompt when the spec doesn't give one.
+const DEFAULT_OUTPUT_TOKENS: u64 = 1000;
+/// Clip length assumed for a scene without `durationSecs`.
+const DEFAULT_VIDEO_SECS: f64 = 10.0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ModelPrice {
+    provider: String,
+    input_per_million_tokens: Option<f64>,
+    output_per_million_tokens: Option<f64>,
+    per_image: Option<f64>,
+    per_second: Option<f64>,
+    /// For video priced per clip rather than per second.
+    per_call: Option<f64>,
+    per_million_chars: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct PriceTable {
+    /// A date, `YYYY-MM-DD`; later versions sort after earlier ones.
+    version: String,
+    currency: String,
+    models: BTreeMap<String, ModelPrice>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PriceSource {
+    Bundled,
+    /// Fetched from the CDN.
+    Cached,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct CostSpec {
+    text_model: Option<String>,
+    prompts: Vec<String>,
+    /// Tokens expected back per prompt.
+    output_tokens: Option<u64>,
+    image_model: Option<String>,
+    video_model: Option<String>,
+    tts_model: Option<String>,
+    scenes: Vec<SceneSpec>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SceneSpec {
+    /// Images made for the scene; one if there is an image model.
+    images: Option<u64>,
+    /// Whether the scene gets a clip; it does if there is a video model.
+    video: Option<bool>,
+    duration_secs: Option<f64>,
+    lines: Vec<LineSpec>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct LineSpec {
+    voice: Option<String>,
+    text: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UsageCounts {
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    /// Characters of dialogue by voice.
+    pub tts_chars: BTreeMap<String, u64>,
+    pub images: u64,
+    pub video_calls: u64,
+    pub video_secs: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CostKind {
+    Text,
+    Image,
+    Video,
+    Speech,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostLine {
+    pub model: String,
+    pub kind: CostKind,
+    pub quantity: f64,
+    /// `tokens`, `images`, `seconds`, `clips` or `chars`.
+    pub unit: String,
+    /// `None` if the price table has no price for it.
+    pub cost: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderCost {
+    pub provider: String,
+    pub lines: Vec<CostLine>,
+    /// Of the priced lines.
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostEstimate {
+    pub currency: String,
+    pub price_version: String,
+    pub price_source: PriceSource,
+    pub counts: UsageCounts,
+    pub providers: Vec<ProviderCost>,
+    pub total: f64,
+    pub confidence: Confidence,
+    /// What the confidence rests on, for showing next to the total.
+    pub note: String,
+}
+
+/// What the sidecar reported a job's generations used, summed over its
+/// sidecar jobs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ActualUsage {
+    /// What the providers billed, of the reports that said.
+    pub cost: Option<f64>,
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    pub tts_chars: u64,
+    pub images: u64,
+    pub video_secs: f64,
+    /// Reports summed, and how many of them had no cost.
+    pub reports: u32,
+    pub unpriced_reports: u32,
+}
+
+impl ActualUsage {
+    /// Adds one sidecar job's report.
+    pub fn add(&mut self, report: &ActualUsage) {
+        match report.cost {
+            Some(cost) => self.cost = Some(self.cost.unwrap_or(0.0) + cost),
+            None => self.unpriced_reports += 1,
+        }
+        self.prompt_tokens += report.prompt_tokens;
+        self.output_tokens += report.output_tokens;
+        self.tts_chars += report.tts_chars;
+        self.images += report.images;
+        self.video_secs += report.video_secs;
+        self.reports += 1;
+    }
+
+    /// Actual cost over the estimate, e.g. 1.2 for 20% over; `None` unless
+    /// every report had a cost.
+    pub fn vs_estimate(&self, estimate: &CostEstimate) -> Option<f64> {
+        let complete = self.reports > 0 && self.unpriced_reports == 0;
+        let cost = self.cost.filter(|_| complete)?;
+        (estimate.total > 0.0).then(|| cost / estimate.total)
+    }
+}
+
+#[derive(Default)]
+pub struct PricingState {
+    table: Mutex<Option<(PriceTable, PriceSource)>>,
+}
+
+/// `o200k_base` and `cl100k_base`; `None` if they failed to load.
+fn tokenizers() -> &'static Option<(CoreBPE, CoreBPE)> {
+    static TOKENIZERS: OnceLock<Option<(CoreBPE, CoreBPE)>> = OnceLock::new();
+    TOKENIZERS.get_or_init(|| {
+        let loaded = tiktoken_rs::o200k_base().and_then(|o| Ok((o, tiktoken_rs::cl100k_base()?)));
+        loaded
+            .inspect_err(|e| warn!(error = %e, "Failed to load tokenizers"))
+            .ok()
+    })
+}
+
+/// Tokens in `text` for `model`, and whether the count is exact.
+fn count_tokens(model: &str, text: &str) -> (u64, bool) {
+    let model = model.to_ascii_lowercase();
+    let Some((o200k, cl100k)) = tokenizers() else {
+        // Between CJK, about a token a character, and Latin, about one in four.
+        return ((text.chars().count() as u64).div_ceil(2), false);
+    };
+    let legacy = model == "gpt-4" || model.starts_with("gpt-4-") || model.starts_with("gpt-3.5");
+    let exact =
+        model.starts_with("gpt-") || ["o1", "o3", "o4"].iter().any(|o| model.starts_with(o));
+    let bpe = if legacy { cl100k } else { o200k };
+    (bpe.encode_with_special_tokens(text).len() as u64, exact)
+}
+
+struct Estimator<'a> {
+    table: &'a PriceTable,
+    providers: BTreeMap<String, Vec<CostLine>>,
+    confidence: Confidence,
+    notes: Vec<String>,
+}
+
+impl Estimator<'_> {
+    fn lower(&mut self, to: Confidence, note: String) {
+        self.confidence = self.confidence.min(to);
+        if !self.notes.contains(&note) {
+            self.notes.push(note);
+        }
+    }
+
+    fn add(
+        &mut self,
+        model: Option<&str>,
+        kind: CostKind,
+        quantity: f64,
+        unit: &str,
+        price: impl Fn(&ModelPrice) -> Option<f64>,
+    ) {
+        let Some(model) = model else {
+            let what = match kind {
+                CostKind::Text => "text",
+                CostKind::Image => "image",
+                CostKind::Video => "video",
+                CostKind::Speech => "speech",
+            };
+            let note = format!("no {} model is set, so it isn't priced", what);
+            self.lower(Confidence::Low, note);
+            return;
+        };
+        let entry = self.table.models.get(model);
+        let cost = entry.and_then(&price);
+        if cost.is_none() {
+            self.lower(Confidence::Low, format!("no price for {}", model));
+        }
+        let provider = entry
+            .map(|e| e.provider.clone())
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| "unknown".into());
+        self.providers.entry(provider).or_default().push(CostLine {
+            model: model.to_owned(),
+            kind,
+            quantity,
+            unit: unit.to_owned(),
+            cost,
+        });
+    }
+}
+
+/// Prices `spec` against `table`. `count` gives the tokens in a prompt for
+/// a model and whether that count is exact.
+fn estimate(
+    spec: &CostSpec,
+    table: &PriceTable,
+    source: PriceSource,
+    count: impl Fn(&str, &str) -> (u64, bool),
+) -> CostEstimate {
+    let mut counts = UsageCounts::default();
+    let mut estimator = Estimator {
+        table,
+        providers: BTreeMap::new(),
+        confidence: Confidence::High,
+        notes: Vec::new(),
+    };
+
+    if !spec.prompts.is_empty() {
+        let model = spec.text_model.as_deref();
+        for prompt in &spec.prompts {
+            let (tokens, exact) = count(model.unwrap_or_default(), prompt);
+            counts.prompt_tokens += tokens;
+            if !exact {
+                let name = model.unwrap_or("the text model");
+                let note = format!("token counts for {} are approximate", name);
+                estimator.lower(Confidence::Medium, note);
+            }
+        }
+        let per_prompt = spec.output_tokens.unwrap_or_else(|| {
+            estimator.lower(Confidence::Medium, "reply lengths are assumed".into());
+            DEFAULT_OUTPUT_TOKENS
+        });
+        counts.output_tokens = per_prompt * spec.prompts.len() as u64;
+        let (input, output) = (counts.prompt_tokens as f64, counts.output_tokens as f64);
+        estimator.add(model, CostKind::Text, input + output, "tokens", |p| {
+            let input = p.input_per_million_tokens? * input;
+            Some((input + p.output_per_million_tokens? * output) / 1e6)
+        });
+    }
+
+    for scene in &spec.scenes {
+        counts.images += scene.images.unwrap_or(u64::from(spec.image_model.is_some()));
+        if scene.video.unwrap_or(spec.video_model.is_some()) {
+            counts.video_calls += 1;
+            counts.video_secs += scene.duration_secs.unwrap_or_else(|| {
+                estimator.lower(Confidence::Medium, "some clip lengths are assumed".into());
+                DEFAULT_VIDEO_SECS
+            });
+        }
+        for line in &scene.lines {
+            let voice = line.voice.clone().unwrap_or_else(|| "default".into());
+            let chars = line.text.trim().chars().count() as u64;
+            *counts.tts_chars.entry(voice).or_default() += chars;
+        }
+    }
+    if counts.images > 0 {
+        let images = counts.images as f64;
+        estimator.add(spec.image_model.as_deref(), CostKind::Image, images, "images", |p| {
+            Some(p.per_image? * images)
+        });
+    }
+    if counts.video_calls > 0 {
+        let (secs, calls) = (counts.video_secs, counts.video_calls as f64);
+        let per_call = spec
+            .video_model
+            .as_ref()
+            .and_then(|m| table.models.get(m))
+            .is_some_and(|p| p.per_second.is_none() && p.per_call.is_some());
+        let (quantity, unit) = if per_call { (calls, "clips") } else { (secs, "seconds") };
+        estimator.add(spec.video_model.as_deref(), CostKind::Video, quantity, unit, |p| {
+            p.per_second.map(|s| s * secs).or_else(|| Some(p.per_call? * calls))
+        });
+    }
+    let chars: u64 = counts.tts_chars.values().sum();
+    if chars > 0 {
+        let chars = chars as f64;
+        estimator.add(spec.tts_model.as_deref(), CostKind::Speech, chars, "chars", |p| {
+            Some(p.per_million_chars? * chars / 1e6)
+        });
+    }
+
+    let providers: Vec<ProviderCost> = estimator
+        .providers
+        .into_iter()
+        .map(|(provider, lines)| ProviderCost {
+            cost: lines.iter().filter_map(|l| l.cost).sum(),
+            provider,
+            lines,
+        })
+        .collect();
+    let note = if estimator.notes.is_empty() {
+        "counts are exact and every model is priced; providers may still bill retries".into()
+    } else {
+        estimator.notes.join("; ")
+    };
+    CostEstimate {
+        currency: table.currency.clone(),
+        price_version: table.version.clone(),
+        price_source: source,
+        total: providers.iter().map(|p| p.cost).sum(),
+        counts,
+        providers,
+        confidence: estimator.confidence,
+        note,
+    }
+}
+
+fn cache_path(app: &AppHandle) -> Result<PathBuf, ServerError> {
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("prices.json"))
+}
+
+fn read_table(path: &Path) -> Option<PriceTable> {
+    let data = std::fs::read(path).ok()?;
+    serde_json::from_slice(&data)
+        .inspect_err(|e| warn!(path = %path.display(), error = %e, "Ignoring invalid price table"))
+        .ok()
+}
+
+/// The fetched table if it's newer than the shipped one, else the shipped.
+fn load_table(app: &AppHandle) -> (PriceTable, PriceSource) {
+    let bundled = app
+        .path()
+        .resolve(BUNDLED_PRICES, BaseDirectory::Resource)
+        .ok()
+        .and_then(|p| read_table(&p))
+        .unwrap_or_default();
+    let cached = cache_path(app).ok().and_then(|p| read_table(&p));
+    match cached {
+        Some(cached) if cached.version > bundled.version => (cached, PriceSource::Cached),
+        _ => (bundled, PriceSource::Bundled),
+    }
+}
+
+fn table(app: &AppHandle) -> (PriceTable, PriceSource) {
+    let state = app.state::<PricingState>();
+    let mut table = state.table.lock().unwrap();
+    table.get_or_insert_with(|| load_table(app)).clone()
+}
+
+/// Fetches the published table into the cache and uses it if it's newer.
+async fn refresh(app: &AppHandle, url: &str) -> Result<(), ServerError> {
+    let resp = app
+        .state::<ServerState>()
+        .client()
+        .get(url)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(ServerError::BadStatus(resp.status().as_u16()));
+    }
+    let data = resp.bytes().await?;
+    let table: PriceTable =
+        serde_json::from_slice(&data).map_err(|e| ServerError::InvalidResponse(e.to_string()))?;
+    if table.models.is_empty() {
+        return Err(ServerError::InvalidResponse("price table has no models".into()));
+    }
+    let path = cache_path(app)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, &data)?;
+    std::fs::rename(&tmp, &path)?;
+
+    let loaded = load_table(app);
+    info!(version = %loaded.0.version, source = ?loaded.1, "Price table refreshed");
+    *app.state::<PricingState>().table.lock().unwrap() = Some(loaded);
+    Ok(())
+}
+
+/// Refreshes the price table daily while online, if the build has a URL
+/// for it.
+pub fn init(app: &AppHandle) {
+    let Some(url) = PRICES_URL else {
+        return;
+    };
+    let handle = app.clone();
+    crash::spawn("prices", async move {
+        loop {
+            let age = cache_path(&handle)
+                .ok()
+                .and_then(|p| std::fs::metadata(p).ok()?.modified().ok())
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+            let stale = age.is_none_or(|age| age >= REFRESH_INTERVAL);
+            if stale && handle.state::<NetworkState>().is_online() {
+                if let Err(e) = refresh(&handle, url).await {
+                    warn!(error = %e, "Failed to refresh the price table");
+                }
+            }
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+fn parse_spec(job_spec: &serde_json::Value) -> Result<CostSpec, ServerError> {
+    serde_json::from_value(job_spec.clone()).map_err(|e| ServerError::Estimate(e.to_string()))
+}
+
+/// The estimate for a queued job, or `None` if its spec lists nothing
+/// billable, as a plain render's doesn't.
+pub fn for_job(app: &AppHandle, job_spec: &serde_json::Value) -> Option<CostEstimate> {
+    let spec = parse_spec(job_spec).ok()?;
+    if spec.prompts.is_empty() && spec.scenes.is_empty() {
+        return None;
+    }
+    let (table, source) = table(app);
+    Some(estimate(&spec, &table, source, count_tokens))
+}
+
+/// Estimates what `job_spec` would cost at current prices, per provider.
+/// Nothing is sent to any provider.
+#[tauri::command]
+pub async fn estimate_generation_cost(
+    app: AppHandle,
+    job_spec: serde_json::Value,
+) -> Result<CostEstimate, ServerError> {
+    let spec = parse_spec(&job_spec)?;
+    let (table, source) = table(&app);
+    // Loading the tokenizers the first time takes a moment.
+    blocking::run(move || estimate(&spec, &table, source, count_tokens))
+        .await
+        .map_err(ServerError::Estimate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn estimate_prices_each_provider_and_explains_its_confidence() {
+        let table: PriceTable = serde_json::from_value(json!({
+            "version": "2026-10-01",
+            "currency": "USD",
+            "models": {
+                "gpt-4o-mini": {
+                    "provider": "openai",
+                    "inputPerMillionTokens": 1.0,
+                    "outputPerMillionTokens": 2.0
+                },
+                "sora-2": { "provider": "openai", "perSecond": 0.1 },
+                "flash-image": { "provider": "google", "perImage": 0.04 }
+            }
+        }))
+        .unwrap();
+        let spec = parse_spec(&json!({
+            "textModel": "gpt-4o-mini",
+            "prompts": ["a", "b"],
+            "outputTokens": 500_000,
+            "imageModel": "flash-image",
+            "videoModel": "sora-2",
+            "scenes": [
+                { "durationSecs": 8, "lines": [{ "voice": "hero", "text": " 你好 " }] },
+                { "images": 2, "video": false, "lines": [{ "voice": "hero", "text": "走" }] }
+            ]
+        }))
+        .unwrap();
+        let counted = |_: &str, _: &str| (250_000, true);
+        let priced = estimate(&spec, &table, PriceSource::Bundled, counted);
+        assert_eq!(priced.counts.prompt_tokens, 500_000);
+        assert_eq!(priced.counts.images, 3);
+        assert_eq!((priced.counts.video_calls, priced.counts.video_secs), (1, 8.0));
+        assert_eq!(priced.counts.tts_chars["hero"], 3);
+        let by_provider: Vec<(&str, f64)> =
+            priced.providers.iter().map(|p| (p.provider.as_str(), p.cost)).collect();
+        // 0.5 + 2.0 for text and 0.8 for video; 3 images at 0.04.
+        assert_eq!(by_provider[0].0, "google");
+        assert!((by_provider[0].1 - 0.12).abs() < 1e-9);
+        assert!((by_provider[1].1 - 3.3).abs() < 1e-9);
+        // Dialogue with no speech model can't be priced.
+        assert_eq!(priced.confidence, Confidence::Low);
+        assert_eq!(priced.note, "no speech model is set, so it isn't priced");
+
+        let guessed = json!({ "textModel": "gpt-4o-mini", "prompts": ["a"] });
+        let guessed = parse_spec(&guessed).unwrap();
+        let priced = estimate(&guessed, &table, PriceSource::Cached, |_, _| (10, false));
+        assert_eq!(priced.confidence, Confidence::Medium);
+        assert_eq!(priced.counts.output_tokens, DEFAULT_OUTPUT_TOKENS);
+    }
+}
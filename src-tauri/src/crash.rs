@@ -0,0 +1,189 @@
+//! Crash reports in `app_log_dir()/crashes/`, one JSON file each: panics
+//! in the Rust shell, long-lived tasks that died, and sidecar crashes.
+//!
+//! The panic hook runs with nothing but the statics set by `init`, and
+//! gives up on writing after a short wait so a stuck filesystem can't hold
+//! up the process on its way down.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{error, warn};
+
+use crate::hardware::{self, HardwareReport};
+use crate::time::now_ms;
+
+/// Reports kept; older ones are removed at startup.
+const MAX_REPORTS: usize = 20;
+/// How long the panic hook waits for its report to be written.
+const HOOK_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CrashKind {
+    Panic,
+    /// A long-lived background task ended abnormally.
+    TaskFailed,
+    SidecarCrash,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub kind: CrashKind,
+    pub message: String,
+    /// `file:line` of a panic.
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: Option<String>,
+    pub app_version: Option<String>,
+    /// Only if it had been collected by then.
+    pub hardware: Option<HardwareReport>,
+    /// Unix time in milliseconds.
+    pub at: u64,
+}
+
+impl CrashReport {
+    fn new(kind: CrashKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            location: None,
+            thread: None,
+            backtrace: None,
+            app_version: APP.get().map(|app| app.package_info().version.to_string()),
+            hardware: APP.get().and_then(hardware::cached),
+            at: now_ms(),
+        }
+    }
+}
+
+fn write_report(dir: &Path, report: &CrashReport) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let json = serde_json::to_vec_pretty(report).map_err(std::io::Error::other)?;
+    let kind = serde_json::to_value(report.kind)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_owned))
+        .unwrap_or_default();
+    std::fs::write(dir.join(format!("{}-{}.json", report.at, kind)), json)
+}
+
+/// Tells the frontend, if a window is still there to listen.
+fn emit(report: &CrashReport) {
+    if let Some(app) = APP.get() {
+        if !app.webview_windows().is_empty() {
+            let _ = app.emit("app-error", report);
+        }
+    }
+}
+
+/// Saves a report and tells the frontend. Never fails; a report that
+/// can't be written is only logged.
+pub fn record(report: CrashReport) {
+    if let Some(dir) = DIR.get() {
+        if let Err(e) = write_report(dir, &report) {
+            warn!(error = %e, "Failed to write crash report");
+        }
+    }
+    emit(&report);
+}
+
+pub fn record_sidecar_crash(code: Option<i32>, signal: Option<i32>) {
+    let message = format!("server exited with code {:?}, signal {:?}", code, signal);
+    record(CrashReport::new(CrashKind::SidecarCrash, message));
+}
+
+/// Installs the panic hook. Reports go nowhere until `init` has run, but
+/// the default hook still prints them.
+pub fn install_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| (*s).to_owned())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic with a non-string payload".to_owned());
+        let mut report = CrashReport::new(CrashKind::Panic, message);
+        report.location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()));
+        report.thread = std::thread::current().name().map(str::to_owned);
+        report.backtrace = Some(std::backtrace::Backtrace::force_capture().to_string());
+
+        // Written on a separate thread so a hung disk only costs the timeout.
+        if let Some(dir) = DIR.get() {
+            let (done_tx, done_rx) = mpsc::channel();
+            let written = report.clone();
+            let spawned = std::thread::Builder::new()
+                .name("crash-report".into())
+                .spawn(move || {
+                    let _ = write_report(dir, &written);
+                    let _ = done_tx.send(());
+                });
+            if spawned.is_ok() {
+                let _ = done_rx.recv_timeout(HOOK_WRITE_TIMEOUT);
+            }
+        }
+        emit(&report);
+        default_hook(info);
+    }));
+}
+
+/// Remembers where reports go and drops all but the newest few.
+pub fn init(app: &AppHandle) {
+    let _ = APP.set(app.clone());
+    let Ok(dir) = app.path().app_log_dir().map(|d| d.join("crashes")) else {
+        return;
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    // Names start with the timestamp, so they sort oldest first.
+    files.sort();
+    let excess = files.len().saturating_sub(MAX_REPORTS);
+    for path in &files[..excess] {
+        let _ = std::fs::remove_file(path);
+    }
+    let _ = DIR.set(dir);
+}
+
+/// Runs a long-lived task, recording a report if it panics rather than
+/// letting it die unnoticed.
+pub fn spawn<F>(name: &'static str, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = tauri::async_runtime::spawn(task);
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = handle.await {
+            error!(task = name, error = %e, "Background task died");
+            record(CrashReport::new(
+                CrashKind::TaskFailed,
+                format!("background task {} died: {}", name, e),
+            ));
+        }
+    });
+}
+
+/// The newest panic or task failure, sidecar crashes aside, so the
+/// frontend can offer to send a report after the app hit an internal error.
+#[tauri::command]
+pub fn get_last_app_error() -> Option<CrashReport> {
+    let dir = DIR.get()?;
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let data = std::fs::read(entry.path()).ok()?;
+            serde_json::from_slice::<CrashReport>(&data).ok()
+        })
+        .filter(|r| r.kind != CrashKind::SidecarCrash)
+        .max_by_key(|r| r.at)
+}
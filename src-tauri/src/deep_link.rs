@@ -0,0 +1,221 @@
+//! `aiyou://` links, `.aiyou` project files opened from the OS and files
+//! dropped on the splash. All of them need the server, so any that arrive
+//! while it boots (or after it failed) wait until it is ready.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, DragDropEvent, Emitter, Manager, Url, Window, WindowEvent};
+use tauri_plugin_deep_link::DeepLinkExt;
+use tracing::warn;
+
+use crate::focus_main_window;
+use crate::server::{ServerState, ServerStatus};
+use crate::splash;
+
+const PROJECT_EXTENSION: &str = "aiyou";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DeepLink {
+    /// `aiyou://template/<id>`
+    Template { id: String },
+    /// A `.aiyou` project file, opened directly or through
+    /// `aiyou://open?path=<absolute path>`.
+    Project { path: String },
+    /// Other files dropped on the splash, for the frontend to import.
+    Files { paths: Vec<String> },
+}
+
+/// Links that arrived before the server was ready, delivered in order once
+/// it is or taken by `get_pending_intents`.
+#[derive(Default)]
+pub struct DeepLinkState {
+    pending: Mutex<Vec<DeepLink>>,
+}
+
+fn valid_template_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn project_file(path: PathBuf) -> Option<DeepLink> {
+    if path.extension().and_then(|e| e.to_str()) != Some(PROJECT_EXTENSION) {
+        return None;
+    }
+    // Must be a real file we can read.
+    if !path.is_file() || std::fs::File::open(&path).is_err() {
+        return None;
+    }
+    let path = path.canonicalize().unwrap_or(path);
+    Some(DeepLink::Project {
+        path: path.to_string_lossy().into_owned(),
+    })
+}
+
+fn from_url(url: &Url) -> Option<DeepLink> {
+    match url.scheme() {
+        "aiyou" => {
+            let segments: Vec<_> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+            match (url.host_str()?, segments.as_slice()) {
+                ("template", [id]) if valid_template_id(id) => {
+                    Some(DeepLink::Template { id: (*id).to_owned() })
+                }
+                ("open", []) => {
+                    let (_, path) = url.query_pairs().find(|(key, _)| key == "path")?;
+                    let path = PathBuf::from(path.as_ref());
+                    // There's no working directory to resolve against.
+                    path.is_absolute().then(|| project_file(path))?
+                }
+                _ => None,
+            }
+        }
+        "file" => project_file(url.to_file_path().ok()?),
+        _ => None,
+    }
+}
+
+/// Parses a launch argument: an `aiyou://` URL or a path to a project file,
+/// relative paths being resolved against `cwd`.
+fn from_arg(arg: &str, cwd: &Path) -> Option<DeepLink> {
+    if arg.starts_with("aiyou://") {
+        return from_url(&arg.parse().ok()?);
+    }
+    project_file(cwd.join(arg))
+}
+
+#[derive(Clone, Serialize)]
+struct OpenProject<'a> {
+    path: &'a str,
+}
+
+#[derive(Clone, Serialize)]
+struct FilesDropped<'a> {
+    paths: &'a [String],
+}
+
+/// Projects go out as `open-project`, dropped files as `files-dropped`,
+/// anything else as `deep-link`.
+fn emit(app: &AppHandle, link: &DeepLink) {
+    let emitted = match link {
+        DeepLink::Project { path } => app.emit("open-project", OpenProject { path }),
+        DeepLink::Files { paths } => app.emit("files-dropped", FilesDropped { paths }),
+        DeepLink::Template { .. } => app.emit("deep-link", link),
+    };
+    if let Err(e) = emitted {
+        warn!(error = %e, "Failed to emit deep link");
+    }
+}
+
+/// Delivers a link now if the server is ready, otherwise queues it.
+fn dispatch(app: &AppHandle, link: DeepLink) {
+    // While the splash is up the main window is hidden on purpose.
+    let visible = app
+        .get_webview_window("main")
+        .and_then(|w| w.is_visible().ok())
+        .unwrap_or(false);
+    if visible {
+        focus_main_window(app);
+    }
+
+    let state = app.state::<DeepLinkState>();
+    let mut pending = state.pending.lock().unwrap();
+    let ready = app.state::<ServerState>().status() == ServerStatus::Ready;
+    if ready && pending.is_empty() {
+        emit(app, &link);
+    } else if !pending.contains(&link) {
+        pending.push(link);
+    }
+}
+
+/// Handles `aiyou://` links and (on macOS) opened files forwarded by the
+/// deep-link plugin.
+fn on_open_urls(app: &AppHandle, urls: Vec<Url>) {
+    for url in urls {
+        match from_url(&url) {
+            Some(link) => dispatch(app, link),
+            None => warn!(%url, "Ignoring unsupported link"),
+        }
+    }
+}
+
+/// Handles argv forwarded from a second launch. Deep-link URLs in it are
+/// routed through the deep-link plugin, so only file paths are handled here.
+pub fn on_second_instance(app: &AppHandle, argv: Vec<String>, cwd: String) {
+    focus_main_window(app);
+    let cwd = PathBuf::from(cwd);
+    for arg in argv.iter().skip(1) {
+        if arg.starts_with("aiyou://") {
+            continue;
+        }
+        if let Some(link) = from_arg(arg, &cwd) {
+            dispatch(app, link);
+        }
+    }
+}
+
+/// Files dropped on the splash: project files are opened, the rest are
+/// passed on together.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    let WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) = event else {
+        return;
+    };
+    if !splash::is_splash(window.label()) {
+        return;
+    }
+    let app = window.app_handle();
+    let mut others = Vec::new();
+    for path in paths {
+        match project_file(path.clone()) {
+            Some(link) => dispatch(app, link),
+            None => others.push(path.to_string_lossy().into_owned()),
+        }
+    }
+    if !others.is_empty() {
+        dispatch(app, DeepLink::Files { paths: others });
+    }
+}
+
+/// Takes the links still waiting for the server, for a frontend that
+/// mounts after they would have been emitted. Taken links aren't emitted
+/// again.
+#[tauri::command]
+pub fn get_pending_intents(state: tauri::State<'_, DeepLinkState>) -> Vec<DeepLink> {
+    std::mem::take(&mut *state.pending.lock().unwrap())
+}
+
+pub fn init(app: &AppHandle) {
+    #[cfg(any(windows, target_os = "linux"))]
+    if let Err(e) = app.deep_link().register_all() {
+        warn!(error = %e, "Failed to register aiyou:// scheme");
+    }
+
+    let handle = app.clone();
+    app.deep_link().on_open_url(move |event| on_open_urls(&handle, event.urls()));
+
+    // Cold launch: links and files passed on the command line.
+    let cwd = std::env::current_dir().unwrap_or_default();
+    for arg in std::env::args().skip(1) {
+        if let Some(link) = from_arg(&arg, &cwd) {
+            dispatch(app, link);
+        }
+    }
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut rx = handle.state::<ServerState>().subscribe();
+        while rx.changed().await.is_ok() {
+            if *rx.borrow_and_update() != ServerStatus::Ready {
+                continue;
+            }
+            let state = handle.state::<DeepLinkState>();
+            let mut pending = state.pending.lock().unwrap();
+            for link in pending.drain(..) {
+                emit(&handle, &link);
+            }
+        }
+    });
+}
@@ -0,0 +1,154 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::AsyncWriteExt;
+
+use crate::assets::{self, VerifyStatus};
+use crate::bandwidth::BandwidthState;
+use crate::error::ServerError;
+use crate::server::ServerState;
+use crate::tempfiles;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgress<'a> {
+    url: &'a str,
+    dest: &'a str,
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+/// Total size from a `Content-Range: bytes 100-199/1000` header.
+fn content_range_total(resp: &reqwest::Response) -> Option<u64> {
+    resp.headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Streams `url` into `dest`, resuming from the partial file an earlier
+/// attempt left if it was interrupted. The partial file is kept when the
+/// transfer drops so a retry in the same session can resume, and removed
+/// when the server rejects the request or the final size doesn't match.
+/// With `sha256`, the finished file is verified and quarantined if it
+/// doesn't match.
+#[tauri::command]
+pub async fn download_file(
+    app: AppHandle,
+    url: String,
+    dest: String,
+    sha256: Option<String>,
+) -> Result<u64, ServerError> {
+    let dest_path = PathBuf::from(&dest);
+    // Keyed on both, so a retry finds the same partial file.
+    let key = format!("{}\n{}", url, dest);
+    let part = tempfiles::keyed_temp_path(&app, "downloads", &key, "part")?;
+    if let Some(dir) = dest_path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let client = app.state::<ServerState>().client();
+
+    let mut offset = tokio::fs::metadata(&part).await.map(|m| m.len()).unwrap_or(0);
+    let mut resp = loop {
+        let mut req = client.get(&url);
+        if offset > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let resp = req.send().await?;
+        // A stale partial that's already complete (or larger than the file).
+        if resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+            let _ = tokio::fs::remove_file(&part).await;
+            offset = 0;
+            continue;
+        }
+        break resp;
+    };
+
+    if !resp.status().is_success() {
+        tempfiles::discard(&app, &part).await;
+        return Err(ServerError::BadStatus(resp.status().as_u16()));
+    }
+
+    let resumed = resp.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let total = if resumed {
+        content_range_total(&resp)
+    } else {
+        // The server ignored the range; start over.
+        offset = 0;
+        resp.content_length()
+    };
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part)
+        .await?;
+
+    let mut downloaded = offset;
+    let mut last_emit = Instant::now();
+    let bandwidth = app.state::<BandwidthState>();
+    while let Some(chunk) = resp.chunk().await? {
+        bandwidth.down.throttle(chunk.len()).await;
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            last_emit = Instant::now();
+            let _ = app.emit(
+                "download-progress",
+                DownloadProgress {
+                    url: &url,
+                    dest: &dest,
+                    downloaded,
+                    total,
+                },
+            );
+        }
+    }
+    file.flush().await?;
+    drop(file);
+
+    if let Some(expected) = total {
+        if downloaded != expected {
+            tempfiles::discard(&app, &part).await;
+            return Err(ServerError::Download(format!(
+                "expected {} bytes, got {}",
+                expected, downloaded
+            )));
+        }
+    }
+
+    tempfiles::commit(&app, &part, &dest_path).await?;
+    if let Some(expected) = sha256 {
+        let result = assets::verify_download(&app, &dest_path, &expected).await;
+        if result.status != VerifyStatus::Ok {
+            return Err(ServerError::Download(format!(
+                "{} failed verification ({:?}){}",
+                dest,
+                result.status,
+                result
+                    .quarantined_to
+                    .map(|q| format!(", moved to {}", q))
+                    .unwrap_or_default()
+            )));
+        }
+    }
+    let _ = app.emit(
+        "download-progress",
+        DownloadProgress {
+            url: &url,
+            dest: &dest,
+            downloaded,
+            total: Some(downloaded),
+        },
+    );
+    Ok(downloaded)
+}
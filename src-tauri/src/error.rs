@@ -0,0 +1,271 @@
+use serde::ser::{Error as _, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+/// Errors returned from Tauri commands.
+///
+/// Serialized as `{ "code": "...", "message": "...", "details": ... }` so
+/// the frontend can branch on `code` instead of matching error text.
+/// `message` is the English text below, for logs and as a fallback;
+/// `details` is the variant's payload, such as the path for
+/// `fileNotFound` or the status for `badStatus`, and is left out for
+/// variants without one. Every command returns this rather than a bare
+/// string; a test below keeps it that way.
+#[derive(Debug, Clone, thiserror::Error, Serialize)]
+#[serde(remote = "Self", tag = "code", content = "details", rename_all = "camelCase")]
+pub enum ServerError {
+    #[error("server is not running")]
+    NotRunning,
+    #[error("failed to spawn server: {0}")]
+    SpawnFailed(String),
+    #[error("server is unreachable: {0}")]
+    Unreachable(String),
+    #[error("request to server timed out")]
+    Timeout,
+    #[error("server is already running")]
+    AlreadyRunning,
+    #[error("server responded with status {0}")]
+    BadStatus(u16),
+    #[error("invalid response from server: {0}")]
+    InvalidResponse(String),
+    #[error("i/o error: {0}")]
+    Io(String),
+    #[error("failed to show notification: {0}")]
+    Notification(String),
+    #[error("power management error: {0}")]
+    Power(String),
+    #[error("download failed: {0}")]
+    Download(String),
+    #[error("window error: {0}")]
+    Window(String),
+    #[error("invalid server config: {0}")]
+    InvalidConfig(String),
+    #[error("update failed: {0}")]
+    Update(String),
+    #[error("no update available")]
+    NoUpdate,
+    #[error("updates aren't configured in this build")]
+    UpdatesNotConfigured,
+    #[error("backup failed: {0}")]
+    Backup(String),
+    #[error("server is busy with active jobs")]
+    Busy,
+    #[error("import failed: {0}")]
+    Import(String),
+    #[error("subtitle export failed: {0}")]
+    Subtitles(String),
+    #[error("failed to set server priority: {0}")]
+    Priority(String),
+    #[error("render queue error: {0}")]
+    Queue(String),
+    #[error("asset library error: {0}")]
+    Library(String),
+    #[error("recovery snapshot error: {0}")]
+    Recovery(String),
+    #[error("clipboard error: {0}")]
+    Clipboard(String),
+    #[error("job {0} has no script")]
+    NoScript(String),
+    #[error("telemetry error: {0}")]
+    Telemetry(String),
+    #[error("invalid log level: {0}")]
+    Logging(String),
+    #[error("generation error: {0}")]
+    Generation(String),
+    #[error("job {0} not found")]
+    NotFound(String),
+    #[error("license error: {0}")]
+    License(String),
+    #[error("frame capture failed: {0}")]
+    Frame(String),
+    #[error("TTS cache error: {0}")]
+    TtsCache(String),
+    #[error("font error: {0}")]
+    Fonts(String),
+    #[error("server at {0} is run outside the app; start and stop it there")]
+    External(String),
+    #[error("invalid API request: {0}")]
+    Proxy(String),
+    #[error("server doesn't support {0}")]
+    Unsupported(String),
+    #[error("server is restarting; send again once it is ready")]
+    ServerRestarting,
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("ffmpeg failed: {0}")]
+    FfmpegFailed(String),
+    #[error("server is frozen while it is being updated")]
+    Frozen,
+    #[error("restart the server to apply {0}")]
+    RestartRequired(String),
+    #[error("project is locked: {0}")]
+    ProjectLocked(String),
+    #[error("file not found: {0}")]
+    FileNotFound(String),
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("not enough disk space: {0}")]
+    InsufficientDiskSpace(String),
+    #[error("invalid archive: {0}")]
+    Archive(String),
+    #[error("shortcut error: {0}")]
+    Shortcut(String),
+    #[error("no translations for locale: {0}")]
+    Locale(String),
+    #[error("data migration failed: {0}")]
+    Migration(String),
+    #[error("media watcher error: {0}")]
+    MediaWatch(String),
+    #[error("publish export failed: {0}")]
+    Publish(String),
+    #[error("cost estimate failed: {0}")]
+    Estimate(String),
+}
+
+impl Serialize for ServerError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // The derived form, `{ code, details }`, with the message added.
+        let tagged = ServerError::serialize(self, serde_json::value::Serializer)
+            .map_err(S::Error::custom)?;
+        let mut out = serializer.serialize_struct("ServerError", 3)?;
+        out.serialize_field("code", &tagged["code"])?;
+        out.serialize_field("message", &self.to_string())?;
+        match tagged.get("details") {
+            Some(details) => out.serialize_field("details", details)?,
+            None => out.skip_field("details")?,
+        }
+        out.end()
+    }
+}
+
+impl From<reqwest::Error> for ServerError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            ServerError::Timeout
+        } else if err.is_decode() {
+            ServerError::InvalidResponse(err.to_string())
+        } else {
+            ServerError::Unreachable(err.to_string())
+        }
+    }
+}
+
+impl From<tauri_plugin_shell::Error> for ServerError {
+    fn from(err: tauri_plugin_shell::Error) -> Self {
+        ServerError::SpawnFailed(err.to_string())
+    }
+}
+
+impl From<tauri_plugin_notification::Error> for ServerError {
+    fn from(err: tauri_plugin_notification::Error) -> Self {
+        ServerError::Notification(err.to_string())
+    }
+}
+
+impl From<tauri_plugin_updater::Error> for ServerError {
+    fn from(err: tauri_plugin_updater::Error) -> Self {
+        ServerError::Update(err.to_string())
+    }
+}
+
+impl From<tauri::Error> for ServerError {
+    fn from(err: tauri::Error) -> Self {
+        ServerError::Window(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for ServerError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => ServerError::FileNotFound(err.to_string()),
+            std::io::ErrorKind::PermissionDenied => ServerError::PermissionDenied(err.to_string()),
+            std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded => {
+                ServerError::InsufficientDiskSpace(err.to_string())
+            }
+            _ => ServerError::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for ServerError {
+    fn from(err: zip::result::ZipError) -> Self {
+        match err {
+            zip::result::ZipError::Io(err) => err.into(),
+            err => ServerError::Archive(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_keep_their_cause() {
+        let kind = |kind: std::io::ErrorKind| -> ServerError { std::io::Error::from(kind).into() };
+        assert!(matches!(kind(std::io::ErrorKind::NotFound), ServerError::FileNotFound(_)));
+        assert!(matches!(
+            kind(std::io::ErrorKind::PermissionDenied),
+            ServerError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            kind(std::io::ErrorKind::StorageFull),
+            ServerError::InsufficientDiskSpace(_)
+        ));
+        assert!(matches!(kind(std::io::ErrorKind::Interrupted), ServerError::Io(_)));
+
+        let json = serde_json::to_value(ServerError::FileNotFound("a.mp4".into())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "code": "fileNotFound",
+                "message": "file not found: a.mp4",
+                "details": "a.mp4",
+            })
+        );
+        let json = serde_json::to_value(ServerError::ServerRestarting).unwrap();
+        assert_eq!(json["code"], "serverRestarting");
+        assert!(json.get("details").is_none());
+    }
+
+    /// Every command in `generate_handler!` either can't fail or fails
+    /// with `ServerError`, so none can slip back to a bare string. The
+    /// check reads signatures, so it can't tell an infallible command from
+    /// one that reports errors inside its value; it does reject the bare
+    /// `String` and `Option<String>` errors used to come back as, outside
+    /// the commands listed in `STRING_RESULTS`.
+    #[test]
+    fn commands_return_server_error() {
+        const STRING_RESULTS: &[&str] = &["server::get_server_url"];
+        let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let lib = include_str!("lib.rs");
+        let start = lib.find("generate_handler![").expect("no generate_handler!");
+        let handlers = &lib[start..start + lib[start..].find(']').unwrap()];
+        let mut checked = 0;
+        for path in handlers.split([',', '[', '\n']).map(str::trim) {
+            let Some((module, name)) = path.split_once("::") else {
+                continue;
+            };
+            let file = std::fs::read_to_string(src.join(format!("{}.rs", module))).unwrap();
+            let at = file
+                .find(&format!("fn {}(", name))
+                .or_else(|| file.find(&format!("fn {}<", name)))
+                .unwrap_or_else(|| panic!("{} not found", path));
+            let signature = &file[at..at + file[at..].find('{').unwrap()];
+            let returns = signature.rsplit_once("->").map_or("", |(_, r)| r.trim());
+            assert!(
+                !returns.starts_with("Result") || returns.ends_with("ServerError>"),
+                "{} returns {}",
+                path,
+                returns
+            );
+            assert!(
+                !matches!(returns, "String" | "Option<String>") || STRING_RESULTS.contains(&path),
+                "{} returns {}",
+                path,
+                returns
+            );
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+}
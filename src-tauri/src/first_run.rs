@@ -0,0 +1,333 @@
+//! Setting up the app's data on first launch, and again when an update
+//! raises [`SETUP_VERSION`].
+//!
+//! Setup creates the directory layout, copies the starter templates listed
+//! in `resources/starter/manifest.json` into `app_data_dir()/templates`,
+//! imports the demo assets into the library and writes default settings.
+//! Every step can be run again safely: copies land by rename and are
+//! skipped once present, and the library ignores content it already has.
+//! `setup.json` is marked in progress before the first step and finished
+//! after the last, so a setup that was killed part way is simply run again
+//! on the next launch.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::error::ServerError;
+use crate::i18n;
+use crate::library::{self, AssetKind};
+use crate::settings::SettingsState;
+use crate::tempfiles;
+
+/// Raised when a release ships new starter content or needs the data
+/// layout changed; setup runs again on the first launch after.
+const SETUP_VERSION: u32 = 1;
+const STARTER_MANIFEST: &str = "resources/starter/manifest.json";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StarterManifest {
+    /// Paths relative to the manifest.
+    templates: Vec<String>,
+    assets: Vec<StarterAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StarterAsset {
+    /// Relative to the manifest.
+    file: String,
+    kind: AssetKind,
+    name: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SetupRecord {
+    /// `SETUP_VERSION` of the last setup that finished; 0 if none has.
+    version: u32,
+    /// Set while setup runs. Still set at launch means it was killed.
+    in_progress: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SetupStep {
+    Layout,
+    Templates,
+    Assets,
+    Settings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirstRunProgress {
+    pub step: SetupStep,
+    pub done: usize,
+    pub total: usize,
+    /// Splash text, e.g. `Installing templates 3/12…`.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnboardingState {
+    /// No setup had finished before this launch; the welcome tour is due.
+    pub first_run: bool,
+    /// The last launch was killed during setup, which ran again.
+    pub resumed: bool,
+    /// False while setup is still running.
+    pub setup_complete: bool,
+    pub setup_version: u32,
+}
+
+#[derive(Default)]
+pub struct FirstRunState {
+    onboarding: Mutex<OnboardingState>,
+}
+
+/// Whether setup has to run for `record`, and the onboarding state that
+/// results.
+fn plan(record: SetupRecord) -> Option<OnboardingState> {
+    if record.version >= SETUP_VERSION && !record.in_progress {
+        return None;
+    }
+    Some(OnboardingState {
+        first_run: record.version == 0,
+        resumed: record.in_progress,
+        setup_complete: false,
+        setup_version: record.version,
+    })
+}
+
+fn record_path(app: &AppHandle) -> Result<PathBuf, ServerError> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("setup.json"))
+}
+
+fn read_record(path: &Path) -> SetupRecord {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+fn write_record(path: &Path, record: SetupRecord) -> Result<(), ServerError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_vec_pretty(&record).map_err(|e| ServerError::Io(e.to_string()))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn progress(app: &AppHandle, step: SetupStep, done: usize, total: usize) {
+    let key = match step {
+        SetupStep::Layout | SetupStep::Settings => "firstRun.preparing",
+        SetupStep::Templates => "firstRun.templates",
+        SetupStep::Assets => "firstRun.assets",
+    };
+    let (done_text, total_text) = (done.to_string(), total.to_string());
+    let message = i18n::t_with(app, key, &[("done", &done_text), ("total", &total_text)]);
+    let _ = app.emit(
+        "first-run-progress",
+        FirstRunProgress {
+            step,
+            done,
+            total,
+            message,
+        },
+    );
+}
+
+fn create_layout(app: &AppHandle) -> Result<(), ServerError> {
+    let path = app.path();
+    let data = path.app_data_dir().map_err(|e| ServerError::Io(e.to_string()))?;
+    let dirs = [
+        path.app_config_dir().map_err(|e| ServerError::Io(e.to_string()))?,
+        path.app_log_dir().map_err(|e| ServerError::Io(e.to_string()))?,
+        data.join("projects"),
+        data.join("videos"),
+        data.join("library"),
+        data.join("templates"),
+        data.join("backups"),
+    ];
+    for dir in dirs {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+fn load_manifest(app: &AppHandle) -> Result<(StarterManifest, PathBuf), ServerError> {
+    let path = app
+        .path()
+        .resolve(STARTER_MANIFEST, BaseDirectory::Resource)
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    let data = std::fs::read_to_string(&path)?;
+    let manifest = serde_json::from_str(&data)
+        .map_err(|e| ServerError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+    let dir = path.parent().map(Path::to_owned).unwrap_or_default();
+    Ok((manifest, dir))
+}
+
+async fn install_templates(
+    app: &AppHandle,
+    templates: &[String],
+    from: &Path,
+) -> Result<(), ServerError> {
+    let dest_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?
+        .join("templates");
+    for (i, template) in templates.iter().enumerate() {
+        progress(app, SetupStep::Templates, i, templates.len());
+        let source = from.join(template);
+        let Some(name) = source.file_name() else {
+            continue;
+        };
+        let dest = dest_dir.join(name);
+        // Present means installed, or since edited by the user.
+        if dest.exists() {
+            continue;
+        }
+        let ext = source.extension().and_then(|e| e.to_str()).unwrap_or("part");
+        let tmp = tempfiles::create_temp_path(app, "starter", ext)?;
+        if let Err(e) = tokio::fs::copy(&source, &tmp).await {
+            tempfiles::discard(app, &tmp).await;
+            return Err(e.into());
+        }
+        tempfiles::commit(app, &tmp, &dest).await?;
+    }
+    progress(app, SetupStep::Templates, templates.len(), templates.len());
+    Ok(())
+}
+
+async fn install_assets(
+    app: &AppHandle,
+    assets: &[StarterAsset],
+    from: &Path,
+) -> Result<(), ServerError> {
+    for (i, asset) in assets.iter().enumerate() {
+        progress(app, SetupStep::Assets, i, assets.len());
+        library::add(app, asset.kind, &from.join(&asset.file), &asset.name, &asset.tags).await?;
+    }
+    progress(app, SetupStep::Assets, assets.len(), assets.len());
+    Ok(())
+}
+
+async fn setup(app: &AppHandle) -> Result<(), ServerError> {
+    progress(app, SetupStep::Layout, 0, 1);
+    create_layout(app)?;
+    let (manifest, from) = load_manifest(app)?;
+    install_templates(app, &manifest.templates, &from).await?;
+    install_assets(app, &manifest.assets, &from).await?;
+    progress(app, SetupStep::Settings, 0, 1);
+    // Writes the current settings, defaults included, if there is no file.
+    app.state::<SettingsState>().update(|_| {})?;
+    Ok(())
+}
+
+/// Runs setup if this launch needs it. Called before the sidecar is
+/// spawned; failures are logged and retried on the next launch rather
+/// than keeping the app from starting.
+pub async fn run(app: &AppHandle) {
+    let state = app.state::<FirstRunState>();
+    let path = match record_path(app) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!(error = %e, "Skipping first-run setup");
+            return;
+        }
+    };
+    let record = read_record(&path);
+    let Some(onboarding) = plan(record) else {
+        *state.onboarding.lock().unwrap() = OnboardingState {
+            setup_complete: true,
+            setup_version: record.version,
+            ..OnboardingState::default()
+        };
+        return;
+    };
+    *state.onboarding.lock().unwrap() = onboarding.clone();
+    info!(
+        from_version = record.version,
+        to_version = SETUP_VERSION,
+        resumed = onboarding.resumed,
+        "Running first-run setup"
+    );
+
+    let started = SetupRecord {
+        in_progress: true,
+        ..record
+    };
+    let finished = async {
+        write_record(&path, started)?;
+        setup(app).await?;
+        write_record(
+            &path,
+            SetupRecord {
+                version: SETUP_VERSION,
+                in_progress: false,
+            },
+        )
+    };
+    match finished.await {
+        Ok(()) => {
+            let mut onboarding = state.onboarding.lock().unwrap();
+            onboarding.setup_complete = true;
+            onboarding.setup_version = SETUP_VERSION;
+            info!("First-run setup finished");
+        }
+        Err(e) => warn!(error = %e, "First-run setup failed; it will run again next launch"),
+    }
+}
+
+/// What the frontend needs to decide on the welcome tour.
+#[tauri::command]
+pub fn get_onboarding_state(state: tauri::State<'_, FirstRunState>) -> OnboardingState {
+    state.onboarding.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_runs_until_a_current_version_finishes() {
+        let fresh = plan(SetupRecord::default()).unwrap();
+        assert!(fresh.first_run && !fresh.resumed);
+
+        // Killed during the very first setup: still a first run.
+        let killed = plan(SetupRecord {
+            version: 0,
+            in_progress: true,
+        })
+        .unwrap();
+        assert!(killed.first_run && killed.resumed);
+
+        // Killed during a setup run for an update.
+        assert!(plan(SetupRecord {
+            version: SETUP_VERSION,
+            in_progress: true,
+        })
+        .is_some_and(|o| !o.first_run && o.resumed));
+
+        assert!(plan(SetupRecord {
+            version: SETUP_VERSION,
+            in_progress: false,
+        })
+        .is_none());
+    }
+}
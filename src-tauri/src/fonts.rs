@@ -0,0 +1,298 @@
+//! Installed fonts for the subtitle style picker, with whether each covers
+//! Chinese and Latin text, since a font without CJK glyphs burns tofu boxes
+//! into the video.
+//!
+//! Checking coverage means reading every font's cmap, so the list is kept
+//! in `app_cache_dir()/fonts.json` and only rebuilt when a font directory's
+//! mtime moves. Fonts shipped in the app's resources are registered with
+//! `register_bundled_font`, always listed first and preferred as defaults.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::path::BaseDirectory;
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::blocking;
+use crate::error::ServerError;
+
+/// Common hanzi and full-width punctuation; a font needs all of them for
+/// dialogue to render cleanly.
+const CJK_SAMPLE: &str =
+    "的一是不了人我在有他这中大来上个们到说和你地出道也时年得就那要下以生会自着去之过家学对可她里后小么心多天而能好都然没，。！？、：；“”《》…";
+/// Letters, digits and the punctuation dialogue uses.
+const LATIN_SAMPLE: &str =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789.,;:!?'\"()-";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Coverage {
+    None,
+    Partial,
+    Full,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontStyle {
+    /// PostScript name, e.g. `NotoSansSC-Bold`.
+    pub name: String,
+    pub weight: u16,
+    pub italic: bool,
+    pub path: String,
+    /// Face index within a collection file.
+    pub index: u32,
+    pub cjk: Coverage,
+    pub latin: Coverage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontFamily {
+    pub family: String,
+    /// Best coverage among the styles.
+    pub cjk: Coverage,
+    pub latin: Coverage,
+    /// Shipped with the app rather than installed.
+    pub bundled: bool,
+    /// Lightest upright style first.
+    pub styles: Vec<FontStyle>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontList {
+    pub families: Vec<FontFamily>,
+    /// The family to preselect: the first bundled one that fully covers
+    /// CJK, otherwise the first installed one that does.
+    pub default_family: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FontCache {
+    /// Newest directory mtime under each font directory, in seconds.
+    fingerprint: BTreeMap<String, u64>,
+    families: Vec<FontFamily>,
+}
+
+/// Bundled font files registered so far.
+#[derive(Default)]
+pub struct FontsState {
+    bundled: Mutex<Vec<PathBuf>>,
+}
+
+/// Where the OS and the user install fonts.
+fn font_dirs(app: &AppHandle) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let home = app.path().home_dir().ok();
+    if cfg!(target_os = "macos") {
+        dirs.push(PathBuf::from("/System/Library/Fonts"));
+        dirs.push(PathBuf::from("/Library/Fonts"));
+        dirs.extend(home.map(|h| h.join("Library/Fonts")));
+    } else if cfg!(windows) {
+        let windir = std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+        dirs.push(PathBuf::from(windir).join("Fonts"));
+        // Fonts installed for the current user only.
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            dirs.push(PathBuf::from(local).join("Microsoft\\Windows\\Fonts"));
+        }
+    } else {
+        dirs.push(PathBuf::from("/usr/share/fonts"));
+        dirs.push(PathBuf::from("/usr/local/share/fonts"));
+        if let Some(home) = home {
+            dirs.push(home.join(".local/share/fonts"));
+            dirs.push(home.join(".fonts"));
+        }
+    }
+    dirs
+}
+
+/// Newest mtime of `dir` and the directories below it; a font added to
+/// a subdirectory only touches that subdirectory.
+fn newest_mtime(dir: &Path) -> u64 {
+    let own = std::fs::metadata(dir)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return own;
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| newest_mtime(&e.path()))
+        .fold(own, u64::max)
+}
+
+fn fingerprint(dirs: &[PathBuf]) -> BTreeMap<String, u64> {
+    dirs.iter()
+        .filter(|d| d.is_dir())
+        .map(|d| (d.to_string_lossy().into_owned(), newest_mtime(d)))
+        .collect()
+}
+
+fn coverage(face: &ttf_parser::Face, sample: &str) -> Coverage {
+    let total = sample.chars().count();
+    let covered = sample
+        .chars()
+        .filter(|c| face.glyph_index(*c).is_some())
+        .count();
+    match covered {
+        0 => Coverage::None,
+        n if n == total => Coverage::Full,
+        _ => Coverage::Partial,
+    }
+}
+
+/// Parses everything loaded into `db` and groups it by family.
+fn families(db: &fontdb::Database, bundled: bool) -> Vec<FontFamily> {
+    let mut by_family: BTreeMap<String, Vec<FontStyle>> = BTreeMap::new();
+    for face in db.faces() {
+        let fontdb::Source::File(path) = &face.source else {
+            continue;
+        };
+        let Some(family) = face.families.first().map(|(name, _)| name.clone()) else {
+            continue;
+        };
+        let Some((cjk, latin)) = db
+            .with_face_data(face.id, |data, index| {
+                let parsed = ttf_parser::Face::parse(data, index).ok()?;
+                Some((coverage(&parsed, CJK_SAMPLE), coverage(&parsed, LATIN_SAMPLE)))
+            })
+            .flatten()
+        else {
+            continue;
+        };
+        by_family.entry(family).or_default().push(FontStyle {
+            name: face.post_script_name.clone(),
+            weight: face.weight.0,
+            italic: face.style != fontdb::Style::Normal,
+            path: path.to_string_lossy().into_owned(),
+            index: face.index,
+            cjk,
+            latin,
+        });
+    }
+    by_family
+        .into_iter()
+        .map(|(family, mut styles)| {
+            styles.sort_by_key(|s| (s.italic, s.weight));
+            FontFamily {
+                family,
+                cjk: styles.iter().map(|s| s.cjk).max().unwrap_or(Coverage::None),
+                latin: styles.iter().map(|s| s.latin).max().unwrap_or(Coverage::None),
+                bundled,
+                styles,
+            }
+        })
+        .collect()
+}
+
+fn installed_fonts(dirs: &[PathBuf], cache_path: Option<&Path>) -> Vec<FontFamily> {
+    let fingerprint = fingerprint(dirs);
+    let cached = cache_path
+        .and_then(|p| std::fs::read(p).ok())
+        .and_then(|data| serde_json::from_slice::<FontCache>(&data).ok())
+        .filter(|cache| cache.fingerprint == fingerprint);
+    if let Some(cache) = cached {
+        return cache.families;
+    }
+
+    let mut db = fontdb::Database::new();
+    for dir in dirs {
+        db.load_fonts_dir(dir);
+    }
+    let families = families(&db, false);
+    info!(families = families.len(), "Scanned installed fonts");
+
+    if let Some(path) = cache_path {
+        let cache = FontCache {
+            fingerprint,
+            families,
+        };
+        let written = serde_json::to_vec(&cache)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, path)
+            });
+        if let Err(e) = written {
+            warn!(error = %e, "Failed to cache font list");
+        }
+        return cache.families;
+    }
+    families
+}
+
+fn bundled_fonts(paths: &[PathBuf]) -> Vec<FontFamily> {
+    let mut db = fontdb::Database::new();
+    for path in paths {
+        if let Err(e) = db.load_font_file(path) {
+            warn!(path = %path.display(), error = %e, "Failed to load bundled font");
+        }
+    }
+    families(&db, true)
+}
+
+/// Installed and bundled fonts by family, bundled first, then by name.
+#[tauri::command]
+pub async fn list_system_fonts(app: AppHandle) -> Result<FontList, ServerError> {
+    blocking::watched("list_system_fonts", async move {
+        let dirs = font_dirs(&app);
+        let cache_path = app.path().app_cache_dir().ok().map(|d| d.join("fonts.json"));
+        let bundled = app.state::<FontsState>().bundled.lock().unwrap().clone();
+        let mut families = blocking::run(move || {
+            let mut families = bundled_fonts(&bundled);
+            // A bundled copy takes the place of an installed one.
+            let installed: Vec<_> = installed_fonts(&dirs, cache_path.as_deref())
+                .into_iter()
+                .filter(|f| !families.iter().any(|b| b.family == f.family))
+                .collect();
+            families.extend(installed);
+            families
+        })
+        .await
+        .map_err(ServerError::Fonts)?;
+        families.sort_by_key(|f| !f.bundled);
+
+        let default_family = families
+            .iter()
+            .find(|f| f.cjk == Coverage::Full)
+            .map(|f| f.family.clone());
+        Ok(FontList {
+            families,
+            default_family,
+        })
+    })
+    .await
+}
+
+/// Adds a font shipped in the app's resources to the list. `path` is
+/// relative to the resource dir.
+#[tauri::command]
+pub fn register_bundled_font(app: AppHandle, path: String) -> Result<String, ServerError> {
+    let resolved = app.path().resolve(&path, BaseDirectory::Resource)?;
+    let resources = app.path().resource_dir()?;
+    let inside = resolved
+        .canonicalize()
+        .ok()
+        .zip(resources.canonicalize().ok())
+        .is_some_and(|(p, r)| p.starts_with(r) && p.is_file());
+    if !inside {
+        return Err(ServerError::Fonts(format!("not a bundled font: {}", path)));
+    }
+    let state = app.state::<FontsState>();
+    let mut bundled = state.bundled.lock().unwrap();
+    if !bundled.contains(&resolved) {
+        bundled.push(resolved.clone());
+    }
+    Ok(resolved.to_string_lossy().into_owned())
+}
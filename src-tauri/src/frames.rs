@@ -0,0 +1,172 @@
+//! Still frames taken from a rendered video with ffmpeg, e.g. to use as an
+//! episode cover.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::backups::valid_project_id;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::recents;
+use crate::tempfiles;
+
+/// Seeking to the exact duration yields no frame, so the last frame is
+/// taken from this far before the end.
+const END_MARGIN_MS: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameFormat {
+    Png,
+    Jpg,
+}
+
+impl FrameFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Jpg => "jpg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedFrame {
+    pub path: String,
+    /// Where the frame was actually taken from.
+    pub timestamp_ms: u64,
+    /// Set when the requested timestamp was past the end of the video.
+    pub warning: Option<String>,
+}
+
+/// Duration in milliseconds, from `ffprobe`.
+pub(crate) async fn duration_ms(video: &Path) -> Result<u64, ServerError> {
+    let output = tokio::process::Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of"])
+        .arg("default=noprint_wrappers=1:nokey=1")
+        .arg(video)
+        .output()
+        .await
+        .map_err(|e| ServerError::Frame(format!("can't run ffprobe: {}", e)))?;
+    if !output.status.success() {
+        return Err(ServerError::FfmpegFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    let secs: f64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|_| ServerError::Frame("video has no duration".into()))?;
+    Ok((secs * 1000.0) as u64)
+}
+
+fn covers_dir(app: &AppHandle, project_id: &str) -> Result<PathBuf, ServerError> {
+    if !valid_project_id(project_id) {
+        return Err(ServerError::Frame(format!(
+            "invalid project id: {}",
+            project_id
+        )));
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("projects").join(project_id).join("covers"))
+}
+
+/// Saves the frame at `timestamp_ms` of `video_path` to `dest`, or to the
+/// project's `covers` folder if no destination was chosen. With
+/// `set_as_cover`, the frame also becomes the project's thumbnail in the
+/// recents list.
+#[tauri::command]
+pub async fn capture_frame(
+    app: AppHandle,
+    video_path: String,
+    timestamp_ms: u64,
+    output_format: FrameFormat,
+    dest: Option<String>,
+    project_id: Option<String>,
+    set_as_cover: Option<bool>,
+) -> Result<CapturedFrame, ServerError> {
+    blocking::watched("capture_frame", async move {
+        let set_as_cover = set_as_cover.unwrap_or(false);
+        if set_as_cover && project_id.is_none() {
+            return Err(ServerError::Frame(
+                "a project is required to set a cover".into(),
+            ));
+        }
+        let video = PathBuf::from(&video_path);
+        let duration = duration_ms(&video).await?;
+        let last = duration.saturating_sub(END_MARGIN_MS);
+        let (at, warning) = if timestamp_ms > last {
+            let message = format!(
+                "{} ms is past the end of the video ({} ms); used the last frame",
+                timestamp_ms, duration
+            );
+            warn!(video = %video_path, timestamp_ms, duration, "Frame timestamp clamped");
+            (last, Some(message))
+        } else {
+            (timestamp_ms, None)
+        };
+
+        let ext = output_format.extension();
+        let dest = match (dest, &project_id) {
+            (Some(dest), _) => PathBuf::from(dest),
+            (None, Some(id)) => covers_dir(&app, id)?.join(format!("{}.{}", at, ext)),
+            (None, None) => {
+                return Err(ServerError::Frame(
+                    "either a destination or a project is required".into(),
+                ))
+            }
+        };
+        if let Some(dir) = dest.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+
+        // ffmpeg picks the encoder from the extension, so the temporary
+        // name keeps it.
+        let tmp = tempfiles::create_temp_path(&app, "frames", ext)?;
+        let mut cmd = tokio::process::Command::new("ffmpeg");
+        // An input `-ss` is frame-accurate when decoding, not snapped to the
+        // previous keyframe.
+        cmd.args(["-v", "error", "-y", "-accurate_seek", "-ss"])
+            .arg(format!("{}.{:03}", at / 1000, at % 1000))
+            .arg("-i")
+            .arg(&video)
+            .args(["-frames:v", "1"]);
+        if output_format == FrameFormat::Jpg {
+            cmd.args(["-q:v", "2"]);
+        }
+        let output = cmd
+            .arg(&tmp)
+            .output()
+            .await
+            .map_err(|e| ServerError::Frame(format!("can't run ffmpeg: {}", e)))?;
+        if !output.status.success() || !tmp.exists() {
+            tempfiles::discard(&app, &tmp).await;
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ServerError::FfmpegFailed(if stderr.trim().is_empty() {
+                "ffmpeg produced no frame".to_owned()
+            } else {
+                stderr.trim().to_owned()
+            }));
+        }
+        tempfiles::commit(&app, &tmp, &dest).await?;
+
+        let path = dest.to_string_lossy().into_owned();
+        if set_as_cover {
+            if let Some(id) = &project_id {
+                recents::set_thumbnail(&app, id, &path)?;
+            }
+        }
+        Ok(CapturedFrame {
+            path,
+            timestamp_ms: at,
+            warning,
+        })
+    })
+    .await
+}
@@ -0,0 +1,383 @@
+//! Generations started from the frontend, proxied to the sidecar with a cap
+//! on how many are in flight at once.
+//!
+//! The sidecar doesn't limit concurrent generations itself, so without this
+//! a user clicking faster than providers answer can pile up requests until
+//! it runs out of memory. A slot is taken when a generation is submitted
+//! and held by its job until the job finishes or is cancelled.
+//!
+//! Before a graceful restart the slots are drained: no new generation is
+//! accepted, and the restart waits for the ones in flight to finish.
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::api;
+use crate::crash;
+use crate::error::ServerError;
+use crate::generation_schema::{self, ValidationReport};
+use crate::job_poll;
+use crate::server::{ServerState, ServerStatus};
+use crate::settings::SettingsState;
+
+/// Upper bound on `Settings::max_concurrent_generations`.
+const MAX_CONCURRENT: usize = 16;
+/// Upper bound on `Settings::generation_retries`.
+const MAX_RETRIES: u32 = 5;
+/// Wait before the first retry, doubled for each one after.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+/// How long to wait for the server to confirm a cancellation.
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Slots {
+    /// Requests the server hasn't accepted yet.
+    submitting: usize,
+    /// Accepted generations by job id.
+    jobs: HashSet<String>,
+    /// No new generation is accepted while set.
+    draining: bool,
+}
+
+impl Slots {
+    fn taken(&self) -> usize {
+        self.submitting + self.jobs.len()
+    }
+}
+
+/// Slots in use. A counter rather than a fixed-size semaphore, so a changed
+/// limit applies to the next request without a restart.
+#[derive(Default)]
+pub struct GenerationState {
+    slots: Mutex<Slots>,
+    /// Woken whenever a slot is given back.
+    freed: Notify,
+}
+
+/// A slot taken for a request in flight. Given back when dropped, unless
+/// the request produced a job that keeps it.
+struct Reservation<'a> {
+    state: &'a GenerationState,
+}
+
+impl Reservation<'_> {
+    fn keep_for(self, job_id: String) {
+        self.state.slots.lock().unwrap().jobs.insert(job_id);
+        // Drop still runs and ends the `submitting` half.
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.state.slots.lock().unwrap().submitting -= 1;
+        self.state.freed.notify_waiters();
+    }
+}
+
+impl GenerationState {
+    fn try_reserve(&self, limit: usize) -> Option<Reservation<'_>> {
+        let mut slots = self.slots.lock().unwrap();
+        if slots.draining || slots.taken() >= limit {
+            return None;
+        }
+        slots.submitting += 1;
+        Some(Reservation { state: self })
+    }
+
+    /// Frees a job's slot. Returns whether it held one.
+    fn release(&self, job_id: &str) -> bool {
+        let held = self.slots.lock().unwrap().jobs.remove(job_id);
+        if held {
+            self.freed.notify_waiters();
+        }
+        held
+    }
+
+    /// Frees every job's slot once the server has gone down, since its jobs
+    /// went with it and no completion event will come for them. Returns
+    /// how many were freed.
+    fn on_server_status(&self, status: ServerStatus) -> usize {
+        if !matches!(
+            status,
+            ServerStatus::Crashed | ServerStatus::Stopped | ServerStatus::Failed
+        ) {
+            return 0;
+        }
+        let freed = std::mem::take(&mut self.slots.lock().unwrap().jobs).len();
+        if freed > 0 {
+            self.freed.notify_waiters();
+        }
+        freed
+    }
+
+    fn set_draining(&self, draining: bool) {
+        self.slots.lock().unwrap().draining = draining;
+    }
+}
+
+/// Gives back the slots of jobs lost when the sidecar stops, crashes or
+/// fails, so they don't stay taken for the rest of the session.
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("generation-slots", async move {
+        let mut rx = handle.state::<ServerState>().subscribe();
+        while rx.changed().await.is_ok() {
+            let status = *rx.borrow_and_update();
+            let freed = handle.state::<GenerationState>().on_server_status(status);
+            if freed > 0 {
+                info!(freed, ?status, "Freed generation slots after server stop");
+            }
+        }
+    });
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Draining {
+    /// Generations still in flight.
+    remaining: usize,
+    /// The timeout elapsed with `remaining` still running.
+    forced: bool,
+}
+
+/// Stops accepting generations and waits up to `timeout` for those in
+/// flight to finish, emitting `server-draining` whenever the count moves.
+/// Returns whether they all finished. New generations stay refused until
+/// [`end_drain`].
+pub async fn drain(app: &AppHandle, timeout: Duration) -> bool {
+    let state = app.state::<GenerationState>();
+    state.set_draining(true);
+    let deadline = Instant::now() + timeout;
+    let mut last = None;
+    loop {
+        // Registered before reading the count, so a slot freed in between
+        // still wakes this wait.
+        let freed = state.freed.notified();
+        tokio::pin!(freed);
+        freed.as_mut().enable();
+
+        let remaining = state.slots.lock().unwrap().taken();
+        if last != Some(remaining) {
+            last = Some(remaining);
+            let _ = app.emit(
+                "server-draining",
+                Draining {
+                    remaining,
+                    forced: false,
+                },
+            );
+        }
+        if remaining == 0 {
+            return true;
+        }
+        if tokio::time::timeout_at(deadline, freed).await.is_err() {
+            warn!(remaining, "Drain timed out; restarting anyway");
+            let _ = app.emit(
+                "server-draining",
+                Draining {
+                    remaining,
+                    forced: true,
+                },
+            );
+            return false;
+        }
+    }
+}
+
+/// Accepts generations again after [`drain`].
+pub fn end_drain(app: &AppHandle) {
+    app.state::<GenerationState>().set_draining(false);
+    info!("Accepting generations again");
+}
+
+/// The job id in a provider response as the sidecar relays it.
+fn job_id(response: &serde_json::Value) -> Option<String> {
+    ["id", "task_id", "taskId"]
+        .iter()
+        .find_map(|key| response.get(*key))
+        .or_else(|| response.get("data")?.get("taskId"))
+        .and_then(|v| v.as_str())
+        .map(str::to_owned)
+}
+
+/// Called by the event bridge to free the slots of finished generations.
+pub fn on_server_event(app: &AppHandle, event: &str, payload: &serde_json::Value) {
+    if !matches!(
+        event,
+        "generation-complete" | "generation-error" | "job-complete"
+    ) {
+        return;
+    }
+    if let Some(id) = payload
+        .get("jobId")
+        .or_else(|| payload.get("id"))
+        .and_then(|v| v.as_str())
+    {
+        app.state::<GenerationState>().release(id);
+    }
+}
+
+/// Unique per generation started, across restarts of the app.
+fn idempotency_key() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!(
+        "{:x}-{:x}-{:x}",
+        nanos,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Submits a generation, retrying with exponential backoff while that
+/// can't create it twice: when the request never reached the server, or
+/// when it timed out and the server dedupes by idempotency key.
+async fn submit(
+    server: &ServerState,
+    endpoint: &str,
+    api_key: Option<&str>,
+    body: &serde_json::Value,
+    retries: u32,
+) -> Result<serde_json::Value, ServerError> {
+    let key = idempotency_key();
+    let mut dedupes = None;
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        let failed = match api::create_generation(server, endpoint, api_key, &key, body).await {
+            Ok(response) => return Ok(response),
+            Err(failed) => failed,
+        };
+        let safe = if !failed.sent {
+            true
+        } else if matches!(failed.error, ServerError::Timeout) {
+            if dedupes.is_none() {
+                let health = api::get_health(server).await;
+                dedupes = Some(health.is_ok_and(|h| h.idempotency_keys));
+            }
+            dedupes == Some(true)
+        } else {
+            false
+        };
+        if !safe || attempt >= retries {
+            return Err(failed.error);
+        }
+        attempt += 1;
+        warn!(%endpoint, attempt, error = %failed.error, ?delay, "Retrying generation");
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelUnconfirmed {
+    job_id: String,
+    reason: String,
+}
+
+/// Proxies a generation request to `endpoint` once it passes
+/// [`validate_generation_request`]. Fails with `Busy` right away when the
+/// configured number of generations is already in flight or the slots are
+/// being drained for a restart, so the UI can queue the request or disable
+/// its button.
+#[tauri::command]
+pub async fn start_generation(
+    app: AppHandle,
+    endpoint: String,
+    body: serde_json::Value,
+    api_key: Option<String>,
+) -> Result<serde_json::Value, ServerError> {
+    let report = validate(&endpoint, &body, api_key.as_deref())?;
+    if !report.valid {
+        return Err(ServerError::Generation(report.summary()));
+    }
+    let settings = app.state::<SettingsState>().get();
+    let limit = settings.max_concurrent_generations.clamp(1, MAX_CONCURRENT);
+    let retries = settings.generation_retries.min(MAX_RETRIES);
+    let state = app.state::<GenerationState>();
+    let reservation = state.try_reserve(limit).ok_or(ServerError::Busy)?;
+    let server = app.state::<ServerState>();
+    let response = submit(&server, &endpoint, api_key.as_deref(), &body, retries).await?;
+    if let Some(id) = job_id(&response) {
+        reservation.keep_for(id.clone());
+        job_poll::watch(&app, id);
+    }
+    Ok(response)
+}
+
+fn validate(
+    endpoint: &str,
+    body: &serde_json::Value,
+    api_key: Option<&str>,
+) -> Result<ValidationReport, ServerError> {
+    generation_schema::validate(endpoint, body, api_key).ok_or_else(|| {
+        ServerError::Generation(format!("not a generation endpoint: {}", endpoint))
+    })
+}
+
+/// Checks a generation request against the rules `start_generation`
+/// applies, without sending it, so the UI can show errors by field.
+#[tauri::command]
+pub fn validate_generation_request(
+    endpoint: String,
+    body: serde_json::Value,
+    api_key: Option<String>,
+) -> Result<ValidationReport, ServerError> {
+    validate(&endpoint, &body, api_key.as_deref())
+}
+
+/// Cancels a generation on the server and frees its slot. The slot is
+/// freed even if the server doesn't confirm in time; the frontend gets
+/// `generation-cancel-unconfirmed` then.
+#[tauri::command]
+pub async fn cancel_generation(app: AppHandle, job_id: String) -> Result<(), ServerError> {
+    let held = app.state::<GenerationState>().release(&job_id);
+    job_poll::forget(&app, &job_id);
+    let server = app.state::<ServerState>();
+    let cancelled = tokio::time::timeout(CANCEL_TIMEOUT, api::cancel_job(&server, &job_id)).await;
+    let reason = match cancelled {
+        Ok(Ok(())) => return Ok(()),
+        // Already gone on the server; fine if it was one of ours.
+        Ok(Err(ServerError::BadStatus(404))) if held => return Ok(()),
+        Ok(Err(ServerError::BadStatus(404))) => return Err(ServerError::NotFound(job_id)),
+        Ok(Err(e @ (ServerError::Unreachable(_) | ServerError::Timeout))) => e.to_string(),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => "server did not confirm in time".to_owned(),
+    };
+    warn!(%job_id, %reason, "Cancellation not confirmed");
+    let _ = app.emit(
+        "generation-cancel-unconfirmed",
+        CancelUnconfirmed { job_id, reason },
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_going_down_frees_job_slots() {
+        let state = GenerationState::default();
+        for job in ["a", "b", "c"] {
+            state.try_reserve(3).unwrap().keep_for(job.into());
+        }
+        assert!(state.try_reserve(3).is_none());
+
+        assert_eq!(state.on_server_status(ServerStatus::Ready), 0);
+        assert_eq!(state.on_server_status(ServerStatus::Crashed), 3);
+        assert_eq!(state.slots.lock().unwrap().taken(), 0);
+        assert!(!state.release("a"));
+        assert!(state.try_reserve(3).is_some());
+    }
+}
@@ -0,0 +1,294 @@
+//! What each generation endpoint requires of a request body, checked before
+//! it is sent.
+//!
+//! The rules follow what the sidecar's handlers read, and what they or the
+//! providers behind them reject. `validate_generation_request` and
+//! `start_generation` both check against them, so a request that validates
+//! is one `start_generation` will send.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    /// A string with something other than whitespace in it.
+    Text,
+    /// Providers take durations either way, e.g. `10` or `"10s"`.
+    TextOrNumber,
+    Bool,
+    TextList,
+    Object,
+}
+
+impl FieldKind {
+    fn accepts(self, value: &serde_json::Value) -> bool {
+        match self {
+            FieldKind::Text => value.as_str().is_some_and(|s| !s.trim().is_empty()),
+            FieldKind::TextOrNumber => {
+                value.is_number() || value.as_str().is_some_and(|s| !s.trim().is_empty())
+            }
+            FieldKind::Bool => value.is_boolean(),
+            FieldKind::TextList => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(serde_json::Value::is_string)),
+            FieldKind::Object => value.is_object(),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            FieldKind::Text => "a non-empty string",
+            FieldKind::TextOrNumber => "a number or a non-empty string",
+            FieldKind::Bool => "true or false",
+            FieldKind::TextList => "a list of strings",
+            FieldKind::Object => "an object",
+        }
+    }
+}
+
+struct Field {
+    /// Dotted for nested fields, e.g. `input.prompt`.
+    path: &'static str,
+    kind: FieldKind,
+    required: bool,
+}
+
+const fn required(path: &'static str, kind: FieldKind) -> Field {
+    Field {
+        path,
+        kind,
+        required: true,
+    }
+}
+
+const fn optional(path: &'static str, kind: FieldKind) -> Field {
+    Field {
+        path,
+        kind,
+        required: false,
+    }
+}
+
+use FieldKind::{Bool, Object, Text, TextList, TextOrNumber};
+
+/// Rules by endpoint. These are the only endpoints `start_generation`
+/// proxies to.
+const SCHEMAS: &[(&str, &[Field])] = &[
+    (
+        "/api/sora/generations",
+        &[
+            // Image-only generations send an empty prompt.
+            optional("prompt", Text),
+            optional("images", TextList),
+            optional("aspect_ratio", Text),
+            optional("duration", TextOrNumber),
+            optional("hd", Bool),
+            optional("watermark", Bool),
+            optional("private", Bool),
+        ],
+    ),
+    (
+        "/api/yunwu/create",
+        &[
+            required("prompt", Text),
+            required("orientation", Text),
+            required("duration", TextOrNumber),
+            optional("model", Text),
+            optional("images", TextList),
+            optional("size", Text),
+            optional("watermark", Bool),
+        ],
+    ),
+    (
+        "/api/yunwuapi/create",
+        &[
+            required("model", Text),
+            optional("prompt", Text),
+            optional("images", TextList),
+            optional("image_url", Text),
+            optional("aspect_ratio", Text),
+            optional("orientation", Text),
+            optional("duration", TextOrNumber),
+            optional("size", Text),
+            optional("watermark", Bool),
+            optional("isPrivate", Bool),
+            optional("enhance_prompt", Bool),
+            optional("enable_upsample", Bool),
+        ],
+    ),
+    (
+        "/api/dayuapi/create",
+        &[
+            required("model", Text),
+            required("prompt", Text),
+            optional("image_url", Text),
+        ],
+    ),
+    (
+        "/api/kie/create",
+        &[
+            required("model", Text),
+            required("input", Object),
+            required("input.prompt", Text),
+            optional("input.image_urls", TextList),
+            optional("input.aspect_ratio", Text),
+            optional("input.n_frames", TextOrNumber),
+            optional("input.remove_watermark", Bool),
+        ],
+    ),
+    (
+        "/api/sutu/create",
+        &[
+            required("prompt", Text),
+            optional("model", Text),
+            optional("url", Text),
+            optional("aspectRatio", Text),
+            optional("duration", TextOrNumber),
+            optional("size", Text),
+        ],
+    ),
+    (
+        "/api/yijiapi/create",
+        &[
+            required("model", Text),
+            required("prompt", Text),
+            optional("size", Text),
+            optional("input_reference", Text),
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldErrorCode {
+    Required,
+    WrongType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldError {
+    /// Dotted path into the body, `apiKey` for the key, or empty for the
+    /// body as a whole.
+    pub field: String,
+    pub code: FieldErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationReport {
+    /// The errors as one line, for refusing a request.
+    pub fn summary(&self) -> String {
+        let messages: Vec<&str> = self.errors.iter().map(|e| e.message.as_str()).collect();
+        messages.join("; ")
+    }
+}
+
+fn lookup<'a>(body: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .try_fold(body, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
+
+/// Checks `body` and `api_key` against `endpoint`'s rules; `None` if it
+/// isn't a generation endpoint.
+pub fn validate(
+    endpoint: &str,
+    body: &serde_json::Value,
+    api_key: Option<&str>,
+) -> Option<ValidationReport> {
+    let (_, fields) = SCHEMAS.iter().find(|(e, _)| *e == endpoint)?;
+    let mut errors = Vec::new();
+    if api_key.is_none_or(|key| key.trim().is_empty()) {
+        errors.push(FieldError {
+            field: "apiKey".into(),
+            code: FieldErrorCode::Required,
+            message: "an API key is required".into(),
+        });
+    }
+    if !body.is_object() {
+        errors.push(FieldError {
+            field: String::new(),
+            code: FieldErrorCode::WrongType,
+            message: "the request must be an object".into(),
+        });
+    } else {
+        for field in fields.iter() {
+            // A nested field is only checked once its parent is valid.
+            let parent_ok = field.path.rsplit_once('.').is_none_or(|(parent, _)| {
+                lookup(body, parent).is_some_and(serde_json::Value::is_object)
+            });
+            if !parent_ok {
+                continue;
+            }
+            let error = match lookup(body, field.path) {
+                None if field.required => Some((FieldErrorCode::Required, "is required")),
+                Some(value) if !field.kind.accepts(value) => {
+                    Some((FieldErrorCode::WrongType, field.kind.describe()))
+                }
+                _ => None,
+            };
+            if let Some((code, what)) = error {
+                let message = match code {
+                    FieldErrorCode::Required => format!("{} {}", field.path, what),
+                    FieldErrorCode::WrongType => format!("{} must be {}", field.path, what),
+                };
+                errors.push(FieldError {
+                    field: field.path.into(),
+                    code,
+                    message,
+                });
+            }
+        }
+    }
+    Some(ValidationReport {
+        valid: errors.is_empty(),
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn fields_are_reported_by_path() {
+        assert!(validate("/api/health", &json!({}), Some("k")).is_none());
+
+        let body = json!({ "prompt": "rain", "orientation": "portrait", "duration": 10 });
+        assert!(validate("/api/yunwu/create", &body, Some("k")).unwrap().valid);
+
+        let body = json!({ "prompt": " ", "duration": null, "images": [1] });
+        let report = validate("/api/yunwu/create", &body, None).unwrap();
+        let fields: Vec<(&str, FieldErrorCode)> =
+            report.errors.iter().map(|e| (e.field.as_str(), e.code)).collect();
+        assert_eq!(
+            fields,
+            [
+                ("apiKey", FieldErrorCode::Required),
+                ("prompt", FieldErrorCode::WrongType),
+                ("orientation", FieldErrorCode::Required),
+                ("duration", FieldErrorCode::Required),
+                ("images", FieldErrorCode::WrongType),
+            ]
+        );
+
+        // Nested fields only once their parent is there.
+        let body = json!({ "model": "sora-2" });
+        let report = validate("/api/kie/create", &body, Some("k")).unwrap();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].field, "input");
+        let body = json!({ "model": "sora-2", "input": { "image_urls": "x" } });
+        let report = validate("/api/kie/create", &body, Some("k")).unwrap();
+        assert_eq!(
+            report.summary(),
+            "input.prompt is required; input.image_urls must be a list of strings"
+        );
+    }
+}
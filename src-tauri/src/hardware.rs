@@ -0,0 +1,554 @@
+//! What the machine can do, for picking export defaults: GPUs, displays,
+//! the video encoders ffmpeg can actually use here, CPU cores and memory.
+//!
+//! Probing encoders means trial encodes, so the report is collected once in
+//! the background at startup and kept for the session.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::OnceCell;
+use tracing::{debug, info};
+
+use crate::blocking;
+use crate::error::ServerError;
+
+/// Limit for one trial encode; a wedged driver shouldn't hold up the report.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Hardware encoder families by ffmpeg name suffix.
+const HW_SUFFIXES: &[&str] = &["nvenc", "qsv", "amf", "videotoolbox", "vaapi", "mf"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VideoCodec {
+    H264,
+    Hevc,
+    Av1,
+}
+
+impl VideoCodec {
+    const ALL: [VideoCodec; 3] = [VideoCodec::H264, VideoCodec::Hevc, VideoCodec::Av1];
+
+    /// Prefix of the codec's hardware encoder names, e.g. `hevc_nvenc`.
+    fn prefix(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::Hevc => "hevc",
+            VideoCodec::Av1 => "av1",
+        }
+    }
+
+    fn software_encoders(self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::H264 => &["libx264"],
+            VideoCodec::Hevc => &["libx265"],
+            VideoCodec::Av1 => &["libsvtav1", "libaom-av1"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuInfo {
+    pub name: String,
+    /// Dedicated video memory; `None` where the platform doesn't say.
+    pub vram_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayInfo {
+    pub name: Option<String>,
+    /// Physical pixels.
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: Option<f64>,
+    pub refresh_hz: Option<u32>,
+    /// Whether the display can show HDR; `None` where that can't be told.
+    pub hdr: Option<bool>,
+}
+
+/// An encoder that completed a trial encode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderInfo {
+    pub name: String,
+    pub codec: VideoCodec,
+    pub hardware: bool,
+    /// Also managed a 3840x2160 frame.
+    pub supports_4k: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HardwareReport {
+    pub gpus: Vec<GpuInfo>,
+    pub displays: Vec<DisplayInfo>,
+    pub encoders: Vec<EncoderInfo>,
+    pub cpu_cores: usize,
+    pub total_memory_bytes: Option<u64>,
+}
+
+impl HardwareReport {
+    fn hardware_encoder(&self, codec: VideoCodec, needs_4k: bool) -> Option<&EncoderInfo> {
+        self.encoders
+            .iter()
+            .find(|e| e.hardware && e.codec == codec && (e.supports_4k || !needs_4k))
+    }
+
+    pub fn has_hdr_display(&self) -> bool {
+        self.displays.iter().any(|d| d.hdr == Some(true))
+    }
+}
+
+#[derive(Default)]
+pub struct HardwareState {
+    report: OnceCell<HardwareReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPreset {
+    pub id: &'static str,
+    pub codec: VideoCodec,
+    pub width: u32,
+    pub height: u32,
+    pub hdr: bool,
+    /// Encoder that makes this preset hardware-accelerated here.
+    pub hardware_encoder: Option<String>,
+    /// Some encoder for the codec works on this machine.
+    pub available: bool,
+    /// Worth offering by default: 4K only with a hardware encoder that
+    /// handles it, HDR only with an HDR display.
+    pub recommended: bool,
+}
+
+/// Export presets as (id, codec, width, height, hdr).
+const PRESETS: &[(&str, VideoCodec, u32, u32, bool)] = &[
+    ("h264-720p", VideoCodec::H264, 1280, 720, false),
+    ("h264-1080p", VideoCodec::H264, 1920, 1080, false),
+    ("hevc-1080p", VideoCodec::Hevc, 1920, 1080, false),
+    ("hevc-2160p", VideoCodec::Hevc, 3840, 2160, false),
+    ("hevc-2160p-hdr", VideoCodec::Hevc, 3840, 2160, true),
+    ("av1-1080p", VideoCodec::Av1, 1920, 1080, false),
+];
+
+/// Encoder names from `ffmpeg -encoders`, whose lines look like
+/// ` V....D libx264              libx264 H.264 / AVC ...`.
+async fn listed_encoders() -> Vec<String> {
+    let Ok(output) = tokio::process::Command::new("ffmpeg")
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .await
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let flags = fields.next()?;
+            let name = fields.next()?;
+            (flags.len() == 6 && flags.starts_with('V')).then(|| name.to_owned())
+        })
+        .collect()
+}
+
+/// Encodes one black frame of `width`x`height` and throws it away.
+async fn trial_encode(encoder: &str, width: u32, height: u32) -> bool {
+    let mut cmd = tokio::process::Command::new("ffmpeg");
+    cmd.args(["-hide_banner", "-v", "error"]);
+    // VAAPI takes frames already uploaded to the device.
+    if encoder.ends_with("_vaapi") {
+        cmd.args(["-vaapi_device", "/dev/dri/renderD128"]);
+    }
+    cmd.args(["-f", "lavfi", "-i"])
+        .arg(format!("color=c=black:s={}x{}:r=30", width, height))
+        .args(["-frames:v", "1"]);
+    if encoder.ends_with("_vaapi") {
+        cmd.args(["-vf", "format=nv12,hwupload"]);
+    }
+    cmd.args(["-c:v", encoder, "-f", "null", "-"])
+        .kill_on_drop(true);
+    matches!(
+        tokio::time::timeout(PROBE_TIMEOUT, cmd.output()).await,
+        Ok(Ok(output)) if output.status.success()
+    )
+}
+
+async fn probe_encoders() -> Vec<EncoderInfo> {
+    let listed = listed_encoders().await;
+    let mut probes = tokio::task::JoinSet::new();
+    for codec in VideoCodec::ALL {
+        for suffix in HW_SUFFIXES {
+            let name = format!("{}_{}", codec.prefix(), suffix);
+            if !listed.contains(&name) {
+                continue;
+            }
+            probes.spawn(async move {
+                let supports_4k = trial_encode(&name, 3840, 2160).await;
+                let works = supports_4k || trial_encode(&name, 1920, 1080).await;
+                debug!(encoder = %name, works, supports_4k, "Probed hardware encoder");
+                works.then_some(EncoderInfo {
+                    name,
+                    codec,
+                    hardware: true,
+                    supports_4k,
+                })
+            });
+        }
+    }
+    let mut encoders: Vec<EncoderInfo> = probes.join_all().await.into_iter().flatten().collect();
+    // Software encoders work wherever ffmpeg has them.
+    for codec in VideoCodec::ALL {
+        let software = codec
+            .software_encoders()
+            .iter()
+            .find(|name| listed.iter().any(|l| l == *name));
+        if let Some(name) = software {
+            encoders.push(EncoderInfo {
+                name: (*name).to_owned(),
+                codec,
+                hardware: false,
+                supports_4k: true,
+            });
+        }
+    }
+    encoders.sort_by_key(|e| (e.codec as u8, !e.hardware));
+    encoders
+}
+
+#[cfg(windows)]
+fn wide_to_string(wide: &[u16]) -> String {
+    let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+    String::from_utf16_lossy(&wide[..len])
+}
+
+/// Adapters and their outputs from DXGI.
+#[cfg(windows)]
+fn platform_gpus_and_displays(_app: &AppHandle) -> (Vec<GpuInfo>, Vec<DisplayInfo>) {
+    use windows::core::{Interface, PCWSTR};
+    use windows::Win32::Graphics::Dxgi::Common::DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020;
+    use windows::Win32::Graphics::Dxgi::{
+        CreateDXGIFactory1, IDXGIFactory1, IDXGIOutput6, DXGI_ADAPTER_FLAG_SOFTWARE,
+    };
+    use windows::Win32::Graphics::Gdi::{EnumDisplaySettingsW, DEVMODEW, ENUM_CURRENT_SETTINGS};
+
+    let mut gpus = Vec::new();
+    let mut displays = Vec::new();
+    // SAFETY: COM calls on interfaces owned by this function; out-structs
+    // are plain data.
+    unsafe {
+        let Ok(factory) = CreateDXGIFactory1::<IDXGIFactory1>() else {
+            return (gpus, displays);
+        };
+        let mut i = 0;
+        while let Ok(adapter) = factory.EnumAdapters1(i) {
+            i += 1;
+            let Ok(desc) = adapter.GetDesc1() else {
+                continue;
+            };
+            if desc.Flags & DXGI_ADAPTER_FLAG_SOFTWARE.0 as u32 != 0 {
+                continue;
+            }
+            gpus.push(GpuInfo {
+                name: wide_to_string(&desc.Description),
+                vram_bytes: Some(desc.DedicatedVideoMemory as u64),
+            });
+
+            let mut j = 0;
+            while let Ok(output) = adapter.EnumOutputs(j) {
+                j += 1;
+                let Ok(desc) = output.cast::<IDXGIOutput6>().and_then(|o| o.GetDesc1()) else {
+                    continue;
+                };
+                let rect = desc.DesktopCoordinates;
+                let mut mode = DEVMODEW {
+                    dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+                    ..Default::default()
+                };
+                let refresh = EnumDisplaySettingsW(
+                    PCWSTR(desc.DeviceName.as_ptr()),
+                    ENUM_CURRENT_SETTINGS,
+                    &mut mode,
+                )
+                .as_bool()
+                .then_some(mode.dmDisplayFrequency);
+                displays.push(DisplayInfo {
+                    name: Some(wide_to_string(&desc.DeviceName)),
+                    width: (rect.right - rect.left).unsigned_abs(),
+                    height: (rect.bottom - rect.top).unsigned_abs(),
+                    scale_factor: None,
+                    refresh_hz: refresh,
+                    // The output is in HDR mode right now.
+                    hdr: Some(desc.ColorSpace == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020),
+                });
+            }
+        }
+    }
+    (gpus, displays)
+}
+
+/// `"8 GB"` or `"1536 MB"` as `system_profiler` reports VRAM.
+#[cfg(target_os = "macos")]
+fn parse_vram(s: &str) -> Option<u64> {
+    let (n, unit) = s.trim().split_once(' ')?;
+    let n: u64 = n.parse().ok()?;
+    match unit {
+        "GB" => Some(n << 30),
+        "MB" => Some(n << 20),
+        _ => None,
+    }
+}
+
+/// GPUs from `system_profiler`, displays from `NSScreen`.
+#[cfg(target_os = "macos")]
+fn platform_gpus_and_displays(app: &AppHandle) -> (Vec<GpuInfo>, Vec<DisplayInfo>) {
+    let gpus = std::process::Command::new("system_profiler")
+        .args(["SPDisplaysDataType", "-json"])
+        .output()
+        .ok()
+        .and_then(|out| serde_json::from_slice::<serde_json::Value>(&out.stdout).ok())
+        .and_then(|json| json.get("SPDisplaysDataType")?.as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|gpu| {
+            Some(GpuInfo {
+                name: gpu.get("sppci_model")?.as_str()?.to_owned(),
+                vram_bytes: gpu
+                    .get("spdisplays_vram")
+                    .or_else(|| gpu.get("spdisplays_vram_shared"))
+                    .and_then(|v| v.as_str())
+                    .and_then(parse_vram),
+            })
+        })
+        .collect();
+
+    // NSScreen is main-thread only.
+    let (tx, rx) = std::sync::mpsc::channel();
+    let _ = app.run_on_main_thread(move || {
+        use objc2::runtime::NSObjectProtocol;
+        use objc2::{sel, MainThreadMarker};
+        use objc2_app_kit::NSScreen;
+
+        let Some(mtm) = MainThreadMarker::new() else {
+            return;
+        };
+        let displays: Vec<DisplayInfo> = NSScreen::screens(mtm)
+            .iter()
+            .map(|screen| {
+                let frame = screen.frame();
+                let scale = screen.backingScaleFactor();
+                DisplayInfo {
+                    name: Some(screen.localizedName().to_string()),
+                    width: (frame.size.width * scale) as u32,
+                    height: (frame.size.height * scale) as u32,
+                    scale_factor: Some(scale),
+                    // macOS 12 and later.
+                    refresh_hz: screen
+                        .respondsToSelector(sel!(maximumFramesPerSecond))
+                        .then(|| u32::try_from(screen.maximumFramesPerSecond()).ok())
+                        .flatten(),
+                    hdr: Some(screen.maximumPotentialExtendedDynamicRangeColorComponentValue() > 1.0),
+                }
+            })
+            .collect();
+        let _ = tx.send(displays);
+    });
+    let displays = rx.recv_timeout(Duration::from_secs(2)).unwrap_or_default();
+    (gpus, displays)
+}
+
+/// GPUs from `lspci -mm`, whose lines look like
+/// `00:02.0 "VGA compatible controller" "Intel Corporation" "UHD Graphics 620" ...`;
+/// displays from the window system, without refresh rate or HDR.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn platform_gpus_and_displays(app: &AppHandle) -> (Vec<GpuInfo>, Vec<DisplayInfo>) {
+    let gpus = std::process::Command::new("lspci")
+        .arg("-mm")
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('"').collect();
+            let class = fields.get(1)?;
+            let is_gpu = ["VGA", "3D", "Display"].iter().any(|c| class.contains(c));
+            is_gpu.then(|| GpuInfo {
+                name: format!("{} {}", fields.get(3).unwrap_or(&""), fields.get(5).unwrap_or(&""))
+                    .trim()
+                    .to_owned(),
+                vram_bytes: None,
+            })
+        })
+        .collect();
+    let displays = app
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|m| DisplayInfo {
+            name: m.name().cloned(),
+            width: m.size().width,
+            height: m.size().height,
+            scale_factor: Some(m.scale_factor()),
+            refresh_hz: None,
+            hdr: None,
+        })
+        .collect();
+    (gpus, displays)
+}
+
+#[cfg(target_os = "linux")]
+fn total_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kb: u64 = meminfo
+        .lines()
+        .find_map(|l| l.strip_prefix("MemTotal:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "macos")]
+fn total_memory() -> Option<u64> {
+    let mut size: u64 = 0;
+    let mut len = std::mem::size_of::<u64>();
+    // SAFETY: `hw.memsize` is a u64 and `size`/`len` describe its buffer.
+    let rc = unsafe {
+        libc::sysctlbyname(
+            c"hw.memsize".as_ptr(),
+            (&mut size as *mut u64).cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (rc == 0).then_some(size)
+}
+
+#[cfg(windows)]
+fn total_memory() -> Option<u64> {
+    use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status = MEMORYSTATUSEX {
+        dwLength: std::mem::size_of::<MEMORYSTATUSEX>() as u32,
+        ..Default::default()
+    };
+    // SAFETY: `status` is initialized with its size as the API requires.
+    unsafe { GlobalMemoryStatusEx(&mut status) }.ok()?;
+    Some(status.ullTotalPhys)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn total_memory() -> Option<u64> {
+    None
+}
+
+async fn collect(app: &AppHandle) -> HardwareReport {
+    let handle = app.clone();
+    let platform = blocking::run(move || (platform_gpus_and_displays(&handle), total_memory()));
+    let (platform, encoders) = tokio::join!(platform, probe_encoders());
+    let ((gpus, displays), total_memory_bytes) = platform.unwrap_or_default();
+    let report = HardwareReport {
+        gpus,
+        displays,
+        encoders,
+        cpu_cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        total_memory_bytes,
+    };
+    info!(
+        gpus = report.gpus.len(),
+        displays = report.displays.len(),
+        hardware_encoders = report.encoders.iter().filter(|e| e.hardware).count(),
+        "Hardware report collected"
+    );
+    report
+}
+
+pub async fn report(app: &AppHandle) -> HardwareReport {
+    app.state::<HardwareState>()
+        .report
+        .get_or_init(|| collect(app))
+        .await
+        .clone()
+}
+
+/// The report if it has been collected, without waiting; for crash reports.
+pub fn cached(app: &AppHandle) -> Option<HardwareReport> {
+    app.try_state::<HardwareState>()?.report.get().cloned()
+}
+
+/// Collects the report in the background so it's ready when needed.
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        report(&handle).await;
+    });
+}
+
+/// Frame height of export preset `id`.
+pub(crate) fn preset_height(id: &str) -> Option<u32> {
+    PRESETS.iter().find(|p| p.0 == id).map(|p| p.3)
+}
+
+/// How to encode for an export preset on this machine.
+#[derive(Debug, Clone)]
+pub struct PresetEncoding {
+    pub encoder: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The encoder and frame size for export preset `id`: its hardware encoder
+/// if there is one here, else any encoder for the codec that works. `None`
+/// for an unknown preset or one nothing here can encode.
+pub async fn preset_encoding(app: &AppHandle, id: &str) -> Option<PresetEncoding> {
+    let &(_, codec, width, height, _) = PRESETS.iter().find(|p| p.0 == id)?;
+    let report = report(app).await;
+    let encoder = report
+        .hardware_encoder(codec, height > 1080)
+        .or_else(|| report.encoders.iter().find(|e| e.codec == codec))?;
+    Some(PresetEncoding {
+        encoder: encoder.name.clone(),
+        width,
+        height,
+    })
+}
+
+#[tauri::command]
+pub async fn get_hardware_report(app: AppHandle) -> Result<HardwareReport, ServerError> {
+    Ok(report(&app).await)
+}
+
+/// Export presets, with which of them this machine encodes in hardware.
+#[tauri::command]
+pub async fn get_export_presets(app: AppHandle) -> Result<Vec<ExportPreset>, ServerError> {
+    let report = report(&app).await;
+    let hdr_display = report.has_hdr_display();
+    Ok(PRESETS
+        .iter()
+        .map(|&(id, codec, width, height, hdr)| {
+            let needs_4k = height > 1080;
+            let hardware_encoder = report
+                .hardware_encoder(codec, needs_4k)
+                .map(|e| e.name.clone());
+            let available = report.encoders.iter().any(|e| e.codec == codec);
+            let recommended = available
+                && (!needs_4k || hardware_encoder.is_some())
+                && (!hdr || hdr_display);
+            ExportPreset {
+                id,
+                codec,
+                width,
+                height,
+                hdr,
+                hardware_encoder,
+                available,
+                recommended,
+            }
+        })
+        .collect())
+}
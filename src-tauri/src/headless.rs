@@ -0,0 +1,199 @@
+//! `--headless` batch rendering: no windows, just the sidecar, the render
+//! jobs and progress on stdout.
+//!
+//! ```text
+//! aiyou --headless --project foo.aiyou --render-all --out ./exports [--json]
+//! ```
+//!
+//! With `--json` every progress line is a JSON object; sidecar log lines
+//! on stdout are prefixed (`[server] …`) and never start with `{`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tracing::{error, warn};
+
+use crate::api::{self, JobState, RenderRequest};
+use crate::error::ServerError;
+use crate::license::{self, Tier};
+use crate::server::{self, ServerState, ServerStatus};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Exit code for bad arguments.
+pub const USAGE_EXIT_CODE: i32 = 2;
+/// Exit code when interrupted with Ctrl+C.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+#[derive(Debug, Clone)]
+pub struct HeadlessArgs {
+    project: PathBuf,
+    out: PathBuf,
+    json: bool,
+}
+
+/// Parses the command line. Returns `Ok(None)` unless `--headless` is
+/// given, so normal launches (including ones with a file or link argument)
+/// are left alone.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<HeadlessArgs>, String> {
+    let args: Vec<String> = args.into_iter().collect();
+    if !args.iter().any(|a| a == "--headless") {
+        return Ok(None);
+    }
+
+    let mut project = None;
+    let mut out = None;
+    let mut render_all = false;
+    let mut json = false;
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--headless" => {}
+            "--project" => project = Some(iter.next().ok_or("--project needs a path")?),
+            "--out" => out = Some(iter.next().ok_or("--out needs a directory")?),
+            "--render-all" => render_all = true,
+            "--json" => json = true,
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+    }
+
+    let project = PathBuf::from(project.ok_or("--project is required")?);
+    if !project.is_file() {
+        return Err(format!("project file not found: {}", project.display()));
+    }
+    if !render_all {
+        return Err("nothing to do, pass --render-all".into());
+    }
+    let out = PathBuf::from(out.ok_or("--out is required")?);
+    std::fs::create_dir_all(&out).map_err(|e| format!("can't create {}: {}", out.display(), e))?;
+
+    Ok(Some(HeadlessArgs {
+        project: project.canonicalize().unwrap_or(project),
+        out: out.canonicalize().unwrap_or(out),
+        json,
+    }))
+}
+
+/// Prints a progress line, as JSON with `--json` and as text otherwise.
+fn report(args: &HeadlessArgs, event: serde_json::Value, text: String) {
+    if args.json {
+        println!("{}", event);
+    } else {
+        println!("{}", text);
+    }
+}
+
+/// Submits the render and follows its jobs to the end. Returns how many
+/// failed.
+async fn render(app: &AppHandle, args: &HeadlessArgs) -> Result<usize, ServerError> {
+    let state = app.state::<ServerState>();
+    let mut status = state.subscribe();
+    let settled = status
+        .wait_for(|s| matches!(s, ServerStatus::Ready | ServerStatus::Failed | ServerStatus::Crashed))
+        .await
+        .map(|s| *s)
+        .unwrap_or(ServerStatus::Crashed);
+    if settled != ServerStatus::Ready {
+        return Err(ServerError::NotRunning);
+    }
+
+    let request = RenderRequest {
+        project_path: args.project.to_string_lossy().into_owned(),
+        out_dir: args.out.to_string_lossy().into_owned(),
+        all: true,
+        watermark: (license::tier(app) == Tier::Free).then_some(true),
+    };
+    let submission = api::submit_render(&state, &request).await?;
+    report(
+        args,
+        serde_json::json!({ "event": "submitted", "jobs": submission.jobs }),
+        format!("Submitted {} job(s)", submission.jobs.len()),
+    );
+
+    let mut pending: HashMap<String, Option<f64>> =
+        submission.jobs.into_iter().map(|id| (id, None)).collect();
+    let mut failed = 0;
+    while !pending.is_empty() {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let ids: Vec<String> = pending.keys().cloned().collect();
+        for id in ids {
+            let job = api::get_job(&state, &id).await?;
+            match job.status {
+                JobState::Done => {
+                    pending.remove(&id);
+                    let output = job.output.unwrap_or_default();
+                    report(
+                        args,
+                        serde_json::json!({ "event": "done", "jobId": id, "output": output }),
+                        format!("[{}] done: {}", id, output),
+                    );
+                }
+                JobState::Failed => {
+                    pending.remove(&id);
+                    failed += 1;
+                    let error = job.error.unwrap_or_else(|| "unknown error".into());
+                    report(
+                        args,
+                        serde_json::json!({ "event": "failed", "jobId": id, "error": error }),
+                        format!("[{}] failed: {}", id, error),
+                    );
+                }
+                JobState::Queued | JobState::Running => {
+                    let last = pending.get_mut(&id).expect("pending job");
+                    if job.progress.is_some() && job.progress != *last {
+                        *last = job.progress;
+                        let progress = job.progress.unwrap_or_default();
+                        report(
+                            args,
+                            serde_json::json!({ "event": "progress", "jobId": id, "progress": progress }),
+                            format!("[{}] {:.0}%", id, progress),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// Starts the sidecar and runs the batch in the background, exiting the
+/// app with a non-zero code if anything fails. The sidecar is stopped on
+/// the way out, including on Ctrl+C.
+pub fn start(app: &AppHandle, args: HeadlessArgs) {
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = server::start(&handle).await {
+            error!(error = %e, "Failed to start server");
+            handle.exit(1);
+            return;
+        }
+
+        let code = tokio::select! {
+            result = render(&handle, &args) => match result {
+                Ok(0) => 0,
+                Ok(failed) => {
+                    eprintln!("{} job(s) failed", failed);
+                    1
+                }
+                Err(e) => {
+                    error!(error = %e, "Headless render failed");
+                    1
+                }
+            },
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!("Interrupted, shutting down");
+                INTERRUPTED_EXIT_CODE
+            }
+        };
+        report(
+            &args,
+            serde_json::json!({ "event": "finished", "exitCode": code }),
+            "Finished".into(),
+        );
+        match server::stop(&handle).await {
+            Ok(_) | Err(ServerError::NotRunning) => {}
+            Err(e) => warn!(error = %e, "Failed to stop server"),
+        }
+        handle.exit(code);
+    });
+}
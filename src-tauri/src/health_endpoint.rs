@@ -0,0 +1,130 @@
+//! Optional HTTP endpoint reporting whether the app as a whole is ready,
+//! for test runners and monitoring that can't talk to the webview.
+//!
+//! Off unless `healthPort` is set in the server config. It listens on
+//! localhost only and answers `GET /health` with 200 while the sidecar is
+//! ready and 503 otherwise, with the statuses as JSON.
+
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::crash;
+use crate::server::{ServerState, ServerStatus};
+
+/// Limit for reading a request, so a stalled client can't hold a task.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppHealth {
+    healthy: bool,
+    server: ServerStatus,
+    version: String,
+}
+
+/// Status line and body for a request line such as `GET /health HTTP/1.1`.
+fn respond(request_line: &str, server: ServerStatus, version: &str) -> (&'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    if !matches!(method, "GET" | "HEAD") {
+        return ("405 Method Not Allowed", String::new());
+    }
+    if path.split('?').next() != Some("/health") {
+        return ("404 Not Found", String::new());
+    }
+    let health = AppHealth {
+        healthy: server == ServerStatus::Ready,
+        server,
+        version: version.to_owned(),
+    };
+    let status = if health.healthy {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    let body = serde_json::to_string(&health).unwrap_or_default();
+    (status, if method == "HEAD" { String::new() } else { body })
+}
+
+async fn serve(app: &AppHandle, mut socket: TcpStream) -> std::io::Result<()> {
+    let mut buf = [0u8; 1024];
+    let read = tokio::time::timeout(READ_TIMEOUT, socket.read(&mut buf))
+        .await
+        .map_err(|_| std::io::ErrorKind::TimedOut)??;
+    let request = String::from_utf8_lossy(&buf[..read]);
+    let request_line = request.lines().next().unwrap_or("");
+    let server = app.state::<ServerState>().status();
+    let version = app.package_info().version.to_string();
+    let (status, body) = respond(request_line, server, &version);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+/// Starts the endpoint if the config asks for one. Port changes apply on
+/// the next launch.
+pub fn init(app: &AppHandle) {
+    let port = app.state::<ServerState>().config().health_port;
+    if port == 0 {
+        return;
+    }
+    let handle = app.clone();
+    crash::spawn("health-endpoint", async move {
+        let listener = match TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!(port, error = %e, "Failed to open health endpoint");
+                return;
+            }
+        };
+        info!(port, "Health endpoint listening");
+        loop {
+            let socket = match listener.accept().await {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    // Such as running out of file descriptors; don't spin.
+                    debug!(error = %e, "Health endpoint accept failed");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let app = handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = serve(&app, socket).await {
+                    debug!(error = %e, "Health endpoint request failed");
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_ready_server_is_healthy() {
+        let (status, body) = respond("GET /health HTTP/1.1", ServerStatus::Ready, "1.2.0");
+        assert_eq!(status, "200 OK");
+        assert_eq!(body, r#"{"healthy":true,"server":"ready","version":"1.2.0"}"#);
+
+        let (status, _) = respond("GET /health?probe=1 HTTP/1.1", ServerStatus::Loading, "1.2.0");
+        assert_eq!(status, "503 Service Unavailable");
+        assert_eq!(respond("HEAD /health HTTP/1.1", ServerStatus::Ready, "1.2.0").1, "");
+        assert_eq!(respond("GET / HTTP/1.1", ServerStatus::Ready, "1.2.0").0, "404 Not Found");
+        assert_eq!(
+            respond("POST /health HTTP/1.1", ServerStatus::Ready, "1.2.0").0,
+            "405 Method Not Allowed"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Translations for text the shell shows itself: menus, the tray,
+//! notifications and splash messages. The webview translates its own.
+//!
+//! Catalogs are flat JSON files in `locales/`, compiled in. A key missing
+//! from the active catalog falls back to English, and one missing there
+//! too shows the key itself. Until the user picks a language, the OS one
+//! is used if there is a catalog for it.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{info, warn};
+
+use crate::error::ServerError;
+use crate::settings::SettingsState;
+use crate::{menu, tray};
+
+const ENGLISH: &str = "en";
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.json")),
+    ("zh-CN", include_str!("../locales/zh-CN.json")),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocaleInfo {
+    pub id: String,
+    /// The language's name in itself, e.g. `简体中文`.
+    pub name: String,
+    pub active: bool,
+}
+
+pub struct I18nState {
+    catalogs: HashMap<&'static str, HashMap<String, String>>,
+    locale: Mutex<&'static str>,
+}
+
+impl Default for I18nState {
+    fn default() -> Self {
+        let catalogs = CATALOGS
+            .iter()
+            .map(|(id, json)| {
+                let strings = serde_json::from_str(json).unwrap_or_else(|e| {
+                    warn!(locale = id, error = %e, "Ignoring unreadable locale catalog");
+                    HashMap::new()
+                });
+                (*id, strings)
+            })
+            .collect();
+        Self {
+            catalogs,
+            locale: Mutex::new(ENGLISH),
+        }
+    }
+}
+
+impl I18nState {
+    /// Uses the saved locale, or the OS one if none was saved.
+    pub fn load(app: &AppHandle) -> Self {
+        let state = Self::default();
+        let saved = app.state::<SettingsState>().get().locale;
+        let locale = match saved {
+            Some(saved) => negotiate(&saved),
+            None => os_locales().iter().find_map(|tag| negotiate(tag)),
+        };
+        *state.locale.lock().unwrap() = locale.unwrap_or(ENGLISH);
+        state
+    }
+
+    pub fn locale(&self) -> &'static str {
+        *self.locale.lock().unwrap()
+    }
+
+    fn lookup(&self, key: &str) -> String {
+        let find = |locale: &str| self.catalogs.get(locale).and_then(|c| c.get(key));
+        find(self.locale())
+            .or_else(|| find(ENGLISH))
+            .cloned()
+            .unwrap_or_else(|| key.to_owned())
+    }
+}
+
+/// The catalog id for a language tag such as `zh_CN.UTF-8` or
+/// `zh-Hans-CN`: an exact match, or else one for the same language.
+fn negotiate(tag: &str) -> Option<&'static str> {
+    let tag = tag.split(['.', '@']).next().unwrap_or("").replace('_', "-");
+    let language = tag.split('-').next().unwrap_or("");
+    let ids = || CATALOGS.iter().map(|(id, _)| *id);
+    ids().find(|id| id.eq_ignore_ascii_case(&tag)).or_else(|| {
+        ids().find(|id| id.split('-').next().is_some_and(|l| l.eq_ignore_ascii_case(language)))
+    })
+}
+
+/// The user's preferred languages, most preferred first.
+fn os_locales() -> Vec<String> {
+    let mut tags = platform_locales();
+    // `LANGUAGE` is a colon-separated list; `C` and `POSIX` match nothing.
+    if let Ok(list) = std::env::var("LANGUAGE") {
+        tags.extend(list.split(':').map(str::to_owned));
+    }
+    for var in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        if let Ok(tag) = std::env::var(var) {
+            tags.push(tag);
+        }
+    }
+    tags.retain(|tag| !tag.is_empty());
+    tags
+}
+
+#[cfg(target_os = "macos")]
+fn platform_locales() -> Vec<String> {
+    // Apps started from Finder get no `LANG`.
+    objc2_foundation::NSLocale::preferredLanguages()
+        .iter()
+        .map(|tag| tag.to_string())
+        .collect()
+}
+
+#[cfg(windows)]
+fn platform_locales() -> Vec<String> {
+    use windows::Win32::Globalization::GetUserDefaultLocaleName;
+
+    let mut buf = [0u16; 85];
+    // SAFETY: the buffer outlives the call and its length is passed along.
+    let len = unsafe { GetUserDefaultLocaleName(&mut buf) };
+    if len <= 1 {
+        return Vec::new();
+    }
+    // The length includes the terminating nul.
+    vec![String::from_utf16_lossy(&buf[..len as usize - 1])]
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn platform_locales() -> Vec<String> {
+    Vec::new()
+}
+
+/// The text for `key` in the current language.
+pub fn t<R: Runtime>(app: &AppHandle<R>, key: &str) -> String {
+    app.state::<I18nState>().lookup(key)
+}
+
+/// Like [`t`], filling in `{name}` placeholders.
+pub fn t_with<R: Runtime>(app: &AppHandle<R>, key: &str, args: &[(&str, &str)]) -> String {
+    fill(t(app, key), args)
+}
+
+fn fill(mut text: String, args: &[(&str, &str)]) -> String {
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+/// The languages the shell has catalogs for.
+#[tauri::command]
+pub fn get_available_locales(state: tauri::State<'_, I18nState>) -> Vec<LocaleInfo> {
+    let current = state.locale();
+    CATALOGS
+        .iter()
+        .map(|(id, _)| LocaleInfo {
+            id: (*id).to_owned(),
+            name: state
+                .catalogs
+                .get(id)
+                .and_then(|c| c.get("locale.name"))
+                .cloned()
+                .unwrap_or_else(|| (*id).to_owned()),
+            active: *id == current,
+        })
+        .collect()
+}
+
+/// Switches the shell's language and saves the choice. The app menu and
+/// tray are rebuilt right away; notifications and splash text use it from
+/// then on. Emits `locale-changed` with the catalog id.
+#[tauri::command]
+pub fn set_locale(app: AppHandle, locale: String) -> Result<(), ServerError> {
+    let id = negotiate(&locale).ok_or_else(|| ServerError::Locale(locale.clone()))?;
+    app.state::<SettingsState>()
+        .update(|s| s.locale = Some(id.to_owned()))?;
+    *app.state::<I18nState>().locale.lock().unwrap() = id;
+    menu::rebuild(&app)?;
+    tray::relabel(&app)?;
+    info!(locale = id, "Locale changed");
+    let _ = app.emit("locale-changed", id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_match_and_fall_back_to_english() {
+        assert_eq!(negotiate("zh_CN.UTF-8"), Some("zh-CN"));
+        assert_eq!(negotiate("zh-Hans-CN"), Some("zh-CN"));
+        assert_eq!(negotiate("en-GB"), Some("en"));
+        assert_eq!(negotiate("C"), None);
+
+        let mut state = I18nState::default();
+        *state.locale.lock().unwrap() = "zh-CN";
+        assert_eq!(state.lookup("menu.file"), "文件");
+        state.catalogs.get_mut("zh-CN").unwrap().remove("menu.file");
+        assert_eq!(state.lookup("menu.file"), "File");
+        assert_eq!(state.lookup("no.such.key"), "no.such.key");
+
+        let args = [("attempt", "2"), ("max", "5")];
+        assert_eq!(fill("attempt {attempt}/{max}".into(), &args), "attempt 2/5");
+    }
+}
@@ -0,0 +1,708 @@
+//! Script import from plain text, Markdown, Word (`.docx`) and Fountain.
+//!
+//! Everything is reduced to scenes of action and dialogue lines; the
+//! frontend maps that onto its own project model.
+
+use quick_xml::events::Event;
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::blocking;
+use crate::error::ServerError;
+
+/// Larger files are refused outright.
+const MAX_FILE_BYTES: u64 = 20 * 1024 * 1024;
+/// Cap on the unpacked `word/document.xml`, against zip bombs.
+const MAX_DOCX_XML_BYTES: u64 = 100 * 1024 * 1024;
+/// Longest `Name: line` prefix still taken as a speaker.
+const MAX_SPEAKER_CHARS: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScriptFormat {
+    Text,
+    Markdown,
+    Docx,
+    Fountain,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LineKind {
+    Action,
+    Dialogue,
+    Transition,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptLine {
+    pub kind: LineKind,
+    pub speaker: Option<String>,
+    /// e.g. `(whispering)`, without the parentheses.
+    pub parenthetical: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scene {
+    pub heading: Option<String>,
+    pub lines: Vec<ScriptLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedScript {
+    pub format: ScriptFormat,
+    /// Detected text encoding; `None` for docx, which is always UTF-8.
+    pub encoding: Option<String>,
+    pub title: Option<String>,
+    pub scenes: Vec<Scene>,
+}
+
+fn detect_format(path: &Path, bytes: &[u8]) -> ScriptFormat {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "fountain" | "spmd" => ScriptFormat::Fountain,
+        "md" | "markdown" => ScriptFormat::Markdown,
+        "docx" => ScriptFormat::Docx,
+        // A renamed Word file is still a zip.
+        _ if bytes.starts_with(b"PK\x03\x04") => ScriptFormat::Docx,
+        _ => ScriptFormat::Text,
+    }
+}
+
+/// Share of characters that are likely in a script: ASCII, CJK ideographs
+/// and CJK/full-width punctuation. Mis-decoded text scores low.
+fn plausibility(text: &str) -> f64 {
+    let (mut good, mut total) = (0usize, 0usize);
+    for c in text.chars() {
+        total += 1;
+        let plausible = matches!(c, ' '..='~' | '\t' | '\r' | '\n')
+            || matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3000}'..='\u{303f}' | '\u{ff00}'..='\u{ffef}')
+            || matches!(c, '\u{2010}'..='\u{2027}');
+        good += plausible as usize;
+    }
+    if total == 0 {
+        0.0
+    } else {
+        good as f64 / total as f64
+    }
+}
+
+/// Decodes text by BOM, then strict UTF-8, then whichever of GB18030 (a
+/// superset of the GBK that Chinese Windows uses) and BOM-less UTF-16
+/// reads best.
+fn decode(bytes: &[u8]) -> (String, &'static str) {
+    if let Some((encoding, bom_len)) = encoding_rs::Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return (text.into_owned(), encoding.name());
+    }
+    // NULs are valid UTF-8, but in a script they are the zero halves of
+    // BOM-less UTF-16.
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        if !text.contains('\0') {
+            return (text.to_owned(), encoding_rs::UTF_8.name());
+        }
+    }
+    // Without a BOM, UTF-16 can only be told apart from GB18030 by which
+    // decoding looks like text.
+    let mut candidates = vec![encoding_rs::GB18030];
+    if bytes.len().is_multiple_of(2) {
+        candidates.extend([encoding_rs::UTF_16LE, encoding_rs::UTF_16BE]);
+    }
+    let best = candidates
+        .into_iter()
+        .filter_map(|encoding| {
+            let text = encoding.decode_without_bom_handling_and_without_replacement(bytes)?;
+            Some((plausibility(&text), text.into_owned(), encoding))
+        })
+        .fold(
+            None::<(f64, String, &'static encoding_rs::Encoding)>,
+            |best, c| match best {
+                Some(b) if b.0 >= c.0 => Some(b),
+                _ => Some(c),
+            },
+        );
+    match best {
+        Some((_, text, encoding)) => (text, encoding.name()),
+        // Undecodable either way; keep what GB18030 makes of it.
+        None => {
+            let (text, _) = encoding_rs::GB18030.decode_without_bom_handling(bytes);
+            (text.into_owned(), encoding_rs::GB18030.name())
+        }
+    }
+}
+
+fn is_scene_heading(line: &str) -> bool {
+    let upper = line.to_uppercase();
+    let english = [
+        "INT.", "EXT.", "EST.", "INT/EXT", "I/E", "INT ", "EXT ", "SCENE ",
+    ]
+    .iter()
+    .any(|p| upper.starts_with(p));
+    // 第一场 / 第3幕 / 场景一
+    let chinese = line.starts_with("场景")
+        || (line.starts_with('第') && line.chars().take(8).any(|c| c == '场' || c == '幕'));
+    english || chinese
+}
+
+/// Uppercase `... TO:` lines, and the common Chinese equivalents.
+fn is_transition(line: &str) -> bool {
+    (line.ends_with("TO:") && !line.chars().any(char::is_lowercase))
+        || ["切至", "转场", "淡入", "淡出"].contains(&line.trim_end_matches(['：', ':']))
+}
+
+/// Splits `Name (aside): text`, with either an ASCII or full-width colon.
+fn split_dialogue(line: &str) -> Option<(String, Option<String>, String)> {
+    let (idx, colon) = line.char_indices().find(|&(_, c)| c == ':' || c == '：')?;
+    let speaker = line[..idx].trim();
+    let text = line[idx + colon.len_utf8()..].trim();
+    if speaker.is_empty()
+        || text.is_empty()
+        || speaker.chars().count() > MAX_SPEAKER_CHARS
+        || speaker.split_whitespace().count() > 4
+        || speaker.contains(['。', '，', ',', '.', '!', '?', '！', '？', '"', '“'])
+    {
+        return None;
+    }
+    let (speaker, parenthetical) = split_parenthetical(speaker);
+    Some((speaker, parenthetical, text.to_owned()))
+}
+
+/// `张三（低声）` -> (`张三`, `低声`).
+fn split_parenthetical(speaker: &str) -> (String, Option<String>) {
+    for (open, close) in [('(', ')'), ('（', '）')] {
+        if let (Some(start), true) = (speaker.find(open), speaker.ends_with(close)) {
+            let inner = &speaker[start + open.len_utf8()..speaker.len() - close.len_utf8()];
+            return (
+                speaker[..start].trim().to_owned(),
+                Some(inner.trim().to_owned()),
+            );
+        }
+    }
+    (speaker.to_owned(), None)
+}
+
+/// Groups paragraphs into scenes. `heading` marks paragraphs that the
+/// source format already flagged as headings.
+fn split_scenes(paragraphs: impl IntoIterator<Item = (String, bool)>) -> Vec<Scene> {
+    let mut scenes = Vec::new();
+    let mut scene = Scene::default();
+    for (text, heading) in paragraphs {
+        let line = text.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if heading || is_scene_heading(line) {
+            if scene.heading.is_some() || !scene.lines.is_empty() {
+                scenes.push(std::mem::take(&mut scene));
+            }
+            scene.heading = Some(line.to_owned());
+        } else if is_transition(line) {
+            scene.lines.push(ScriptLine {
+                kind: LineKind::Transition,
+                speaker: None,
+                parenthetical: None,
+                text: line.to_owned(),
+            });
+        } else if let Some((speaker, parenthetical, text)) = split_dialogue(line) {
+            scene.lines.push(ScriptLine {
+                kind: LineKind::Dialogue,
+                speaker: Some(speaker),
+                parenthetical,
+                text,
+            });
+        } else {
+            scene.lines.push(ScriptLine {
+                kind: LineKind::Action,
+                speaker: None,
+                parenthetical: None,
+                text: line.to_owned(),
+            });
+        }
+    }
+    if scene.heading.is_some() || !scene.lines.is_empty() {
+        scenes.push(scene);
+    }
+    scenes
+}
+
+/// Strips inline Markdown: emphasis, code spans, links and images.
+fn strip_inline_markdown(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '*' | '`' | '~' => rest = &rest[1..],
+            '_' if rest.starts_with("__") => rest = &rest[2..],
+            '!' if rest.starts_with("![") => rest = &rest[1..],
+            '[' => {
+                // [text](url) -> text
+                let parsed = rest.find("](").and_then(|mid| {
+                    let end = rest[mid..].find(')')? + mid;
+                    Some((&rest[1..mid], end))
+                });
+                match parsed {
+                    Some((text, end)) => {
+                        out.push_str(text);
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        out.push('[');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            _ => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// Returns the title (a leading `# heading`) and the scenes.
+fn parse_markdown(text: &str) -> (Option<String>, Vec<Scene>) {
+    let mut title = None;
+    let mut seen_content = false;
+    let mut paragraphs = Vec::new();
+    for raw in text.lines() {
+        let line = raw.trim();
+        if line.starts_with("```") || line.chars().all(|c| c == '-' || c == '*' || c == '_') {
+            continue;
+        }
+        let heading = line.starts_with('#');
+        let mut line = line
+            .trim_start_matches('#')
+            .trim_start_matches('>')
+            .trim_start();
+        for marker in ["- ", "* ", "+ "] {
+            line = line.strip_prefix(marker).unwrap_or(line);
+        }
+        let line = strip_inline_markdown(line);
+        if heading && raw.starts_with("# ") && !seen_content && title.is_none() {
+            title = Some(line.trim().to_owned());
+            continue;
+        }
+        seen_content |= !line.trim().is_empty();
+        paragraphs.push((line, heading));
+    }
+    (title, split_scenes(paragraphs))
+}
+
+/// Paragraph text from `word/document.xml`, with heading-styled paragraphs
+/// flagged.
+fn docx_paragraphs(bytes: &[u8]) -> Result<Vec<(String, bool)>, ServerError> {
+    let invalid =
+        |e: &dyn std::fmt::Display| ServerError::Import(format!("not a valid .docx file: {}", e));
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).map_err(|e| invalid(&e))?;
+    let entry = archive
+        .by_name("word/document.xml")
+        .map_err(|e| invalid(&e))?;
+    if entry.size() > MAX_DOCX_XML_BYTES {
+        return Err(ServerError::Import(
+            "document is too large to import".into(),
+        ));
+    }
+    let mut xml = String::new();
+    entry.take(MAX_DOCX_XML_BYTES).read_to_string(&mut xml)?;
+
+    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut paragraphs = Vec::new();
+    let mut text = String::new();
+    let mut heading = false;
+    let mut in_text = false;
+    loop {
+        match reader.read_event().map_err(|e| invalid(&e))? {
+            Event::Start(e) if e.name().as_ref() == b"w:p" => {
+                text.clear();
+                heading = false;
+            }
+            Event::Start(e) if e.name().as_ref() == b"w:t" => in_text = true,
+            Event::End(e) if e.name().as_ref() == b"w:t" => in_text = false,
+            Event::Empty(e) => match e.name().as_ref() {
+                b"w:pStyle" => {
+                    heading = e.attributes().flatten().any(|a| {
+                        a.key.as_ref() == b"w:val"
+                            && String::from_utf8_lossy(&a.value)
+                                .to_lowercase()
+                                .starts_with("heading")
+                    });
+                }
+                b"w:tab" => text.push('\t'),
+                b"w:br" | b"w:cr" => text.push('\n'),
+                _ => {}
+            },
+            Event::Text(t) if in_text => {
+                text.push_str(&t.decode().map_err(|e| invalid(&e))?);
+            }
+            Event::GeneralRef(r) if in_text => {
+                let name = r.decode().map_err(|e| invalid(&e))?;
+                match quick_xml::escape::resolve_predefined_entity(&name) {
+                    Some(s) => text.push_str(s),
+                    None => {
+                        if let Ok(Some(c)) = r.resolve_char_ref() {
+                            text.push(c);
+                        }
+                    }
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"w:p" => {
+                // Soft line breaks inside a paragraph are separate lines of
+                // the script.
+                for (i, line) in text.split('\n').enumerate() {
+                    paragraphs.push((line.to_owned(), heading && i == 0));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(paragraphs)
+}
+
+/// Removes `/* boneyard */` and `[[notes]]`, which may span lines.
+fn strip_fountain_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let next = [("/*", "*/"), ("[[", "]]")]
+            .into_iter()
+            .filter_map(|(open, close)| Some((rest.find(open)?, open, close)))
+            .min_by_key(|&(at, _, _)| at);
+        let Some((at, open, close)) = next else {
+            out.push_str(rest);
+            return out;
+        };
+        out.push_str(&rest[..at]);
+        rest = match rest[at + open.len()..].find(close) {
+            Some(end) => &rest[at + open.len() + end + close.len()..],
+            None => "",
+        };
+    }
+}
+
+/// Removes emphasis markers and backslash escapes.
+fn strip_fountain_emphasis(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            '*' | '_' => {}
+            _ => out.push(c),
+        }
+    }
+    out.trim().to_owned()
+}
+
+fn is_character_cue(line: &str) -> bool {
+    if line.starts_with('@') {
+        return true;
+    }
+    // Ignore extensions like (V.O.) or (cont'd) when checking case.
+    let name = line.split('(').next().unwrap_or(line);
+    name.chars().any(char::is_uppercase) && !name.chars().any(char::is_lowercase)
+}
+
+/// `@McCLANE (V.O.) ^` -> `McCLANE`.
+fn character_name(line: &str) -> String {
+    let line = line.trim_start_matches('@').trim_end_matches('^');
+    line.split('(').next().unwrap_or(line).trim().to_owned()
+}
+
+fn parse_fountain(text: &str) -> (Option<String>, Vec<Scene>) {
+    let text = strip_fountain_comments(text);
+    let mut lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+
+    // The title page is a block of `Key: value` pairs at the very top.
+    let mut title = None;
+    if lines
+        .first()
+        .is_some_and(|l| l.contains(':') && !is_scene_heading(l))
+    {
+        let end = lines
+            .iter()
+            .position(|l| l.trim().is_empty())
+            .unwrap_or(lines.len());
+        let mut key = "";
+        for line in &lines[..end] {
+            let value = match line.split_once(':') {
+                Some((k, v)) if !line.starts_with([' ', '\t']) => {
+                    key = k.trim();
+                    v.trim()
+                }
+                _ => line.trim(),
+            };
+            if key.eq_ignore_ascii_case("title") && !value.is_empty() {
+                let value = strip_fountain_emphasis(value);
+                title = Some(match title {
+                    Some(t) => format!("{} {}", t, value),
+                    None => value,
+                });
+            }
+        }
+        lines.drain(..end);
+    }
+
+    let mut scenes = Vec::new();
+    let mut scene = Scene::default();
+    let mut i = 0;
+    let mut prev_blank = true;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        let next_blank = lines.get(i + 1).is_none_or(|l| l.trim().is_empty());
+        i += 1;
+        if line.is_empty() {
+            prev_blank = true;
+            continue;
+        }
+        let was_blank = std::mem::replace(&mut prev_blank, false);
+
+        // Sections, synopses and page breaks carry no script content.
+        if line.starts_with('#') || line.starts_with('=') {
+            continue;
+        }
+        let forced_heading = line.starts_with('.') && !line.starts_with("..");
+        if forced_heading || (was_blank && is_scene_heading(line) && !line.starts_with('!')) {
+            if scene.heading.is_some() || !scene.lines.is_empty() {
+                scenes.push(std::mem::take(&mut scene));
+            }
+            let heading = line.strip_prefix('.').unwrap_or(line);
+            // Drop a trailing scene number: `INT. HOUSE #1#`.
+            let heading = match heading.find(" #") {
+                Some(at) if heading.ends_with('#') => &heading[..at],
+                _ => heading,
+            };
+            scene.heading = Some(strip_fountain_emphasis(heading));
+            continue;
+        }
+        let centered = line.starts_with('>') && line.ends_with('<');
+        if !centered && (line.starts_with('>') || (was_blank && next_blank && is_transition(line)))
+        {
+            scene.lines.push(ScriptLine {
+                kind: LineKind::Transition,
+                speaker: None,
+                parenthetical: None,
+                text: strip_fountain_emphasis(line.trim_start_matches('>')),
+            });
+            continue;
+        }
+        if was_blank && !next_blank && !line.starts_with('!') && is_character_cue(line) {
+            let speaker = character_name(line);
+            let mut parenthetical = None;
+            let mut text = String::new();
+            while let Some(next) = lines.get(i).map(|l| l.trim()) {
+                if next.is_empty() {
+                    break;
+                }
+                i += 1;
+                if next.starts_with('(') && next.ends_with(')') {
+                    // A parenthetical mid-speech starts a new line.
+                    if !text.is_empty() {
+                        scene.lines.push(ScriptLine {
+                            kind: LineKind::Dialogue,
+                            speaker: Some(speaker.clone()),
+                            parenthetical: parenthetical.take(),
+                            text: std::mem::take(&mut text),
+                        });
+                    }
+                    parenthetical = Some(next[1..next.len() - 1].trim().to_owned());
+                    continue;
+                }
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&strip_fountain_emphasis(next.trim_start_matches('~')));
+            }
+            if !text.is_empty() || parenthetical.is_some() {
+                scene.lines.push(ScriptLine {
+                    kind: LineKind::Dialogue,
+                    speaker: Some(speaker),
+                    parenthetical,
+                    text,
+                });
+            }
+            continue;
+        }
+
+        // Action: the next lines up to a blank one belong to the same
+        // paragraph.
+        let line = line.strip_prefix('!').unwrap_or(line);
+        let line = line.trim_start_matches('>').trim_end_matches('<');
+        let mut text = strip_fountain_emphasis(line.trim_start_matches('~'));
+        match scene.lines.last_mut() {
+            Some(last) if !was_blank && last.kind == LineKind::Action => {
+                last.text.push('\n');
+                last.text.push_str(&text);
+            }
+            _ => scene.lines.push(ScriptLine {
+                kind: LineKind::Action,
+                speaker: None,
+                parenthetical: None,
+                text: std::mem::take(&mut text),
+            }),
+        }
+    }
+    if scene.heading.is_some() || !scene.lines.is_empty() {
+        scenes.push(scene);
+    }
+    (title, scenes)
+}
+
+fn import(path: &Path) -> Result<ImportedScript, ServerError> {
+    let size = std::fs::metadata(path)?.len();
+    if size > MAX_FILE_BYTES {
+        return Err(ServerError::Import(format!(
+            "file is {:.1} MB; scripts larger than {} MB can't be imported",
+            size as f64 / (1024.0 * 1024.0),
+            MAX_FILE_BYTES / (1024 * 1024)
+        )));
+    }
+    let bytes = std::fs::read(path)?;
+    let format = detect_format(path, &bytes);
+
+    let (encoding, title, scenes) = if format == ScriptFormat::Docx {
+        (None, None, split_scenes(docx_paragraphs(&bytes)?))
+    } else {
+        let (text, encoding) = decode(&bytes);
+        let (title, scenes) = match format {
+            ScriptFormat::Fountain => parse_fountain(&text),
+            ScriptFormat::Markdown => parse_markdown(&text),
+            _ => (
+                None,
+                split_scenes(text.lines().map(|l| (l.to_owned(), false))),
+            ),
+        };
+        (Some(encoding.to_owned()), title, scenes)
+    };
+
+    let title = title.or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()));
+    Ok(ImportedScript {
+        format,
+        encoding,
+        title,
+        scenes,
+    })
+}
+
+/// Reads a script file and splits it into scenes and lines.
+#[tauri::command]
+pub async fn import_script(path: String) -> Result<ImportedScript, ServerError> {
+    blocking::watched("import_script", async move {
+        let path = PathBuf::from(path);
+        blocking::run(move || import(&path))
+            .await
+            .map_err(ServerError::Import)?
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|u| if little_endian { u.to_le_bytes() } else { u.to_be_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_chinese_scripts_in_any_common_encoding() {
+        let line = "第一场 张三：你好。";
+        let (gbk, _, _) = encoding_rs::GB18030.encode(line);
+        assert_eq!(decode(&gbk), (line.to_owned(), "gb18030"));
+        assert_eq!(decode(&utf16(line, true)), (line.to_owned(), "UTF-16LE"));
+        assert_eq!(decode(&utf16(line, false)), (line.to_owned(), "UTF-16BE"));
+        // All-ASCII UTF-16 is valid UTF-8 too, NULs and all.
+        assert_eq!(decode(&utf16("INT. HOUSE", true)), ("INT. HOUSE".to_owned(), "UTF-16LE"));
+
+        let mut bom = b"\xEF\xBB\xBF".to_vec();
+        bom.extend_from_slice(line.as_bytes());
+        assert_eq!(decode(&bom), (line.to_owned(), "UTF-8"));
+    }
+
+    #[test]
+    fn splits_speakers_with_full_width_punctuation() {
+        assert_eq!(
+            split_dialogue("张三（低声）：快走"),
+            Some(("张三".into(), Some("低声".into()), "快走".into()))
+        );
+        assert_eq!(
+            split_dialogue("Bob (whispering): go"),
+            Some(("Bob".into(), Some("whispering".into()), "go".into()))
+        );
+        assert_eq!(split_dialogue("天黑了，他说：走吧"), None);
+        assert_eq!(split_dialogue("：没有名字"), None);
+        assert_eq!(split_parenthetical("李四(笑)"), ("李四".into(), Some("笑".into())));
+        assert_eq!(split_parenthetical("李四"), ("李四".into(), None));
+    }
+
+    #[test]
+    fn parses_fountain() {
+        let text = "Title: **The Pier**\nAuthor: Someone\n\n\
+                    .OPENING\n\n\
+                    INT. HOUSE - NIGHT #1#\n\n\
+                    MARY sits.\n/* She used to\nstand. */\n\n\
+                    BOB (V.O.)\n(quietly)\nHello there.\n\n\
+                    [[a note]]\nCUT TO:\n";
+        let (title, scenes) = parse_fountain(text);
+        assert_eq!(title.as_deref(), Some("The Pier"));
+        let headings: Vec<_> = scenes.iter().map(|s| s.heading.as_deref()).collect();
+        assert_eq!(headings, [Some("OPENING"), Some("INT. HOUSE - NIGHT")]);
+
+        let lines = &scenes[1].lines;
+        assert_eq!(lines.len(), 3);
+        assert_eq!((lines[0].kind, lines[0].text.as_str()), (LineKind::Action, "MARY sits."));
+        assert_eq!(lines[1].kind, LineKind::Dialogue);
+        assert_eq!(lines[1].speaker.as_deref(), Some("BOB"));
+        assert_eq!(lines[1].parenthetical.as_deref(), Some("quietly"));
+        assert_eq!(lines[1].text, "Hello there.");
+        assert_eq!((lines[2].kind, lines[2].text.as_str()), (LineKind::Transition, "CUT TO:"));
+    }
+
+    #[test]
+    fn reads_docx_paragraphs() {
+        let xml = r#"<w:document><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr>
+                <w:r><w:t>第一场 客厅</w:t></w:r></w:p>
+            <w:p><w:r><w:t>张三：你好 &amp; 再见</w:t><w:br/>
+                <w:t>李四：嗯</w:t></w:r></w:p>
+        </w:body></w:document>"#;
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("word/document.xml", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(xml.as_bytes()).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        assert_eq!(
+            docx_paragraphs(&bytes).unwrap(),
+            [
+                ("第一场 客厅".to_owned(), true),
+                ("张三：你好 & 再见".to_owned(), false),
+                ("李四：嗯".to_owned(), false),
+            ]
+        );
+        assert!(matches!(docx_paragraphs(b"not a zip"), Err(ServerError::Import(_))));
+    }
+
+    #[test]
+    fn refuses_files_over_the_size_limit() {
+        let path = std::env::temp_dir().join(format!("aiyou-import-{}.txt", std::process::id()));
+        // Sparse, so the test doesn't write 20 MB.
+        std::fs::File::create(&path).unwrap().set_len(MAX_FILE_BYTES + 1).unwrap();
+        let result = import(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(ServerError::Import(_))));
+    }
+}
@@ -0,0 +1,174 @@
+//! Job progress for server builds without the `/api/events` stream.
+//!
+//! Started jobs are registered here; once the bridge finds there is no
+//! event stream, each one is polled at `/api/jobs/{id}` and the result is
+//! dispatched as the same `generation-progress`, `generation-complete` and
+//! `generation-error` events the stream would have sent.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tracing::debug;
+
+use crate::api::{self, JobState};
+use crate::bridge;
+use crate::crash;
+use crate::error::ServerError;
+use crate::server::ServerState;
+use crate::settings::SettingsState;
+
+/// Bounds on `Settings::job_poll_interval_ms`.
+const MIN_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_INTERVAL: Duration = Duration::from_secs(10);
+/// Longest wait between polls while the server is unreachable.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sse {
+    /// The bridge hasn't connected yet.
+    Unknown,
+    Available,
+    Unavailable,
+}
+
+pub struct JobPollState {
+    sse: Mutex<Sse>,
+    /// Jobs being followed; removing one stops its poller.
+    jobs: Mutex<HashSet<String>>,
+}
+
+impl Default for JobPollState {
+    fn default() -> Self {
+        Self {
+            sse: Mutex::new(Sse::Unknown),
+            jobs: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl JobPollState {
+    fn sse(&self) -> Sse {
+        *self.sse.lock().unwrap()
+    }
+
+    fn watching(&self, job_id: &str) -> bool {
+        self.jobs.lock().unwrap().contains(job_id)
+    }
+}
+
+/// Called by the bridge once it knows whether the server streams events.
+pub fn set_sse_available(app: &AppHandle, available: bool) {
+    let sse = if available {
+        Sse::Available
+    } else {
+        Sse::Unavailable
+    };
+    *app.state::<JobPollState>().sse.lock().unwrap() = sse;
+}
+
+fn interval(app: &AppHandle) -> Duration {
+    let ms = app.state::<SettingsState>().get().job_poll_interval_ms;
+    Duration::from_millis(ms).clamp(MIN_INTERVAL, MAX_INTERVAL)
+}
+
+/// Follows a started job until it finishes, fails or is cancelled. Does
+/// nothing once the event stream turns out to be available.
+pub fn watch(app: &AppHandle, job_id: String) {
+    let state = app.state::<JobPollState>();
+    if state.sse() == Sse::Available || !state.jobs.lock().unwrap().insert(job_id.clone()) {
+        return;
+    }
+    let handle = app.clone();
+    crash::spawn("job-poll", async move {
+        poll(&handle, &job_id).await;
+        handle
+            .state::<JobPollState>()
+            .jobs
+            .lock()
+            .unwrap()
+            .remove(&job_id);
+    });
+}
+
+/// Stops polling a job, e.g. because it was cancelled.
+pub fn forget(app: &AppHandle, job_id: &str) {
+    app.state::<JobPollState>()
+        .jobs
+        .lock()
+        .unwrap()
+        .remove(job_id);
+}
+
+async fn poll(app: &AppHandle, job_id: &str) {
+    let state = app.state::<JobPollState>();
+    let mut last_progress = None;
+    let mut backoff = None;
+    loop {
+        let delay = backoff.unwrap_or_else(|| interval(app));
+        tokio::time::sleep(delay).await;
+        if !state.watching(job_id) {
+            return;
+        }
+        match state.sse() {
+            Sse::Available => return,
+            Sse::Unknown => continue,
+            Sse::Unavailable => {}
+        }
+
+        let job = match api::get_job(&app.state::<ServerState>(), job_id).await {
+            Ok(job) => job,
+            Err(ServerError::Unreachable(_) | ServerError::Timeout) => {
+                let next = backoff.map_or(interval(app) * 2, |b: Duration| b * 2);
+                backoff = Some(next.min(MAX_BACKOFF));
+                debug!(%job_id, backoff = ?backoff, "Server unreachable while polling job");
+                continue;
+            }
+            Err(e) => {
+                // Gone, most likely cancelled on the server.
+                debug!(%job_id, error = %e, "Stopped polling job");
+                return;
+            }
+        };
+        backoff = None;
+
+        // Only sent when it moved, as the stream would.
+        if job.progress.is_some() && job.progress != last_progress {
+            last_progress = job.progress;
+            bridge::dispatch(
+                app,
+                "generation-progress",
+                serde_json::json!({ "jobId": job_id, "progress": job.progress }),
+            );
+        }
+        if !state.watching(job_id) {
+            return;
+        }
+        match job.status {
+            JobState::Done => {
+                bridge::dispatch(
+                    app,
+                    "generation-complete",
+                    serde_json::json!({
+                        "jobId": job_id,
+                        "output": job.output,
+                        "usage": job.usage,
+                    }),
+                );
+                return;
+            }
+            JobState::Failed => {
+                bridge::dispatch(
+                    app,
+                    "generation-error",
+                    serde_json::json!({
+                        "jobId": job_id,
+                        "error": job.error.unwrap_or_else(|| "generation failed".into()),
+                    }),
+                );
+                return;
+            }
+            JobState::Queued | JobState::Running => {}
+        }
+    }
+}
@@ -1,103 +1,381 @@
+mod api;
+mod app_info;
+mod assets;
+mod backups;
+mod bandwidth;
+mod blocking;
+mod bridge;
+mod clipboard;
+mod config;
+mod cost_estimate;
+mod crash;
+mod deep_link;
+mod downloads;
+mod error;
+mod first_run;
+mod fonts;
+mod frames;
+mod generation;
+mod generation_schema;
+mod hardware;
+mod headless;
+mod health_endpoint;
+mod i18n;
+mod import;
+mod job_poll;
+mod library;
+mod license;
+mod logging;
+mod media_watch;
+mod menu;
+mod migrations;
+mod monitor;
+mod network;
+mod notify;
+mod power;
+mod preview;
+mod priority;
+mod project_lock;
+mod proxy;
+mod publish;
+mod recents;
+mod recovery;
+mod redact;
+mod render_governor;
+mod render_queue;
+mod server;
+mod server_log;
+mod settings;
+mod shortcut;
+mod splash;
+mod stats;
+mod subtitles;
+mod system_prefs;
+mod taskbar;
+mod telemetry;
+mod tempfiles;
+mod time;
+mod tray;
+mod tts_cache;
+mod updater;
+mod video_protocol;
+mod window_state;
+mod ws;
+
 use tauri::Manager;
-use tauri_plugin_shell::ShellExt;
-use std::sync::Mutex;
+use tracing::{error, warn};
 
-struct ServerState {
-    child_id: Mutex<Option<u32>>,
-}
+pub use error::ServerError;
 
-#[tauri::command]
-async fn check_server_health() -> Result<bool, String> {
-    let client = reqwest::Client::new();
-    match client
-        .get("http://localhost:3001/api/health")
-        .timeout(std::time::Duration::from_secs(2))
-        .send()
-        .await
-    {
-        Ok(resp) => Ok(resp.status().is_success()),
-        Err(_) => Ok(false),
+/// Restores and focuses the main window, if it exists.
+pub(crate) fn focus_main_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
     }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    crash::install_hook();
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let server_mode = match server::take_server_mode_arg(&mut args) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("aiyou: {}", e);
+            std::process::exit(headless::USAGE_EXIT_CODE);
+        }
+    };
+    let headless = match headless::parse(args) {
+        Ok(headless) => headless,
+        Err(e) => {
+            eprintln!("aiyou: {}", e);
+            std::process::exit(headless::USAGE_EXIT_CODE);
+        }
+    };
+
+    let mut context = tauri::generate_context!();
+    let mut builder = tauri::Builder::default();
+    if headless.is_some() {
+        // No windows at all; batch runs also shouldn't hand off to a
+        // running GUI instance.
+        context.config_mut().app.windows.clear();
+    } else {
+        builder = builder
+            // Must be registered first so a second launch exits before doing any work.
+            .plugin(tauri_plugin_single_instance::init(
+                deep_link::on_second_instance,
+            ))
+            .plugin(tauri_plugin_deep_link::init());
+    }
+    // Only with a signing key; see `updater`.
+    if updater::configured(context.config()) {
+        builder = builder.plugin(tauri_plugin_updater::Builder::new().build());
+    }
+
+    builder
         .plugin(tauri_plugin_shell::init())
-        .manage(ServerState {
-            child_id: Mutex::new(None),
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .register_asynchronous_uri_scheme_protocol(video_protocol::SCHEME, video_protocol::handle)
+        .manage(logging::LoggingState::default())
+        .manage(server::ServerState::default())
+        .manage(notify::NotificationState::default())
+        .manage(deep_link::DeepLinkState::default())
+        .manage(power::PowerState::default())
+        .manage(taskbar::TaskbarState::default())
+        .manage(window_state::WindowStateTracker::default())
+        .manage(ws::WsState::default())
+        .manage(shortcut::PauseShortcutState::default())
+        .manage(updater::UpdaterState::default())
+        .manage(app_info::AppInfoState::default())
+        .manage(backups::BackupState::default())
+        .manage(priority::PriorityState::default())
+        .manage(stats::StatsState::default())
+        .manage(recovery::RecoveryState::default())
+        .manage(assets::AssetsState::default())
+        .manage(network::NetworkState::default())
+        .manage(generation::GenerationState::default())
+        .manage(job_poll::JobPollState::default())
+        .manage(tts_cache::TtsCacheState::default())
+        .manage(fonts::FontsState::default())
+        .manage(hardware::HardwareState::default())
+        .manage(license::LicenseState::default())
+        .manage(system_prefs::SystemPrefsState::default())
+        .manage(proxy::ProxyState::default())
+        .manage(server_log::ServerLogState::default())
+        .manage(project_lock::ProjectLockState::default())
+        .manage(bandwidth::BandwidthState::default())
+        .manage(first_run::FirstRunState::default())
+        .manage(migrations::MigrationState::default())
+        .manage(media_watch::MediaWatchState::default())
+        .manage(render_governor::GovernorState::default())
+        .manage(cost_estimate::PricingState::default())
+        .on_menu_event(menu::on_menu_event)
+        .on_window_event(|window, event| {
+            window_state::on_window_event(window, event);
+            preview::on_window_event(window, event);
+            system_prefs::on_window_event(window, event);
+            deep_link::on_window_event(window, event);
         })
-        .setup(|app| {
+        .setup(move |app| {
             let handle = app.handle().clone();
+            logging::init(&handle, headless.is_some());
+            crash::init(&handle);
+            app.manage(settings::SettingsState::load(&handle));
+            app.manage(i18n::I18nState::load(&handle));
+            let log_level = app.state::<settings::SettingsState>().get().log_level;
+            if let Err(e) = logging::set_level(&handle, &log_level) {
+                warn!(%log_level, error = %e, "Ignoring invalid log level");
+            }
+            app.state::<server::ServerState>()
+                .set_config(config::load(&handle));
+            if let Some(mode) = server_mode {
+                app.state::<server::ServerState>().set_mode_override(mode);
+            }
+            health_endpoint::init(&handle);
+            app.manage(recents::RecentsState::load(&handle));
+            app.manage(library::LibraryState::load(&handle));
+            license::init(&handle);
+            app.manage(telemetry::TelemetryState::load(&handle));
+            app.manage(tempfiles::TempFilesState::load(&handle));
+            bandwidth::init(&handle);
+            if let Some(args) = headless {
+                #[cfg(target_os = "macos")]
+                app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+                migrations::run(&handle);
+                headless::start(&handle, args);
+                return Ok(());
+            }
+            if let Some(window) = app.get_webview_window("main") {
+                let saved = app.state::<settings::SettingsState>().get().window;
+                if let Some(geom) = saved {
+                    window_state::restore(&window, geom);
+                }
+            }
+            // Set here rather than on the builder so it is built in the
+            // saved language.
+            handle.set_menu(menu::build(&handle)?)?;
+            notify::init(&handle)?;
+            tray::init(&handle)?;
+            power::init(&handle);
+            generation::init(&handle);
+            taskbar::init(&handle);
+            deep_link::init(&handle);
+            shortcut::init(&handle);
+            recents::init(&handle);
+            updater::init(&handle);
+            bridge::spawn(handle.clone());
+            monitor::spawn(&handle);
+            backups::init(&handle);
+            app.manage(render_queue::QueueState::load(&handle));
+            render_queue::init(&handle);
+            render_governor::init(&handle);
+            cost_estimate::init(&handle);
+            priority::init(&handle);
+            network::init(&handle);
+            telemetry::init(&handle);
+            project_lock::init(&handle);
+            hardware::init(&handle);
+            system_prefs::init(&handle);
 
-            // Spawn sidecar server
-            tauri::async_runtime::spawn(async move {
-                let shell = handle.shell();
-
-                let (mut rx, child) = shell
-                    .sidecar("aiyou-server")
-                    .expect("failed to create sidecar command")
-                    .spawn()
-                    .expect("failed to spawn sidecar");
-
-                // Store child PID for cleanup
-                let state = handle.state::<ServerState>();
-                *state.child_id.lock().unwrap() = Some(child.pid());
-
-                // Log sidecar output
-                tauri::async_runtime::spawn(async move {
-                    use tauri_plugin_shell::process::CommandEvent;
-                    while let Some(event) = rx.recv().await {
-                        match event {
-                            CommandEvent::Stdout(line) => {
-                                let s = String::from_utf8_lossy(&line);
-                                println!("[server] {}", s);
-                            }
-                            CommandEvent::Stderr(line) => {
-                                let s = String::from_utf8_lossy(&line);
-                                eprintln!("[server:err] {}", s);
-                            }
-                            CommandEvent::Terminated(payload) => {
-                                eprintln!(
-                                    "[server] terminated with code {:?}, signal {:?}",
-                                    payload.code, payload.signal
-                                );
-                                break;
-                            }
-                            _ => {}
-                        }
-                    }
-                });
+            // The main window stays hidden behind the splash until the
+            // server has settled.
+            if let Err(e) = splash::open(&handle) {
+                warn!(error = %e, "Failed to open splash window");
+                splash::close(&handle);
+            }
 
-                // Wait for server to be ready
-                let client = reqwest::Client::new();
-                let mut ready = false;
-                for i in 0..60 {
-                    match client
-                        .get("http://localhost:3001/api/health")
-                        .timeout(std::time::Duration::from_secs(2))
-                        .send()
-                        .await
-                    {
-                        Ok(resp) if resp.status().is_success() => {
-                            println!("[tauri] Server ready after {} attempts", i + 1);
-                            ready = true;
-                            break;
-                        }
-                        _ => {
-                            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                        }
-                    }
+            // Spawn sidecar server once the data dir it uses is migrated
+            // and laid out by first-run setup.
+            crash::spawn("first-run", async move {
+                let migrating = handle.clone();
+                if let Err(e) = blocking::run(move || migrations::run(&migrating)).await {
+                    error!(error = %e, "Data migration didn't finish");
                 }
-
-                if !ready {
-                    eprintln!("[tauri] Server failed to start within 30 seconds");
+                first_run::run(&handle).await;
+                if let Err(e) = server::start(&handle).await {
+                    error!(error = %e, "Failed to start server");
+                    splash::close(&handle);
                 }
             });
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![check_server_health])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(tauri::generate_handler![
+            server::check_server_health,
+            server::ping_server,
+            server::get_server_status,
+            server::freeze_server,
+            server::unfreeze_server,
+            server::get_server_url,
+            server::wait_for_ready,
+            server::get_health_history,
+            server::start_server,
+            server::stop_server,
+            server::restart_server,
+            server::reload_server_config,
+            server::run_server_subcommand,
+            config::get_server_config,
+            config::set_server_config,
+            config::export_config,
+            config::import_config,
+            api::get_queue_status,
+            api::clear_server_cache,
+            api::get_server_log_level,
+            api::set_server_log_level,
+            downloads::download_file,
+            notify::notify,
+            settings::get_settings,
+            settings::set_settings,
+            logging::set_log_level,
+            window_state::reset_window_state,
+            power::acquire_render_lock,
+            power::release_render_lock,
+            power::get_power_assertions,
+            taskbar::set_taskbar_progress,
+            preview::open_preview_window,
+            preview::close_preview_window,
+            menu::set_menu_item_enabled,
+            ws::open_ws_channel,
+            ws::close_ws_channel,
+            shortcut::set_global_shortcut,
+            updater::check_for_update,
+            updater::download_and_install_update,
+            app_info::get_app_info,
+            app_info::copy_diagnostics_to_clipboard,
+            app_info::get_environment_info,
+            recents::add_recent_project,
+            recents::get_recent_projects,
+            recents::remove_recent_project,
+            backups::list_backups,
+            backups::create_backup_now,
+            backups::restore_backup,
+            import::import_script,
+            subtitles::export_subtitles,
+            frames::capture_frame,
+            tts_cache::get_tts_cache_report,
+            tts_cache::prune_tts_cache,
+            fonts::list_system_fonts,
+            fonts::register_bundled_font,
+            hardware::get_hardware_report,
+            hardware::get_export_presets,
+            publish::export_publish_bundle,
+            deep_link::get_pending_intents,
+            system_prefs::get_system_preferences,
+            priority::set_server_priority,
+            render_queue::enqueue_render,
+            render_queue::reorder_queue,
+            render_queue::cancel_job,
+            render_queue::pause_queue,
+            render_queue::resume_queue,
+            render_queue::get_queue,
+            render_governor::get_governor_state,
+            cost_estimate::estimate_generation_cost,
+            render_queue::set_job_run_now,
+            render_queue::get_render_schedule,
+            render_queue::set_render_schedule,
+            stats::get_project_stats,
+            stats::get_all_projects_stats,
+            library::add_library_asset,
+            library::list_library_assets,
+            library::remove_library_asset,
+            library::use_asset_in_project,
+            recovery::save_recovery_snapshot,
+            recovery::get_recovery_snapshot,
+            recovery::discard_recovery_snapshot,
+            recovery::project_saved,
+            clipboard::copy_to_clipboard,
+            clipboard::copy_generated_script,
+            clipboard::read_clipboard_image,
+            clipboard::write_image_to_clipboard,
+            assets::verify_asset,
+            assets::verify_all_assets,
+            network::get_network_status,
+            network::refresh_network_status,
+            network::check_upstream_connectivity,
+            telemetry::set_telemetry_enabled,
+            telemetry::track_event,
+            telemetry::get_telemetry_status,
+            tempfiles::get_temp_usage,
+            generation::start_generation,
+            generation::validate_generation_request,
+            generation::cancel_generation,
+            proxy::api_request,
+            proxy::get_proxy_stats,
+            server_log::frontend_ready,
+            project_lock::open_project_guard,
+            project_lock::close_project_guard,
+            bandwidth::set_bandwidth_limit,
+            bandwidth::get_transfer_stats,
+            i18n::get_available_locales,
+            i18n::set_locale,
+            video_protocol::get_video_url,
+            first_run::get_onboarding_state,
+            migrations::get_migration_history,
+            crash::get_last_app_error,
+            license::activate_license,
+            license::get_license_status,
+            license::deactivate_license,
+        ])
+        .build(context)
+        .expect("error while running tauri application")
+        .run(|app, event| match event {
+            // The sidecar is still up here, unlike at `Exit`.
+            tauri::RunEvent::ExitRequested { .. } => {
+                proxy::on_exit(app);
+                backups::on_exit(app);
+                project_lock::on_exit(app);
+                media_watch::on_exit(app);
+                server_log::on_exit(app);
+            }
+            tauri::RunEvent::Exit => {
+                app.state::<power::PowerState>().release_all();
+                shortcut::unregister(app);
+            }
+            _ => {}
+        });
 }
@@ -0,0 +1,339 @@
+//! Assets shared across projects: character voices, style presets and
+//! intro/outro stingers.
+//!
+//! Files are stored once under `app_data_dir()/library/objects/<sha256>`;
+//! the index in `library/library.json` records names, tags and which
+//! projects use each asset.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::backups::valid_project_id;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::tempfiles;
+use crate::time::now_ms;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AssetKind {
+    Voice,
+    StylePreset,
+    Stinger,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryAsset {
+    pub id: String,
+    pub kind: AssetKind,
+    pub name: String,
+    pub tags: Vec<String>,
+    /// SHA-256 of the content, also its file name in the object store.
+    pub hash: String,
+    /// Extension of the imported file, kept for copies into projects.
+    pub extension: Option<String>,
+    pub size: u64,
+    /// Unix time in milliseconds.
+    pub added_at: u64,
+    /// Projects the asset has been used in.
+    pub projects: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveOutcome {
+    pub removed: bool,
+    /// Projects still using the asset; it is only removed if this is
+    /// empty or removal was forced.
+    pub referenced_by: Vec<String>,
+}
+
+pub struct LibraryState {
+    dir: Option<PathBuf>,
+    assets: Mutex<Vec<LibraryAsset>>,
+}
+
+fn hash_file(path: &Path) -> std::io::Result<(String, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// `name` made safe to use as a file name.
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(64)
+        .collect();
+    if stem.is_empty() {
+        "asset".into()
+    } else {
+        stem
+    }
+}
+
+impl LibraryState {
+    pub fn load(app: &AppHandle) -> Self {
+        let dir = app.path().app_data_dir().ok().map(|d| d.join("library"));
+        let assets = dir
+            .as_ref()
+            .and_then(|d| std::fs::read_to_string(d.join("library.json")).ok())
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(assets) => Some(assets),
+                Err(e) => {
+                    warn!(error = %e, "Ignoring invalid library index");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            dir,
+            assets: Mutex::new(assets),
+        }
+    }
+
+    fn dir(&self) -> Result<&Path, ServerError> {
+        self.dir
+            .as_deref()
+            .ok_or_else(|| ServerError::Library("no app data directory".into()))
+    }
+
+    fn object_path(&self, hash: &str) -> Result<PathBuf, ServerError> {
+        Ok(self.dir()?.join("objects").join(hash))
+    }
+
+    /// Applies `f` to the index, writes it to disk and returns `f`'s result.
+    fn update<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<LibraryAsset>) -> Result<T, ServerError>,
+    ) -> Result<T, ServerError> {
+        let mut assets = self.assets.lock().unwrap();
+        let mut updated = assets.clone();
+        let result = f(&mut updated)?;
+
+        let dir = self.dir()?;
+        std::fs::create_dir_all(dir)?;
+        let json =
+            serde_json::to_string_pretty(&updated).map_err(|e| ServerError::Io(e.to_string()))?;
+        // Written aside and renamed, so a crash can't leave half an index.
+        let tmp = dir.join("library.json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, dir.join("library.json"))?;
+        *assets = updated;
+        Ok(result)
+    }
+}
+
+/// Copies a file into the library. Importing content that is already there
+/// under the same kind returns the existing asset.
+pub(crate) async fn add(
+    app: &AppHandle,
+    kind: AssetKind,
+    source: &Path,
+    name: &str,
+    tags: &[String],
+) -> Result<LibraryAsset, ServerError> {
+    let hashed = source.to_owned();
+    let (hash, size) = blocking::run(move || hash_file(&hashed))
+        .await
+        .map_err(ServerError::Library)??;
+
+    let state = app.state::<LibraryState>();
+    let object = state.object_path(&hash)?;
+    if !object.exists() {
+        std::fs::create_dir_all(object.parent().unwrap_or(&object))?;
+        // Copied aside first so a partial copy never looks complete.
+        let tmp = tempfiles::create_temp_path(app, "library", "part")?;
+        if let Err(e) = tokio::fs::copy(source, &tmp).await {
+            tempfiles::discard(app, &tmp).await;
+            return Err(e.into());
+        }
+        tempfiles::commit(app, &tmp, &object).await?;
+    }
+
+    let extension = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    state.update(|assets| {
+        if let Some(existing) = assets.iter().find(|a| a.hash == hash && a.kind == kind) {
+            return Ok(existing.clone());
+        }
+        let asset = LibraryAsset {
+            id: format!("{}-{}", &hash[..12], now_ms()),
+            kind,
+            name: name.trim().to_owned(),
+            tags: tags
+                .iter()
+                .map(|t| t.trim().to_owned())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            hash: hash.clone(),
+            extension,
+            size,
+            added_at: now_ms(),
+            projects: Vec::new(),
+        };
+        assets.push(asset.clone());
+        Ok(asset)
+    })
+}
+
+#[tauri::command]
+pub async fn add_library_asset(
+    app: AppHandle,
+    kind: AssetKind,
+    source_path: String,
+    name: String,
+    tags: Vec<String>,
+) -> Result<LibraryAsset, ServerError> {
+    blocking::watched("add_library_asset", async move {
+        add(&app, kind, Path::new(&source_path), &name, &tags).await
+    })
+    .await
+}
+
+/// Assets of `kind` (or all) whose name or tags contain `query`, newest
+/// first.
+#[tauri::command]
+pub fn list_library_assets(
+    state: tauri::State<'_, LibraryState>,
+    kind: Option<AssetKind>,
+    query: Option<String>,
+) -> Vec<LibraryAsset> {
+    let query = query.map(|q| q.trim().to_lowercase()).unwrap_or_default();
+    let mut assets: Vec<LibraryAsset> = state
+        .assets
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|a| kind.is_none_or(|k| a.kind == k))
+        .filter(|a| {
+            query.is_empty()
+                || a.name.to_lowercase().contains(&query)
+                || a.tags.iter().any(|t| t.to_lowercase().contains(&query))
+        })
+        .cloned()
+        .collect();
+    assets.sort_by_key(|a| std::cmp::Reverse(a.added_at));
+    assets
+}
+
+/// Removes an asset. If projects use it, nothing is removed unless `force`
+/// is set; the outcome lists them either way. Project copies are left alone.
+#[tauri::command]
+pub fn remove_library_asset(
+    state: tauri::State<'_, LibraryState>,
+    id: String,
+    force: Option<bool>,
+) -> Result<RemoveOutcome, ServerError> {
+    let (removed, unused_hash, referenced_by) = state.update(|assets| {
+        let index = assets
+            .iter()
+            .position(|a| a.id == id)
+            .ok_or_else(|| ServerError::Library(format!("no library asset {}", id)))?;
+        let referenced_by = assets[index].projects.clone();
+        if !referenced_by.is_empty() && !force.unwrap_or(false) {
+            return Ok((false, None, referenced_by));
+        }
+        let asset = assets.remove(index);
+        // Another kind may share the same content.
+        let shared = assets.iter().any(|a| a.hash == asset.hash);
+        Ok((true, (!shared).then_some(asset.hash), referenced_by))
+    })?;
+    if let Some(hash) = unused_hash {
+        if let Err(e) = std::fs::remove_file(state.object_path(&hash)?) {
+            warn!(%hash, error = %e, "Failed to remove library object");
+        }
+    }
+    Ok(RemoveOutcome {
+        removed,
+        referenced_by,
+    })
+}
+
+/// Places an asset in the project's `library` folder, hard-linked where the
+/// filesystem allows and copied otherwise. Returns the path in the project.
+#[tauri::command]
+pub async fn use_asset_in_project(
+    app: AppHandle,
+    asset_id: String,
+    project_id: String,
+) -> Result<String, ServerError> {
+    blocking::watched("use_asset_in_project", async move {
+        if !valid_project_id(&project_id) {
+            return Err(ServerError::Library(format!(
+                "invalid project id: {}",
+                project_id
+            )));
+        }
+        let state = app.state::<LibraryState>();
+        let asset = state
+            .assets
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|a| a.id == asset_id)
+            .cloned()
+            .ok_or_else(|| ServerError::Library(format!("no library asset {}", asset_id)))?;
+
+        let data_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| ServerError::Io(e.to_string()))?;
+        let dir = data_dir.join("projects").join(&project_id).join("library");
+        tokio::fs::create_dir_all(&dir).await?;
+        let mut file_name = format!("{}-{}", file_stem(&asset.name), &asset.hash[..8]);
+        if let Some(ext) = &asset.extension {
+            file_name.push('.');
+            file_name.push_str(ext);
+        }
+        let dest = dir.join(file_name);
+        if !dest.exists() {
+            let object = state.object_path(&asset.hash)?;
+            let linked = blocking::run({
+                let (object, dest) = (object.clone(), dest.clone());
+                move || std::fs::hard_link(object, dest)
+            })
+            .await
+            .map_err(ServerError::Library)?;
+            if linked.is_err() {
+                tokio::fs::copy(&object, &dest).await?;
+            }
+        }
+
+        state.update(|assets| {
+            if let Some(a) = assets.iter_mut().find(|a| a.id == asset_id) {
+                if !a.projects.contains(&project_id) {
+                    a.projects.push(project_id.clone());
+                }
+            }
+            Ok(())
+        })?;
+        Ok(dest.to_string_lossy().into_owned())
+    })
+    .await
+}
@@ -0,0 +1,384 @@
+//! Paid-tier licensing, checked here rather than in the sidecar or webview,
+//! which are both plain JS on disk.
+//!
+//! Activation trades a license key for a token signed by the license
+//! server. The token is kept in the OS keychain and verified against the
+//! public key built into the app, so status works offline. It is revalidated
+//! online once a day; after `Settings::license_grace_days` without a
+//! successful check the app falls back to the free tier.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::crash;
+use crate::error::ServerError;
+use crate::network::NetworkState;
+use crate::server::ServerState;
+use crate::settings::SettingsState;
+use crate::time::now_secs;
+
+/// License server base URL and token public key (32 bytes, base64url),
+/// both set at build time. Without them every build is free tier.
+const API: Option<&str> = option_env!("AIYOU_LICENSE_API");
+const PUBLIC_KEY: Option<&str> = option_env!("AIYOU_LICENSE_PUBLIC_KEY");
+
+const KEYCHAIN_SERVICE: &str = "com.aiyou.app";
+const KEYCHAIN_ACCOUNT: &str = "license";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const REVALIDATE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const DAY_SECS: u64 = 24 * 60 * 60;
+/// Upper bound on `Settings::license_grace_days`.
+const MAX_GRACE_DAYS: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Tier {
+    #[default]
+    Free,
+    Pro,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Standing {
+    /// No license activated.
+    Unlicensed,
+    Active,
+    Expired,
+    /// Not revalidated online within the grace window.
+    GraceEnded,
+    /// The stored token doesn't verify.
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseStatus {
+    /// What the app actually allows; free unless `standing` is active.
+    pub tier: Tier,
+    pub standing: Standing,
+    pub license_id: Option<String>,
+    /// Unix time in seconds; `None` for a perpetual license.
+    pub expires_at: Option<u64>,
+    /// Days left to work offline before falling back to free.
+    pub grace_days_remaining: Option<u32>,
+    /// Unix time in seconds of the last successful online check.
+    pub last_validated: Option<u64>,
+}
+
+/// What the license server signs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Claims {
+    license_id: String,
+    tier: Tier,
+    /// Unix time in seconds.
+    expires_at: Option<u64>,
+}
+
+/// Kept in the keychain as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredLicense {
+    token: String,
+    /// Unix time in seconds.
+    last_validated: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+#[derive(Default)]
+pub struct LicenseState {
+    stored: Mutex<Option<StoredLicense>>,
+}
+
+fn public_key() -> Option<VerifyingKey> {
+    let bytes = URL_SAFE_NO_PAD.decode(PUBLIC_KEY?).ok()?;
+    VerifyingKey::from_bytes(&bytes.try_into().ok()?).ok()
+}
+
+/// Checks a `<payload>.<signature>` token and returns its claims.
+fn verify(key: &VerifyingKey, token: &str) -> Option<Claims> {
+    let (payload, signature) = token.split_once('.')?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    let signature = Signature::from_slice(&signature).ok()?;
+    key.verify(&payload, &signature).ok()?;
+    serde_json::from_slice(&payload).ok()
+}
+
+fn evaluate(
+    claims: Option<Claims>,
+    last_validated: u64,
+    now: u64,
+    grace_days: u32,
+) -> LicenseStatus {
+    let Some(claims) = claims else {
+        return LicenseStatus {
+            tier: Tier::Free,
+            standing: Standing::Invalid,
+            license_id: None,
+            expires_at: None,
+            grace_days_remaining: None,
+            last_validated: Some(last_validated),
+        };
+    };
+    let grace_secs = u64::from(grace_days) * DAY_SECS;
+    let offline_secs = now.saturating_sub(last_validated);
+    let standing = if claims.expires_at.is_some_and(|at| at <= now) {
+        Standing::Expired
+    } else if offline_secs > grace_secs {
+        Standing::GraceEnded
+    } else {
+        Standing::Active
+    };
+    LicenseStatus {
+        tier: if standing == Standing::Active {
+            claims.tier
+        } else {
+            Tier::Free
+        },
+        standing,
+        license_id: Some(claims.license_id),
+        expires_at: claims.expires_at,
+        grace_days_remaining: (standing == Standing::Active)
+            .then(|| (grace_secs - offline_secs).div_ceil(DAY_SECS) as u32),
+        last_validated: Some(last_validated),
+    }
+}
+
+fn keychain() -> Result<keyring::Entry, ServerError> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        .map_err(|e| ServerError::License(format!("keychain unavailable: {}", e)))
+}
+
+fn load_stored() -> Option<StoredLicense> {
+    let json = match keychain().ok()?.get_password() {
+        Ok(json) => json,
+        Err(keyring::Error::NoEntry) => return None,
+        Err(e) => {
+            warn!(error = %e, "Failed to read license from keychain");
+            return None;
+        }
+    };
+    serde_json::from_str(&json).ok()
+}
+
+fn save(app: &AppHandle, stored: Option<StoredLicense>) -> Result<(), ServerError> {
+    let entry = keychain()?;
+    match &stored {
+        Some(stored) => {
+            let json = serde_json::to_string(stored).map_err(|e| ServerError::License(e.to_string()))?;
+            entry
+                .set_password(&json)
+                .map_err(|e| ServerError::License(format!("keychain write failed: {}", e)))?;
+        }
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => {
+                return Err(ServerError::License(format!("keychain delete failed: {}", e)));
+            }
+        },
+    }
+    *app.state::<LicenseState>().stored.lock().unwrap() = stored;
+    let _ = app.emit("license-changed", status(app));
+    Ok(())
+}
+
+/// Current status, computed from the stored token without the network.
+pub fn status(app: &AppHandle) -> LicenseStatus {
+    let stored = app.state::<LicenseState>().stored.lock().unwrap().clone();
+    let Some(stored) = stored else {
+        return LicenseStatus {
+            tier: Tier::Free,
+            standing: Standing::Unlicensed,
+            license_id: None,
+            expires_at: None,
+            grace_days_remaining: None,
+            last_validated: None,
+        };
+    };
+    let grace_days = app
+        .state::<SettingsState>()
+        .get()
+        .license_grace_days
+        .min(MAX_GRACE_DAYS);
+    let claims = public_key().and_then(|key| verify(&key, &stored.token));
+    evaluate(claims, stored.last_validated, now_secs(), grace_days)
+}
+
+pub fn tier(app: &AppHandle) -> Tier {
+    status(app).tier
+}
+
+/// Forces the watermark on a render spec unless the paid tier is active.
+pub fn apply_to_render(app: &AppHandle, spec: &mut serde_json::Value) {
+    if tier(app) == Tier::Free {
+        if let Some(spec) = spec.as_object_mut() {
+            spec.insert("watermark".into(), serde_json::Value::Bool(true));
+        }
+    }
+}
+
+async fn request_token(
+    app: &AppHandle,
+    path: &str,
+    body: serde_json::Value,
+) -> Result<String, ServerError> {
+    let api = API.ok_or_else(|| {
+        ServerError::License("licensing isn't available in this build".into())
+    })?;
+    let resp = app
+        .state::<ServerState>()
+        .client()
+        .post(format!("{}{}", api.trim_end_matches('/'), path))
+        .json(&body)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?;
+    if !resp.status().is_success() {
+        return Err(ServerError::BadStatus(resp.status().as_u16()));
+    }
+    let resp: TokenResponse = resp.json().await?;
+    Ok(resp.token)
+}
+
+/// Refreshes the stored token online. A license the server rejects is
+/// removed; an unreachable server leaves it to the grace window.
+async fn revalidate(app: &AppHandle) -> Result<(), ServerError> {
+    let stored = app.state::<LicenseState>().stored.lock().unwrap().clone();
+    let Some(stored) = stored else {
+        return Ok(());
+    };
+    let body = serde_json::json!({ "token": stored.token });
+    match request_token(app, "/validate", body).await {
+        Ok(token) => save(
+            app,
+            Some(StoredLicense {
+                token,
+                last_validated: now_secs(),
+            }),
+        ),
+        Err(ServerError::BadStatus(status)) if (400..500).contains(&status) => {
+            info!(status, "License rejected by the license server; removing it");
+            save(app, None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Loads the stored license and revalidates it daily while online.
+pub fn init(app: &AppHandle) {
+    *app.state::<LicenseState>().stored.lock().unwrap() = load_stored();
+    if API.is_none() {
+        return;
+    }
+    let handle = app.clone();
+    crash::spawn("license", async move {
+        loop {
+            if handle.state::<NetworkState>().is_online() {
+                if let Err(e) = revalidate(&handle).await {
+                    warn!(error = %e, "License revalidation failed");
+                }
+            }
+            tokio::time::sleep(REVALIDATE_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn activate_license(app: AppHandle, key: String) -> Result<LicenseStatus, ServerError> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(ServerError::License("license key is empty".into()));
+    }
+    let token = request_token(&app, "/activate", serde_json::json!({ "key": key })).await?;
+    let verifies = public_key().and_then(|k| verify(&k, &token)).is_some();
+    if !verifies {
+        return Err(ServerError::License(
+            "license server returned a token that doesn't verify".into(),
+        ));
+    }
+    save(
+        &app,
+        Some(StoredLicense {
+            token,
+            last_validated: now_secs(),
+        }),
+    )?;
+    Ok(status(&app))
+}
+
+#[tauri::command]
+pub fn get_license_status(app: AppHandle) -> LicenseStatus {
+    status(&app)
+}
+
+/// Releases the activation on the license server if it can be reached,
+/// and forgets the license locally either way.
+#[tauri::command]
+pub async fn deactivate_license(app: AppHandle) -> Result<(), ServerError> {
+    let stored = app.state::<LicenseState>().stored.lock().unwrap().clone();
+    if let Some(stored) = stored {
+        let body = serde_json::json!({ "token": stored.token });
+        if let Err(e) = request_token(&app, "/deactivate", body).await {
+            warn!(error = %e, "Couldn't release the license on the server");
+        }
+    }
+    save(&app, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn token(key: &SigningKey, claims: &serde_json::Value) -> String {
+        let payload = serde_json::to_vec(claims).unwrap();
+        let signature = key.sign(&payload);
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    #[test]
+    fn verifies_tokens_and_applies_grace() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public = key.verifying_key();
+        let claims = serde_json::json!({
+            "licenseId": "lic-1",
+            "tier": "pro",
+            "expiresAt": 1_000 * DAY_SECS,
+        });
+        let good = token(&key, &claims);
+        assert!(verify(&public, &good).is_some());
+
+        let forged = token(&SigningKey::from_bytes(&[8; 32]), &claims);
+        assert!(verify(&public, &forged).is_none());
+        assert!(verify(&public, "not-a-token").is_none());
+
+        let now = 500 * DAY_SECS;
+        let fresh = evaluate(verify(&public, &good), now - DAY_SECS, now, 14);
+        assert_eq!(fresh.tier, Tier::Pro);
+        assert_eq!(fresh.grace_days_remaining, Some(13));
+
+        let stale = evaluate(verify(&public, &good), now - 15 * DAY_SECS, now, 14);
+        assert_eq!(stale.standing, Standing::GraceEnded);
+        assert_eq!(stale.tier, Tier::Free);
+
+        let expired = evaluate(verify(&public, &good), now, 1_001 * DAY_SECS, 14);
+        assert_eq!(expired.standing, Standing::Expired);
+        assert_eq!(expired.tier, Tier::Free);
+    }
+}
@@ -0,0 +1,103 @@
+//! Logging for the Rust layer through `tracing`.
+//!
+//! Events go to `app.<date>.log` in the app log dir, next to the sidecar's
+//! `server.log`, and to the console in dev builds. The filter comes from
+//! `Settings::log_level` and can be changed at runtime with `set_log_level`,
+//! e.g. to `info,aiyou_lib::server=debug` for one user's machine.
+
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+use crate::error::ServerError;
+use crate::settings::SettingsState;
+
+/// Daily log files kept.
+const MAX_LOG_FILES: usize = 7;
+
+#[derive(Default)]
+pub struct LoggingState {
+    filter: Mutex<Option<reload::Handle<EnvFilter, Registry>>>,
+    /// Flushes the file writer when dropped at exit.
+    guard: Mutex<Option<WorkerGuard>>,
+}
+
+pub(crate) fn parse_filter(level: &str) -> Result<EnvFilter, ServerError> {
+    EnvFilter::try_new(level.trim()).map_err(|e| ServerError::Logging(e.to_string()))
+}
+
+/// Installs the global subscriber at `info` until settings are loaded.
+/// Headless runs log to stderr, as their stdout is the progress output.
+pub fn init(app: &AppHandle, headless: bool) {
+    let (filter, handle) = reload::Layer::new(EnvFilter::new("info"));
+
+    let console = (cfg!(debug_assertions) || headless).then(|| {
+        let writer = if headless {
+            BoxMakeWriter::new(std::io::stderr)
+        } else {
+            BoxMakeWriter::new(std::io::stdout)
+        };
+        fmt::layer().with_writer(writer)
+    });
+
+    let state = app.state::<LoggingState>();
+    let file = app.path().app_log_dir().ok().and_then(|dir| {
+        std::fs::create_dir_all(&dir).ok()?;
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("app")
+            .filename_suffix("log")
+            .max_log_files(MAX_LOG_FILES)
+            .build(dir)
+            .map_err(|e| eprintln!("aiyou: can't open app log: {}", e))
+            .ok()?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        *state.guard.lock().unwrap() = Some(guard);
+        // Sidecar output already has its own `server.log`.
+        Some(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+                .with_filter(filter_fn(|meta| meta.target() != "sidecar")),
+        )
+    });
+
+    let installed = tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(file)
+        .try_init();
+    if let Err(e) = installed {
+        eprintln!("aiyou: logging already initialized: {}", e);
+        return;
+    }
+    *state.filter.lock().unwrap() = Some(handle);
+}
+
+/// Switches to `level`, which may be a plain level such as `debug` or a
+/// full filter directive.
+pub fn set_level(app: &AppHandle, level: &str) -> Result<(), ServerError> {
+    let filter = parse_filter(level)?;
+    let state = app.state::<LoggingState>();
+    if let Some(handle) = state.filter.lock().unwrap().as_ref() {
+        handle
+            .reload(filter)
+            .map_err(|e| ServerError::Logging(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Applies and saves a new log level.
+#[tauri::command]
+pub fn set_log_level(app: AppHandle, level: String) -> Result<(), ServerError> {
+    set_level(&app, &level)?;
+    app.state::<SettingsState>()
+        .update(|s| s.log_level = level.trim().to_owned())?;
+    tracing::info!(level = level.trim(), "Log level changed");
+    Ok(())
+}
@@ -0,0 +1,354 @@
+//! Watches the `media` folder of each open project for files added,
+//! changed or removed outside the app, and emits `project-media-changed`.
+//!
+//! Events are collected until the folder has been quiet for [`DEBOUNCE`],
+//! then compared with what was there before, so an editor saving a clip in
+//! several writes is one change. Temporary files, and files `tempfiles` is
+//! still writing, are left out until they are finished. Watching starts
+//! with `open_project_guard` and stops with `close_project_guard` or exit.
+
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::backups::valid_project_id;
+use crate::blocking;
+use crate::crash;
+use crate::error::ServerError;
+use crate::frames;
+use crate::tempfiles;
+
+/// Projects watched at once; more are opened unwatched.
+const MAX_WATCHED: usize = 8;
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MediaKind {
+    Video,
+    Audio,
+    Image,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaChange {
+    pub path: String,
+    pub change: ChangeKind,
+    pub kind: MediaKind,
+    /// From ffprobe, for added or modified video and audio.
+    pub duration_ms: Option<u64>,
+    /// Why probing failed; the file may be damaged or not media at all.
+    pub probe_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectMediaChanged {
+    project_id: String,
+    changes: Vec<MediaChange>,
+}
+
+/// Watchers by project id. Dropping one stops it, and its task ends once
+/// the events it had sent are handled.
+#[derive(Default)]
+pub struct MediaWatchState {
+    watchers: Mutex<HashMap<String, RecommendedWatcher>>,
+}
+
+/// Size and modification time; a file whose stamp is unchanged is skipped.
+type Stamp = (u64, Option<SystemTime>);
+
+fn media_dir(app: &AppHandle, project_id: &str) -> Result<PathBuf, ServerError> {
+    if !valid_project_id(project_id) {
+        return Err(ServerError::MediaWatch(format!(
+            "invalid project id: {}",
+            project_id
+        )));
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("projects").join(project_id).join("media"))
+}
+
+fn media_kind(path: &Path) -> Option<MediaKind> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "mp4" | "m4v" | "mov" | "webm" | "mkv" | "avi" => Some(MediaKind::Video),
+        "mp3" | "wav" | "m4a" | "aac" | "flac" | "ogg" => Some(MediaKind::Audio),
+        "png" | "jpg" | "jpeg" | "webp" | "gif" => Some(MediaKind::Image),
+        _ => None,
+    }
+}
+
+/// Names editors and downloaders give files they haven't finished.
+fn is_partial(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return true;
+    };
+    name.starts_with('.')
+        || name.ends_with('~')
+        || [".part", ".tmp", ".crdownload", ".download"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some((meta.len(), meta.modified().ok()))
+}
+
+/// Media files under `dir`, with their stamps.
+fn scan(dir: &Path) -> HashMap<PathBuf, Stamp> {
+    let mut found = HashMap::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                dirs.push(path);
+            } else if media_kind(&path).is_some() && !is_partial(&path) {
+                if let Some(stamp) = stamp(&path) {
+                    found.insert(path, stamp);
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Updates `known` for the paths that had events and returns what changed.
+/// A path that is gone takes everything known under it along, for folders
+/// removed or moved away whole.
+fn diff(
+    known: &mut HashMap<PathBuf, Stamp>,
+    touched: BTreeSet<PathBuf>,
+    in_progress: impl Fn(&Path) -> bool,
+) -> Vec<(PathBuf, ChangeKind, MediaKind)> {
+    let mut changes = Vec::new();
+    for path in touched {
+        let files: Vec<(PathBuf, Stamp)> = if path.is_dir() {
+            // Moved in whole: its files get no events of their own.
+            scan(&path).into_iter().collect()
+        } else if let Some(stamp) = stamp(&path) {
+            vec![(path, stamp)]
+        } else {
+            let gone: Vec<PathBuf> =
+                known.keys().filter(|k| k.starts_with(&path)).cloned().collect();
+            for file in gone {
+                known.remove(&file);
+                if let Some(kind) = media_kind(&file) {
+                    changes.push((file, ChangeKind::Removed, kind));
+                }
+            }
+            continue;
+        };
+        for (file, stamp) in files {
+            let Some(kind) = media_kind(&file) else {
+                continue;
+            };
+            if is_partial(&file) || in_progress(&file) {
+                continue;
+            }
+            let change = match known.insert(file.clone(), stamp) {
+                None => ChangeKind::Added,
+                Some(old) if old != stamp => ChangeKind::Modified,
+                Some(_) => continue,
+            };
+            changes.push((file, change, kind));
+        }
+    }
+    changes
+}
+
+async fn describe(path: PathBuf, change: ChangeKind, kind: MediaKind) -> MediaChange {
+    let probe = change != ChangeKind::Removed && kind != MediaKind::Image;
+    let (duration_ms, probe_error) = if probe {
+        match frames::duration_ms(&path).await {
+            Ok(ms) => (Some(ms), None),
+            Err(e) => (None, Some(e.to_string())),
+        }
+    } else {
+        (None, None)
+    };
+    MediaChange {
+        path: path.to_string_lossy().into_owned(),
+        change,
+        kind,
+        duration_ms,
+        probe_error,
+    }
+}
+
+async fn run(
+    app: AppHandle,
+    project_id: String,
+    dir: PathBuf,
+    mut rx: mpsc::UnboundedReceiver<PathBuf>,
+) {
+    let Ok(mut known) = blocking::run(move || scan(&dir)).await else {
+        return;
+    };
+    while let Some(first) = rx.recv().await {
+        let mut touched = BTreeSet::from([first]);
+        // `None` once the watcher is dropped; what came before still counts.
+        let mut open = true;
+        while open {
+            match tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                Ok(Some(path)) => {
+                    touched.insert(path);
+                }
+                Ok(None) => open = false,
+                Err(_) => break,
+            }
+        }
+        let handle = app.clone();
+        let settled = blocking::run(move || {
+            let changes = diff(&mut known, touched, |p| tempfiles::in_progress(&handle, p));
+            (known, changes)
+        })
+        .await;
+        let Ok((settled_known, changes)) = settled else {
+            return;
+        };
+        known = settled_known;
+        if !changes.is_empty() {
+            let mut described = Vec::with_capacity(changes.len());
+            for (path, change, kind) in changes {
+                described.push(describe(path, change, kind).await);
+            }
+            debug!(%project_id, changes = described.len(), "Project media changed");
+            let _ = app.emit(
+                "project-media-changed",
+                ProjectMediaChanged {
+                    project_id: project_id.clone(),
+                    changes: described,
+                },
+            );
+        }
+        if !open {
+            return;
+        }
+    }
+}
+
+/// Starts watching `project_id`'s media folder, creating it if needed.
+/// Watching a project again is a no-op.
+pub fn watch(app: &AppHandle, project_id: &str) -> Result<(), ServerError> {
+    let state = app.state::<MediaWatchState>();
+    let mut watchers = state.watchers.lock().unwrap();
+    if watchers.contains_key(project_id) {
+        return Ok(());
+    }
+    if watchers.len() >= MAX_WATCHED {
+        return Err(ServerError::MediaWatch(format!(
+            "already watching {} projects",
+            MAX_WATCHED
+        )));
+    }
+    let dir = media_dir(app, project_id)?;
+    std::fs::create_dir_all(&dir)?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            // Reads, ffprobe's included, and metadata touches change nothing.
+            Ok(notify::Event {
+                kind: EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)),
+                ..
+            }) => {}
+            Ok(event) => {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+            Err(e) => warn!(error = %e, "Media watcher error"),
+        }
+    })
+    .map_err(|e| ServerError::MediaWatch(e.to_string()))?;
+    watcher
+        .watch(&dir, RecursiveMode::Recursive)
+        .map_err(|e| ServerError::MediaWatch(e.to_string()))?;
+
+    crash::spawn("media-watch", run(app.clone(), project_id.to_owned(), dir, rx));
+    watchers.insert(project_id.to_owned(), watcher);
+    info!(project_id, "Watching project media");
+    Ok(())
+}
+
+/// Stops watching `project_id`, if it was watched.
+pub fn unwatch(app: &AppHandle, project_id: &str) {
+    let removed = app
+        .state::<MediaWatchState>()
+        .watchers
+        .lock()
+        .unwrap()
+        .remove(project_id);
+    if removed.is_some() {
+        info!(project_id, "Stopped watching project media");
+    }
+}
+
+pub fn on_exit(app: &AppHandle) {
+    app.state::<MediaWatchState>().watchers.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_classified_against_what_was_known() {
+        let dir = std::env::temp_dir().join(format!("aiyou-media-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("takes")).unwrap();
+        let clip = dir.join("clip.mp4");
+        std::fs::write(&clip, b"one").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"x").unwrap();
+        let mut known = scan(&dir);
+        assert_eq!(known.len(), 1);
+
+        std::fs::write(&clip, b"longer").unwrap();
+        let added = dir.join("takes/b-roll.mov");
+        std::fs::write(&added, b"new").unwrap();
+        let partial = dir.join("render.mp4.part");
+        std::fs::write(&partial, b"half").unwrap();
+        let ours = dir.join("export.mp4");
+        std::fs::write(&ours, b"being copied").unwrap();
+        let touched = BTreeSet::from([clip.clone(), dir.join("takes"), partial, ours.clone()]);
+        let mut changes = diff(&mut known, touched, |p| p == ours);
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            changes,
+            [
+                (clip.clone(), ChangeKind::Modified, MediaKind::Video),
+                (added, ChangeKind::Added, MediaKind::Video),
+            ]
+        );
+
+        // Nothing new since: no change. Then the folder goes away.
+        assert!(diff(&mut known, BTreeSet::from([clip.clone()]), |_| false).is_empty());
+        std::fs::remove_dir_all(dir.join("takes")).unwrap();
+        let changes = diff(&mut known, BTreeSet::from([dir.join("takes")]), |_| false);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].1, ChangeKind::Removed);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
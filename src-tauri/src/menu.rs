@@ -0,0 +1,211 @@
+//! Native application menu. Items emit `menu-action` with their id and the
+//! frontend maps it to a handler.
+
+use tauri::menu::{Menu, MenuEvent, MenuItem, MenuItemBuilder, Submenu, SubmenuBuilder};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::warn;
+
+use crate::error::ServerError;
+use crate::i18n;
+use crate::recents::{RecentProject, RecentsState};
+
+const OPEN_RECENT: &str = "open-recent";
+/// Prefix of "Open Recent" item ids, followed by the project id.
+const RECENT_PREFIX: &str = "open-recent:";
+
+/// Ids of every item that emits `menu-action`. Tray menu clicks reach the
+/// same global handler, so anything else is ignored.
+const ACTIONS: &[&str] = &[
+    "new-project",
+    "open-project",
+    "save-project",
+    "save-project-as",
+    "export-episode",
+    "restart-server",
+    "open-settings",
+    "open-docs",
+    "report-issue",
+    "about",
+];
+
+fn item<R: Runtime>(
+    app: &AppHandle<R>,
+    id: &str,
+    text: &str,
+    accelerator: Option<&str>,
+) -> tauri::Result<MenuItem<R>> {
+    let mut builder = MenuItemBuilder::with_id(id, text);
+    if let Some(accelerator) = accelerator {
+        builder = builder.accelerator(accelerator);
+    }
+    builder.build(app)
+}
+
+/// Builds the application menu in the current language. On macOS the
+/// first submenu becomes the app menu, which is where Settings and Quit
+/// live there.
+pub fn build<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<Menu<R>> {
+    let t = |key: &str| i18n::t(app, key);
+    let settings = item(app, "open-settings", &t("menu.settings"), Some("CmdOrCtrl+,"))?;
+
+    let mut file = SubmenuBuilder::new(app, t("menu.file"))
+        .item(&item(app, "new-project", &t("menu.newProject"), Some("CmdOrCtrl+N"))?)
+        .item(&item(app, "open-project", &t("menu.openProject"), Some("CmdOrCtrl+O"))?)
+        .item(
+            &SubmenuBuilder::with_id(app, OPEN_RECENT, t("menu.openRecent"))
+                .item(&no_recents(app)?)
+                .build()?,
+        )
+        .separator()
+        .item(&item(app, "save-project", &t("menu.save"), Some("CmdOrCtrl+S"))?)
+        .item(&item(app, "save-project-as", &t("menu.saveAs"), Some("CmdOrCtrl+Shift+S"))?);
+    if !cfg!(target_os = "macos") {
+        file = file
+            .separator()
+            .item(&settings)
+            .separator()
+            .quit_with_text(t("menu.quit"));
+    }
+
+    let edit = SubmenuBuilder::new(app, t("menu.edit"))
+        .undo_with_text(t("menu.undo"))
+        .redo_with_text(t("menu.redo"))
+        .separator()
+        .cut_with_text(t("menu.cut"))
+        .copy_with_text(t("menu.copy"))
+        .paste_with_text(t("menu.paste"))
+        .select_all_with_text(t("menu.selectAll"))
+        .build()?;
+
+    // Export needs an open project; the frontend enables it.
+    let export = MenuItemBuilder::with_id("export-episode", t("menu.exportEpisode"))
+        .accelerator("CmdOrCtrl+E")
+        .enabled(false)
+        .build(app)?;
+    let project = SubmenuBuilder::new(app, t("menu.project"))
+        .item(&export)
+        .separator()
+        .item(&item(app, "restart-server", &t("menu.restartServer"), None)?)
+        .build()?;
+
+    let mut help = SubmenuBuilder::new(app, t("menu.help"))
+        .item(&item(app, "open-docs", &t("menu.documentation"), None)?)
+        .item(&item(app, "report-issue", &t("menu.reportIssue"), None)?);
+    if !cfg!(target_os = "macos") {
+        help = help.separator().item(&item(app, "about", &t("menu.about"), None)?);
+    }
+
+    let mut submenus: Vec<Submenu<R>> = Vec::new();
+    if cfg!(target_os = "macos") {
+        submenus.push(
+            SubmenuBuilder::new(app, t("menu.app"))
+                .item(&item(app, "about", &t("menu.about"), None)?)
+                .separator()
+                .item(&settings)
+                .separator()
+                .services()
+                .separator()
+                .hide()
+                .hide_others()
+                .show_all()
+                .separator()
+                .quit()
+                .build()?,
+        );
+    }
+    submenus.extend([file.build()?, edit, project, help.build()?]);
+
+    let menu = Menu::new(app)?;
+    for submenu in &submenus {
+        menu.append(submenu)?;
+    }
+    Ok(menu)
+}
+
+/// Replaces the app menu with a fresh one in the current language,
+/// keeping Open Recent and which items the frontend greyed out.
+pub fn rebuild(app: &AppHandle) -> tauri::Result<()> {
+    let old = app.menu();
+    let menu = build(app)?;
+    if let Some(old) = &old {
+        for id in ACTIONS {
+            if let (Some(was), Some(now)) = (find(old, id), find(&menu, id)) {
+                now.set_enabled(was.is_enabled()?)?;
+            }
+        }
+    }
+    app.set_menu(menu)?;
+    set_recent_projects(app, &app.state::<RecentsState>().get());
+    Ok(())
+}
+
+pub fn on_menu_event<R: Runtime>(app: &AppHandle<R>, event: MenuEvent) {
+    let id = event.id().as_ref();
+    if ACTIONS.contains(&id) {
+        let _ = app.emit("menu-action", id);
+    } else if let Some(project_id) = id.strip_prefix(RECENT_PREFIX) {
+        let recents = app.state::<RecentsState>().get();
+        if let Some(project) = recents.iter().find(|p| p.id == project_id) {
+            let _ = app.emit("open-recent-project", project);
+        }
+    }
+}
+
+fn no_recents<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<MenuItem<R>> {
+    MenuItemBuilder::with_id("open-recent-empty", i18n::t(app, "menu.noRecents"))
+        .enabled(false)
+        .build(app)
+}
+
+fn open_recent_menu<R: Runtime>(menu: &Menu<R>) -> Option<Submenu<R>> {
+    menu.items()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|kind| kind.as_submenu())
+        .find_map(|submenu| submenu.get(OPEN_RECENT))
+        .and_then(|kind| kind.as_submenu().cloned())
+}
+
+/// Rebuilds "Open Recent" from the given list.
+pub fn set_recent_projects<R: Runtime>(app: &AppHandle<R>, projects: &[RecentProject]) {
+    let Some(submenu) = app.menu().and_then(|menu| open_recent_menu(&menu)) else {
+        return;
+    };
+    let rebuilt = (|| -> tauri::Result<()> {
+        for item in submenu.items()? {
+            submenu.remove(&item)?;
+        }
+        if projects.is_empty() {
+            return submenu.append(&no_recents(app)?);
+        }
+        for project in projects {
+            let id = format!("{}{}", RECENT_PREFIX, project.id);
+            submenu.append(&MenuItemBuilder::with_id(id, &project.name).build(app)?)?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = rebuilt {
+        warn!(error = %e, "Failed to update Open Recent menu");
+    }
+}
+
+fn find<R: Runtime>(menu: &Menu<R>, id: &str) -> Option<MenuItem<R>> {
+    menu.items()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|kind| kind.as_submenu())
+        .find_map(|submenu| submenu.get(id))
+        .and_then(|kind| kind.as_menuitem().cloned())
+}
+
+/// Greys a menu item out or back in, e.g. Export Episode while no project
+/// is open or Restart Server while the server is down.
+#[tauri::command]
+pub fn set_menu_item_enabled(app: AppHandle, id: String, enabled: bool) -> Result<(), ServerError> {
+    let item = app
+        .menu()
+        .and_then(|menu| find(&menu, &id))
+        .ok_or_else(|| ServerError::Window(format!("unknown menu item: {}", id)))?;
+    item.set_enabled(enabled)?;
+    Ok(())
+}
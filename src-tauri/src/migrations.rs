@@ -0,0 +1,470 @@
+//! Upgrades settings and project data written by older versions.
+//!
+//! The data dir records its schema version in `schema.json`. At launch,
+//! every step in [`MIGRATIONS`] newer than that runs in order, before the
+//! sidecar is spawned; the sidecar isn't started against a directory that
+//! is behind or was left mid-step.
+//!
+//! Before a step runs, the files it says it affects are copied to
+//! `migrations/v<version>/`, and the step is marked in progress. A step
+//! that fails is rolled back from that copy. One that was still marked at
+//! launch was cut off by a crash or a kill, so it is rolled back the same
+//! way and run again. The copies are kept afterwards.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{error, info, warn};
+
+use crate::error::ServerError;
+use crate::i18n;
+use crate::time::now_ms;
+
+/// Where a step finds the files it migrates.
+#[derive(Debug, Clone)]
+pub struct MigrationContext {
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+}
+
+pub struct Migration {
+    /// The schema version once this step has run. Steps are listed in
+    /// increasing order.
+    pub version: u32,
+    pub name: &'static str,
+    /// Every file the step may change, create or delete. Only these are
+    /// backed up and rolled back.
+    pub affects: fn(&MigrationContext) -> Vec<PathBuf>,
+    /// Migrates the files `affects` returned. Missing files are not an
+    /// error; a fresh data dir runs every step too.
+    pub run: fn(&[PathBuf], &MigrationContext) -> Result<(), ServerError>,
+}
+
+/// All steps, oldest first. Append new ones with the next version; never
+/// change or remove one that has shipped.
+const MIGRATIONS: &[Migration] = &[];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationOutcome {
+    Applied,
+    /// Failed and was rolled back.
+    RolledBack,
+    /// Found cut off at launch and rolled back, to be run again.
+    Interrupted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationRecord {
+    pub version: u32,
+    pub name: String,
+    /// Unix time in milliseconds.
+    pub at: u64,
+    pub outcome: MigrationOutcome,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct SchemaMarker {
+    version: u32,
+    /// The step that was running. Still set at launch means it was cut off.
+    in_progress: Option<u32>,
+    history: Vec<MigrationRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationProgress {
+    pub version: u32,
+    pub name: String,
+    pub done: usize,
+    pub total: usize,
+    /// Splash text, e.g. `Upgrading data 1/3…`.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationHistory {
+    /// Schema version of the data dir.
+    pub version: u32,
+    /// Schema version this build expects.
+    pub latest: u32,
+    pub history: Vec<MigrationRecord>,
+    /// Why the data dir couldn't be brought up to date, if it couldn't.
+    pub error: Option<String>,
+}
+
+/// Whether the data dir is current, so the sidecar may start.
+#[derive(Default)]
+pub struct MigrationState {
+    current: AtomicBool,
+    error: Mutex<Option<String>>,
+}
+
+/// One backed-up file; `saved` is `None` if it didn't exist, in which
+/// case rolling back deletes whatever the step created there.
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupEntry {
+    path: PathBuf,
+    saved: Option<String>,
+}
+
+fn latest(steps: &[Migration]) -> u32 {
+    steps.last().map_or(0, |s| s.version)
+}
+
+fn marker_path(ctx: &MigrationContext) -> PathBuf {
+    ctx.data_dir.join("schema.json")
+}
+
+fn backup_dir(ctx: &MigrationContext, version: u32) -> PathBuf {
+    ctx.data_dir.join("migrations").join(format!("v{}", version))
+}
+
+/// Written aside and renamed, so a kill can't leave half a file.
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), ServerError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_vec_pretty(value).map_err(|e| ServerError::Io(e.to_string()))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn read_marker(ctx: &MigrationContext) -> Result<SchemaMarker, ServerError> {
+    match std::fs::read(marker_path(ctx)) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map_err(|e| ServerError::Migration(format!("unreadable schema.json: {}", e))),
+        // Written by a version from before migrations.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SchemaMarker::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn back_up(dir: &Path, paths: &[PathBuf]) -> Result<(), ServerError> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)?;
+    }
+    std::fs::create_dir_all(dir)?;
+    let mut entries = Vec::with_capacity(paths.len());
+    for (i, path) in paths.iter().enumerate() {
+        let saved = if path.is_file() {
+            let name = i.to_string();
+            std::fs::copy(path, dir.join(&name))?;
+            Some(name)
+        } else {
+            None
+        };
+        entries.push(BackupEntry {
+            path: path.clone(),
+            saved,
+        });
+    }
+    // Written last, so a backup with a manifest is complete.
+    write_json(&dir.join("manifest.json"), &entries)
+}
+
+fn restore(dir: &Path) -> Result<(), ServerError> {
+    let data = std::fs::read(dir.join("manifest.json"))?;
+    let entries: Vec<BackupEntry> =
+        serde_json::from_slice(&data).map_err(|e| ServerError::Migration(e.to_string()))?;
+    for entry in entries {
+        match entry.saved {
+            Some(name) => {
+                if let Some(parent) = entry.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(dir.join(name), &entry.path)?;
+            }
+            None if entry.path.exists() => std::fs::remove_file(&entry.path)?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn record(
+    marker: &mut SchemaMarker,
+    step: &Migration,
+    outcome: MigrationOutcome,
+    error: Option<String>,
+) {
+    marker.history.push(MigrationRecord {
+        version: step.version,
+        name: step.name.to_owned(),
+        at: now_ms(),
+        outcome,
+        error,
+    });
+}
+
+/// Brings the data dir in `ctx` up to the last of `steps`, calling
+/// `progress` before each one runs.
+fn migrate(
+    ctx: &MigrationContext,
+    steps: &[Migration],
+    mut progress: impl FnMut(&Migration, usize, usize),
+) -> Result<(), ServerError> {
+    let path = marker_path(ctx);
+    let mut marker = read_marker(ctx)?;
+
+    if let Some(version) = marker.in_progress {
+        let step = steps.iter().find(|s| s.version == version).ok_or_else(|| {
+            ServerError::Migration(format!("unknown step {} was cut off", version))
+        })?;
+        warn!(version, name = step.name, "Rolling back interrupted migration");
+        restore(&backup_dir(ctx, version))?;
+        marker.in_progress = None;
+        record(&mut marker, step, MigrationOutcome::Interrupted, None);
+        write_json(&path, &marker)?;
+    }
+    if marker.version > latest(steps) {
+        return Err(ServerError::Migration(format!(
+            "data is from a newer version (schema {}, this build knows {})",
+            marker.version,
+            latest(steps)
+        )));
+    }
+
+    let pending: Vec<&Migration> = steps.iter().filter(|s| s.version > marker.version).collect();
+    for (done, step) in pending.iter().enumerate() {
+        progress(step, done, pending.len());
+        info!(version = step.version, name = step.name, "Running migration");
+        let paths = (step.affects)(ctx);
+        let backup = backup_dir(ctx, step.version);
+        back_up(&backup, &paths)?;
+        marker.in_progress = Some(step.version);
+        write_json(&path, &marker)?;
+
+        let ran = (step.run)(&paths, ctx);
+        marker.in_progress = None;
+        if let Err(e) = ran {
+            error!(version = step.version, name = step.name, error = %e, "Migration failed");
+            let restored = restore(&backup);
+            record(&mut marker, step, MigrationOutcome::RolledBack, Some(e.to_string()));
+            if let Err(restore_error) = restored {
+                // Left marked, so the next launch tries the rollback again.
+                marker.in_progress = Some(step.version);
+                write_json(&path, &marker)?;
+                return Err(restore_error);
+            }
+            write_json(&path, &marker)?;
+            return Err(ServerError::Migration(format!("{}: {}", step.name, e)));
+        }
+        marker.version = step.version;
+        record(&mut marker, step, MigrationOutcome::Applied, None);
+        write_json(&path, &marker)?;
+    }
+    // Stamps data dirs from before migrations existed.
+    if !path.exists() {
+        write_json(&path, &marker)?;
+    }
+    Ok(())
+}
+
+fn context(app: &AppHandle) -> Result<MigrationContext, ServerError> {
+    let path = app.path();
+    Ok(MigrationContext {
+        data_dir: path.app_data_dir().map_err(|e| ServerError::Io(e.to_string()))?,
+        config_dir: path.app_config_dir().map_err(|e| ServerError::Io(e.to_string()))?,
+    })
+}
+
+/// Migrates the app's data dir. Runs during setup, before the sidecar is
+/// spawned; a failure is kept for [`ensure_current`] to report and emitted
+/// as `migration-failed`.
+pub fn run(app: &AppHandle) {
+    let migrated = context(app).and_then(|ctx| {
+        migrate(&ctx, MIGRATIONS, |step, done, total| {
+            let (done_text, total_text) = ((done + 1).to_string(), total.to_string());
+            let args = [("done", done_text.as_str()), ("total", total_text.as_str())];
+            let _ = app.emit(
+                "migration-progress",
+                MigrationProgress {
+                    version: step.version,
+                    name: step.name.to_owned(),
+                    done,
+                    total,
+                    message: i18n::t_with(app, "migration.running", &args),
+                },
+            );
+        })
+    });
+    let state = app.state::<MigrationState>();
+    match migrated {
+        Ok(()) => state.current.store(true, Ordering::SeqCst),
+        Err(e) => {
+            error!(error = %e, "Data migration failed");
+            *state.error.lock().unwrap() = Some(e.to_string());
+            let _ = app.emit("migration-failed", e.to_string());
+        }
+    }
+}
+
+/// Fails unless migrations have run to the end this launch.
+pub fn ensure_current(app: &AppHandle) -> Result<(), ServerError> {
+    let state = app.state::<MigrationState>();
+    if state.current.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let reason = state.error.lock().unwrap().clone();
+    Err(ServerError::Migration(
+        reason.unwrap_or_else(|| "data hasn't been migrated yet".into()),
+    ))
+}
+
+/// Schema version and every migration run against the data dir.
+#[tauri::command]
+pub fn get_migration_history(app: AppHandle) -> Result<MigrationHistory, ServerError> {
+    let marker = read_marker(&context(&app)?)?;
+    Ok(MigrationHistory {
+        version: marker.version,
+        latest: latest(MIGRATIONS),
+        history: marker.history,
+        error: app.state::<MigrationState>().error.lock().unwrap().clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// An old-format data dir: settings with `notify` instead of
+    /// `notifications`, and a project with its scenes inline.
+    fn fixture(name: &str) -> MigrationContext {
+        let dir = format!("aiyou-migrate-{}-{}", name, std::process::id());
+        let root = std::env::temp_dir().join(dir);
+        let _ = std::fs::remove_dir_all(&root);
+        let ctx = MigrationContext {
+            data_dir: root.join("data"),
+            config_dir: root.join("config"),
+        };
+        let project = ctx.data_dir.join("projects/ep1");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(&ctx.config_dir).unwrap();
+        let settings = json!({ "notify": false, "logLevel": "debug" });
+        std::fs::write(ctx.config_dir.join("settings.json"), settings.to_string()).unwrap();
+        let scenes = json!({ "title": "Ep 1", "scenes": [{ "id": "s1" }, { "id": "s2" }] });
+        std::fs::write(project.join("project.json"), scenes.to_string()).unwrap();
+        ctx
+    }
+
+    fn read(path: PathBuf) -> Value {
+        serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+    }
+
+    fn settings_files(ctx: &MigrationContext) -> Vec<PathBuf> {
+        vec![ctx.config_dir.join("settings.json")]
+    }
+
+    fn rename_notify(paths: &[PathBuf], _: &MigrationContext) -> Result<(), ServerError> {
+        let mut settings = read(paths[0].clone());
+        let object = settings.as_object_mut().unwrap();
+        if let Some(value) = object.remove("notify") {
+            object.insert("notifications".into(), value);
+        }
+        write_json(&paths[0], &settings)
+    }
+
+    fn project_files(ctx: &MigrationContext) -> Vec<PathBuf> {
+        let project = ctx.data_dir.join("projects/ep1");
+        vec![project.join("project.json"), project.join("scenes.json")]
+    }
+
+    fn split_scenes(paths: &[PathBuf], _: &MigrationContext) -> Result<(), ServerError> {
+        let mut project = read(paths[0].clone());
+        let scenes = project.as_object_mut().unwrap().remove("scenes").unwrap_or(json!([]));
+        write_json(&paths[1], &scenes)?;
+        project["scenesFile"] = json!("scenes.json");
+        write_json(&paths[0], &project)
+    }
+
+    fn split_scenes_then_fail(
+        paths: &[PathBuf],
+        ctx: &MigrationContext,
+    ) -> Result<(), ServerError> {
+        split_scenes(paths, ctx)?;
+        Err(ServerError::Migration("disk went away".into()))
+    }
+
+    const STEPS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "settings-notifications",
+            affects: settings_files,
+            run: rename_notify,
+        },
+        Migration {
+            version: 2,
+            name: "project-scenes-file",
+            affects: project_files,
+            run: split_scenes,
+        },
+    ];
+
+    #[test]
+    fn chain_upgrades_an_old_data_dir() {
+        let ctx = fixture("chain");
+        let mut seen = Vec::new();
+        migrate(&ctx, STEPS, |step, done, total| seen.push((step.version, done, total))).unwrap();
+        assert_eq!(seen, [(1, 0, 2), (2, 1, 2)]);
+
+        let settings = read(ctx.config_dir.join("settings.json"));
+        assert_eq!(settings, json!({ "notifications": false, "logLevel": "debug" }));
+        let project = read(ctx.data_dir.join("projects/ep1/project.json"));
+        assert_eq!(project, json!({ "title": "Ep 1", "scenesFile": "scenes.json" }));
+        let scenes = read(ctx.data_dir.join("projects/ep1/scenes.json"));
+        assert_eq!(scenes, json!([{ "id": "s1" }, { "id": "s2" }]));
+
+        let marker = read_marker(&ctx).unwrap();
+        assert_eq!((marker.version, marker.in_progress), (2, None));
+        assert_eq!(marker.history.len(), 2);
+        // Nothing left to do the second time.
+        migrate(&ctx, STEPS, |_, _, _| panic!("ran again")).unwrap();
+        let _ = std::fs::remove_dir_all(ctx.data_dir.parent().unwrap());
+    }
+
+    #[test]
+    fn failed_and_interrupted_steps_are_rolled_back() {
+        let ctx = fixture("rollback");
+        let original = read(ctx.data_dir.join("projects/ep1/project.json"));
+        let failing = [
+            Migration { run: rename_notify, ..STEPS[0] },
+            Migration { run: split_scenes_then_fail, ..STEPS[1] },
+        ];
+        assert!(migrate(&ctx, &failing, |_, _, _| {}).is_err());
+        assert_eq!(read(ctx.data_dir.join("projects/ep1/project.json")), original);
+        assert!(!ctx.data_dir.join("projects/ep1/scenes.json").exists());
+        let marker = read_marker(&ctx).unwrap();
+        assert_eq!((marker.version, marker.in_progress), (1, None));
+        assert_eq!(marker.history[1].outcome, MigrationOutcome::RolledBack);
+
+        // Killed after step 2 wrote its files but before it was recorded.
+        split_scenes(&project_files(&ctx), &ctx).unwrap();
+        write_json(&marker_path(&ctx), &SchemaMarker { in_progress: Some(2), ..marker }).unwrap();
+        migrate(&ctx, STEPS, |_, _, _| {}).unwrap();
+        let marker = read_marker(&ctx).unwrap();
+        assert_eq!(marker.version, 2);
+        let outcomes: Vec<_> = marker.history.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            [
+                MigrationOutcome::Applied,
+                MigrationOutcome::RolledBack,
+                MigrationOutcome::Interrupted,
+                MigrationOutcome::Applied
+            ]
+        );
+        let scenes = read(ctx.data_dir.join("projects/ep1/scenes.json"));
+        assert_eq!(scenes, json!([{ "id": "s1" }, { "id": "s2" }]));
+        let _ = std::fs::remove_dir_all(ctx.data_dir.parent().unwrap());
+    }
+}
@@ -0,0 +1,99 @@
+//! Background heartbeat once the server is ready, every
+//! `heartbeat_interval_secs` from the server config. Each beat refreshes the
+//! health cache and is kept in the health history. A server that stops
+//! answering is flagged unreachable and `server-lost` is emitted, as it
+//! also is when a serving process dies (but not when it never started).
+//! One answering with an error status is flagged unhealthy the same way,
+//! while one reporting that it is loading (reloading models, say) is
+//! flagged loading and its progress forwarded. No beats go out while the
+//! server is frozen.
+
+use serde::Serialize;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::crash;
+use crate::server::{self, ServerState, ServerStatus, StatusDetail};
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerLost {
+    /// Unix time in milliseconds of the last successful health check.
+    last_healthy_at: Option<u64>,
+    restart_count: u32,
+}
+
+/// Tells the frontend a server that had been serving is gone.
+pub fn emit_lost(app: &AppHandle) {
+    let state = app.state::<ServerState>();
+    let payload = ServerLost {
+        last_healthy_at: state
+            .last_healthy()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as u64),
+        restart_count: state.restart_count(),
+    };
+    let _ = app.emit("server-lost", payload);
+}
+
+pub fn spawn(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("monitor", async move {
+        let state = handle.state::<ServerState>();
+        let mut rx = state.subscribe();
+        loop {
+            // Loading during startup belongs to the readiness loop.
+            let serving = rx
+                .wait_for(|s| match s {
+                    ServerStatus::Ready
+                    | ServerStatus::Unreachable
+                    | ServerStatus::Unhealthy => true,
+                    ServerStatus::Loading => state.reached_ready(),
+                    _ => false,
+                })
+                .await
+                .is_ok();
+            if !serving {
+                return;
+            }
+            let interval = state.config().heartbeat_interval_secs;
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            // Paused while the sidecar is being replaced.
+            if state.is_frozen() {
+                continue;
+            }
+
+            let beat = state.heartbeat().await;
+            let loading = state.loading();
+            let next = match (&beat, &loading) {
+                (Ok(true), _) => ServerStatus::Ready,
+                (Ok(false), Some(_)) => ServerStatus::Loading,
+                (Ok(false), None) => ServerStatus::Unhealthy,
+                (Err(_), _) => ServerStatus::Unreachable,
+            };
+            if let Some(loading) = &loading {
+                let _ = handle.emit("server-loading-progress", loading);
+            }
+            let current = state.status();
+            let detail = match &beat {
+                Err(e) => StatusDetail::because(e.to_string()),
+                Ok(false) if loading.is_none() => StatusDetail::because("health check failed"),
+                Ok(_) => StatusDetail::default(),
+            };
+            if next == current || !server::transition(&handle, current, next, detail) {
+                continue;
+            }
+            match next {
+                ServerStatus::Ready => info!(was = ?current, "Server is responding again"),
+                ServerStatus::Loading => info!(?loading, "Server is loading"),
+                _ => {
+                    warn!(status = ?next, ?beat, "Server stopped serving");
+                    if matches!(current, ServerStatus::Ready | ServerStatus::Loading) {
+                        emit_lost(&handle);
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,354 @@
+//! Connectivity monitor: periodically checks that the update server and the
+//! AI providers configured in the sidecar can be reached, and emits
+//! `network-status` when that changes.
+//!
+//! There's no portable OS network-change API here; the frontend calls
+//! `refresh_network_status` from the webview's `online`/`offline` events
+//! instead, which come from the OS.
+//!
+//! `check_upstream_connectivity` goes further for the providers: the
+//! sidecar calls each with its key, so a failed generation can be put down
+//! to a rejected key rather than reported as a generic failure.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Url};
+use tracing::{info, warn};
+
+use crate::api::{self, AuthStatus, ProviderCheck};
+use crate::crash;
+use crate::error::ServerError;
+use crate::redact::Redactor;
+use crate::render_queue::{self, QueueState};
+use crate::server::{ServerState, ServerStatus};
+use crate::settings::SettingsState;
+use crate::time::now_ms;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EndpointKind {
+    Update,
+    Provider,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointStatus {
+    pub name: String,
+    pub url: String,
+    pub kind: EndpointKind,
+    pub reachable: bool,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStatus {
+    /// Whether any endpoint was reachable at the last check.
+    pub online: bool,
+    /// Unix time in milliseconds of the last check; `None` before the first.
+    pub checked_at: Option<u64>,
+    pub endpoints: Vec<EndpointStatus>,
+}
+
+impl NetworkStatus {
+    /// Whether every provider is down, which is when renders would fail.
+    /// False if there are no providers to check.
+    fn providers_down(&self) -> bool {
+        let mut providers = self
+            .endpoints
+            .iter()
+            .filter(|e| e.kind == EndpointKind::Provider)
+            .peekable();
+        providers.peek().is_some() && providers.all(|e| !e.reachable)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamProvider {
+    pub name: String,
+    pub reachable: bool,
+    pub auth: AuthStatus,
+    pub latency_ms: Option<u64>,
+    /// Redacted, in case a provider echoed the key back.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpstreamStatus {
+    /// False when the server can't probe its providers and only their
+    /// reachability from here was checked; `auth` is then `unknown`.
+    pub checked_by_server: bool,
+    /// Unix time in milliseconds.
+    pub checked_at: u64,
+    pub providers: Vec<UpstreamProvider>,
+}
+
+#[derive(Default)]
+pub struct NetworkState {
+    status: Mutex<NetworkStatus>,
+    /// Provider URLs from the last time the sidecar could tell us.
+    providers: Mutex<Vec<(String, String)>>,
+    /// Whether the queue is paused because of us rather than the user.
+    auto_paused: AtomicBool,
+}
+
+impl NetworkState {
+    /// Whether anything was reachable at the last check; false before it.
+    pub fn is_online(&self) -> bool {
+        self.status.lock().unwrap().online
+    }
+}
+
+/// Origin of the first updater endpoint in `tauri.conf.json`.
+fn update_endpoint(app: &AppHandle) -> Option<String> {
+    let endpoints = app.config().plugins.0.get("updater")?.get("endpoints")?;
+    let url = Url::parse(endpoints.get(0)?.as_str()?).ok()?;
+    Some(url.origin().ascii_serialization())
+}
+
+async fn endpoints(app: &AppHandle) -> Vec<(String, String, EndpointKind)> {
+    let mut endpoints = Vec::new();
+    if let Some(url) = update_endpoint(app) {
+        endpoints.push(("updates".to_owned(), url, EndpointKind::Update));
+    }
+    let state = app.state::<NetworkState>();
+    let server = app.state::<ServerState>();
+    if server.status() == ServerStatus::Ready {
+        match api::get_providers(&server).await {
+            Ok(providers) => {
+                *state.providers.lock().unwrap() = providers
+                    .into_iter()
+                    .filter_map(|p| Some((p.name, p.base_url?)))
+                    .collect();
+            }
+            Err(e) => warn!(error = %e, "Failed to get providers"),
+        }
+    }
+    let providers = state.providers.lock().unwrap().clone();
+    endpoints.extend(
+        providers
+            .into_iter()
+            .map(|(name, url)| (name, url, EndpointKind::Provider)),
+    );
+    endpoints
+}
+
+/// Any HTTP response counts as reachable; providers answer a bare `HEAD`
+/// with 401 or 404, and that still means the network path works.
+async fn probe(
+    client: &reqwest::Client,
+    name: String,
+    url: String,
+    kind: EndpointKind,
+) -> EndpointStatus {
+    let started = Instant::now();
+    let result = client.head(&url).timeout(CHECK_TIMEOUT).send().await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(_) => EndpointStatus {
+            name,
+            url,
+            kind,
+            reachable: true,
+            latency_ms: Some(latency_ms),
+            error: None,
+        },
+        Err(e) => EndpointStatus {
+            name,
+            url,
+            kind,
+            reachable: false,
+            latency_ms: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Pauses the queue when providers go down and resumes it when they are
+/// back, but only if it was us that paused it.
+fn follow_providers(app: &AppHandle, status: &NetworkStatus) {
+    let state = app.state::<NetworkState>();
+    if status.providers_down() {
+        let enabled = app.state::<SettingsState>().get().pause_queue_when_offline;
+        let Some(queue) = app.try_state::<QueueState>() else {
+            return;
+        };
+        if enabled && !queue.get().paused {
+            info!("AI providers unreachable; pausing the render queue");
+            if render_queue::pause_queue(app.clone()).is_ok() {
+                state.auto_paused.store(true, Ordering::SeqCst);
+            }
+        }
+    } else if state.auto_paused.swap(false, Ordering::SeqCst) {
+        info!("AI providers reachable again; resuming the render queue");
+        if let Err(e) = render_queue::resume_queue(app.clone()) {
+            warn!(error = %e, "Failed to resume the render queue");
+        }
+    }
+}
+
+async fn check(app: &AppHandle) -> NetworkStatus {
+    let client = app.state::<ServerState>().client();
+    let mut probes = tokio::task::JoinSet::new();
+    for (index, (name, url, kind)) in endpoints(app).await.into_iter().enumerate() {
+        let client = client.clone();
+        probes.spawn(async move { (index, probe(&client, name, url, kind).await) });
+    }
+    let mut endpoints = Vec::new();
+    while let Some(joined) = probes.join_next().await {
+        if let Ok(probed) = joined {
+            endpoints.push(probed);
+        }
+    }
+    endpoints.sort_by_key(|(index, _)| *index);
+    let endpoints: Vec<EndpointStatus> = endpoints.into_iter().map(|(_, e)| e).collect();
+    let status = NetworkStatus {
+        online: endpoints.iter().any(|e| e.reachable),
+        checked_at: Some(now_ms()),
+        endpoints,
+    };
+
+    let state = app.state::<NetworkState>();
+    let changed = {
+        let mut current = state.status.lock().unwrap();
+        let reachability = |s: &NetworkStatus| -> Vec<(String, bool)> {
+            s.endpoints
+                .iter()
+                .map(|e| (e.url.clone(), e.reachable))
+                .collect()
+        };
+        let changed = current.checked_at.is_none()
+            || current.online != status.online
+            || reachability(&current) != reachability(&status);
+        *current = status.clone();
+        changed
+    };
+    if changed {
+        info!(online = status.online, "Network status changed");
+        let _ = app.emit("network-status", &status);
+    }
+    follow_providers(app, &status);
+    status
+}
+
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("network", async move {
+        loop {
+            check(&handle).await;
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+fn upstream_from_server(checks: Vec<ProviderCheck>, redactor: &Redactor) -> Vec<UpstreamProvider> {
+    checks
+        .into_iter()
+        .map(|check| UpstreamProvider {
+            // A key can't be judged without an answer from the provider.
+            auth: if check.reachable || check.auth == AuthStatus::Missing {
+                check.auth
+            } else {
+                AuthStatus::Unknown
+            },
+            name: check.name,
+            reachable: check.reachable,
+            latency_ms: check.latency_ms,
+            error: check.error.map(|e| redactor.redact(&e).into_owned()),
+        })
+        .collect()
+}
+
+fn upstream_from_network(status: &NetworkStatus) -> Vec<UpstreamProvider> {
+    status
+        .endpoints
+        .iter()
+        .filter(|e| e.kind == EndpointKind::Provider)
+        .map(|e| UpstreamProvider {
+            name: e.name.clone(),
+            reachable: e.reachable,
+            auth: AuthStatus::Unknown,
+            latency_ms: e.latency_ms,
+            error: e.error.clone(),
+        })
+        .collect()
+}
+
+/// Asks the sidecar to reach each of its AI providers and check its key
+/// with them. Servers without that check get the reachability probe from
+/// here instead, with the keys unchecked.
+#[tauri::command]
+pub async fn check_upstream_connectivity(app: AppHandle) -> Result<UpstreamStatus, ServerError> {
+    let server = app.state::<ServerState>();
+    let (checked_by_server, providers) = match api::check_providers(&server).await {
+        Ok(checks) => (true, upstream_from_server(checks, &Redactor::default())),
+        Err(ServerError::BadStatus(404 | 405 | 501)) => {
+            info!("Server can't check its providers; probing them from here");
+            (false, upstream_from_network(&check(&app).await))
+        }
+        Err(e) => return Err(e),
+    };
+    Ok(UpstreamStatus {
+        checked_by_server,
+        checked_at: now_ms(),
+        providers,
+    })
+}
+
+#[tauri::command]
+pub fn get_network_status(state: tauri::State<'_, NetworkState>) -> NetworkStatus {
+    state.status.lock().unwrap().clone()
+}
+
+/// Checks again right away, e.g. when the webview reports going online or
+/// offline.
+#[tauri::command]
+pub async fn refresh_network_status(app: AppHandle) -> NetworkStatus {
+    check(&app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_checks_hide_keys_and_unjudged_auth() {
+        let checks = vec![
+            ProviderCheck {
+                name: "openai".into(),
+                reachable: true,
+                auth: AuthStatus::Invalid,
+                latency_ms: Some(120),
+                error: Some("401: Incorrect API key provided: sk-abcdefghijklmnopqrstu".into()),
+            },
+            ProviderCheck {
+                name: "kie".into(),
+                reachable: false,
+                auth: AuthStatus::Invalid,
+                latency_ms: None,
+                error: Some("connect timed out".into()),
+            },
+            ProviderCheck {
+                name: "sutu".into(),
+                reachable: false,
+                auth: AuthStatus::Missing,
+                latency_ms: None,
+                error: None,
+            },
+        ];
+        let providers = upstream_from_server(checks, &Redactor::default());
+        let auth: Vec<AuthStatus> = providers.iter().map(|p| p.auth).collect();
+        assert_eq!(auth, [AuthStatus::Invalid, AuthStatus::Unknown, AuthStatus::Missing]);
+        let error = providers[0].error.as_deref().unwrap();
+        assert!(!error.contains("sk-abcdefghijklmnopqrstu") && error.starts_with("401"));
+    }
+}
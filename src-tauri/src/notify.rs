@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tracing::warn;
+
+use crate::error::ServerError;
+use crate::focus_main_window;
+use crate::i18n;
+use crate::settings::SettingsState;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NotificationKind {
+    Info,
+    Success,
+    Error,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NotificationClicked {
+    job_id: Option<String>,
+    kind: NotificationKind,
+}
+
+/// How many shown notifications are remembered for click routing. Older
+/// ones have long left the notification center.
+const SHOWN_LIMIT: usize = 50;
+
+type Shown = (i32, Option<String>, NotificationKind);
+
+/// Remembers which job each recently shown notification belongs to, so a
+/// click can be routed back to the frontend.
+#[derive(Default)]
+pub struct NotificationState {
+    next_id: AtomicI32,
+    /// Oldest first, at most `SHOWN_LIMIT`.
+    shown: Mutex<VecDeque<Shown>>,
+}
+
+impl NotificationState {
+    fn remember(&self, id: i32, job_id: Option<String>, kind: NotificationKind) {
+        let mut shown = self.shown.lock().unwrap();
+        if shown.len() == SHOWN_LIMIT {
+            shown.pop_front();
+        }
+        shown.push_back((id, job_id, kind));
+    }
+
+    fn take(&self, id: i32) -> Option<(Option<String>, NotificationKind)> {
+        let mut shown = self.shown.lock().unwrap();
+        let pos = shown.iter().position(|(shown_id, ..)| *shown_id == id)?;
+        shown.remove(pos).map(|(_, job_id, kind)| (job_id, kind))
+    }
+}
+
+/// Registers the click handler. Must run after the notification plugin is
+/// initialized.
+pub fn init(app: &AppHandle) -> Result<(), ServerError> {
+    let handle = app.clone();
+    app.notification().on_action(move |action| {
+        if action.action_id() != "tap" {
+            return;
+        }
+        let Some(id) = action.notification().map(|n| n.id()) else {
+            return;
+        };
+        let state = handle.state::<NotificationState>();
+        let Some((job_id, kind)) = state.take(id) else {
+            return;
+        };
+
+        focus_main_window(&handle);
+        let _ = handle.emit("notification-clicked", NotificationClicked { job_id, kind });
+    })?;
+    Ok(())
+}
+
+fn main_window_focused(app: &AppHandle) -> bool {
+    app.get_webview_window("main")
+        .and_then(|w| w.is_focused().ok())
+        .unwrap_or(false)
+}
+
+/// Shows a notification unless notifications are disabled in settings or the
+/// main window already has focus.
+pub fn send(
+    app: &AppHandle,
+    title: &str,
+    body: &str,
+    kind: NotificationKind,
+    job_id: Option<String>,
+) -> Result<(), ServerError> {
+    if !app.state::<SettingsState>().get().notifications || main_window_focused(app) {
+        return Ok(());
+    }
+
+    let state = app.state::<NotificationState>();
+    let id = state.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    state.remember(id, job_id, kind);
+
+    app.notification()
+        .builder()
+        .id(id)
+        .title(title)
+        .body(body)
+        .show()?;
+    Ok(())
+}
+
+/// Called by the event bridge for every relayed server event; notifies for
+/// the ones that mark the end of a long-running job.
+pub fn on_server_event(app: &AppHandle, event: &str, payload: &serde_json::Value) {
+    let field = |key: &str| payload.get(key).and_then(|v| v.as_str()).map(str::to_owned);
+    let job_id = field("jobId").or_else(|| field("id"));
+
+    let output_name = || {
+        field("output").or_else(|| field("outputPath")).map(|p| {
+            std::path::Path::new(&p)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or(p)
+        })
+    };
+
+    let t = |key: &str| i18n::t(app, key);
+    let (title, body, kind) = match event {
+        "generation-complete" | "job-complete" => (
+            t("notify.dramaReady"),
+            output_name()
+                .or_else(|| field("title"))
+                .unwrap_or_else(|| t("notify.generationFinished")),
+            NotificationKind::Success,
+        ),
+        "generation-error" => (
+            t("notify.generationFailed"),
+            field("error")
+                .or_else(|| field("message"))
+                .unwrap_or_else(|| t("notify.generationStopped")),
+            NotificationKind::Error,
+        ),
+        "export-finished" => (
+            t("notify.exportFinished"),
+            output_name().unwrap_or_else(|| t("notify.exportReady")),
+            NotificationKind::Success,
+        ),
+        _ => return,
+    };
+
+    if let Err(e) = send(app, &title, &body, kind, job_id) {
+        warn!(error = %e, "Failed to show notification");
+    }
+}
+
+#[tauri::command]
+pub fn notify(
+    app: AppHandle,
+    title: String,
+    body: String,
+    kind: NotificationKind,
+) -> Result<(), ServerError> {
+    send(&app, &title, &body, kind, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_recent_notifications_are_remembered() {
+        let state = NotificationState::default();
+        for id in 1..=SHOWN_LIMIT as i32 + 10 {
+            state.remember(id, Some(format!("job-{id}")), NotificationKind::Success);
+        }
+        assert_eq!(state.shown.lock().unwrap().len(), SHOWN_LIMIT);
+        assert!(state.take(10).is_none());
+
+        let (job_id, _) = state.take(SHOWN_LIMIT as i32).unwrap();
+        assert_eq!(job_id.as_deref(), Some("job-50"));
+        // A click is routed once.
+        assert!(state.take(SHOWN_LIMIT as i32).is_none());
+    }
+}
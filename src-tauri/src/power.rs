@@ -0,0 +1,189 @@
+//! Keeps the system awake (display may still sleep) while renders run.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::crash;
+use crate::error::ServerError;
+use crate::server::{ServerState, ServerStatus};
+use crate::time::now_ms;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeldJob {
+    pub job_id: String,
+    /// Outstanding `acquire_render_lock` calls for this job.
+    pub count: u32,
+    /// Held on behalf of the job's progress events.
+    pub automatic: bool,
+    /// Unix time in milliseconds when the job first took the lock.
+    pub since: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerAssertions {
+    pub active: bool,
+    pub jobs: Vec<HeldJob>,
+}
+
+#[derive(Default)]
+struct Inner {
+    jobs: BTreeMap<String, HeldJob>,
+    /// Dropping this releases the OS assertion; see [`hold_awake`].
+    assertion: Option<mpsc::Sender<()>>,
+}
+
+#[derive(Default)]
+pub struct PowerState {
+    inner: Mutex<Inner>,
+}
+
+/// Takes the OS assertion on a dedicated thread, since on Windows the
+/// execution state belongs to the thread that set it. The assertion is
+/// released when the returned sender is dropped.
+fn hold_awake() -> Result<mpsc::Sender<()>, ServerError> {
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let awake = keepawake::Builder::default()
+            .idle(true)
+            .sleep(true)
+            .reason("Rendering video")
+            .app_name("AIYOU")
+            .app_reverse_domain("com.aiyou.app")
+            .create();
+        match awake {
+            Ok(_awake) => {
+                let _ = ready_tx.send(Ok(()));
+                // Blocks until the sender is dropped.
+                let _ = release_rx.recv();
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e.to_string()));
+            }
+        }
+    });
+    match ready_rx.recv() {
+        Ok(Ok(())) => Ok(release_tx),
+        Ok(Err(e)) => Err(ServerError::Power(e)),
+        Err(_) => Err(ServerError::Power("power assertion thread exited".into())),
+    }
+}
+
+impl PowerState {
+    fn hold(&self, job_id: &str, automatic: bool) -> Result<(), ServerError> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.assertion.is_none() {
+            inner.assertion = Some(hold_awake()?);
+        }
+        let job = inner
+            .jobs
+            .entry(job_id.to_owned())
+            .or_insert_with(|| HeldJob {
+                job_id: job_id.to_owned(),
+                count: 0,
+                automatic: false,
+                since: now_ms(),
+            });
+        if automatic {
+            job.automatic = true;
+        } else {
+            job.count += 1;
+        }
+        Ok(())
+    }
+
+    fn release(&self, job_id: &str, automatic: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(job) = inner.jobs.get_mut(job_id) {
+            if automatic {
+                job.automatic = false;
+            } else {
+                job.count = job.count.saturating_sub(1);
+            }
+            if job.count == 0 && !job.automatic {
+                inner.jobs.remove(job_id);
+            }
+        }
+        if inner.jobs.is_empty() {
+            inner.assertion = None;
+        }
+    }
+
+    /// Drops every hold, e.g. when the sidecar dies or the app exits.
+    pub fn release_all(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.jobs.clear();
+        inner.assertion = None;
+    }
+
+    fn snapshot(&self) -> PowerAssertions {
+        let inner = self.inner.lock().unwrap();
+        PowerAssertions {
+            active: inner.assertion.is_some(),
+            jobs: inner.jobs.values().cloned().collect(),
+        }
+    }
+}
+
+/// Releases all holds whenever the sidecar stops or crashes, since its jobs
+/// die with it.
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("power", async move {
+        let mut rx = handle.state::<ServerState>().subscribe();
+        while rx.changed().await.is_ok() {
+            let status = *rx.borrow_and_update();
+            if matches!(status, ServerStatus::Crashed | ServerStatus::Stopped) {
+                handle.state::<PowerState>().release_all();
+            }
+        }
+    });
+}
+
+/// Called by the event bridge: progress holds the lock for that job,
+/// completion or failure releases it.
+pub fn on_server_event(app: &AppHandle, event: &str, payload: &serde_json::Value) {
+    let Some(job_id) = payload
+        .get("jobId")
+        .or_else(|| payload.get("id"))
+        .and_then(|v| v.as_str())
+    else {
+        return;
+    };
+    let state = app.state::<PowerState>();
+    match event {
+        "generation-progress" | "render-progress" => {
+            if let Err(e) = state.hold(job_id, true) {
+                warn!(error = %e, "Failed to prevent sleep");
+            }
+        }
+        "generation-complete" | "generation-error" | "job-complete" | "export-finished" => {
+            state.release(job_id, true);
+        }
+        _ => {}
+    }
+}
+
+#[tauri::command]
+pub fn acquire_render_lock(
+    state: tauri::State<'_, PowerState>,
+    job_id: String,
+) -> Result<(), ServerError> {
+    state.hold(&job_id, false)
+}
+
+#[tauri::command]
+pub fn release_render_lock(state: tauri::State<'_, PowerState>, job_id: String) {
+    state.release(&job_id, false);
+}
+
+#[tauri::command]
+pub fn get_power_assertions(state: tauri::State<'_, PowerState>) -> PowerAssertions {
+    state.snapshot()
+}
@@ -0,0 +1,110 @@
+//! Detached per-scene video preview windows.
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Url, WebviewUrl, WebviewWindowBuilder, Window, WindowEvent};
+
+use crate::error::ServerError;
+use crate::system_prefs;
+use crate::window_state;
+
+const LABEL_PREFIX: &str = "preview-";
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PreviewClosed<'a> {
+    scene_id: &'a str,
+}
+
+pub fn is_preview(label: &str) -> bool {
+    label.starts_with(LABEL_PREFIX)
+}
+
+/// Scene ids end up in the window label, which only allows a small set of
+/// characters.
+fn label(scene_id: &str) -> Result<String, ServerError> {
+    let valid = !scene_id.is_empty()
+        && scene_id.len() <= 64
+        && scene_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(ServerError::InvalidArgument(format!("invalid scene id: {}", scene_id)));
+    }
+    Ok(format!("{}{}", LABEL_PREFIX, scene_id))
+}
+
+/// Closes every open preview window.
+pub fn close_all(app: &AppHandle) {
+    for (label, window) in app.webview_windows() {
+        if is_preview(&label) {
+            let _ = window.close();
+        }
+    }
+}
+
+/// Previews close along with the main window, and report their own closing
+/// so the main UI can reset its "pop out" button.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if !matches!(event, WindowEvent::Destroyed) {
+        return;
+    }
+    let label = window.label();
+    if label == "main" {
+        close_all(window.app_handle());
+    } else if let Some(scene_id) = label.strip_prefix(LABEL_PREFIX) {
+        let _ = window.emit("preview-window-closed", PreviewClosed { scene_id });
+    }
+}
+
+/// Opens `media_url` in a preview window for the scene, or focuses the one
+/// already open.
+#[tauri::command]
+pub fn open_preview_window(
+    app: AppHandle,
+    scene_id: String,
+    media_url: String,
+    always_on_top: Option<bool>,
+) -> Result<(), ServerError> {
+    let label = label(&scene_id)?;
+    let url: Url = media_url
+        .parse()
+        .map_err(|_| ServerError::Window(format!("invalid media url: {}", media_url)))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ServerError::Window(format!("unsupported media url: {}", media_url)));
+    }
+
+    if let Some(window) = app.get_webview_window(&label) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+        if let Some(on_top) = always_on_top {
+            let _ = window.set_always_on_top(on_top);
+        }
+        return Ok(());
+    }
+
+    let config = serde_json::json!({ "sceneId": scene_id, "mediaUrl": url.as_str() });
+    let window = WebviewWindowBuilder::new(&app, &label, WebviewUrl::App("preview.html".into()))
+        .title(format!("AIYOU — {}", scene_id))
+        .inner_size(640.0, 360.0)
+        .min_inner_size(320.0, 180.0)
+        .always_on_top(always_on_top.unwrap_or(false))
+        .visible(false)
+        .background_color(system_prefs::background(&app))
+        .initialization_script(format!("window.__AIYOU_PREVIEW__ = {};", config))
+        .build()?;
+    if let Some(geom) = window_state::saved(&app, &label) {
+        window_state::restore(&window, geom);
+    }
+    window.show()?;
+    window.set_focus()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn close_preview_window(app: AppHandle, scene_id: String) -> Result<(), ServerError> {
+    if let Some(window) = app.get_webview_window(&label(&scene_id)?) {
+        window.close()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+//! Lowers the sidecar's OS scheduling priority while its queue is idle, so
+//! it doesn't compete with the UI on slow machines.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::api;
+use crate::crash;
+use crate::error::ServerError;
+use crate::server::{ServerState, ServerStatus};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Priority {
+    Normal,
+    BelowNormal,
+}
+
+#[derive(Default)]
+pub struct PriorityState {
+    /// Set by `set_server_priority`; `None` follows the queue.
+    manual: Mutex<Option<Priority>>,
+    /// Last priority applied, and to which process.
+    applied: Mutex<Option<(u32, Priority)>>,
+}
+
+#[cfg(unix)]
+fn set_os_priority(pid: u32, priority: Priority) -> std::io::Result<()> {
+    let nice = match priority {
+        Priority::Normal => 0,
+        Priority::BelowNormal => 10,
+    };
+    // On Linux the nice value is per thread, so set it on every thread of
+    // the process rather than only the main one.
+    #[cfg(target_os = "linux")]
+    let ids: Vec<u32> = std::fs::read_dir(format!("/proc/{}/task", pid))
+        .map(|tasks| {
+            tasks
+                .flatten()
+                .filter_map(|t| t.file_name().to_str()?.parse().ok())
+                .collect()
+        })
+        .unwrap_or_else(|_| vec![pid]);
+    #[cfg(not(target_os = "linux"))]
+    let ids = [pid];
+
+    for id in ids {
+        // SAFETY: plain syscall with integer arguments.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, id as libc::id_t, nice) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_os_priority(pid: u32, priority: Priority) -> std::io::Result<()> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{
+        OpenProcess, SetPriorityClass, BELOW_NORMAL_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+        PROCESS_SET_INFORMATION,
+    };
+
+    let class = match priority {
+        Priority::Normal => NORMAL_PRIORITY_CLASS,
+        Priority::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+    };
+    // SAFETY: the handle is only used for this call and closed after it.
+    unsafe {
+        let process = OpenProcess(PROCESS_SET_INFORMATION, false, pid)?;
+        let result = SetPriorityClass(process, class);
+        let _ = CloseHandle(process);
+        result?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_os_priority(_pid: u32, _priority: Priority) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Whether a lowered priority can be put back. Unprivileged Unix processes
+/// can only raise a nice value, never lower it, unless `RLIMIT_NICE` allows.
+#[cfg(unix)]
+fn can_restore() -> bool {
+    // SAFETY: plain syscall with no arguments.
+    if unsafe { libc::geteuid() } == 0 {
+        return true;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: `limit` is a valid out-pointer for the call.
+        // A limit of 20 allows nice values down to 0.
+        if unsafe { libc::getrlimit(libc::RLIMIT_NICE, &mut limit) } == 0 && limit.rlim_cur >= 20 {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(not(unix))]
+fn can_restore() -> bool {
+    true
+}
+
+/// What the sidecar should run at right now.
+async fn wanted(app: &AppHandle) -> Result<Priority, ServerError> {
+    if let Some(priority) = *app.state::<PriorityState>().manual.lock().unwrap() {
+        return Ok(priority);
+    }
+    // Lowering a priority we couldn't raise again would slow down the next
+    // generation, so leave it alone.
+    if !can_restore() {
+        return Ok(Priority::Normal);
+    }
+    let queue = api::queue_status(&app.state::<ServerState>()).await?;
+    Ok(if queue.active > 0 || queue.queued > 0 {
+        Priority::Normal
+    } else {
+        Priority::BelowNormal
+    })
+}
+
+/// Brings the running sidecar to the wanted priority, if it isn't already.
+async fn update(app: &AppHandle) -> Result<(), ServerError> {
+    let Some(pid) = app.state::<ServerState>().pid() else {
+        return Ok(());
+    };
+    let priority = wanted(app).await?;
+    let state = app.state::<PriorityState>();
+    if *state.applied.lock().unwrap() == Some((pid, priority)) {
+        return Ok(());
+    }
+    set_os_priority(pid, priority).map_err(|e| ServerError::Priority(e.to_string()))?;
+    *state.applied.lock().unwrap() = Some((pid, priority));
+    info!(?priority, "Server priority set");
+    Ok(())
+}
+
+/// Follows the queue while the server is ready.
+pub fn init(app: &AppHandle) {
+    if !can_restore() {
+        info!(
+            "Can't restore a lowered server priority here; leaving it at normal when idle"
+        );
+    }
+    let handle = app.clone();
+    crash::spawn("priority", async move {
+        // Only log an error once until it changes, not on every poll.
+        let mut last_error = None;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if handle.state::<ServerState>().status() != ServerStatus::Ready {
+                continue;
+            }
+            let error = update(&handle).await.err().map(|e| e.to_string());
+            if let Some(e) = error.as_ref().filter(|&e| Some(e) != last_error.as_ref()) {
+                warn!(error = %e, "Failed to update server priority");
+            }
+            last_error = error;
+        }
+    });
+}
+
+/// Pins the sidecar's priority, or with `None` goes back to following the
+/// queue. Applied right away if the server is ready.
+#[tauri::command]
+pub async fn set_server_priority(
+    app: AppHandle,
+    priority: Option<Priority>,
+) -> Result<(), ServerError> {
+    *app.state::<PriorityState>().manual.lock().unwrap() = priority;
+    if app.state::<ServerState>().status() != ServerStatus::Ready {
+        return Ok(());
+    }
+    update(&app).await
+}
@@ -0,0 +1,285 @@
+//! Advisory locks on open projects, so two machines sharing the data dir
+//! through a synced folder don't both write one project.
+//!
+//! Opening a project writes `projects/<id>/project.lock` naming this
+//! machine and process, and a background task refreshes its heartbeat
+//! while the project stays open. A lock whose heartbeat is older than
+//! `STALE_AFTER` was left by a machine that went away and is ignored. The
+//! sidecar gets our `owner` string as `AIYOU_LOCK_OWNER` and only writes
+//! projects whose lock names it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::backups::valid_project_id;
+use crate::crash;
+use crate::error::ServerError;
+use crate::media_watch;
+use crate::time::now_ms;
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+/// A lock not refreshed for this long is treated as abandoned.
+const STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectLock {
+    /// `<machine>:<pid>`, unique to one running app.
+    pub owner: String,
+    pub machine: String,
+    pub pid: u32,
+    /// Unix time in milliseconds.
+    pub acquired_at: u64,
+    /// Unix time in milliseconds, refreshed every `HEARTBEAT_INTERVAL`.
+    pub heartbeat: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LockLost {
+    project_id: String,
+    /// Who holds it now, if anyone.
+    holder: Option<ProjectLock>,
+}
+
+/// Locks this app holds, by project id.
+#[derive(Default)]
+pub struct ProjectLockState {
+    held: Mutex<HashMap<String, PathBuf>>,
+}
+
+#[cfg(unix)]
+fn host_name() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer outlives the call and its length is passed along.
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+#[cfg(not(unix))]
+fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+fn machine() -> &'static str {
+    static MACHINE: OnceLock<String> = OnceLock::new();
+    MACHINE.get_or_init(|| {
+        host_name()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "unknown".to_owned())
+    })
+}
+
+/// Identifies this app in lock files.
+pub fn owner() -> String {
+    format!("{}:{}", machine(), std::process::id())
+}
+
+fn lock_path(app: &AppHandle, project_id: &str) -> Result<PathBuf, ServerError> {
+    if !valid_project_id(project_id) {
+        return Err(ServerError::ProjectLocked(format!(
+            "invalid project id: {}",
+            project_id
+        )));
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("projects").join(project_id).join("project.lock"))
+}
+
+fn read(path: &Path) -> Option<ProjectLock> {
+    let data = std::fs::read(path).ok()?;
+    match serde_json::from_slice(&data) {
+        Ok(lock) => Some(lock),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Ignoring unreadable project lock");
+            None
+        }
+    }
+}
+
+/// Written aside and renamed, so a sync client never uploads half a lock.
+fn write(path: &Path, lock: &ProjectLock) -> Result<(), ServerError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_vec_pretty(lock).map_err(|e| ServerError::Io(e.to_string()))?;
+    let tmp = path.with_extension("lock.tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn age(lock: &ProjectLock, now: u64) -> Duration {
+    Duration::from_millis(now.saturating_sub(lock.heartbeat))
+}
+
+/// Someone else's lock that is still being refreshed.
+fn held_elsewhere(existing: Option<ProjectLock>, owner: &str, now: u64) -> Option<ProjectLock> {
+    existing.filter(|lock| lock.owner != owner && age(lock, now) < STALE_AFTER)
+}
+
+fn describe(lock: &ProjectLock, now: u64) -> String {
+    format!(
+        "open on {} (pid {}), last seen {}s ago",
+        lock.machine,
+        lock.pid,
+        age(lock, now).as_secs()
+    )
+}
+
+/// Refreshes every held lock. One that now names another owner was taken
+/// over with `force` elsewhere; it is dropped and `project-lock-lost`
+/// emitted so the frontend can stop editing.
+fn refresh(app: &AppHandle) {
+    let owner = owner();
+    let held: Vec<(String, PathBuf)> = app
+        .state::<ProjectLockState>()
+        .held
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, path)| (id.clone(), path.clone()))
+        .collect();
+    for (project_id, path) in held {
+        let current = read(&path);
+        match current {
+            Some(mut lock) if lock.owner == owner => {
+                lock.heartbeat = now_ms();
+                if let Err(e) = write(&path, &lock) {
+                    warn!(project_id, error = %e, "Failed to refresh project lock");
+                }
+            }
+            holder => {
+                let taken_by = holder.as_ref().map(|l| l.owner.as_str());
+                warn!(project_id, ?taken_by, "Project lock lost");
+                app.state::<ProjectLockState>().held.lock().unwrap().remove(&project_id);
+                let _ = app.emit("project-lock-lost", LockLost { project_id, holder });
+            }
+        }
+    }
+}
+
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("project-lock", async move {
+        loop {
+            tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+            refresh(&handle);
+        }
+    });
+}
+
+/// Removes the lock files this app holds.
+pub fn on_exit(app: &AppHandle) {
+    let owner = owner();
+    let held = std::mem::take(&mut *app.state::<ProjectLockState>().held.lock().unwrap());
+    for (_, path) in held {
+        if read(&path).is_some_and(|lock| lock.owner == owner) {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Locks `project_id` for this app. Fails with `projectLocked` saying who
+/// holds it if another machine or instance has it open, unless `force`
+/// is set. Opening a project this app already holds just refreshes it.
+#[tauri::command]
+pub fn open_project_guard(
+    app: AppHandle,
+    project_id: String,
+    force: Option<bool>,
+) -> Result<ProjectLock, ServerError> {
+    let path = lock_path(&app, &project_id)?;
+    let owner = owner();
+    let now = now_ms();
+    let existing = read(&path);
+    let acquired_at = existing
+        .as_ref()
+        .filter(|lock| lock.owner == owner)
+        .map_or(now, |lock| lock.acquired_at);
+    if let Some(holder) = held_elsewhere(existing, &owner, now) {
+        if !force.unwrap_or(false) {
+            return Err(ServerError::ProjectLocked(describe(&holder, now)));
+        }
+        warn!(project_id, holder = %holder.owner, "Taking over project lock");
+    }
+    let lock = ProjectLock {
+        owner,
+        machine: machine().to_owned(),
+        pid: std::process::id(),
+        acquired_at,
+        heartbeat: now,
+    };
+    write(&path, &lock)?;
+    app.state::<ProjectLockState>()
+        .held
+        .lock()
+        .unwrap()
+        .insert(project_id.clone(), path);
+    info!(project_id, "Project locked");
+    // The project opens either way; it just won't pick up outside edits.
+    if let Err(e) = media_watch::watch(&app, &project_id) {
+        warn!(project_id, error = %e, "Not watching project media");
+    }
+    Ok(lock)
+}
+
+/// Releases the lock taken by `open_project_guard`, if this app still
+/// holds it.
+#[tauri::command]
+pub fn close_project_guard(app: AppHandle, project_id: String) -> Result<(), ServerError> {
+    let held = app
+        .state::<ProjectLockState>()
+        .held
+        .lock()
+        .unwrap()
+        .remove(&project_id);
+    media_watch::unwatch(&app, &project_id);
+    let Some(path) = held else {
+        return Ok(());
+    };
+    if read(&path).is_some_and(|lock| lock.owner == owner()) {
+        std::fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(owner: &str, heartbeat: u64) -> ProjectLock {
+        ProjectLock {
+            owner: owner.into(),
+            machine: "studio-pc".into(),
+            pid: 42,
+            acquired_at: 0,
+            heartbeat,
+        }
+    }
+
+    #[test]
+    fn only_fresh_foreign_locks_block() {
+        let now = 10 * 60 * 1000;
+        let fresh = now - 40_000;
+        let stale = now - STALE_AFTER.as_millis() as u64 - 1;
+
+        let holder = held_elsewhere(Some(lock("studio-pc:42", fresh)), "laptop:7", now).unwrap();
+        assert_eq!(describe(&holder, now), "open on studio-pc (pid 42), last seen 40s ago");
+        assert!(held_elsewhere(Some(lock("studio-pc:42", stale)), "laptop:7", now).is_none());
+        // Our own lock, e.g. from reopening the project.
+        assert!(held_elsewhere(Some(lock("laptop:7", fresh)), "laptop:7", now).is_none());
+        assert!(held_elsewhere(None, "laptop:7", now).is_none());
+    }
+}
@@ -0,0 +1,530 @@
+//! `api_request`, the frontend's generic path to sidecar endpoints, with a
+//! small scheduler in front so a burst of requests can't swamp the
+//! single-threaded sidecar and starve a render.
+//!
+//! Requests are limited per category (`Settings::proxy_limits`); those
+//! over the limit wait in a lane where user-initiated ones go ahead of
+//! background prefetches. Identical GETs already in flight share one
+//! upstream response. Health checks are never held back.
+//!
+//! While the server is restarting, GETs that fail to reach it are retried
+//! with backoff (`Settings::proxy_retry`); other requests fail right away
+//! with `ServerRestarting`, for the frontend to send again after
+//! `server-ready`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{oneshot, watch};
+use tracing::debug;
+
+use crate::api;
+use crate::error::ServerError;
+use crate::server::ServerState;
+use crate::settings::SettingsState;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Category {
+    #[default]
+    Metadata,
+    Generation,
+    /// Never limited.
+    Health,
+}
+
+/// Later variants are served first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RequestPriority {
+    /// Prefetches the user isn't waiting on.
+    Background,
+    #[default]
+    User,
+}
+
+/// Requests sent to the sidecar at once per category; 0 is unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyLimits {
+    pub metadata: usize,
+    pub generation: usize,
+}
+
+impl Default for ProxyLimits {
+    fn default() -> Self {
+        Self {
+            metadata: 4,
+            generation: 1,
+        }
+    }
+}
+
+impl ProxyLimits {
+    fn get(&self, category: Category) -> usize {
+        match category {
+            Category::Metadata => self.metadata,
+            Category::Generation => self.generation,
+            Category::Health => 0,
+        }
+    }
+}
+
+/// Upper bound on `ProxyRetry::attempts`.
+const MAX_RETRIES: u32 = 10;
+/// Wait before the first retry, doubled for each one after.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+/// Longest wait between retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(4);
+
+/// Which failures `api_request` retries while the server restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyRetry {
+    /// Retries after the first attempt. Capped at 10.
+    pub attempts: u32,
+    pub connection_refused: bool,
+    /// Response statuses that mean the server isn't up yet.
+    pub statuses: Vec<u16>,
+}
+
+impl Default for ProxyRetry {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            connection_refused: true,
+            statuses: vec![502, 503],
+        }
+    }
+}
+
+impl ProxyRetry {
+    fn retryable(&self, error: &ServerError) -> bool {
+        match error {
+            ServerError::Unreachable(_) => self.connection_refused,
+            ServerError::BadStatus(code) => self.statuses.contains(code),
+            _ => false,
+        }
+    }
+}
+
+/// `delay` give or take a quarter, so requests that failed together don't
+/// all come back at once.
+fn jittered(delay: Duration) -> Duration {
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    let spread = delay / 2;
+    let offset = Duration::from_nanos(random % (spread.as_nanos() as u64).max(1));
+    delay - delay / 4 + offset
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaneStats {
+    /// Requests sent to the sidecar and not yet answered.
+    pub in_flight: usize,
+    /// Requests waiting for a slot.
+    pub queued: usize,
+    pub completed: u64,
+    /// Requests answered with another one's response.
+    pub coalesced: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyStats {
+    pub total: LaneStats,
+    pub by_category: BTreeMap<Category, LaneStats>,
+}
+
+struct Waiter {
+    priority: RequestPriority,
+    /// Arrival order; earlier goes first within a priority.
+    seq: u64,
+    grant: oneshot::Sender<Permit>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.seq == other.seq
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Default)]
+struct Slots {
+    running: usize,
+    waiting: BinaryHeap<Waiter>,
+    next_seq: u64,
+}
+
+/// One category's slots. A counter rather than a fixed-size semaphore, so
+/// a changed limit applies without a restart.
+#[derive(Default)]
+struct Lane {
+    slots: Mutex<Slots>,
+    /// The limit the last request saw; 0 is unlimited.
+    limit: AtomicUsize,
+    completed: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// A slot in a lane. Handed to the next waiter when dropped.
+struct Permit {
+    lane: Arc<Lane>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let limit = self.lane.limit.load(Ordering::Relaxed);
+        let next = {
+            let mut slots = self.lane.slots.lock().unwrap();
+            let over = limit != 0 && slots.running > limit;
+            match slots.waiting.pop() {
+                Some(waiter) if !over => Some(waiter),
+                popped => {
+                    slots.waiting.extend(popped);
+                    slots.running -= 1;
+                    None
+                }
+            }
+        };
+        if let Some(waiter) = next {
+            // A waiter that gave up hands the slot back, which moves it on
+            // to the next one.
+            let _ = waiter.grant.send(Permit {
+                lane: self.lane.clone(),
+            });
+        }
+    }
+}
+
+impl Lane {
+    async fn acquire(self: &Arc<Self>, limit: usize, priority: RequestPriority) -> Permit {
+        self.limit.store(limit, Ordering::Relaxed);
+        let granted = {
+            let mut slots = self.slots.lock().unwrap();
+            if limit == 0 || slots.running < limit {
+                slots.running += 1;
+                return Permit { lane: self.clone() };
+            }
+            let (grant, granted) = oneshot::channel();
+            let seq = slots.next_seq;
+            slots.next_seq += 1;
+            slots.waiting.push(Waiter {
+                priority,
+                seq,
+                grant,
+            });
+            granted
+        };
+        granted
+            .await
+            .expect("waiters are only dropped after being granted a slot")
+    }
+
+    fn stats(&self) -> LaneStats {
+        let slots = self.slots.lock().unwrap();
+        LaneStats {
+            in_flight: slots.running,
+            queued: slots
+                .waiting
+                .iter()
+                .filter(|w| !w.grant.is_closed())
+                .count(),
+            completed: self.completed.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+}
+
+type Shared = watch::Receiver<Option<Result<serde_json::Value, ServerError>>>;
+
+pub struct ProxyState {
+    lanes: Mutex<HashMap<Category, Arc<Lane>>>,
+    /// GETs in flight by path, for requests to join.
+    pending: Mutex<HashMap<String, Shared>>,
+    /// Set once the app starts exiting; ends any retries.
+    exiting: watch::Sender<bool>,
+}
+
+impl Default for ProxyState {
+    fn default() -> Self {
+        Self {
+            lanes: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            exiting: watch::Sender::new(false),
+        }
+    }
+}
+
+impl ProxyState {
+    fn lane(&self, category: Category) -> Arc<Lane> {
+        self.lanes
+            .lock()
+            .unwrap()
+            .entry(category)
+            .or_default()
+            .clone()
+    }
+}
+
+/// Takes a GET out of `pending` once it is answered, or abandoned.
+struct Leader<'a> {
+    state: &'a ProxyState,
+    path: String,
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        self.state.pending.lock().unwrap().remove(&self.path);
+    }
+}
+
+async fn send(
+    app: &AppHandle,
+    category: Category,
+    priority: RequestPriority,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, ServerError> {
+    let limit = app
+        .state::<SettingsState>()
+        .get()
+        .proxy_limits
+        .get(category);
+    let lane = app.state::<ProxyState>().lane(category);
+    let _permit = lane.acquire(limit, priority).await;
+    let timeout = match category {
+        Category::Generation => api::GENERATION_TIMEOUT,
+        _ => api::REQUEST_TIMEOUT,
+    };
+    let result = api::request(&app.state::<ServerState>(), method, path, body, timeout).await;
+    lane.completed.fetch_add(1, Ordering::Relaxed);
+    result
+}
+
+/// `send`, retried while the server is restarting if the request is a GET
+/// or HEAD and fails in a way `Settings::proxy_retry` lists. Other requests
+/// failing that way come back as `ServerRestarting`.
+async fn send_retrying(
+    app: &AppHandle,
+    category: Category,
+    priority: RequestPriority,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, ServerError> {
+    let policy = app.state::<SettingsState>().get().proxy_retry;
+    let server = app.state::<ServerState>();
+    let idempotent = matches!(method, reqwest::Method::GET | reqwest::Method::HEAD);
+    let mut exiting = app.state::<ProxyState>().exiting.subscribe();
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        let error = match send(app, category, priority, method.clone(), path, body).await {
+            Ok(response) => return Ok(response),
+            Err(error) => error,
+        };
+        if !policy.retryable(&error) || !server.restarting() {
+            return Err(error);
+        }
+        if !idempotent || attempt >= policy.attempts.min(MAX_RETRIES) {
+            return Err(ServerError::ServerRestarting);
+        }
+        attempt += 1;
+        let wait = jittered(delay);
+        debug!(%path, attempt, error = %error, ?wait, "Retrying request while the server restarts");
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = exiting.wait_for(|exiting| *exiting) => return Err(error),
+        }
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+/// Ends retries for good; called when the app starts exiting.
+pub fn on_exit(app: &AppHandle) {
+    app.state::<ProxyState>().exiting.send_replace(true);
+}
+
+/// Sends a request to a sidecar `/api/` endpoint and returns its JSON
+/// response (`null` if empty). `method` defaults to GET, `category` to
+/// `metadata` and `priority` to `user`.
+#[tauri::command]
+pub async fn api_request(
+    app: AppHandle,
+    path: String,
+    method: Option<String>,
+    body: Option<serde_json::Value>,
+    category: Option<Category>,
+    priority: Option<RequestPriority>,
+) -> Result<serde_json::Value, ServerError> {
+    if !path.starts_with("/api/") || path.contains("..") {
+        return Err(ServerError::Proxy(format!("not an API path: {}", path)));
+    }
+    let method = match method.as_deref().map(str::to_ascii_uppercase) {
+        None => reqwest::Method::GET,
+        Some(m) => reqwest::Method::from_bytes(m.as_bytes())
+            .map_err(|_| ServerError::Proxy(format!("invalid method: {}", m)))?,
+    };
+    let category = category.unwrap_or_default();
+    let priority = priority.unwrap_or_default();
+    if method != reqwest::Method::GET || body.is_some() {
+        return send_retrying(&app, category, priority, method, &path, body.as_ref()).await;
+    }
+
+    let state = app.state::<ProxyState>();
+    let answer = loop {
+        let joined = {
+            let mut pending = state.pending.lock().unwrap();
+            match pending.get(&path) {
+                Some(shared) => Err(shared.clone()),
+                None => {
+                    let (answer, shared) = watch::channel(None);
+                    pending.insert(path.clone(), shared);
+                    Ok(answer)
+                }
+            }
+        };
+        let mut shared = match joined {
+            Ok(answer) => break answer,
+            Err(shared) => shared,
+        };
+        // Gone without an answer if the first request was dropped; then
+        // this one tries again.
+        if let Ok(answer) = shared.wait_for(Option::is_some).await {
+            state.lane(category).coalesced.fetch_add(1, Ordering::Relaxed);
+            return (*answer).clone().expect("waited for an answer");
+        }
+    };
+    let _leader = Leader {
+        state: &state,
+        path: path.clone(),
+    };
+    let result = send_retrying(&app, category, priority, method, &path, None).await;
+    answer.send_replace(Some(result.clone()));
+    result
+}
+
+/// Request counts for the diagnostics page.
+#[tauri::command]
+pub fn get_proxy_stats(state: tauri::State<'_, ProxyState>) -> ProxyStats {
+    let by_category: BTreeMap<Category, LaneStats> = state
+        .lanes
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(category, lane)| (*category, lane.stats()))
+        .collect();
+    let total = by_category
+        .values()
+        .fold(LaneStats::default(), |total, lane| LaneStats {
+            in_flight: total.in_flight + lane.in_flight,
+            queued: total.queued + lane.queued,
+            completed: total.completed + lane.completed,
+            coalesced: total.coalesced + lane.coalesced,
+        });
+    ProxyStats { total, by_category }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn user_requests_jump_the_queue() {
+        let lane = Arc::new(Lane::default());
+        let first = lane.acquire(1, RequestPriority::User).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut waiting = Vec::new();
+        for (name, priority) in [
+            ("prefetch-1", RequestPriority::Background),
+            ("prefetch-2", RequestPriority::Background),
+            ("click", RequestPriority::User),
+        ] {
+            let (lane, order) = (lane.clone(), order.clone());
+            waiting.push(tokio::spawn(async move {
+                let _permit = lane.acquire(1, priority).await;
+                order.lock().unwrap().push(name);
+            }));
+            // Queue them in a known order.
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(lane.stats().queued, 3);
+
+        drop(first);
+        for task in waiting {
+            task.await.unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), ["click", "prefetch-1", "prefetch-2"]);
+        assert_eq!(lane.stats().in_flight, 0);
+    }
+
+    #[test]
+    fn retry_policy() {
+        let policy = ProxyRetry::default();
+        assert!(policy.retryable(&ServerError::Unreachable("connection refused".into())));
+        assert!(policy.retryable(&ServerError::BadStatus(503)));
+        assert!(!policy.retryable(&ServerError::BadStatus(500)));
+        assert!(!policy.retryable(&ServerError::Timeout));
+
+        let delay = Duration::from_millis(400);
+        for _ in 0..100 {
+            let wait = jittered(delay);
+            assert!(wait >= Duration::from_millis(300) && wait < Duration::from_millis(500));
+        }
+    }
+
+    #[tokio::test]
+    async fn abandoned_waiters_pass_their_slot_on() {
+        let lane = Arc::new(Lane::default());
+        let first = lane.acquire(1, RequestPriority::User).await;
+        let gave_up = tokio::spawn({
+            let lane = lane.clone();
+            async move { lane.acquire(1, RequestPriority::User).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        gave_up.abort();
+        let _ = gave_up.await;
+
+        let next = tokio::spawn({
+            let lane = lane.clone();
+            async move {
+                let _permit = lane.acquire(1, RequestPriority::Background).await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), next)
+            .await
+            .expect("slot was passed on")
+            .unwrap();
+        assert_eq!(lane.stats().in_flight, 0);
+    }
+}
@@ -0,0 +1,554 @@
+//! Episode bundles for publishing platforms: the video at an export preset,
+//! a cover frame, SRT subtitles and the title, description and tags, in
+//! one folder or zip named from a template.
+//!
+//! Each artifact is made on its own, with the same code as the matching
+//! command, and one that fails is reported without throwing away the
+//! others. Progress for the whole bundle goes out as `publish-progress`
+//! under one task id.
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{info, warn};
+
+use crate::api;
+use crate::backups::valid_project_id;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::frames::{self, FrameFormat};
+use crate::hardware;
+use crate::server::ServerState;
+use crate::subtitles::{self, SubtitleFormat};
+use crate::tempfiles;
+use crate::time::now_secs;
+
+const DEFAULT_TEMPLATE: &str = "{project}-{ep:02}-{date}";
+/// Where the cover is taken from when not chosen; the first frames are
+/// often a fade from black.
+const DEFAULT_COVER_AT_MS: u64 = 1000;
+/// Share of the progress bar each artifact takes, in the order made.
+const WEIGHTS: &[(Artifact, f64)] = &[
+    (Artifact::Video, 0.7),
+    (Artifact::Cover, 0.1),
+    (Artifact::Subtitles, 0.1),
+    (Artifact::Metadata, 0.05),
+    (Artifact::Zip, 0.05),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Artifact {
+    Video,
+    Cover,
+    Subtitles,
+    Metadata,
+    Zip,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PublishOptions {
+    /// The rendered episode.
+    pub video_path: String,
+    /// One of `get_export_presets`; the video is copied unchanged without.
+    pub preset: Option<String>,
+    /// Placeholders: `{project}`, `{episode}`, `{ep}` or `{ep:02}` for the
+    /// zero-padded episode number, and `{date}` as `YYYY-MM-DD` in UTC.
+    pub name_template: Option<String>,
+    /// Used for `{ep}` when the episode doesn't say.
+    pub episode_number: Option<u32>,
+    pub cover_at_ms: Option<u64>,
+    /// Zips the bundle and removes the folder.
+    pub zip: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishMetadata {
+    pub project: String,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub episode_number: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtifactResult {
+    pub artifact: Artifact,
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishResult {
+    pub task_id: String,
+    /// The bundle folder, or the zip.
+    pub path: String,
+    pub artifacts: Vec<ArtifactResult>,
+    /// Every artifact was made.
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PublishProgress<'a> {
+    task_id: &'a str,
+    artifact: Artifact,
+    /// For the whole bundle, 0 to 1.
+    progress: f64,
+}
+
+fn task_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("publish-{:x}-{:x}", nanos, std::process::id())
+}
+
+/// Today in UTC as `YYYY-MM-DD`.
+fn utc_date(secs: u64) -> String {
+    // Days to civil date, from Howard Hinnant's `civil_from_days`.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Drops characters that aren't allowed in file names somewhere.
+fn file_safe(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    cleaned.trim().trim_matches('.').to_owned()
+}
+
+/// Fills in the name template. `{ep:NN}` pads the episode number to `NN`
+/// digits; it is left empty if the number isn't known.
+fn bundle_name(template: &str, meta: &PublishMetadata, date: &str) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('}') else {
+            break;
+        };
+        let placeholder = &rest[open + 1..open + close];
+        let (name, width) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+        match name {
+            "project" => out.push_str(&meta.project),
+            "episode" => out.push_str(&meta.title),
+            "date" => out.push_str(date),
+            "ep" => {
+                if let Some(n) = meta.episode_number {
+                    let width = width.parse().unwrap_or(0);
+                    out.push_str(&format!("{:0width$}", n, width = width));
+                }
+            }
+            _ => out.push_str(&rest[open..=open + close]),
+        }
+        rest = &rest[open + close + 1..];
+    }
+    out.push_str(rest);
+    file_safe(&out)
+}
+
+/// SQLite hands JSON columns back as strings.
+fn json_field(value: &serde_json::Value) -> serde_json::Value {
+    match value.as_str() {
+        Some(text) => serde_json::from_str(text).unwrap_or_default(),
+        None => value.clone(),
+    }
+}
+
+/// The episode node's title, description and tags, from a project export.
+fn episode_metadata(
+    project: &serde_json::Value,
+    episode_id: &str,
+) -> Result<PublishMetadata, ServerError> {
+    let node = project
+        .get("nodes")
+        .and_then(|n| n.as_array())
+        .and_then(|nodes| {
+            nodes
+                .iter()
+                .find(|n| n.get("id").and_then(|v| v.as_str()) == Some(episode_id))
+        })
+        .ok_or_else(|| ServerError::Publish(format!("episode {} not found", episode_id)))?;
+    let data = node.get("data").map(json_field).unwrap_or_default();
+    let text = |value: Option<&serde_json::Value>| {
+        value.and_then(|v| v.as_str()).map(str::trim).filter(|s| !s.is_empty())
+    };
+    let tags = match data.get("tags") {
+        Some(serde_json::Value::Array(tags)) => {
+            tags.iter().filter_map(|t| text(Some(t))).map(str::to_owned).collect()
+        }
+        Some(serde_json::Value::String(tags)) => tags
+            .split([',', '，'])
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let episode_number = ["episodeNumber", "episode"]
+        .iter()
+        .find_map(|key| data.get(*key)?.as_u64())
+        .map(|n| n as u32);
+    Ok(PublishMetadata {
+        project: text(project.get("title")).unwrap_or_default().to_owned(),
+        title: text(node.get("title"))
+            .or_else(|| text(data.get("title")))
+            .unwrap_or_default()
+            .to_owned(),
+        description: ["description", "synopsis", "summary"]
+            .iter()
+            .find_map(|key| text(data.get(*key)))
+            .unwrap_or_default()
+            .to_owned(),
+        tags,
+        episode_number,
+    })
+}
+
+fn metadata_text(meta: &PublishMetadata) -> String {
+    format!(
+        "{}\n\n{}\n\n{}\n",
+        meta.title,
+        meta.description,
+        meta.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")
+    )
+}
+
+struct Progress<'a> {
+    app: &'a AppHandle,
+    task_id: &'a str,
+}
+
+impl Progress<'_> {
+    /// Reports `artifact` as `fraction` done.
+    fn report(&self, artifact: Artifact, fraction: f64) {
+        let before: f64 = WEIGHTS
+            .iter()
+            .take_while(|(a, _)| *a != artifact)
+            .map(|(_, w)| w)
+            .sum();
+        let weight = WEIGHTS
+            .iter()
+            .find(|(a, _)| *a == artifact)
+            .map_or(0.0, |(_, w)| *w);
+        let _ = self.app.emit(
+            "publish-progress",
+            PublishProgress {
+                task_id: self.task_id,
+                artifact,
+                progress: (before + weight * fraction.clamp(0.0, 1.0)).min(1.0),
+            },
+        );
+    }
+}
+
+/// Encodes `source` to `dest` at `preset`, or copies it without one.
+async fn export_video(
+    app: &AppHandle,
+    source: &Path,
+    dest: &Path,
+    preset: Option<&str>,
+    progress: &Progress<'_>,
+) -> Result<(), ServerError> {
+    let Some(preset) = preset else {
+        let tmp = tempfiles::create_temp_path(app, "exports", "mp4")?;
+        if let Err(e) = tokio::fs::copy(source, &tmp).await {
+            tempfiles::discard(app, &tmp).await;
+            return Err(e.into());
+        }
+        return tempfiles::commit(app, &tmp, dest).await;
+    };
+    let encoding = hardware::preset_encoding(app, preset)
+        .await
+        .ok_or_else(|| ServerError::Publish(format!("can't encode preset {} here", preset)))?;
+    let duration = frames::duration_ms(source).await?;
+
+    let (w, h) = (encoding.width, encoding.height);
+    let tmp = tempfiles::create_temp_path(app, "exports", "mp4")?;
+    let mut child = tokio::process::Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(source)
+        .arg("-vf")
+        .arg(format!(
+            "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2"
+        ))
+        .args(["-c:v", &encoding.encoder, "-c:a", "aac", "-movflags", "+faststart"])
+        .args(["-progress", "pipe:1", "-nostats"])
+        .arg(&tmp)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| ServerError::Publish(format!("can't run ffmpeg: {}", e)))?;
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let done_us = line.strip_prefix("out_time_us=").and_then(|v| v.parse::<u64>().ok());
+            if let Some(done_us) = done_us.filter(|_| duration > 0) {
+                progress.report(Artifact::Video, done_us as f64 / 1000.0 / duration as f64);
+            }
+        }
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| ServerError::Publish(e.to_string()))?;
+    if !output.status.success() {
+        tempfiles::discard(app, &tmp).await;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ServerError::FfmpegFailed(stderr.trim().to_owned()));
+    }
+    tempfiles::commit(app, &tmp, dest).await
+}
+
+async fn write_metadata(
+    app: &AppHandle,
+    dir: &Path,
+    meta: &PublishMetadata,
+) -> Result<(), ServerError> {
+    let json = serde_json::to_vec_pretty(meta).map_err(|e| ServerError::Publish(e.to_string()))?;
+    let text = metadata_text(meta).into_bytes();
+    for (name, content) in [("metadata.json", json), ("metadata.txt", text)] {
+        let tmp = tempfiles::create_temp_path(app, "exports", "part")?;
+        if let Err(e) = tokio::fs::write(&tmp, content).await {
+            tempfiles::discard(app, &tmp).await;
+            return Err(e.into());
+        }
+        tempfiles::commit(app, &tmp, &dir.join(name)).await?;
+    }
+    Ok(())
+}
+
+fn zip_dir(dir: &Path, dest: &Path) -> Result<(), ServerError> {
+    let written = (|| -> zip::result::ZipResult<()> {
+        let mut zip = zip::ZipWriter::new(File::create(dest)?);
+        // Video is already compressed.
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .large_file(true);
+        for entry in std::fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            zip.start_file(entry.file_name().to_string_lossy(), options)?;
+            std::io::copy(&mut File::open(&path)?, &mut zip)?;
+        }
+        zip.finish()?.flush()?;
+        Ok(())
+    })();
+    written.map_err(|e| match e {
+        zip::result::ZipError::Io(e) => e.into(),
+        e => ServerError::Publish(e.to_string()),
+    })
+}
+
+fn outcome(artifact: Artifact, path: &Path, made: Result<(), ServerError>) -> ArtifactResult {
+    match made {
+        Ok(()) => ArtifactResult {
+            artifact,
+            path: Some(path.to_string_lossy().into_owned()),
+            error: None,
+        },
+        Err(e) => {
+            warn!(?artifact, error = %e, "Publish artifact failed");
+            ArtifactResult {
+                artifact,
+                path: None,
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Assembles a publishing bundle for `episode_id` in `dest_dir`. Artifacts
+/// that fail are listed with their error in the result, next to the ones
+/// that were made; only problems that stop the bundle altogether, such as
+/// an unknown episode, fail the command.
+#[tauri::command]
+pub async fn export_publish_bundle(
+    app: AppHandle,
+    project_id: String,
+    episode_id: String,
+    dest_dir: String,
+    options: PublishOptions,
+) -> Result<PublishResult, ServerError> {
+    blocking::watched("export_publish_bundle", async move {
+        if !valid_project_id(&project_id) {
+            return Err(ServerError::InvalidArgument(format!("invalid project id: {}", project_id)));
+        }
+        let source = PathBuf::from(&options.video_path);
+        if !source.is_file() {
+            return Err(ServerError::FileNotFound(options.video_path.clone()));
+        }
+        let project = api::get_project(&app.state::<ServerState>(), &project_id).await?;
+        let mut meta = episode_metadata(&project, &episode_id)?;
+        meta.episode_number = meta.episode_number.or(options.episode_number);
+        if meta.project.is_empty() {
+            meta.project = project_id.clone();
+        }
+
+        let secs = now_secs();
+        let template = options.name_template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+        let name = Some(bundle_name(template, &meta, &utc_date(secs)))
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| file_safe(&episode_id));
+        let dir = PathBuf::from(&dest_dir).join(&name);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let task_id = task_id();
+        info!(%task_id, project_id, episode_id, dir = %dir.display(), "Exporting publish bundle");
+        let progress = Progress {
+            app: &app,
+            task_id: &task_id,
+        };
+        let mut artifacts = Vec::new();
+
+        progress.report(Artifact::Video, 0.0);
+        let video = dir.join(format!("{}.mp4", name));
+        let made = export_video(&app, &source, &video, options.preset.as_deref(), &progress).await;
+        let video_ok = made.is_ok();
+        artifacts.push(outcome(Artifact::Video, &video, made));
+
+        progress.report(Artifact::Cover, 0.0);
+        let cover = dir.join(format!("{}.jpg", name));
+        // From the bundled video when there is one, so it matches the preset.
+        let from = if video_ok { &video } else { &source };
+        let made = frames::capture_frame(
+            app.clone(),
+            from.to_string_lossy().into_owned(),
+            options.cover_at_ms.unwrap_or(DEFAULT_COVER_AT_MS),
+            FrameFormat::Jpg,
+            Some(cover.to_string_lossy().into_owned()),
+            None,
+            None,
+        )
+        .await
+        .map(|_| ());
+        artifacts.push(outcome(Artifact::Cover, &cover, made));
+
+        progress.report(Artifact::Subtitles, 0.0);
+        let srt = dir.join(format!("{}.srt", name));
+        let made = subtitles::export_subtitles(
+            app.clone(),
+            Some(project_id.clone()),
+            None,
+            SubtitleFormat::Srt,
+            srt.to_string_lossy().into_owned(),
+            None,
+            Some(true),
+        )
+        .await
+        .map(|_| ());
+        artifacts.push(outcome(Artifact::Subtitles, &srt, made));
+
+        progress.report(Artifact::Metadata, 0.0);
+        let made = write_metadata(&app, &dir, &meta).await;
+        artifacts.push(outcome(Artifact::Metadata, &dir.join("metadata.json"), made));
+
+        let mut path = dir.clone();
+        if options.zip {
+            progress.report(Artifact::Zip, 0.0);
+            let zip_path = PathBuf::from(&dest_dir).join(format!("{}.zip", name));
+            let tmp = tempfiles::create_temp_path(&app, "exports", "zip")?;
+            let (from, to) = (dir.clone(), tmp.clone());
+            let made = blocking::run(move || zip_dir(&from, &to))
+                .await
+                .map_err(ServerError::Publish)
+                .and_then(|zipped| zipped);
+            let made = match made {
+                Ok(()) => tempfiles::commit(&app, &tmp, &zip_path).await,
+                Err(e) => {
+                    tempfiles::discard(&app, &tmp).await;
+                    Err(e)
+                }
+            };
+            // The folder stays if the zip couldn't be made.
+            if made.is_ok() {
+                let _ = tokio::fs::remove_dir_all(&dir).await;
+                path = zip_path.clone();
+            }
+            artifacts.push(outcome(Artifact::Zip, &zip_path, made));
+        }
+        progress.report(Artifact::Zip, 1.0);
+
+        let complete = artifacts.iter().all(|a| a.error.is_none());
+        Ok(PublishResult {
+            task_id,
+            path: path.to_string_lossy().into_owned(),
+            artifacts,
+            complete,
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn metadata_and_names_come_from_the_episode() {
+        let project = json!({
+            "title": "雨夜/长街",
+            "nodes": [
+                { "id": "n1", "title": "", "data": "{}" },
+                {
+                    "id": "ep3",
+                    "title": "Homecoming",
+                    "data": json!({
+                        "description": " Back at last ",
+                        "tags": "drama， rain",
+                        "episodeNumber": 3
+                    })
+                    .to_string()
+                }
+            ]
+        });
+        let meta = episode_metadata(&project, "ep3").unwrap();
+        assert_eq!(
+            meta,
+            PublishMetadata {
+                project: "雨夜/长街".into(),
+                title: "Homecoming".into(),
+                description: "Back at last".into(),
+                tags: vec!["drama".into(), "rain".into()],
+                episode_number: Some(3),
+            }
+        );
+        assert!(episode_metadata(&project, "missing").is_err());
+
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(1_709_210_096), "2024-02-29");
+        assert_eq!(
+            bundle_name(DEFAULT_TEMPLATE, &meta, "2024-02-29"),
+            "雨夜_长街-03-2024-02-29"
+        );
+        assert_eq!(bundle_name("{episode} {ep} {other}", &meta, ""), "Homecoming 3 {other}");
+    }
+}
@@ -0,0 +1,187 @@
+//! Recently opened projects, persisted to `recents.json` in the app config
+//! dir and mirrored into the "Open Recent" menu and the OS recents.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::error::ServerError;
+use crate::menu;
+use crate::time::now_ms;
+
+const MAX_RECENTS: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentProject {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub thumbnail: Option<String>,
+    /// Unix time in milliseconds.
+    pub last_opened: u64,
+}
+
+pub struct RecentsState {
+    path: Option<PathBuf>,
+    /// Most recent first.
+    items: Mutex<Vec<RecentProject>>,
+}
+
+impl RecentsState {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join("recents.json"));
+        let items = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(items) => Some(items),
+                Err(e) => {
+                    warn!(error = %e, "Ignoring invalid recents file");
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            items: Mutex::new(items),
+        }
+    }
+
+    /// Applies `f` to the list, writes it to disk and returns the result.
+    fn update(
+        &self,
+        f: impl FnOnce(&mut Vec<RecentProject>),
+    ) -> Result<Vec<RecentProject>, ServerError> {
+        let mut items = self.items.lock().unwrap();
+        let mut updated = items.clone();
+        f(&mut updated);
+        updated.truncate(MAX_RECENTS);
+
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let json = serde_json::to_string_pretty(&updated)
+                .map_err(|e| ServerError::Io(e.to_string()))?;
+            std::fs::write(path, json)?;
+        }
+        *items = updated.clone();
+        Ok(updated)
+    }
+
+    pub fn get(&self) -> Vec<RecentProject> {
+        self.items.lock().unwrap().clone()
+    }
+}
+
+/// Adds the project to the OS recent documents (dock menu on macOS, jump
+/// list on Windows).
+#[cfg(target_os = "macos")]
+fn note_os_recent(app: &AppHandle, path: &str) {
+    let path = path.to_owned();
+    let _ = app.run_on_main_thread(move || {
+        use objc2::MainThreadMarker;
+        use objc2_app_kit::NSDocumentController;
+        use objc2_foundation::{NSString, NSURL};
+
+        let Some(mtm) = MainThreadMarker::new() else {
+            return;
+        };
+        let url = NSURL::fileURLWithPath(&NSString::from_str(&path));
+        NSDocumentController::sharedDocumentController(mtm).noteNewRecentDocumentURL(&url);
+    });
+}
+
+#[cfg(windows)]
+fn note_os_recent(_app: &AppHandle, path: &str) {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::Win32::UI::Shell::{SHAddToRecentDocs, SHARD_PATHW};
+
+    let wide: Vec<u16> = std::ffi::OsStr::new(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    // SAFETY: `wide` is a NUL-terminated UTF-16 path that outlives the call.
+    unsafe { SHAddToRecentDocs(SHARD_PATHW.0 as u32, Some(wide.as_ptr().cast())) };
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn note_os_recent(_app: &AppHandle, _path: &str) {}
+
+/// Fills the "Open Recent" menu from the saved list.
+pub fn init(app: &AppHandle) {
+    menu::set_recent_projects(app, &app.state::<RecentsState>().get());
+}
+
+/// Replaces a project's thumbnail, e.g. with a frame chosen as its cover.
+/// Does nothing for projects not in the list.
+pub fn set_thumbnail(app: &AppHandle, project_id: &str, thumbnail: &str) -> Result<(), ServerError> {
+    let state = app.state::<RecentsState>();
+    if !state.get().iter().any(|p| p.id == project_id) {
+        return Ok(());
+    }
+    let items = state.update(|items| {
+        if let Some(project) = items.iter_mut().find(|p| p.id == project_id) {
+            project.thumbnail = Some(thumbnail.to_owned());
+        }
+    })?;
+    menu::set_recent_projects(app, &items);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn add_recent_project(
+    app: AppHandle,
+    id: String,
+    name: String,
+    path: String,
+    thumbnail: Option<String>,
+) -> Result<(), ServerError> {
+    let project = RecentProject {
+        id,
+        name,
+        path,
+        thumbnail,
+        last_opened: now_ms(),
+    };
+    note_os_recent(&app, &project.path);
+    let items = app.state::<RecentsState>().update(|items| {
+        items.retain(|p| p.id != project.id);
+        items.insert(0, project);
+    })?;
+    menu::set_recent_projects(&app, &items);
+    Ok(())
+}
+
+/// Returns up to `limit` recent projects, most recent first. Projects that
+/// no longer exist on disk are dropped for good.
+#[tauri::command]
+pub fn get_recent_projects(
+    app: AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<RecentProject>, ServerError> {
+    let state = app.state::<RecentsState>();
+    let mut items = state.get();
+    if items.iter().any(|p| !Path::new(&p.path).exists()) {
+        items = state.update(|items| items.retain(|p| Path::new(&p.path).exists()))?;
+        menu::set_recent_projects(&app, &items);
+    }
+    items.truncate(limit.unwrap_or(MAX_RECENTS));
+    Ok(items)
+}
+
+#[tauri::command]
+pub fn remove_recent_project(app: AppHandle, id: String) -> Result<(), ServerError> {
+    let items = app
+        .state::<RecentsState>()
+        .update(|items| items.retain(|p| p.id != id))?;
+    menu::set_recent_projects(&app, &items);
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Crash-recovery snapshots of unsaved edits, in
+//! `app_data_dir()/recovery/<project>.json`.
+//!
+//! The frontend saves one on a debounce while a project has unsaved
+//! changes; if the webview dies, the next open offers to restore it. The
+//! snapshot before the latest is kept as `<project>.prev.json` in case the
+//! latest one is bad.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::backups::valid_project_id;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::time::now_ms;
+
+/// Larger payloads are refused rather than written on every keystroke.
+const MAX_PAYLOAD_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoverySnapshot {
+    pub project_id: String,
+    /// Unix time in milliseconds.
+    pub saved_at: u64,
+    pub payload: serde_json::Value,
+}
+
+/// Serializes snapshot writes, so a slow write can't be overtaken by and
+/// then clobber a newer one.
+#[derive(Default)]
+pub struct RecoveryState {
+    busy: tokio::sync::Mutex<()>,
+}
+
+struct SnapshotPaths {
+    latest: PathBuf,
+    previous: PathBuf,
+    tmp: PathBuf,
+}
+
+fn paths(app: &AppHandle, project_id: &str) -> Result<SnapshotPaths, ServerError> {
+    if !valid_project_id(project_id) {
+        return Err(ServerError::Recovery(format!(
+            "invalid project id: {}",
+            project_id
+        )));
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?
+        .join("recovery");
+    Ok(SnapshotPaths {
+        latest: dir.join(format!("{}.json", project_id)),
+        previous: dir.join(format!("{}.prev.json", project_id)),
+        tmp: dir.join(format!("{}.json.tmp", project_id)),
+    })
+}
+
+async fn read_snapshot(path: &Path) -> Option<RecoverySnapshot> {
+    let data = tokio::fs::read(path).await.ok()?;
+    match serde_json::from_slice(&data) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            warn!(
+                path = %path.display(),
+                error = %e,
+                "Ignoring invalid recovery snapshot"
+            );
+            None
+        }
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> Result<(), ServerError> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub async fn save_recovery_snapshot(
+    app: AppHandle,
+    project_id: String,
+    payload: serde_json::Value,
+) -> Result<(), ServerError> {
+    blocking::watched("save_recovery_snapshot", async move {
+        let paths = paths(&app, &project_id)?;
+        let snapshot = RecoverySnapshot {
+            project_id,
+            saved_at: now_ms(),
+            payload,
+        };
+        let json = serde_json::to_vec(&snapshot).map_err(|e| ServerError::Recovery(e.to_string()))?;
+        if json.len() > MAX_PAYLOAD_BYTES {
+            return Err(ServerError::Recovery(format!(
+                "snapshot is {} bytes, over the {} byte limit",
+                json.len(),
+                MAX_PAYLOAD_BYTES
+            )));
+        }
+
+        let state = app.state::<RecoveryState>();
+        let _busy = state.busy.lock().await;
+        if let Some(dir) = paths.latest.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        // Written aside first, so a crash mid-write leaves the last good
+        // snapshot in place.
+        tokio::fs::write(&paths.tmp, json).await?;
+        if tokio::fs::try_exists(&paths.latest).await.unwrap_or(false) {
+            tokio::fs::rename(&paths.latest, &paths.previous).await?;
+        }
+        tokio::fs::rename(&paths.tmp, &paths.latest).await?;
+        Ok(())
+    })
+    .await
+}
+
+/// The latest snapshot for a project, or the one before it if the latest
+/// can't be read.
+#[tauri::command]
+pub async fn get_recovery_snapshot(
+    app: AppHandle,
+    project_id: String,
+) -> Result<Option<RecoverySnapshot>, ServerError> {
+    let paths = paths(&app, &project_id)?;
+    let state = app.state::<RecoveryState>();
+    let _busy = state.busy.lock().await;
+    match read_snapshot(&paths.latest).await {
+        Some(snapshot) => Ok(Some(snapshot)),
+        None => Ok(read_snapshot(&paths.previous).await),
+    }
+}
+
+#[tauri::command]
+pub async fn discard_recovery_snapshot(
+    app: AppHandle,
+    project_id: String,
+) -> Result<(), ServerError> {
+    let paths = paths(&app, &project_id)?;
+    let state = app.state::<RecoveryState>();
+    let _busy = state.busy.lock().await;
+    remove_if_exists(&paths.latest).await?;
+    remove_if_exists(&paths.previous).await?;
+    remove_if_exists(&paths.tmp).await
+}
+
+/// Called by the frontend after a project saved successfully; its
+/// snapshots have nothing left to recover.
+#[tauri::command]
+pub async fn project_saved(app: AppHandle, project_id: String) -> Result<(), ServerError> {
+    discard_recovery_snapshot(app, project_id).await
+}
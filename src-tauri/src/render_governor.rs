@@ -0,0 +1,245 @@
+//! How many render slots the queue may fill, adjusted to the load on the
+//! machine.
+//!
+//! The target comes from `Settings::render_parallelism`, or from the core
+//! count when that is automatic. Every [`SAMPLE_INTERVAL`] the system-wide
+//! CPU use and available memory are sampled: above [`BUSY_CPU_PERCENT`] or
+//! below [`MIN_AVAILABLE_MEMORY`] the limit drops a slot, and once the
+//! machine is quiet again it climbs back a slot at a time. Lowering the
+//! limit never stops a running job; the queue just starts nothing new
+//! until enough have finished.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use sysinfo::System;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::info;
+
+use crate::crash;
+use crate::hardware;
+use crate::render_queue;
+use crate::settings::SettingsState;
+use crate::time::now_ms;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+/// Most slots the automatic target picks.
+const MAX_AUTO: usize = 4;
+/// Upper bound on a chosen `Settings::render_parallelism`.
+const MAX_PARALLEL: usize = 8;
+const BUSY_CPU_PERCENT: f32 = 85.0;
+/// CPU use the limit climbs back under, lower than [`BUSY_CPU_PERCENT`] so
+/// it doesn't rise and fall on every sample.
+const QUIET_CPU_PERCENT: f32 = 70.0;
+const MIN_AVAILABLE_MEMORY: u64 = 2 * 1024 * 1024 * 1024;
+/// Slots taken by a job at 4K or above.
+const UHD_WEIGHT: usize = 2;
+
+/// Why the limit last moved.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum AdjustReason {
+    CpuBusy { percent: f32 },
+    MemoryLow { available_bytes: u64 },
+    /// The machine is quiet again and the limit was under the target.
+    Recovered,
+    /// The setting changed, or the limit was first set.
+    Target { target: usize },
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GovernorStatus {
+    /// Slots the setting asks for.
+    pub target: usize,
+    /// Slots the queue may fill right now; 0 before the first sample.
+    pub limit: usize,
+    /// The target follows the core count.
+    pub auto: bool,
+    /// System-wide, 0 to 100, at the last sample.
+    pub cpu_percent: Option<f32>,
+    pub available_memory_bytes: Option<u64>,
+    pub reasons: Vec<AdjustReason>,
+    /// Unix time in milliseconds of the last change to the limit.
+    pub adjusted_at: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct GovernorState {
+    status: Mutex<GovernorStatus>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sample {
+    cpu_percent: Option<f32>,
+    available_memory_bytes: Option<u64>,
+}
+
+fn target(setting: Option<usize>, cores: usize) -> usize {
+    match setting {
+        Some(n) => n.clamp(1, MAX_PARALLEL),
+        None => (cores / 4).clamp(1, MAX_AUTO),
+    }
+}
+
+/// The next limit, and why it changed; `None` if it stays.
+fn adjust(limit: usize, target: usize, sample: Sample) -> Option<(usize, Vec<AdjustReason>)> {
+    if limit == 0 || limit > target {
+        return Some((target, vec![AdjustReason::Target { target }]));
+    }
+    let mut busy = Vec::new();
+    if let Some(percent) = sample.cpu_percent.filter(|p| *p > BUSY_CPU_PERCENT) {
+        busy.push(AdjustReason::CpuBusy { percent });
+    }
+    if let Some(available_bytes) = sample
+        .available_memory_bytes
+        .filter(|b| *b < MIN_AVAILABLE_MEMORY)
+    {
+        busy.push(AdjustReason::MemoryLow { available_bytes });
+    }
+    if !busy.is_empty() {
+        return (limit > 1).then(|| (limit - 1, busy));
+    }
+    let quiet = sample.cpu_percent.is_none_or(|p| p < QUIET_CPU_PERCENT);
+    (limit < target && quiet).then(|| (limit + 1, vec![AdjustReason::Recovered]))
+}
+
+/// Slots a queued job takes: its `weight` if the spec gives one, two at 4K
+/// or above by its `preset` or `height`, otherwise one.
+pub fn job_weight(spec: &serde_json::Value) -> usize {
+    if let Some(weight) = spec.get("weight").and_then(|w| w.as_u64()) {
+        return (weight as usize).clamp(1, MAX_PARALLEL);
+    }
+    let height = spec
+        .get("preset")
+        .and_then(|p| p.as_str())
+        .and_then(hardware::preset_height)
+        .or_else(|| spec.get("height")?.as_u64().map(|h| h as u32));
+    if height.is_some_and(|h| h >= 2160) {
+        UHD_WEIGHT
+    } else {
+        1
+    }
+}
+
+fn current_target(app: &AppHandle) -> (usize, bool) {
+    let setting = app.state::<SettingsState>().get().render_parallelism;
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    (target(setting, cores), setting.is_none())
+}
+
+/// Slots the render queue may fill. Before the first sample that's the
+/// target.
+pub fn limit(app: &AppHandle) -> usize {
+    match app.state::<GovernorState>().status.lock().unwrap().limit {
+        0 => current_target(app).0,
+        limit => limit,
+    }
+}
+
+fn sample(system: &mut System) -> Sample {
+    system.refresh_cpu_usage();
+    system.refresh_memory();
+    let available = system.available_memory();
+    Sample {
+        cpu_percent: Some(system.global_cpu_usage()),
+        // 0 where it can't be read, which isn't the same as none left.
+        available_memory_bytes: (available > 0).then_some(available),
+    }
+}
+
+/// Samples the load and moves the limit, waking the queue when it rises.
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("render-governor", async move {
+        let mut system = System::new();
+        // CPU use is measured between two refreshes.
+        system.refresh_cpu_usage();
+        loop {
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+            let sample = sample(&mut system);
+            let (target, auto) = current_target(&handle);
+            let state = handle.state::<GovernorState>();
+            let (raised, changed) = {
+                let mut status = state.status.lock().unwrap();
+                status.target = target;
+                status.auto = auto;
+                status.cpu_percent = sample.cpu_percent;
+                status.available_memory_bytes = sample.available_memory_bytes;
+                match adjust(status.limit, target, sample) {
+                    Some((limit, reasons)) => {
+                        info!(from = status.limit, to = limit, ?reasons, "Render limit changed");
+                        let raised = limit > status.limit;
+                        status.limit = limit;
+                        status.reasons = reasons;
+                        status.adjusted_at = Some(now_ms());
+                        (raised, Some(status.clone()))
+                    }
+                    None => (false, None),
+                }
+            };
+            if let Some(status) = changed {
+                let _ = handle.emit("render-governor-changed", status);
+            }
+            if raised {
+                render_queue::wake(&handle);
+            }
+        }
+    });
+}
+
+/// The current limit and why it was last changed, for the queue view.
+#[tauri::command]
+pub fn get_governor_state(app: AppHandle) -> GovernorStatus {
+    let mut status = app.state::<GovernorState>().status.lock().unwrap().clone();
+    if status.limit == 0 {
+        (status.target, status.auto) = current_target(&app);
+        status.limit = status.target;
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn limit_backs_off_under_load_and_recovers_a_slot_at_a_time() {
+        assert_eq!(target(None, 16), 4);
+        assert_eq!(target(None, 2), 1);
+        assert_eq!(target(Some(0), 16), 1);
+
+        let quiet = Sample {
+            cpu_percent: Some(20.0),
+            available_memory_bytes: Some(8 << 30),
+        };
+        let busy = Sample {
+            cpu_percent: Some(97.0),
+            available_memory_bytes: Some(1 << 30),
+        };
+        assert_eq!(adjust(0, 4, quiet), Some((4, vec![AdjustReason::Target { target: 4 }])));
+        assert_eq!(adjust(4, 4, quiet), None);
+        let (limit, reasons) = adjust(4, 4, busy).unwrap();
+        assert_eq!(limit, 3);
+        assert!(matches!(
+            reasons[..],
+            [AdjustReason::CpuBusy { .. }, AdjustReason::MemoryLow { .. }]
+        ));
+        assert_eq!(adjust(1, 4, busy), None);
+        // Between the thresholds it holds where it is.
+        let warm = Sample {
+            cpu_percent: Some(80.0),
+            ..quiet
+        };
+        assert_eq!(adjust(2, 4, warm), None);
+        assert_eq!(adjust(2, 4, quiet), Some((3, vec![AdjustReason::Recovered])));
+        // A lower setting applies at once.
+        assert_eq!(adjust(4, 2, busy).unwrap().0, 2);
+
+        assert_eq!(job_weight(&json!({ "projectPath": "a.aiyou" })), 1);
+        assert_eq!(job_weight(&json!({ "preset": "hevc-2160p" })), 2);
+        assert_eq!(job_weight(&json!({ "height": 2160 })), 2);
+        assert_eq!(job_weight(&json!({ "preset": "hevc-2160p", "weight": 3 })), 3);
+    }
+}
@@ -0,0 +1,910 @@
+//! Render queue owned by the app rather than the webview, persisted to
+//! `render_queue.json` in the app data dir so a crash doesn't lose it.
+//!
+//! Jobs are submitted to the sidecar as slots free up and advanced by the
+//! progress events coming through the bridge. How many slots there are is
+//! up to `render_governor`. With a render schedule, jobs are only
+//! submitted inside its window unless marked to run now.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::api;
+use crate::cost_estimate::{self, ActualUsage, CostEstimate};
+use crate::crash;
+use crate::error::ServerError;
+use crate::job_poll;
+use crate::license;
+use crate::render_governor;
+use crate::server::{ServerState, ServerStatus};
+use crate::settings::SettingsState;
+use crate::taskbar;
+use crate::time::now_ms;
+
+/// Finished jobs kept for the frontend to show; older ones are dropped.
+const MAX_FINISHED: usize = 50;
+/// Server job outcomes that arrived before their submission returned.
+const MAX_EARLY_OUTCOMES: usize = 100;
+/// How often the scheduler looks at the clock. It only re-evaluates the
+/// window when the local minute changes, but checking often means a jump
+/// in wall time, such as waking from sleep, is noticed within seconds.
+const SCHEDULE_POLL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QueuedJobState {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedJob {
+    pub id: String,
+    /// Render request body, passed to the sidecar unchanged.
+    pub spec: serde_json::Value,
+    pub state: QueuedJobState,
+    /// Set when the job was running when the app or server went down and
+    /// has been queued again.
+    pub retried: bool,
+    /// Sidecar jobs the render was split into that haven't finished.
+    pub server_jobs: Vec<String>,
+    /// Sidecar jobs in total, once submitted.
+    pub server_job_count: usize,
+    /// Percent complete, 0 to 100.
+    pub progress: Option<f64>,
+    pub error: Option<String>,
+    /// Unix time in milliseconds.
+    pub enqueued_at: u64,
+    /// Submitted even outside the render schedule's window.
+    #[serde(default)]
+    pub run_now: bool,
+    /// Made when the job was queued, if its spec lists generations.
+    #[serde(default)]
+    pub estimate: Option<CostEstimate>,
+    /// As the sidecar reports it, while the job runs.
+    #[serde(default)]
+    pub actual: Option<ActualUsage>,
+    /// Actual cost over the estimate once the job is done.
+    #[serde(default)]
+    pub actual_vs_estimate: Option<f64>,
+}
+
+impl QueuedJob {
+    fn finished(&self) -> bool {
+        matches!(
+            self.state,
+            QueuedJobState::Done | QueuedJobState::Failed | QueuedJobState::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RenderQueue {
+    pub paused: bool,
+    /// In queue order; finished jobs stay in place until pruned.
+    pub jobs: Vec<QueuedJob>,
+}
+
+/// When queued renders may start, in local time. Jobs already running
+/// when the window closes finish; later ones wait for it to open again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RenderSchedule {
+    pub enabled: bool,
+    /// `HH:MM`, 24-hour.
+    pub start: String,
+    /// `HH:MM`, 24-hour. Earlier than `start` for a window past midnight;
+    /// equal to it for the whole day.
+    pub end: String,
+    /// Days the window opens on, 0 for Sunday to 6 for Saturday. A window
+    /// past midnight runs into the next day.
+    pub days: Vec<u8>,
+}
+
+impl Default for RenderSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: "22:00".into(),
+            end: "07:00".into(),
+            days: (0..7).collect(),
+        }
+    }
+}
+
+/// Minutes after midnight for `HH:MM`.
+fn parse_time(value: &str) -> Option<u16> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let (hours, minutes): (u16, u16) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl RenderSchedule {
+    fn validate(&self) -> Result<(), ServerError> {
+        for time in [&self.start, &self.end] {
+            if parse_time(time).is_none() {
+                return Err(ServerError::Queue(format!("invalid time {:?}, expected HH:MM", time)));
+            }
+        }
+        if let Some(day) = self.days.iter().find(|d| **d > 6) {
+            return Err(ServerError::Queue(format!("invalid day {}, expected 0 to 6", day)));
+        }
+        Ok(())
+    }
+
+    /// Whether the window is open at `now`. Only the wall clock matters, so
+    /// an hour repeated or skipped by a DST change is treated like any
+    /// other: nothing is started twice, and no window is missed outright.
+    fn is_open(&self, now: LocalTime) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        let (Some(start), Some(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return true;
+        };
+        let opens_on = |day: u8| self.days.contains(&day);
+        let yesterday = (now.weekday + 6) % 7;
+        match start.cmp(&end) {
+            std::cmp::Ordering::Equal => opens_on(now.weekday),
+            std::cmp::Ordering::Less => opens_on(now.weekday) && (start..end).contains(&now.minute),
+            std::cmp::Ordering::Greater => {
+                (opens_on(now.weekday) && now.minute >= start)
+                    || (opens_on(yesterday) && now.minute < end)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LocalTime {
+    /// 0 for Sunday.
+    weekday: u8,
+    /// Minutes after midnight.
+    minute: u16,
+}
+
+#[cfg(unix)]
+fn local_now() -> Option<LocalTime> {
+    // SAFETY: `localtime_r` only writes to the `tm` passed in.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return None;
+        }
+        Some(LocalTime {
+            weekday: tm.tm_wday as u8,
+            minute: (tm.tm_hour * 60 + tm.tm_min) as u16,
+        })
+    }
+}
+
+#[cfg(windows)]
+fn local_now() -> Option<LocalTime> {
+    // SAFETY: takes no arguments and returns the time by value.
+    let now = unsafe { windows::Win32::System::SystemInformation::GetLocalTime() };
+    Some(LocalTime {
+        weekday: now.wDayOfWeek as u8,
+        minute: now.wHour * 60 + now.wMinute,
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn local_now() -> Option<LocalTime> {
+    None
+}
+
+/// How a sidecar job ended.
+#[derive(Debug, Clone, Default)]
+struct Outcome {
+    /// `None` for success.
+    error: Option<String>,
+    usage: Option<ActualUsage>,
+}
+
+pub struct QueueState {
+    path: Option<PathBuf>,
+    queue: Mutex<RenderQueue>,
+    /// Outcomes for sidecar job ids nobody has claimed yet.
+    early: Mutex<HashMap<String, Outcome>>,
+    wake: Notify,
+    next_id: AtomicU32,
+    /// Set while the render schedule's window is closed.
+    outside_schedule: AtomicBool,
+}
+
+/// Puts jobs that were running back at the front of the queue.
+fn requeue_running(queue: &mut RenderQueue) -> usize {
+    let (mut running, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut queue.jobs)
+        .into_iter()
+        .partition(|j| j.state == QueuedJobState::Running);
+    for job in &mut running {
+        job.state = QueuedJobState::Pending;
+        job.retried = true;
+        job.server_jobs.clear();
+        job.server_job_count = 0;
+        job.progress = None;
+        job.actual = None;
+        job.actual_vs_estimate = None;
+    }
+    let count = running.len();
+    queue.jobs = running;
+    queue.jobs.extend(rest);
+    count
+}
+
+/// The queue saved at `path`, empty if there is none or it can't be read.
+fn read_queue(path: &Path) -> RenderQueue {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return RenderQueue::default();
+    };
+    serde_json::from_str(&contents).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring invalid render queue file");
+        RenderQueue::default()
+    })
+}
+
+fn write_queue(path: &Path, queue: &RenderQueue) -> Result<(), ServerError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(queue).map_err(|e| ServerError::Io(e.to_string()))?;
+    // Written aside and renamed, so a crash can't leave half a queue.
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+impl QueueState {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join("render_queue.json"));
+        let mut queue = path.as_deref().map(read_queue).unwrap_or_default();
+        let retried = requeue_running(&mut queue);
+        if retried > 0 {
+            info!(count = retried, "Re-queued interrupted render jobs");
+        }
+        Self {
+            path,
+            queue: Mutex::new(queue),
+            early: Mutex::new(HashMap::new()),
+            wake: Notify::new(),
+            next_id: AtomicU32::new(0),
+            outside_schedule: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get(&self) -> RenderQueue {
+        self.queue.lock().unwrap().clone()
+    }
+
+    /// Applies `f` to the queue, writes it to disk and returns `f`'s result.
+    fn update<T>(
+        &self,
+        f: impl FnOnce(&mut RenderQueue) -> Result<T, ServerError>,
+    ) -> Result<T, ServerError> {
+        let mut queue = self.queue.lock().unwrap();
+        let mut updated = queue.clone();
+        let result = f(&mut updated)?;
+
+        let finished = updated.jobs.iter().filter(|j| j.finished()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED);
+        updated.jobs.retain(|j| {
+            let drop = excess > 0 && j.finished();
+            excess -= drop as usize;
+            !drop
+        });
+
+        if let Some(path) = &self.path {
+            write_queue(path, &updated)?;
+        }
+        *queue = updated;
+        Ok(result)
+    }
+}
+
+/// Runs `f` on the queue, then tells the frontend and the worker.
+fn change<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut RenderQueue) -> Result<T, ServerError>,
+) -> Result<T, ServerError> {
+    let state = app.state::<QueueState>();
+    let result = state.update(f)?;
+    let _ = app.emit("render-queue-changed", state.get());
+    state.wake.notify_one();
+    Ok(result)
+}
+
+fn job_mut<'a>(queue: &'a mut RenderQueue, id: &str) -> Result<&'a mut QueuedJob, ServerError> {
+    queue
+        .jobs
+        .iter_mut()
+        .find(|j| j.id == id)
+        .ok_or_else(|| ServerError::Queue(format!("no queued job {}", id)))
+}
+
+/// Records `server_job` finishing. Returns `false` if no running job owns it.
+fn finish_server_job(queue: &mut RenderQueue, server_job: &str, outcome: Outcome) -> bool {
+    let Some(job) = queue.jobs.iter_mut().find(|j| {
+        j.state == QueuedJobState::Running && j.server_jobs.iter().any(|s| s == server_job)
+    }) else {
+        return false;
+    };
+    job.server_jobs.retain(|s| s != server_job);
+    if let Some(usage) = &outcome.usage {
+        job.actual.get_or_insert_default().add(usage);
+    }
+    if let Some(error) = outcome.error {
+        job.state = QueuedJobState::Failed;
+        job.error = Some(error);
+    } else if job.server_jobs.is_empty() {
+        job.state = QueuedJobState::Done;
+        job.progress = Some(100.0);
+        job.actual_vs_estimate = job
+            .actual
+            .as_ref()
+            .zip(job.estimate.as_ref())
+            .and_then(|(actual, estimate)| actual.vs_estimate(estimate));
+    }
+    true
+}
+
+/// Submits one pending job and records the sidecar's job ids for it.
+async fn submit(app: &AppHandle, id: String, mut spec: serde_json::Value) {
+    license::apply_to_render(app, &mut spec);
+    let result = api::submit_render(&app.state::<ServerState>(), &spec).await;
+    let state = app.state::<QueueState>();
+    let updated = change(app, |queue| {
+        let job = job_mut(queue, &id)?;
+        // Cancelled while the request was in flight.
+        if job.state != QueuedJobState::Running {
+            return Ok(result.map(|s| s.jobs).unwrap_or_default());
+        }
+        match result {
+            Ok(submission) if submission.jobs.is_empty() => {
+                job.state = QueuedJobState::Failed;
+                job.error = Some("server returned no jobs".into());
+            }
+            Ok(submission) => {
+                job.server_job_count = submission.jobs.len();
+                job.server_jobs = submission.jobs;
+                for server_job in &job.server_jobs {
+                    job_poll::watch(app, server_job.clone());
+                }
+                let mut early = state.early.lock().unwrap();
+                let outcomes: Vec<_> = job
+                    .server_jobs
+                    .iter()
+                    .filter_map(|s| Some((s.clone(), early.remove(s)?)))
+                    .collect();
+                drop(early);
+                for (server_job, outcome) in outcomes {
+                    finish_server_job(queue, &server_job, outcome);
+                }
+            }
+            // The server went away or is frozen for an update; try again
+            // once it's back.
+            Err(ServerError::Unreachable(_) | ServerError::Timeout | ServerError::Frozen) => {
+                job.state = QueuedJobState::Pending;
+            }
+            Err(e) => {
+                job.state = QueuedJobState::Failed;
+                job.error = Some(e.to_string());
+            }
+        }
+        Ok(Vec::new())
+    });
+    match updated {
+        // Cancel whatever the server started for a job cancelled meanwhile.
+        Ok(orphaned) => {
+            for server_job in orphaned {
+                let _ = api::cancel_job(&app.state::<ServerState>(), &server_job).await;
+            }
+        }
+        Err(e) => warn!(error = %e, "Failed to update render queue"),
+    }
+}
+
+/// Starts pending jobs, in queue order, while their weights fit within the
+/// governor's limit. A job heavier than the whole limit still starts once
+/// nothing else is running, so it can't be held up forever. Starts nothing
+/// while the server is frozen for an update.
+fn fill_slots(app: &AppHandle) {
+    if app.state::<ServerState>().is_frozen() {
+        return;
+    }
+    let limit = render_governor::limit(app);
+    // Not `change`: that would wake the worker, which calls this again.
+    let state = app.state::<QueueState>();
+    let outside_schedule = state.outside_schedule.load(Ordering::SeqCst);
+    let started = state.update(|queue| {
+        if queue.paused {
+            return Ok(Vec::new());
+        }
+        let mut used: usize = queue
+            .jobs
+            .iter()
+            .filter(|j| j.state == QueuedJobState::Running)
+            .map(|j| render_governor::job_weight(&j.spec))
+            .sum();
+        let mut started = Vec::new();
+        for job in queue.jobs.iter_mut() {
+            if job.state != QueuedJobState::Pending || (outside_schedule && !job.run_now) {
+                continue;
+            }
+            let weight = render_governor::job_weight(&job.spec);
+            // Later jobs wait too, rather than overtaking a heavy one.
+            if used > 0 && used + weight > limit {
+                break;
+            }
+            used += weight;
+            job.state = QueuedJobState::Running;
+            job.error = None;
+            started.push((job.id.clone(), job.spec.clone()));
+        }
+        Ok(started)
+    });
+    match started {
+        Ok(started) => {
+            if !started.is_empty() {
+                let _ = app.emit("render-queue-changed", state.get());
+            }
+            for (id, spec) in started {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move { submit(&app, id, spec).await });
+            }
+        }
+        Err(e) => warn!(error = %e, "Failed to update render queue"),
+    }
+}
+
+/// Has the worker look for free slots again.
+pub fn wake(app: &AppHandle) {
+    app.state::<QueueState>().wake.notify_one();
+}
+
+/// Runs the queue worker: submits jobs while the server is ready, and puts
+/// running jobs back in the queue if it goes down.
+pub fn init(app: &AppHandle) {
+    apply_schedule(app);
+    spawn_scheduler(app);
+    let handle = app.clone();
+    crash::spawn("render-queue", async move {
+        let state = handle.state::<QueueState>();
+        let mut status = handle.state::<ServerState>().subscribe();
+        loop {
+            let current = *status.borrow_and_update();
+            match current {
+                ServerStatus::Ready => fill_slots(&handle),
+                ServerStatus::Stopped | ServerStatus::Failed | ServerStatus::Crashed => {
+                    match state.update(|queue| Ok(requeue_running(queue))) {
+                        Ok(0) => {}
+                        Ok(n) => {
+                            info!(count = n, "Re-queued render jobs after server stop");
+                            let _ = handle.emit("render-queue-changed", state.get());
+                        }
+                        Err(e) => warn!(error = %e, "Failed to update render queue"),
+                    }
+                }
+                _ => {}
+            }
+            tokio::select! {
+                changed = status.changed() => if changed.is_err() { return },
+                _ = state.wake.notified() => {}
+            }
+        }
+    });
+}
+
+/// Opens or closes the queue to match the render schedule, telling the
+/// frontend when that changes.
+fn apply_schedule(app: &AppHandle) {
+    let schedule = app.state::<SettingsState>().get().render_schedule;
+    let open = local_now().is_none_or(|now| schedule.is_open(now));
+    let state = app.state::<QueueState>();
+    if state.outside_schedule.swap(!open, Ordering::SeqCst) == !open {
+        return;
+    }
+    if open {
+        info!("Render schedule window opened");
+        let _ = app.emit("queue-schedule-resumed", ());
+        state.wake.notify_one();
+    } else {
+        info!("Render schedule window closed");
+        let _ = app.emit("queue-schedule-paused", ());
+    }
+}
+
+/// Re-evaluates the render schedule whenever the local minute changes.
+fn spawn_scheduler(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("render-schedule", async move {
+        let mut evaluated = None;
+        loop {
+            let now = local_now();
+            if now != evaluated {
+                apply_schedule(&handle);
+                evaluated = now;
+            }
+            tokio::time::sleep(SCHEDULE_POLL).await;
+        }
+    });
+}
+
+/// Called by the event bridge to advance running jobs.
+pub fn on_server_event(app: &AppHandle, event: &str, payload: &serde_json::Value) {
+    let Some(server_job) = payload
+        .get("jobId")
+        .or_else(|| payload.get("id"))
+        .and_then(|v| v.as_str())
+    else {
+        return;
+    };
+    let error = match event {
+        "generation-progress" | "render-progress" => {
+            let Some(progress) = payload.get("progress").and_then(|v| v.as_f64()) else {
+                return;
+            };
+            // Progress isn't worth a disk write; it restarts on recovery anyway.
+            let state = app.state::<QueueState>();
+            let mut queue = state.queue.lock().unwrap();
+            if let Some(job) = queue.jobs.iter_mut().find(|j| {
+                j.state == QueuedJobState::Running && j.server_jobs.iter().any(|s| s == server_job)
+            }) {
+                // Each sidecar job is an equal share of the whole.
+                let total = job.server_job_count.max(1) as f64;
+                let done = (job.server_job_count - job.server_jobs.len()) as f64;
+                job.progress = Some((done * 100.0 + progress.clamp(0.0, 100.0)) / total);
+                let _ = app.emit("render-queue-changed", &*queue);
+            }
+            return;
+        }
+        "generation-complete" | "job-complete" | "export-finished" => None,
+        "generation-error" => Some(
+            payload
+                .get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or("render failed")
+                .to_owned(),
+        ),
+        _ => return,
+    };
+
+    let outcome = Outcome {
+        error,
+        usage: payload
+            .get("usage")
+            .and_then(|u| serde_json::from_value(u.clone()).ok()),
+    };
+    let state = app.state::<QueueState>();
+    match owner(&state.queue.lock().unwrap(), server_job) {
+        Owner::Job => {
+            if let Err(e) = change(app, |queue| {
+                Ok(finish_server_job(queue, server_job, outcome))
+            }) {
+                warn!(error = %e, "Failed to update render queue");
+            }
+        }
+        // Kept for the submission to claim once it returns.
+        Owner::Submitting => {
+            let mut early = state.early.lock().unwrap();
+            if early.len() >= MAX_EARLY_OUTCOMES {
+                early.clear();
+            }
+            early.insert(server_job.to_owned(), outcome);
+        }
+        // Someone else's generation; nothing to write.
+        Owner::Nobody => {}
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Owner {
+    /// A running job lists the sidecar job.
+    Job,
+    /// None does, but a submission is in flight and may be about to.
+    Submitting,
+    Nobody,
+}
+
+fn owner(queue: &RenderQueue, server_job: &str) -> Owner {
+    let running = || queue.jobs.iter().filter(|j| j.state == QueuedJobState::Running);
+    if running().any(|j| j.server_jobs.iter().any(|s| s == server_job)) {
+        Owner::Job
+    } else if running().any(|j| j.server_job_count == 0) {
+        Owner::Submitting
+    } else {
+        Owner::Nobody
+    }
+}
+
+/// `run_now` starts the job even outside the render schedule's window.
+#[tauri::command]
+pub fn enqueue_render(
+    app: AppHandle,
+    job_spec: serde_json::Value,
+    run_now: Option<bool>,
+) -> Result<QueuedJob, ServerError> {
+    let state = app.state::<QueueState>();
+    let job = QueuedJob {
+        id: format!(
+            "{}-{}",
+            now_ms(),
+            state.next_id.fetch_add(1, Ordering::SeqCst)
+        ),
+        spec: job_spec.clone(),
+        state: QueuedJobState::Pending,
+        retried: false,
+        server_jobs: Vec::new(),
+        server_job_count: 0,
+        progress: None,
+        error: None,
+        enqueued_at: now_ms(),
+        run_now: run_now.unwrap_or(false),
+        estimate: cost_estimate::for_job(&app, &job_spec),
+        actual: None,
+        actual_vs_estimate: None,
+    };
+    change(&app, |queue| {
+        queue.jobs.push(job.clone());
+        Ok(())
+    })?;
+    Ok(job)
+}
+
+/// Reorders pending jobs to follow `ids`. Pending jobs not listed keep
+/// their relative order after the listed ones; other jobs stay in place.
+#[tauri::command]
+pub fn reorder_queue(app: AppHandle, ids: Vec<String>) -> Result<RenderQueue, ServerError> {
+    change(&app, |queue| reorder(queue, &ids))?;
+    Ok(app.state::<QueueState>().get())
+}
+
+fn reorder(queue: &mut RenderQueue, ids: &[String]) -> Result<(), ServerError> {
+    let slots: Vec<usize> = queue
+        .jobs
+        .iter()
+        .enumerate()
+        .filter(|(_, j)| j.state == QueuedJobState::Pending)
+        .map(|(i, _)| i)
+        .collect();
+    let mut pending: Vec<QueuedJob> = slots.iter().map(|&i| queue.jobs[i].clone()).collect();
+    for id in ids {
+        if !pending.iter().any(|j| &j.id == id) {
+            return Err(ServerError::Queue(format!("no pending job {}", id)));
+        }
+    }
+    pending.sort_by_key(|j| ids.iter().position(|id| id == &j.id).unwrap_or(ids.len()));
+    for (slot, job) in slots.into_iter().zip(pending) {
+        queue.jobs[slot] = job;
+    }
+    Ok(())
+}
+
+/// Cancels a pending job, or a running one along with its sidecar jobs.
+#[tauri::command]
+pub async fn cancel_job(app: AppHandle, id: String) -> Result<(), ServerError> {
+    let server_jobs = change(&app, |queue| {
+        let job = job_mut(queue, &id)?;
+        if job.finished() {
+            return Err(ServerError::Queue(format!(
+                "job {} has already finished",
+                id
+            )));
+        }
+        job.state = QueuedJobState::Cancelled;
+        Ok(std::mem::take(&mut job.server_jobs))
+    })?;
+    let state = app.state::<ServerState>();
+    for server_job in &server_jobs {
+        job_poll::forget(&app, server_job);
+        if let Err(e) = api::cancel_job(&state, server_job).await {
+            warn!(%server_job, error = %e, "Failed to cancel server job");
+        }
+    }
+    taskbar::forget_jobs(&app, &server_jobs);
+    Ok(())
+}
+
+/// Stops starting new jobs; running ones finish.
+#[tauri::command]
+pub fn pause_queue(app: AppHandle) -> Result<(), ServerError> {
+    change(&app, |queue| {
+        queue.paused = true;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn resume_queue(app: AppHandle) -> Result<(), ServerError> {
+    change(&app, |queue| {
+        queue.paused = false;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn get_queue(state: tauri::State<'_, QueueState>) -> RenderQueue {
+    state.get()
+}
+
+/// Marks a pending job to start regardless of the render schedule, or to
+/// wait for its window again.
+#[tauri::command]
+pub fn set_job_run_now(app: AppHandle, id: String, run_now: bool) -> Result<(), ServerError> {
+    change(&app, |queue| {
+        job_mut(queue, &id)?.run_now = run_now;
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn get_render_schedule(state: tauri::State<'_, SettingsState>) -> RenderSchedule {
+    state.get().render_schedule
+}
+
+/// Saves the schedule and applies it right away.
+#[tauri::command]
+pub fn set_render_schedule(app: AppHandle, schedule: RenderSchedule) -> Result<(), ServerError> {
+    schedule.validate()?;
+    app.state::<SettingsState>()
+        .update(|s| s.render_schedule = schedule)?;
+    apply_schedule(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(weekday: u8, time: &str) -> LocalTime {
+        LocalTime {
+            weekday,
+            minute: parse_time(time).unwrap(),
+        }
+    }
+
+    fn job(id: &str, state: QueuedJobState) -> QueuedJob {
+        QueuedJob {
+            id: id.into(),
+            spec: serde_json::json!({}),
+            state,
+            retried: false,
+            server_jobs: Vec::new(),
+            server_job_count: 0,
+            progress: None,
+            error: None,
+            enqueued_at: 0,
+            run_now: false,
+            estimate: None,
+            actual: None,
+            actual_vs_estimate: None,
+        }
+    }
+
+    fn ids(queue: &RenderQueue) -> Vec<&str> {
+        queue.jobs.iter().map(|j| j.id.as_str()).collect()
+    }
+
+    #[test]
+    fn running_jobs_go_back_to_the_front() {
+        let mut running = job("b", QueuedJobState::Running);
+        running.server_jobs = vec!["s1".into()];
+        running.server_job_count = 1;
+        running.progress = Some(40.0);
+        let mut queue = RenderQueue {
+            paused: false,
+            jobs: vec![job("a", QueuedJobState::Done), running, job("c", QueuedJobState::Pending)],
+        };
+        assert_eq!(requeue_running(&mut queue), 1);
+        assert_eq!(ids(&queue), ["b", "a", "c"]);
+        let b = &queue.jobs[0];
+        assert_eq!(b.state, QueuedJobState::Pending);
+        assert!(b.retried);
+        assert!(b.server_jobs.is_empty() && b.server_job_count == 0 && b.progress.is_none());
+        assert!(!queue.jobs[2].retried);
+    }
+
+    #[test]
+    fn reorder_moves_only_pending_jobs() {
+        let mut queue = RenderQueue {
+            paused: false,
+            jobs: vec![
+                job("a", QueuedJobState::Pending),
+                job("r", QueuedJobState::Running),
+                job("b", QueuedJobState::Pending),
+                job("c", QueuedJobState::Pending),
+            ],
+        };
+        // Unlisted pending jobs follow the listed ones in their old order.
+        reorder(&mut queue, &["c".into()]).unwrap();
+        assert_eq!(ids(&queue), ["c", "r", "a", "b"]);
+        for bad in ["r", "nope"] {
+            let before = ids(&queue).join(",");
+            assert!(matches!(reorder(&mut queue, &[bad.into()]), Err(ServerError::Queue(_))));
+            assert_eq!(ids(&queue).join(","), before);
+        }
+    }
+
+    #[test]
+    fn events_for_other_jobs_are_ignored() {
+        let mut submitted = job("a", QueuedJobState::Running);
+        submitted.server_jobs = vec!["s1".into()];
+        submitted.server_job_count = 1;
+        let mut queue = RenderQueue {
+            paused: false,
+            jobs: vec![submitted],
+        };
+        assert_eq!(owner(&queue, "s1"), Owner::Job);
+        assert_eq!(owner(&queue, "gen-9"), Owner::Nobody);
+        queue.jobs.push(job("b", QueuedJobState::Running));
+        assert_eq!(owner(&queue, "gen-9"), Owner::Submitting);
+    }
+
+    #[test]
+    fn queue_file_round_trips() {
+        let dir = std::env::temp_dir().join(format!("aiyou-queue-{}", std::process::id()));
+        let path = dir.join("render_queue.json");
+        assert!(read_queue(&path).jobs.is_empty());
+
+        let mut queue = RenderQueue {
+            paused: true,
+            jobs: vec![job("a", QueuedJobState::Pending), job("b", QueuedJobState::Failed)],
+        };
+        queue.jobs[1].error = Some("ffmpeg failed".into());
+        write_queue(&path, &queue).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        let read = read_queue(&path);
+        assert!(read.paused);
+        assert_eq!(ids(&read), ["a", "b"]);
+        assert_eq!(read.jobs[1].error.as_deref(), Some("ffmpeg failed"));
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(read_queue(&path).jobs.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn overnight_window_runs_into_the_next_day() {
+        // Friday and Saturday nights, 22:00 to 07:00.
+        let schedule = RenderSchedule {
+            enabled: true,
+            days: vec![5, 6],
+            ..RenderSchedule::default()
+        };
+        assert!(!schedule.is_open(at(5, "21:59")));
+        assert!(schedule.is_open(at(5, "22:00")));
+        assert!(schedule.is_open(at(6, "06:59")));
+        assert!(!schedule.is_open(at(6, "07:00")));
+        assert!(schedule.is_open(at(0, "03:00")));
+        // Sunday night isn't scheduled.
+        assert!(!schedule.is_open(at(0, "23:00")));
+        assert!(!schedule.is_open(at(1, "03:00")));
+
+        let daytime = RenderSchedule {
+            enabled: true,
+            start: "09:00".into(),
+            end: "17:30".into(),
+            days: vec![1],
+        };
+        assert!(daytime.is_open(at(1, "17:29")));
+        assert!(!daytime.is_open(at(1, "17:30")));
+        assert!(!daytime.is_open(at(2, "10:00")));
+    }
+}
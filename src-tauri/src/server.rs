@@ -0,0 +1,2256 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use regex::Regex;
+use tauri::{AppHandle, Emitter, Manager, Url};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+use tokio::sync::watch;
+use tracing::{debug, error, info, info_span, trace, warn, Instrument};
+
+use crate::api;
+use crate::blocking;
+use crate::config::{self, ServerConfig, ServerMode};
+use crate::crash;
+use crate::error::ServerError;
+use crate::generation;
+use crate::i18n;
+use crate::migrations;
+use crate::monitor;
+use crate::project_lock;
+use crate::redact::{self, Redactor};
+use crate::render_queue;
+use crate::server_log;
+use crate::time::now_ms;
+use crate::video_protocol;
+use crate::ws;
+
+/// Delay between readiness checks while the server starts.
+const READY_INTERVAL: Duration = Duration::from_millis(500);
+/// Health results younger than this are served from cache.
+const HEALTH_CACHE_TTL: Duration = Duration::from_secs(1);
+/// Limit for a single request identifying a server found on the port.
+/// Health requests use the configured timeouts instead.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+/// Heartbeat results kept for `get_health_history`.
+const HEALTH_HISTORY_LEN: usize = 100;
+/// How long the server gets to answer health again after a config reload.
+const RELOAD_HEALTH_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause before spawning again after a failed startup attempt.
+const STARTUP_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerStatus {
+    Stopped,
+    Starting,
+    Ready,
+    /// Spawned, but exited or never became healthy within the startup
+    /// window.
+    Failed,
+    Stopping,
+    /// Exited without being asked to.
+    Crashed,
+    /// Was ready, but has stopped answering health checks.
+    Unreachable,
+    /// Answering, but still warming up: health is a 503 reporting loading
+    /// progress.
+    Loading,
+    /// Was ready, but health now answers with an error status.
+    Unhealthy,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupProgress {
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub message: String,
+}
+
+/// Sent with `server-start-failed` once every startup attempt has failed,
+/// typically because of a bad config.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StartFailed {
+    /// How the last attempt's sidecar exited; both `None` if it was still
+    /// running but never became ready.
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub attempts: u32,
+}
+
+/// Why the status changed, for `server-state-changed`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatusDetail {
+    /// The process the change is about, once it is no longer the current
+    /// one.
+    pub pid: Option<u32>,
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub reason: Option<String>,
+}
+
+impl StatusDetail {
+    pub(crate) fn because(reason: impl Into<String>) -> Self {
+        Self {
+            reason: Some(reason.into()),
+            ..Self::default()
+        }
+    }
+
+    fn exited(pid: u32, code: Option<i32>, signal: Option<i32>) -> Self {
+        Self {
+            pid: Some(pid),
+            code,
+            signal,
+            reason: None,
+        }
+    }
+}
+
+/// Sent with `server-state-changed` on every status transition, so the
+/// frontend can follow the server from this one event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStateChanged {
+    /// Goes up by one per transition; an event with a lower one than the
+    /// last seen arrived late and is stale.
+    pub seq: u64,
+    pub from: ServerStatus,
+    pub to: ServerStatus,
+    pub pid: Option<u32>,
+    pub port: u16,
+    /// Exit code and signal, when the change is the sidecar exiting.
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub reason: Option<String>,
+    /// Unix time in milliseconds.
+    pub at: u64,
+}
+
+/// What a warming-up server says on `/api/health`, sent with
+/// `server-loading-progress`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadingProgress {
+    /// Between 0 and 1, if the server says.
+    pub progress: Option<f64>,
+    /// What it is doing, e.g. `loading voice models`.
+    pub stage: Option<String>,
+}
+
+/// The body of a 503 health response.
+#[derive(Deserialize)]
+struct HealthBody {
+    status: Option<String>,
+    progress: Option<f64>,
+    stage: Option<String>,
+}
+
+/// One health response: its status, and the loading progress if it was a
+/// 503 saying so.
+struct Probe {
+    code: u16,
+    loading: Option<LoadingProgress>,
+}
+
+/// What the readiness loop reports as it goes.
+enum Readiness<'a> {
+    /// About to check; `max_attempts` moves out while loading progresses.
+    Attempt { attempt: u32, max_attempts: u32 },
+    /// The server reported loading progress different from before.
+    Loading(&'a LoadingProgress),
+}
+
+type HealthResult = Result<bool, ServerError>;
+
+/// One heartbeat, for drawing uptime and spotting latency creep.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthSample {
+    /// Unix time in milliseconds.
+    pub at: u64,
+    pub reachable: bool,
+    /// `None` when the server couldn't be reached.
+    pub response_ms: Option<u64>,
+    pub status_code: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RestartReason {
+    Crash,
+    /// The server stopped answering health checks.
+    Watchdog,
+    Manual,
+    ConfigChange,
+    /// A project backup was restored over the database.
+    Restore,
+    /// An update failed to install and the old server was brought back.
+    Update,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatusInfo {
+    pub status: ServerStatus,
+    /// Running a server left over from an earlier session.
+    pub adopted: bool,
+    pub restart_count: u32,
+    pub last_restart_reason: Option<RestartReason>,
+    /// Set while the status is `Loading`.
+    pub loading: Option<LoadingProgress>,
+    /// Between `freeze_server` and `unfreeze_server`.
+    pub frozen: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerUrlChanged {
+    url: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PortDetected {
+    port: u16,
+    url: String,
+}
+
+/// How `start` got a server, sent with `server-start-mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StartMode {
+    Spawned,
+    /// Our server from an earlier session was still listening and is used
+    /// as is.
+    Adopted,
+    /// Something else holds the configured port; spawned on a free one.
+    Relocated,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerStartMode {
+    mode: StartMode,
+    port: u16,
+}
+
+/// What `/api/version` says about the server answering on a port.
+#[derive(Deserialize)]
+struct VersionInfo {
+    name: Option<String>,
+    pid: Option<u32>,
+}
+
+/// A server left over from an earlier session that we took over.
+#[derive(Clone, Copy)]
+struct Adopted {
+    /// Reported by the server; without it we can't stop it.
+    pid: Option<u32>,
+}
+
+pub struct ServerState {
+    child: Mutex<Option<CommandChild>>,
+    config: Mutex<ServerConfig>,
+    /// Port the current (or last) server process was started on. Differs
+    /// from the configured port when that is 0 (pick a free one).
+    port: Mutex<u16>,
+    status: watch::Sender<ServerStatus>,
+    /// Bumped for every readiness loop started; a loop ends when it moves,
+    /// so only the newest one runs.
+    readiness: watch::Sender<u64>,
+    /// Rebuilt when the TLS settings in the config change.
+    client: Mutex<reqwest::Client>,
+    /// When a health check last succeeded.
+    last_healthy: Mutex<Option<SystemTime>>,
+    /// Restarts requested this session, and why the last one was.
+    restart_count: AtomicU32,
+    /// Whether the current server has reported ready since it started;
+    /// picks the steady health timeout over the startup one.
+    reached_ready: AtomicBool,
+    last_restart_reason: Mutex<Option<RestartReason>>,
+    /// Crash or Watchdog while the server is down because of one; what a
+    /// manual restart is put down to.
+    failure: Mutex<Option<RestartReason>>,
+    /// Last health result and the port it was checked against. Held across
+    /// the request so concurrent callers share a single in-flight check.
+    health: tokio::sync::Mutex<Option<(u16, Instant, HealthResult)>>,
+    /// Set while in external mode; nothing is spawned then.
+    external_url: Mutex<Option<Url>>,
+    /// From `AIYOU_SERVER_MODE` or the command line; wins over the config.
+    mode_override: Mutex<Option<ServerMode>>,
+    adopted: Mutex<Option<Adopted>>,
+    /// Recent heartbeats, oldest first.
+    health_history: Mutex<VecDeque<HealthSample>>,
+    /// Loading progress from the last health check, if it reported any.
+    loading: Mutex<Option<LoadingProgress>>,
+    /// Set from a restart's stop until its start, when the status alone
+    /// reads as stopped.
+    restarting: AtomicBool,
+    /// Which spawn of the current startup is running, from 1 up to
+    /// `startup_max_attempts`.
+    startup_attempt: AtomicU32,
+    /// Last `seq` sent with `server-state-changed`.
+    status_seq: AtomicU64,
+    /// Set by `freeze_server` while the sidecar binary is being replaced.
+    frozen: AtomicBool,
+}
+
+impl Default for ServerState {
+    fn default() -> Self {
+        Self {
+            child: Mutex::new(None),
+            config: Mutex::new(ServerConfig::default()),
+            port: Mutex::new(ServerConfig::default().port),
+            last_healthy: Mutex::new(None),
+            restart_count: AtomicU32::new(0),
+            reached_ready: AtomicBool::new(false),
+            last_restart_reason: Mutex::new(None),
+            failure: Mutex::new(None),
+            status: watch::Sender::new(ServerStatus::Stopped),
+            readiness: watch::Sender::new(0),
+            client: Mutex::new(build_client(&ServerConfig::default())),
+            health: tokio::sync::Mutex::new(None),
+            external_url: Mutex::new(None),
+            mode_override: Mutex::new(None),
+            adopted: Mutex::new(None),
+            health_history: Mutex::new(VecDeque::with_capacity(HEALTH_HISTORY_LEN)),
+            loading: Mutex::new(None),
+            restarting: AtomicBool::new(false),
+            startup_attempt: AtomicU32::new(1),
+            status_seq: AtomicU64::new(0),
+            frozen: AtomicBool::new(false),
+        }
+    }
+}
+
+impl ServerState {
+    /// Shared HTTP client for talking to the server. Cheap to clone.
+    pub fn client(&self) -> reqwest::Client {
+        self.client.lock().unwrap().clone()
+    }
+
+    pub fn config(&self) -> ServerConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: ServerConfig) {
+        let mut current = self.config.lock().unwrap();
+        if current.danger_accept_invalid_certs != config.danger_accept_invalid_certs {
+            *self.client.lock().unwrap() = build_client(&config);
+        }
+        *current = config;
+    }
+
+    pub fn set_mode_override(&self, mode: ServerMode) {
+        *self.mode_override.lock().unwrap() = Some(mode);
+    }
+
+    /// The mode the next start uses: the override if there is one,
+    /// otherwise the config's.
+    pub fn mode(&self) -> ServerMode {
+        self.mode_override
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| self.config.lock().unwrap().server_mode.clone())
+    }
+
+    pub fn mode_overridden(&self) -> bool {
+        self.mode_override.lock().unwrap().is_some()
+    }
+
+    /// Points every request at the external server in external mode, or
+    /// back at the sidecar otherwise.
+    fn apply_mode(&self, mode: &ServerMode) {
+        let url = mode.external_url();
+        if let Some(port) = url.as_ref().and_then(|u| u.port_or_known_default()) {
+            self.record_port(port);
+        }
+        *self.external_url.lock().unwrap() = url;
+    }
+
+    pub fn external_url(&self) -> Option<Url> {
+        self.external_url.lock().unwrap().clone()
+    }
+
+    /// Whether a sidecar process is running, spawned or adopted. Always
+    /// false with an external server.
+    pub fn is_running(&self) -> bool {
+        self.child.lock().unwrap().is_some() || self.is_adopted()
+    }
+
+    /// Whether the server is one left over from an earlier session.
+    pub fn is_adopted(&self) -> bool {
+        self.adopted.lock().unwrap().is_some()
+    }
+
+    /// OS process id of the running sidecar, if known.
+    pub fn pid(&self) -> Option<u32> {
+        if let Some(child) = self.child.lock().unwrap().as_ref() {
+            return Some(child.pid());
+        }
+        self.adopted.lock().unwrap().and_then(|a| a.pid)
+    }
+
+    pub fn port(&self) -> u16 {
+        *self.port.lock().unwrap()
+    }
+
+    /// Records the port a server was started on. Returns whether it
+    /// changed, i.e. whether URLs handed out earlier are now stale.
+    pub fn record_port(&self, port: u16) -> bool {
+        let mut current = self.port.lock().unwrap();
+        let changed = *current != port;
+        *current = port;
+        changed
+    }
+
+    /// Absolute URL for a server path such as `/api/health`.
+    pub fn url(&self, path: &str) -> String {
+        if let Some(base) = self.external_url.lock().unwrap().as_ref() {
+            return format!("{}{}", base.as_str().trim_end_matches('/'), path);
+        }
+        let scheme = self.config.lock().unwrap().scheme;
+        format!("{}://localhost:{}{}", scheme.as_str(), self.port(), path)
+    }
+
+    /// Checks server health, returning a cached result if one is fresh
+    /// enough and `force` is not set.
+    pub async fn check_health(&self, force: bool) -> HealthResult {
+        self.check_health_recorded(force, false).await
+    }
+
+    /// A fresh health check that also goes into the health history; what
+    /// the background monitor runs.
+    pub async fn heartbeat(&self) -> HealthResult {
+        self.check_health_recorded(true, true).await
+    }
+
+    async fn check_health_recorded(&self, force: bool, record: bool) -> HealthResult {
+        let mut cache = self.health.lock().await;
+        let port = self.port();
+        if !force {
+            if let Some((checked_port, checked_at, result)) = cache.as_ref() {
+                if *checked_port == port && checked_at.elapsed() < HEALTH_CACHE_TTL {
+                    return result.clone();
+                }
+            }
+        }
+
+        let started = Instant::now();
+        let timeout = self.health_timeout();
+        let probe = probe(&self.client(), &self.url("/api/health"), timeout).await;
+        *self.loading.lock().unwrap() = probe.as_ref().ok().and_then(|p| p.loading.clone());
+        let status = probe.map(|p| p.code);
+        if record {
+            self.record_sample(HealthSample {
+                at: now_ms(),
+                reachable: status.is_ok(),
+                response_ms: status.is_ok().then(|| started.elapsed().as_millis() as u64),
+                status_code: status.as_ref().ok().copied(),
+            });
+        }
+        let result = status.map(|code| (200..300).contains(&code));
+        trace!(port, ?result, "Health check");
+        if let Ok(true) = result {
+            *self.last_healthy.lock().unwrap() = Some(SystemTime::now());
+        }
+        *cache = Some((port, Instant::now(), result.clone()));
+        result
+    }
+
+    /// Round trip of one uncached health request, in milliseconds. Any
+    /// answer counts, even an error status.
+    pub async fn ping(&self) -> Result<u64, ServerError> {
+        let started = Instant::now();
+        probe_status(&self.client(), &self.url("/api/health"), self.health_timeout()).await?;
+        Ok(started.elapsed().as_millis() as u64)
+    }
+
+    /// Loading progress from the last health check, while the server is
+    /// warming up.
+    pub fn loading(&self) -> Option<LoadingProgress> {
+        self.loading.lock().unwrap().clone()
+    }
+
+    fn health_timeout(&self) -> Duration {
+        let config = self.config.lock().unwrap();
+        let ms = if self.reached_ready.load(Ordering::Relaxed) {
+            config.steady_health_timeout_ms
+        } else {
+            config.startup_health_timeout_ms
+        };
+        Duration::from_millis(ms)
+    }
+
+    /// Whether the server is on its way back: being restarted, or started
+    /// and not ready yet.
+    pub fn restarting(&self) -> bool {
+        self.restarting.load(Ordering::SeqCst)
+            || matches!(self.status(), ServerStatus::Starting | ServerStatus::Loading)
+    }
+
+    /// Whether the current server has reported ready since it started.
+    pub fn reached_ready(&self) -> bool {
+        self.reached_ready.load(Ordering::Relaxed)
+    }
+
+    /// Tracks whether the server has been ready since it last started, and
+    /// whether it is down because it crashed or stopped answering.
+    fn note_status(&self, status: ServerStatus) {
+        match status {
+            ServerStatus::Ready => self.reached_ready.store(true, Ordering::Relaxed),
+            ServerStatus::Starting => self.reached_ready.store(false, Ordering::Relaxed),
+            _ => {}
+        }
+        let mut failure = self.failure.lock().unwrap();
+        match status {
+            ServerStatus::Crashed => *failure = Some(RestartReason::Crash),
+            ServerStatus::Unreachable | ServerStatus::Unhealthy => {
+                *failure = Some(RestartReason::Watchdog)
+            }
+            ServerStatus::Ready | ServerStatus::Stopped => *failure = None,
+            _ => {}
+        }
+    }
+
+    /// Counts a restart and returns the reason recorded for it. Crash and
+    /// Watchdog come only from what the monitor saw: a manual restart of a
+    /// server that is down because of one is put down to it, and either
+    /// one asked for while the server is fine is recorded as manual.
+    pub fn note_restart(&self, requested: RestartReason) -> RestartReason {
+        let failure = self.failure.lock().unwrap().take();
+        let reason = match requested {
+            RestartReason::Manual | RestartReason::Crash | RestartReason::Watchdog => {
+                failure.unwrap_or(RestartReason::Manual)
+            }
+            other => other,
+        };
+        self.restart_count.fetch_add(1, Ordering::SeqCst);
+        *self.last_restart_reason.lock().unwrap() = Some(reason);
+        reason
+    }
+
+    fn record_sample(&self, sample: HealthSample) {
+        let mut history = self.health_history.lock().unwrap();
+        if history.len() == HEALTH_HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(sample);
+    }
+
+    pub fn health_history(&self) -> Vec<HealthSample> {
+        self.health_history.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Issues a throwaway request so name resolution and client setup are
+    /// paid before the readiness loop's first real check. The server usually
+    /// isn't listening yet, so the result is ignored. Returns how long it took.
+    async fn prewarm(&self) -> Duration {
+        let started = Instant::now();
+        let _ = self
+            .client()
+            .head(self.url("/api/health"))
+            .timeout(Duration::from_millis(500))
+            .send()
+            .await;
+        started.elapsed()
+    }
+
+    pub fn last_healthy(&self) -> Option<SystemTime> {
+        *self.last_healthy.lock().unwrap()
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::SeqCst)
+    }
+
+    pub fn restart_count(&self) -> u32 {
+        self.restart_count.load(Ordering::SeqCst)
+    }
+
+    pub fn last_restart_reason(&self) -> Option<RestartReason> {
+        *self.last_restart_reason.lock().unwrap()
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        *self.status.borrow()
+    }
+
+    /// Receiver that observes every status transition.
+    pub fn subscribe(&self) -> watch::Receiver<ServerStatus> {
+        self.status.subscribe()
+    }
+
+    /// Resolves once the readiness loop marks the server ready, right away
+    /// if it already is.
+    pub async fn wait_for_ready(&self, timeout: Duration) -> Result<(), ServerError> {
+        let mut rx = self.subscribe();
+        match tokio::time::timeout(timeout, rx.wait_for(|s| *s == ServerStatus::Ready)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(_)) => Err(ServerError::NotRunning),
+            Err(_) => Err(ServerError::Timeout),
+        }
+    }
+
+    /// Checks health every `READY_INTERVAL` until the server answers,
+    /// reporting each attempt and any change in loading progress to
+    /// `on_event`. Gives up after `timeout` without progress: the countdown
+    /// restarts whenever the server reports loading progress it hadn't
+    /// before, so only an unreachable server or one stuck at the same
+    /// progress runs it out. Returns whether it answered, or `None` as soon
+    /// as the status leaves `Starting` or `Loading`, so a stop during
+    /// startup doesn't leave requests going to a server being torn down,
+    /// or once a newer loop has been started.
+    async fn poll_until_ready(
+        &self,
+        timeout: Duration,
+        mut on_event: impl FnMut(Readiness),
+    ) -> Option<bool> {
+        let mut rx = self.subscribe();
+        let mut superseded = self.readiness.subscribe();
+        let checks = async {
+            let warmup = self.prewarm().await;
+            let mut deadline = Instant::now() + timeout;
+            let mut last_loading = None;
+            for attempt in 1u32.. {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let max_attempts =
+                    attempt + (left.as_millis() / READY_INTERVAL.as_millis()) as u32;
+                on_event(Readiness::Attempt {
+                    attempt,
+                    max_attempts,
+                });
+                let checked_at = Instant::now();
+                let health = self.check_health(true).await;
+                if attempt == 1 {
+                    debug!(
+                        ?warmup,
+                        first_check = ?checked_at.elapsed(),
+                        "Health client warmed up"
+                    );
+                }
+                debug!(attempt, max_attempts, ?health, "Readiness check");
+                if let Ok(true) = health {
+                    info!(attempt, "Server ready");
+                    return true;
+                }
+                if let Some(loading) = self.loading().filter(|l| last_loading.as_ref() != Some(l)) {
+                    deadline = Instant::now() + timeout;
+                    on_event(Readiness::Loading(&loading));
+                    last_loading = Some(loading);
+                }
+                if Instant::now() >= deadline {
+                    break;
+                }
+                tokio::time::sleep(READY_INTERVAL).await;
+            }
+            false
+        };
+        tokio::select! {
+            ready = checks => Some(ready),
+            _ = rx.wait_for(|s| !matches!(s, ServerStatus::Starting | ServerStatus::Loading)) => {
+                None
+            }
+            _ = superseded.changed() => None,
+        }
+    }
+}
+
+/// One uncached health request against `url`, or `Err` if the server
+/// couldn't be reached in time. The body is only read for a 503, where it
+/// may report loading progress.
+async fn probe(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Duration,
+) -> Result<Probe, ServerError> {
+    let resp = client.get(url).timeout(timeout).send().await?;
+    let code = resp.status().as_u16();
+    if code != 503 {
+        return Ok(Probe {
+            code,
+            loading: None,
+        });
+    }
+    let loading = resp
+        .json::<HealthBody>()
+        .await
+        .ok()
+        .filter(|body| body.status.as_deref() == Some("loading"))
+        .map(|body| LoadingProgress {
+            progress: body.progress.map(|p| p.clamp(0.0, 1.0)),
+            stage: body.stage,
+        });
+    Ok(Probe { code, loading })
+}
+
+/// The response status of one uncached health request against `url`.
+async fn probe_status(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Duration,
+) -> Result<u16, ServerError> {
+    probe(client, url, timeout).await.map(|p| p.code)
+}
+
+fn build_client(config: &ServerConfig) -> reqwest::Client {
+    reqwest::Client::builder()
+        .danger_accept_invalid_certs(config.danger_accept_invalid_certs)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Records a status transition and emits `server-status` if it changed.
+fn set_status(app: &AppHandle, status: ServerStatus) {
+    set_status_with(app, status, StatusDetail::default());
+}
+
+fn set_status_with(app: &AppHandle, status: ServerStatus, detail: StatusDetail) {
+    let state = app.state::<ServerState>();
+    let mut change = None;
+    state.status.send_if_modified(|current| {
+        if *current == status {
+            return false;
+        }
+        // Numbered under the watch's lock, so in the order applied.
+        change = Some((*current, state.status_seq.fetch_add(1, Ordering::SeqCst) + 1));
+        *current = status;
+        true
+    });
+    if let Some((from, seq)) = change {
+        announce(app, seq, from, status, detail);
+    }
+}
+
+/// Moves from `from` to `to` only if the status is still `from`, so a
+/// check that raced with a stop or crash doesn't overwrite it.
+pub(crate) fn transition(
+    app: &AppHandle,
+    from: ServerStatus,
+    to: ServerStatus,
+    detail: StatusDetail,
+) -> bool {
+    let state = app.state::<ServerState>();
+    let mut seq = 0;
+    let changed = state.status.send_if_modified(|current| {
+        if *current != from {
+            return false;
+        }
+        seq = state.status_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        *current = to;
+        true
+    });
+    if changed {
+        announce(app, seq, from, to, detail);
+    }
+    changed
+}
+
+fn announce(app: &AppHandle, seq: u64, from: ServerStatus, to: ServerStatus, detail: StatusDetail) {
+    let state = app.state::<ServerState>();
+    state.note_status(to);
+    let _ = app.emit("server-status", to);
+    let at = now_ms();
+    let _ = app.emit(
+        "server-state-changed",
+        ServerStateChanged {
+            seq,
+            from,
+            to,
+            pid: detail.pid.or_else(|| state.pid()),
+            port: state.port(),
+            code: detail.code,
+            signal: detail.signal,
+            reason: detail.reason,
+            at,
+        },
+    );
+}
+
+fn open_log_file(app: &AppHandle) -> Option<std::fs::File> {
+    let dir = app.path().app_log_dir().ok()?;
+    std::fs::create_dir_all(&dir).ok()?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join("server.log"))
+        .ok()
+}
+
+/// An environment variable passed to the sidecar. Values marked `secret`
+/// are masked wherever its output ends up.
+struct SidecarEnv {
+    name: &'static str,
+    value: String,
+    secret: bool,
+}
+
+fn sidecar_env(port: u16, config: &ServerConfig, videos_dir: Option<&Path>) -> Vec<SidecarEnv> {
+    let mut env = vec![
+        SidecarEnv {
+            name: "PORT",
+            value: port.to_string(),
+            secret: false,
+        },
+        SidecarEnv {
+            name: "AIYOU_PROFILE",
+            value: config.profile.clone(),
+            secret: false,
+        },
+        // Matched against `project.lock` before the sidecar writes a project.
+        SidecarEnv {
+            name: "AIYOU_LOCK_OWNER",
+            value: project_lock::owner(),
+            secret: false,
+        },
+    ];
+    // Where `aiyou-video://` serves from.
+    if let Some(dir) = videos_dir {
+        env.push(SidecarEnv {
+            name: "AIYOU_VIDEOS_DIR",
+            value: dir.to_string_lossy().into_owned(),
+            secret: false,
+        });
+    }
+    env
+}
+
+/// Masks the configured patterns plus the secret values in `env` and in
+/// our own environment, which the sidecar inherits.
+fn redactor(config: &ServerConfig, env: &[SidecarEnv]) -> Redactor {
+    let literals: Vec<String> = env
+        .iter()
+        .filter(|e| e.secret)
+        .map(|e| e.value.clone())
+        .chain(
+            std::env::vars()
+                .filter(|(name, _)| redact::is_secret_name(name))
+                .map(|(_, value)| value),
+        )
+        .collect();
+    Redactor::new(&config.redact_patterns, literals.clone()).unwrap_or_else(|e| {
+        warn!(error = %e, "Invalid redaction pattern, using defaults");
+        let defaults = ServerConfig::default().redact_patterns;
+        Redactor::new(&defaults, literals).unwrap_or_default()
+    })
+}
+
+/// The configured port, or a free one if it is 0.
+fn resolve_port(configured: u16) -> Result<u16, ServerError> {
+    if configured != 0 {
+        return Ok(configured);
+    }
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+/// Whether something accepts connections on `port` on this machine.
+fn port_in_use(port: u16) -> bool {
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    std::net::TcpStream::connect_timeout(&addr, Duration::from_millis(300)).is_ok()
+}
+
+/// Asks whatever listens on the current port whether it is our server.
+/// Returns it as adoptable if so.
+async fn identify(state: &ServerState) -> Option<Adopted> {
+    let resp = state
+        .client()
+        .get(state.url("/api/version"))
+        .timeout(HEALTH_TIMEOUT)
+        .send()
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let ours_header = resp.headers().contains_key("x-aiyou-server");
+    let info: VersionInfo = resp.json().await.ok()?;
+    let ours = ours_header || info.name.as_deref() == Some("aiyou-server");
+    ours.then_some(Adopted { pid: info.pid })
+}
+
+fn emit_start_mode(app: &AppHandle, mode: StartMode, port: u16) {
+    info!(?mode, port, "Server start mode");
+    let _ = app.emit("server-start-mode", ServerStartMode { mode, port });
+}
+
+/// Spawns the sidecar and starts the readiness loop in the background. If
+/// the configured port is taken, the listener is adopted when it is our
+/// server from an earlier session, and a free port is used otherwise.
+pub async fn start(app: &AppHandle) -> Result<(), ServerError> {
+    let state = app.state::<ServerState>();
+    ensure_unfrozen(&state)?;
+    if state.child.lock().unwrap().is_some() || state.is_adopted() {
+        return Err(ServerError::AlreadyRunning);
+    }
+
+    let config = state.config();
+    state.startup_attempt.store(1, Ordering::SeqCst);
+    state.apply_mode(&state.mode());
+    if let Some(url) = state.external_url() {
+        info!(%url, "Using external server");
+        set_status(app, ServerStatus::Starting);
+        wait_until_ready(app, config.startup_timeout_secs);
+        return Ok(());
+    }
+    // Never against data an older version left behind. Probing the port
+    // waits on a connect, so it stays off the async runtime.
+    let handle = app.clone();
+    let configured = config.port;
+    let (port, taken) = blocking::run(move || -> Result<_, ServerError> {
+        migrations::ensure_current(&handle)?;
+        let port = resolve_port(configured)?;
+        Ok((port, configured != 0 && port_in_use(port)))
+    })
+    .await
+    .map_err(ServerError::SpawnFailed)??;
+    if !taken {
+        return spawn_sidecar(app, port, StartMode::Spawned);
+    }
+
+    // Identifying the listener takes a request, so it finishes in the
+    // background like the readiness loop.
+    set_status(app, ServerStatus::Starting);
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = handle.state::<ServerState>();
+        record_port(&handle, port);
+        if let Some(adopted) = identify(&state).await {
+            *state.adopted.lock().unwrap() = Some(adopted);
+            emit_start_mode(&handle, StartMode::Adopted, port);
+            wait_until_ready(&handle, config.startup_timeout_secs);
+            return;
+        }
+        warn!(port, "Port is taken by another process");
+        let spawned =
+            resolve_port(0).and_then(|free| spawn_sidecar(&handle, free, StartMode::Relocated));
+        if let Err(e) = spawned {
+            error!(error = %e, "Failed to start server");
+            set_status_with(&handle, ServerStatus::Failed, StatusDetail::because(e.to_string()));
+            let _ = handle.emit("server-failed", ());
+        }
+    });
+    Ok(())
+}
+
+/// Records the port and tells the frontend if its URLs are now stale.
+fn record_port(app: &AppHandle, port: u16) {
+    let state = app.state::<ServerState>();
+    if state.record_port(port) {
+        info!(port, "Server port changed");
+        let _ = app.emit("server-url-changed", ServerUrlChanged { url: state.url("") });
+    }
+}
+
+/// The port in a sidecar line announcing where it listens, such as
+/// `Listening on 3042` or `📍 HTTP: http://localhost:3042`.
+fn listening_port(line: &str) -> Option<u16> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)(?:listening on|http:)\s*(?:port\s*)?(?:https?://[^\s/]+:)?(\d{1,5})\b")
+            .expect("valid pattern")
+    });
+    pattern.captures(line)?[1].parse().ok()
+}
+
+/// Some server builds choose their own port. If it isn't the one we
+/// passed, follows it and starts checking readiness there.
+fn on_port_detected(app: &AppHandle, port: u16) {
+    let state = app.state::<ServerState>();
+    let expected = state.port();
+    if port == expected || state.external_url().is_some() {
+        return;
+    }
+    warn!(expected, port, "Server is listening on another port");
+    record_port(app, port);
+    let _ = app.emit(
+        "server-port-detected",
+        PortDetected {
+            port,
+            url: state.url(""),
+        },
+    );
+    if state.status() == ServerStatus::Starting {
+        wait_until_ready(app, state.config().startup_timeout_secs);
+    }
+}
+
+fn spawn_sidecar(app: &AppHandle, port: u16, mode: StartMode) -> Result<(), ServerError> {
+    let state = app.state::<ServerState>();
+    let mut child_slot = state.child.lock().unwrap();
+    if child_slot.is_some() {
+        return Err(ServerError::AlreadyRunning);
+    }
+    let config = state.config();
+    record_port(app, port);
+    info!(
+        attempt = state.startup_attempt.load(Ordering::SeqCst),
+        max_attempts = config.startup_max_attempts,
+        port,
+        "Spawning server"
+    );
+    let videos_dir = video_protocol::videos_dir(app).ok();
+    let env = sidecar_env(port, &config, videos_dir.as_deref());
+    let redactor = redactor(&config, &env);
+    let (mut rx, child) = server_command(app)?
+        .envs(env.into_iter().map(|e| (e.name, e.value)))
+        .spawn()?;
+    let pid = child.pid();
+    *child_slot = Some(child);
+    drop(child_slot);
+    set_status(app, ServerStatus::Starting);
+    emit_start_mode(app, mode, port);
+
+    // Log sidecar output
+    let handle = app.clone();
+    crash::spawn("sidecar-output", async move {
+        let mut log_file = open_log_file(&handle);
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(line) => {
+                    let s = String::from_utf8_lossy(&line);
+                    let s = redactor.redact(&s);
+                    info!(target: "sidecar", pid, "{}", s.trim_end());
+                    if let Some(f) = log_file.as_mut() {
+                        let _ = writeln!(f, "{}", s.trim_end());
+                    }
+                    server_log::push(&handle, server_log::Stream::Stdout, s.trim_end());
+                    if let Some(port) = listening_port(&s) {
+                        on_port_detected(&handle, port);
+                    }
+                }
+                CommandEvent::Stderr(line) => {
+                    let s = String::from_utf8_lossy(&line);
+                    let s = redactor.redact(&s);
+                    warn!(target: "sidecar", pid, "{}", s.trim_end());
+                    if let Some(f) = log_file.as_mut() {
+                        let _ = writeln!(f, "[err] {}", s.trim_end());
+                    }
+                    server_log::push(&handle, server_log::Stream::Stderr, s.trim_end());
+                }
+                CommandEvent::Terminated(payload) => {
+                    warn!(
+                        pid,
+                        code = ?payload.code,
+                        signal = ?payload.signal,
+                        "Server terminated"
+                    );
+                    on_terminated(&handle, pid, payload.code, payload.signal);
+                    break;
+                }
+                _ => {}
+            }
+        }
+    });
+
+    wait_until_ready(app, config.startup_timeout_secs);
+    Ok(())
+}
+
+/// The sidecar, or the custom command in that mode.
+fn server_command(app: &AppHandle) -> Result<tauri_plugin_shell::process::Command, ServerError> {
+    match app.state::<ServerState>().mode() {
+        ServerMode::Bundled => Ok(app.shell().sidecar("aiyou-server")?),
+        ServerMode::CustomCommand { program, args } => {
+            info!(%program, ?args, "Running server from custom command");
+            Ok(app.shell().command(program).args(args))
+        }
+        ServerMode::External { base_url } => Err(ServerError::External(base_url)),
+    }
+}
+
+/// Fails while frozen for an update, so nothing spawns or talks to a
+/// sidecar that is half replaced.
+pub(crate) fn ensure_unfrozen(state: &ServerState) -> Result<(), ServerError> {
+    if state.is_frozen() {
+        return Err(ServerError::Frozen);
+    }
+    Ok(())
+}
+
+/// Fails in external mode, where the server isn't ours to stop or restart.
+fn ensure_managed(state: &ServerState) -> Result<(), ServerError> {
+    match state.mode() {
+        ServerMode::External { base_url } => Err(ServerError::External(base_url)),
+        _ => Ok(()),
+    }
+}
+
+/// Polls health until the server answers, then moves from `Starting` to
+/// `Ready`, or to `Failed` after `timeout_secs`. Gives up quietly if the
+/// server is stopped meanwhile.
+fn wait_until_ready(app: &AppHandle, timeout_secs: u64) {
+    app.state::<ServerState>().readiness.send_modify(|n| *n += 1);
+    let handle = app.clone();
+    let span = info_span!("readiness", port = app.state::<ServerState>().port(), timeout_secs);
+    let task = async move {
+        let state = handle.state::<ServerState>();
+        let timeout = Duration::from_secs(timeout_secs);
+        let mut loading_message = None;
+        let polled = state
+            .poll_until_ready(timeout, |event| match event {
+                Readiness::Attempt {
+                    attempt,
+                    max_attempts,
+                } => {
+                    let message = match &loading_message {
+                        Some(message) => message.clone(),
+                        None if attempt == 1 => i18n::t(&handle, "splash.starting"),
+                        None => i18n::t_with(
+                            &handle,
+                            "splash.waiting",
+                            &[
+                                ("attempt", &attempt.to_string()),
+                                ("max", &max_attempts.to_string()),
+                            ],
+                        ),
+                    };
+                    let _ = handle.emit(
+                        "server-startup-progress",
+                        StartupProgress {
+                            attempt,
+                            max_attempts,
+                            message,
+                        },
+                    );
+                }
+                Readiness::Loading(loading) => {
+                    let fallback = i18n::t(&handle, "splash.loading");
+                    loading_message = Some(loading_message_for(loading, &fallback));
+                    let detail = StatusDetail::default();
+                    transition(&handle, ServerStatus::Starting, ServerStatus::Loading, detail);
+                    let _ = handle.emit("server-loading-progress", loading);
+                }
+            })
+            .await;
+
+        let Some(ready) = polled else {
+            debug!(status = ?state.status(), "Readiness loop aborted");
+            return;
+        };
+        if !matches!(state.status(), ServerStatus::Starting | ServerStatus::Loading) {
+            return;
+        }
+        if ready {
+            set_status(&handle, ServerStatus::Ready);
+            let _ = handle.emit("server-ready", ());
+        } else if !retry_startup(&handle, "not ready in time") {
+            error!(timeout_secs, "Server failed to start in time");
+            let detail = StatusDetail::because("not ready in time");
+            set_status_with(&handle, ServerStatus::Failed, detail);
+            let _ = handle.emit("server-failed", ());
+            emit_start_failed(&handle, None, None);
+        }
+    };
+    tauri::async_runtime::spawn(task.instrument(span));
+}
+
+/// Splash text for a loading report, e.g. `Loading voice models… 40%`.
+/// `fallback` stands in for a missing stage.
+fn loading_message_for(loading: &LoadingProgress, fallback: &str) -> String {
+    let mut message = match loading.stage.as_deref() {
+        Some(stage) if !stage.is_empty() => {
+            let mut chars = stage.chars();
+            let first = chars.next().map(|c| c.to_uppercase().collect::<String>());
+            format!("{}{}…", first.unwrap_or_default(), chars.as_str())
+        }
+        _ => fallback.to_owned(),
+    };
+    if let Some(progress) = loading.progress {
+        message.push_str(&format!(" {}%", (progress * 100.0).round() as u32));
+    }
+    message
+}
+
+fn emit_start_failed(app: &AppHandle, code: Option<i32>, signal: Option<i32>) {
+    let attempts = app.state::<ServerState>().startup_attempt.load(Ordering::SeqCst);
+    let _ = app.emit(
+        "server-start-failed",
+        StartFailed {
+            code,
+            signal,
+            attempts,
+        },
+    );
+}
+
+/// After a failed startup window, kills what is left of the sidecar and
+/// spawns it again, on a free port if the configured one has been taken
+/// meanwhile. Returns false once `startup_max_attempts` are used up, and
+/// for servers we didn't spawn.
+fn retry_startup(app: &AppHandle, reason: &str) -> bool {
+    let state = app.state::<ServerState>();
+    let max_attempts = state.config().startup_max_attempts;
+    let attempt = state.startup_attempt.load(Ordering::SeqCst);
+    if attempt >= max_attempts || state.external_url().is_some() || state.is_adopted() {
+        return false;
+    }
+    state.startup_attempt.store(attempt + 1, Ordering::SeqCst);
+    warn!(attempt, max_attempts, reason, "Server startup attempt failed, trying again");
+    if let Some(child) = state.child.lock().unwrap().take() {
+        let pid = child.pid();
+        // Its exit event no longer matches the current child.
+        if let Err(e) = child.kill() {
+            warn!(pid, error = %e, "Failed to kill unready server");
+        }
+    }
+    set_status(app, ServerStatus::Starting);
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_RETRY_DELAY).await;
+        let state = handle.state::<ServerState>();
+        // Stopped, or started some other way, in the meantime.
+        if state.status() != ServerStatus::Starting || state.child.lock().unwrap().is_some() {
+            return;
+        }
+        let configured = state.config().port;
+        let taken = configured != 0
+            && blocking::run(move || port_in_use(configured)).await.unwrap_or(false);
+        let spawned = if taken {
+            warn!(port = configured, "Port was taken during startup");
+            resolve_port(0).and_then(|free| spawn_sidecar(&handle, free, StartMode::Relocated))
+        } else {
+            resolve_port(configured)
+                .and_then(|port| spawn_sidecar(&handle, port, StartMode::Spawned))
+        };
+        if let Err(e) = spawned {
+            error!(error = %e, "Failed to start server");
+            set_status_with(&handle, ServerStatus::Failed, StatusDetail::because(e.to_string()));
+            emit_start_failed(&handle, None, None);
+        }
+    });
+    true
+}
+
+/// Handles the sidecar exiting. `pid` identifies which spawn the event
+/// belongs to, so a late exit from a previous process is ignored.
+fn on_terminated(app: &AppHandle, pid: u32, code: Option<i32>, signal: Option<i32>) {
+    let state = app.state::<ServerState>();
+    let mut child_slot = state.child.lock().unwrap();
+    let is_current = child_slot.as_ref().map(|c| c.pid()) == Some(pid);
+    if is_current {
+        child_slot.take();
+    }
+    drop(child_slot);
+
+    if is_current {
+        // Already reported if it had stopped responding first.
+        let was_serving = match state.status() {
+            ServerStatus::Ready => true,
+            ServerStatus::Loading => state.reached_ready(),
+            _ => false,
+        };
+        let was_starting = !was_serving
+            && matches!(state.status(), ServerStatus::Starting | ServerStatus::Loading);
+        crash::record_sidecar_crash(code, signal);
+        if was_starting {
+            // Ends the readiness wait now rather than at its timeout.
+            if !retry_startup(app, "exited before it was ready") {
+                error!(?code, ?signal, "Server exited before it was ready");
+                let detail = StatusDetail {
+                    reason: Some("exited before it was ready".into()),
+                    ..StatusDetail::exited(pid, code, signal)
+                };
+                set_status_with(app, ServerStatus::Failed, detail);
+                emit_start_failed(app, code, signal);
+            }
+            return;
+        }
+        set_status_with(app, ServerStatus::Crashed, StatusDetail::exited(pid, code, signal));
+        if was_serving {
+            monitor::emit_lost(app);
+        }
+    } else if state.status() == ServerStatus::Stopping {
+        set_status_with(app, ServerStatus::Stopped, StatusDetail::exited(pid, code, signal));
+    }
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> std::io::Result<()> {
+    // SAFETY: plain syscall with integer arguments.
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn terminate(pid: u32) -> std::io::Result<()> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+    // SAFETY: the handle is only used for this call and closed after it.
+    unsafe {
+        let process = OpenProcess(PROCESS_TERMINATE, false, pid)?;
+        let result = TerminateProcess(process, 1);
+        let _ = CloseHandle(process);
+        result?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn terminate(_pid: u32) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Asks a server without a reload endpoint to reread its config.
+#[cfg(unix)]
+fn hang_up(pid: u32) -> std::io::Result<()> {
+    // SAFETY: plain syscall with integer arguments.
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGHUP) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn hang_up(_pid: u32) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(unix)]
+fn kill_hard(pid: u32) -> std::io::Result<()> {
+    // SAFETY: plain syscall with integer arguments.
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// `terminate` is already forceful here.
+#[cfg(not(unix))]
+fn kill_hard(pid: u32) -> std::io::Result<()> {
+    terminate(pid)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProcessState {
+    Running,
+    /// Exited but not yet waited for.
+    Zombie,
+    Gone,
+}
+
+/// What the OS says about `pid`, where it can tell.
+#[cfg(unix)]
+fn process_state(pid: u32) -> Option<ProcessState> {
+    // SAFETY: signal 0 only checks that the process exists.
+    if unsafe { libc::kill(pid as libc::pid_t, 0) } != 0
+        && std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH)
+    {
+        return Some(ProcessState::Gone);
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // The state follows the parenthesized command name.
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        let state = stat.rsplit_once(')')?.1.trim_start().chars().next()?;
+        if state == 'Z' {
+            return Some(ProcessState::Zombie);
+        }
+    }
+    Some(ProcessState::Running)
+}
+
+#[cfg(not(unix))]
+fn process_state(_pid: u32) -> Option<ProcessState> {
+    None
+}
+
+/// Waits a zombie child so it goes away. Returns whether it did.
+#[cfg(unix)]
+fn reap(pid: u32) -> bool {
+    let mut status = 0;
+    // SAFETY: `status` outlives the call; WNOHANG keeps it from blocking.
+    unsafe { libc::waitpid(pid as libc::pid_t, &mut status, libc::WNOHANG) > 0 }
+}
+
+#[cfg(not(unix))]
+fn reap(_pid: u32) -> bool {
+    false
+}
+
+/// How far `stop` had to go before the server was gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopStep {
+    /// Exited when asked to (SIGTERM; on Windows, the first termination).
+    Terminated,
+    /// Needed SIGKILL.
+    Killed,
+    /// Outlived SIGKILL as a zombie and was reaped here.
+    Reaped,
+    /// Couldn't be confirmed gone, or had no pid to signal. Its health
+    /// endpoint may still answer.
+    Abandoned,
+}
+
+/// Time a server gets to exit after SIGTERM.
+const TERM_GRACE: Duration = Duration::from_secs(5);
+/// Time it gets after SIGKILL.
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// Whether the server is gone: its process no longer runs and its health
+/// endpoint no longer answers. Without an OS answer, a spawned sidecar is
+/// gone once its exit was reported and an adopted one once its port closes.
+async fn is_gone(app: &AppHandle, pid: u32, spawned: bool) -> bool {
+    let state = app.state::<ServerState>();
+    let exited = match process_state(pid) {
+        Some(ProcessState::Running) => false,
+        Some(ProcessState::Zombie | ProcessState::Gone) => true,
+        None if spawned => state.status() == ServerStatus::Stopped,
+        None => !port_in_use(state.port()),
+    };
+    exited && state.ping().await.is_err()
+}
+
+async fn wait_gone(app: &AppHandle, pid: u32, spawned: bool, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if is_gone(app, pid, spawned).await {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Stops the server at `pid`: SIGTERM, then SIGKILL, then reaping a
+/// zombie left behind, checking after each that the process and its
+/// health endpoint are gone. `spawned` is false for an adopted server,
+/// which isn't our child and so can't be reaped.
+async fn escalate(app: &AppHandle, pid: u32, spawned: bool) -> Result<StopStep, ServerError> {
+    terminate(pid).map_err(|e| ServerError::Io(e.to_string()))?;
+    if wait_gone(app, pid, spawned, TERM_GRACE).await {
+        return Ok(StopStep::Terminated);
+    }
+    warn!(pid, "Server ignored termination; killing it");
+    if let Err(e) = kill_hard(pid) {
+        // Most likely it exited in between.
+        debug!(pid, error = %e, "Kill failed");
+    }
+    if wait_gone(app, pid, spawned, KILL_GRACE).await {
+        return Ok(StopStep::Killed);
+    }
+    let process = process_state(pid);
+    if spawned && process == Some(ProcessState::Zombie) && reap(pid) {
+        warn!(pid, "Server outlived SIGKILL as a zombie; reaped it");
+        return Ok(StopStep::Reaped);
+    }
+    error!(pid, ?process, "Server still present after SIGKILL; giving up on it");
+    Ok(StopStep::Abandoned)
+}
+
+/// Stops an adopted server. There's no exit event for a process we didn't
+/// spawn, so this watches the process and its port instead.
+async fn stop_adopted(app: &AppHandle, adopted: Adopted) -> Result<StopStep, ServerError> {
+    let Some(pid) = adopted.pid else {
+        warn!("Adopted server didn't report its pid; leaving it running");
+        set_status(app, ServerStatus::Stopped);
+        return Ok(StopStep::Abandoned);
+    };
+    set_status(app, ServerStatus::Stopping);
+    let step = escalate(app, pid, false).await;
+    set_status(app, ServerStatus::Stopped);
+    step
+}
+
+/// Stops the sidecar, escalating until it is gone, and returns how far
+/// that took.
+pub async fn stop(app: &AppHandle) -> Result<StopStep, ServerError> {
+    let state = app.state::<ServerState>();
+    let child = state.child.lock().unwrap().take();
+    let Some(child) = child else {
+        let adopted = state.adopted.lock().unwrap().take();
+        return match adopted {
+            Some(adopted) => stop_adopted(app, adopted).await,
+            None => Err(ServerError::NotRunning),
+        };
+    };
+
+    set_status(app, ServerStatus::Stopping);
+    let pid = child.pid();
+    // Signalled by pid from here on; dropping the handle doesn't kill.
+    drop(child);
+    let step = escalate(app, pid, true).await;
+    // The exit event may trail the process going away.
+    let mut rx = state.subscribe();
+    let reported = tokio::time::timeout(
+        Duration::from_secs(1),
+        rx.wait_for(|s| *s == ServerStatus::Stopped),
+    )
+    .await;
+    if reported.is_err() {
+        warn!("Server did not report exit");
+        set_status(app, ServerStatus::Stopped);
+    }
+    let step = step?;
+    info!(pid, ?step, "Server stopped");
+    Ok(step)
+}
+
+pub async fn restart(app: &AppHandle, reason: RestartReason) -> Result<(), ServerError> {
+    let state = app.state::<ServerState>();
+    ensure_unfrozen(&state)?;
+    ensure_managed(&state)?;
+    let reason = state.note_restart(reason);
+    info!(?reason, "Restarting server");
+    state.restarting.store(true, Ordering::SeqCst);
+    let restarted = match stop(app).await {
+        Ok(_) | Err(ServerError::NotRunning) => start(app).await,
+        Err(e) => Err(e),
+    };
+    state.restarting.store(false, Ordering::SeqCst);
+    restarted
+}
+
+/// Returns whether the server answers its health check. Results are cached
+/// briefly; pass `force` to always issue a fresh request.
+#[tauri::command]
+pub async fn check_server_health(
+    state: tauri::State<'_, ServerState>,
+    force: Option<bool>,
+) -> Result<bool, ServerError> {
+    ensure_unfrozen(&state)?;
+    state.check_health(force.unwrap_or(false)).await
+}
+
+/// Times a single health request, for a latency badge. Unlike
+/// `check_server_health` it is never cached.
+#[tauri::command]
+pub async fn ping_server(state: tauri::State<'_, ServerState>) -> Result<u64, ServerError> {
+    ensure_unfrozen(&state)?;
+    state.ping().await
+}
+
+/// Takes `<flag> <value>` (or `<flag>=<value>`) out of `args`.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    let prefix = format!("{}=", flag);
+    let Some(i) = args
+        .iter()
+        .position(|a| a == flag || a.starts_with(&prefix))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(i);
+    match arg.strip_prefix(&prefix) {
+        Some(value) => Ok(Some(value.to_owned())),
+        None if i < args.len() => Ok(Some(args.remove(i))),
+        None => Err(format!("{} needs a value", flag)),
+    }
+}
+
+/// Takes `--server-url <url>` (or `--server-url=<url>`) out of `args`.
+/// The URL must be absolute http(s) with a host, and may carry a base path.
+pub fn take_server_url_arg(args: &mut Vec<String>) -> Result<Option<Url>, String> {
+    let Some(value) = take_flag(args, "--server-url")? else {
+        return Ok(None);
+    };
+    config::parse_server_url(&value)
+        .map(Some)
+        .map_err(|e| format!("invalid --server-url {}", e))
+}
+
+/// The server mode forced for this run, if any: `--server-mode <mode>`,
+/// then `--server-url <url>` as external mode, then `AIYOU_SERVER_MODE`.
+/// Both flags are taken out of `args`.
+pub fn take_server_mode_arg(args: &mut Vec<String>) -> Result<Option<ServerMode>, String> {
+    let url = take_server_url_arg(args)?;
+    if let Some(value) = take_flag(args, "--server-mode")? {
+        return ServerMode::parse(&value)
+            .map(Some)
+            .map_err(|e| format!("invalid --server-mode {}", e));
+    }
+    if let Some(url) = url {
+        return Ok(Some(ServerMode::External {
+            base_url: url.to_string(),
+        }));
+    }
+    match std::env::var("AIYOU_SERVER_MODE") {
+        Ok(value) if !value.trim().is_empty() => ServerMode::parse(&value)
+            .map(Some)
+            .map_err(|e| format!("invalid AIYOU_SERVER_MODE {}", e)),
+        _ => Ok(None),
+    }
+}
+
+/// Maintenance subcommands the sidecar binary supports. Only these can be
+/// run from the frontend.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerSubcommand {
+    Migrate,
+    ClearCache,
+}
+
+impl ServerSubcommand {
+    fn arg(self) -> &'static str {
+        match self {
+            ServerSubcommand::Migrate => "--migrate",
+            ServerSubcommand::ClearCache => "--clear-cache",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubcommandOutput {
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs the sidecar binary once with a maintenance subcommand and waits for
+/// it to finish.
+pub async fn run_subcommand(
+    app: &AppHandle,
+    subcommand: ServerSubcommand,
+) -> Result<SubcommandOutput, ServerError> {
+    ensure_unfrozen(&app.state::<ServerState>())?;
+    let output = server_command(app)?.arg(subcommand.arg()).output().await?;
+    let redactor = redactor(&app.state::<ServerState>().config(), &[]);
+    let redact = |bytes: &[u8]| {
+        let text = String::from_utf8_lossy(bytes);
+        text.lines()
+            .map(|line| redactor.redact(line).into_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    Ok(SubcommandOutput {
+        exit_code: output.status.code(),
+        stdout: redact(&output.stdout),
+        stderr: redact(&output.stderr),
+    })
+}
+
+#[tauri::command]
+pub async fn run_server_subcommand(
+    app: AppHandle,
+    subcommand: ServerSubcommand,
+) -> Result<SubcommandOutput, ServerError> {
+    run_subcommand(&app, subcommand).await
+}
+
+/// Base URL of the running server. Re-read after `server-url-changed`.
+#[tauri::command]
+pub fn get_server_url(state: tauri::State<'_, ServerState>) -> String {
+    state.url("")
+}
+
+#[tauri::command]
+pub fn get_server_status(state: tauri::State<'_, ServerState>) -> ServerStatusInfo {
+    ServerStatusInfo {
+        status: state.status(),
+        adopted: state.is_adopted(),
+        restart_count: state.restart_count(),
+        last_restart_reason: state.last_restart_reason(),
+        loading: state
+            .loading()
+            .filter(|_| state.status() == ServerStatus::Loading),
+        frozen: state.is_frozen(),
+    }
+}
+
+/// Recent heartbeats, oldest first, for an uptime sparkline.
+#[tauri::command]
+pub fn get_health_history(state: tauri::State<'_, ServerState>) -> Vec<HealthSample> {
+    state.health_history()
+}
+
+/// Lets the frontend await readiness instead of polling
+/// `check_server_health`. Defaults to the configured startup timeout.
+#[tauri::command]
+pub async fn wait_for_ready(
+    state: tauri::State<'_, ServerState>,
+    timeout_ms: Option<u64>,
+) -> Result<(), ServerError> {
+    ensure_unfrozen(&state)?;
+    let timeout = match timeout_ms {
+        Some(ms) => Duration::from_millis(ms),
+        None => Duration::from_secs(state.config().startup_timeout_secs),
+    };
+    state.wait_for_ready(timeout).await
+}
+
+#[tauri::command]
+pub async fn start_server(app: AppHandle) -> Result<(), ServerError> {
+    start(&app).await
+}
+
+/// Returns which step stopped it, to spot setups where the server
+/// ignores SIGTERM.
+#[tauri::command]
+pub async fn stop_server(app: AppHandle) -> Result<StopStep, ServerError> {
+    ensure_unfrozen(&app.state::<ServerState>())?;
+    ensure_managed(&app.state::<ServerState>())?;
+    ws::close(&app);
+    stop(&app).await
+}
+
+/// `reason` defaults to a manual restart. Crash and Watchdog are only
+/// recorded when the monitor saw one; see [`ServerState::note_restart`].
+/// With `drain`, no new generation is accepted and the restart waits up to
+/// `drainTimeoutSecs` for those in flight to finish, emitting
+/// `server-draining` meanwhile; after that it restarts regardless.
+#[tauri::command]
+pub async fn restart_server(
+    app: AppHandle,
+    reason: Option<RestartReason>,
+    drain: Option<bool>,
+) -> Result<(), ServerError> {
+    let reason = reason.unwrap_or(RestartReason::Manual);
+    if !drain.unwrap_or(false) {
+        return restart(&app, reason).await;
+    }
+    let state = app.state::<ServerState>();
+    ensure_managed(&state)?;
+    let timeout = Duration::from_secs(state.config().drain_timeout_secs);
+    generation::drain(&app, timeout).await;
+    let restarted = restart(&app, reason).await;
+    generation::end_drain(&app);
+    restarted
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct ReloadReport {
+    /// Settings that changed but only take effect after a restart.
+    requires_restart: Vec<String>,
+}
+
+/// Has the server reread its config in place, through `/api/reload` or,
+/// for servers without one, SIGHUP. Jobs in flight carry on.
+pub async fn reload_config(app: &AppHandle) -> Result<(), ServerError> {
+    let state = app.state::<ServerState>();
+    ensure_unfrozen(&state)?;
+    if state.restarting() {
+        return Err(ServerError::ServerRestarting);
+    }
+    let unsupported = || ServerError::Unsupported("reloading its config without a restart".into());
+    let reloaded =
+        api::request(&state, reqwest::Method::POST, "/api/reload", None, api::REQUEST_TIMEOUT)
+            .await;
+    let report = match reloaded {
+        Ok(body) => serde_json::from_value::<ReloadReport>(body).unwrap_or_default(),
+        Err(ServerError::BadStatus(404)) => {
+            let pid = state.pid().ok_or_else(unsupported)?;
+            hang_up(pid).map_err(|e| match e.kind() {
+                std::io::ErrorKind::Unsupported => unsupported(),
+                _ => ServerError::Io(e.to_string()),
+            })?;
+            ReloadReport::default()
+        }
+        Err(e) => return Err(e),
+    };
+
+    let deadline = Instant::now() + RELOAD_HEALTH_TIMEOUT;
+    loop {
+        match state.check_health(true).await {
+            Ok(true) => break,
+            result if Instant::now() >= deadline => {
+                warn!(?result, "Server not healthy after reloading its config");
+                return Err(result.err().unwrap_or_else(|| {
+                    ServerError::Unreachable("not healthy after reloading its config".into())
+                }));
+            }
+            _ => tokio::time::sleep(READY_INTERVAL).await,
+        }
+    }
+
+    if !report.requires_restart.is_empty() {
+        info!(settings = ?report.requires_restart, "Config reloaded; some settings need a restart");
+        return Err(ServerError::RestartRequired(report.requires_restart.join(", ")));
+    }
+    info!("Server config reloaded");
+    Ok(())
+}
+
+/// Applies config changes without stopping the server, confirming with a
+/// health check afterwards. Fails with `restartRequired` naming the
+/// settings the server couldn't apply in place; everything else was.
+#[tauri::command]
+pub async fn reload_server_config(app: AppHandle) -> Result<(), ServerError> {
+    reload_config(&app).await
+}
+
+/// Stops commands from starting, stopping or talking to the sidecar, and
+/// pauses the health monitor, until `unfreeze`. Commands already under way
+/// finish.
+pub fn freeze(app: &AppHandle) {
+    if !app.state::<ServerState>().frozen.swap(true, Ordering::SeqCst) {
+        info!("Server frozen");
+        let _ = app.emit("server-frozen", ());
+    }
+}
+
+/// Ends `freeze`, and has the render queue look for jobs it held back.
+pub fn unfreeze(app: &AppHandle) {
+    if app.state::<ServerState>().frozen.swap(false, Ordering::SeqCst) {
+        info!("Server unfrozen");
+        let _ = app.emit("server-unfrozen", ());
+        render_queue::wake(app);
+    }
+}
+
+/// Holds the sidecar still while its binary is replaced: lifecycle and
+/// proxy commands fail with `frozen` until `unfreeze_server`.
+#[tauri::command]
+pub fn freeze_server(app: AppHandle) {
+    freeze(&app);
+}
+
+/// Ends `freeze_server`. Doesn't start the server; call `start_server` or
+/// `restart_server` after.
+#[tauri::command]
+pub fn unfreeze_server(app: AppHandle) {
+    unfreeze(&app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers every request on a fresh port with `status`.
+    async fn mock_server(status: &'static str) -> (u16, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (port, task)
+    }
+
+    /// Answers every request on a fresh port with a JSON `body`.
+    async fn json_server(body: &'static str) -> (u16, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (port, task)
+    }
+
+    /// Answers health with a 503 reporting a little more loading progress
+    /// each time, then with 200 from the `ready_after`th request on.
+    async fn loading_server(ready_after: u32) -> (u16, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            for n in 1u32.. {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response = if n >= ready_after {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+                } else {
+                    let body = format!(
+                        r#"{{"status":"loading","progress":{},"stage":"loading voice models"}}"#,
+                        f64::from(n) / f64::from(ready_after)
+                    );
+                    format!(
+                        "HTTP/1.1 503 Service Unavailable\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (port, task)
+    }
+
+    /// Accepts connections on a fresh port but never answers.
+    async fn silent_server() -> (u16, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                open.push(socket);
+            }
+        });
+        (port, task)
+    }
+
+    /// A port nothing is listening on.
+    async fn closed_port() -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    fn health_url(port: u16) -> String {
+        format!("http://127.0.0.1:{}/api/health", port)
+    }
+
+    #[tokio::test]
+    async fn health_ok() {
+        let (port, _server) = mock_server("200 OK").await;
+        let result = probe_status(&reqwest::Client::new(), &health_url(port), HEALTH_TIMEOUT).await;
+        assert!(matches!(result, Ok(200)));
+    }
+
+    #[tokio::test]
+    async fn health_server_error() {
+        let (port, _server) = mock_server("500 Internal Server Error").await;
+        let result = probe_status(&reqwest::Client::new(), &health_url(port), HEALTH_TIMEOUT).await;
+        assert!(matches!(result, Ok(500)));
+    }
+
+    #[tokio::test]
+    async fn health_timeout() {
+        let (port, _server) = silent_server().await;
+        let timeout = Duration::from_millis(200);
+        let result = probe_status(&reqwest::Client::new(), &health_url(port), timeout).await;
+        assert!(matches!(result, Err(ServerError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn health_connection_refused() {
+        let port = closed_port().await;
+        let result = probe_status(&reqwest::Client::new(), &health_url(port), HEALTH_TIMEOUT).await;
+        assert!(matches!(result, Err(ServerError::Unreachable(_))));
+    }
+
+    #[tokio::test]
+    async fn health_result_is_cached_until_forced() {
+        let state = ServerState::default();
+        let (port, server) = mock_server("200 OK").await;
+        state.record_port(port);
+        assert!(matches!(state.check_health(false).await, Ok(true)));
+        assert!(state.last_healthy().is_some());
+
+        server.abort();
+        let _ = server.await;
+        assert!(matches!(state.check_health(false).await, Ok(true)));
+        assert!(state.check_health(true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn health_checks_follow_port_after_restart() {
+        let state = ServerState::default();
+
+        let (old_port, old_server) = mock_server("503 Service Unavailable").await;
+        state.record_port(old_port);
+        assert!(matches!(state.check_health(false).await, Ok(false)));
+
+        // Restart comes up on a different port; the old one goes away.
+        old_server.abort();
+        let (new_port, _new_server) = mock_server("200 OK").await;
+        assert_ne!(old_port, new_port);
+        assert!(state.record_port(new_port));
+        assert!(!state.record_port(new_port));
+
+        assert_eq!(state.url("/api/health"), format!("http://localhost:{}/api/health", new_port));
+        // Not forced: a cached result for the old port must not be reused.
+        assert!(matches!(state.check_health(false).await, Ok(true)));
+    }
+
+    #[tokio::test]
+    async fn adopts_only_our_server() {
+        let state = ServerState::default();
+        let ours = r#"{"name":"aiyou-server","version":"1.0","pid":4242}"#;
+        let (port, _server) = json_server(ours).await;
+        state.record_port(port);
+        assert!(port_in_use(port));
+        assert_eq!(identify(&state).await.and_then(|a| a.pid), Some(4242));
+
+        let (port, _server) = json_server(r#"{"name":"some-other-app"}"#).await;
+        state.record_port(port);
+        assert!(identify(&state).await.is_none());
+
+        let (port, _server) = mock_server("404 Not Found").await;
+        state.record_port(port);
+        assert!(identify(&state).await.is_none());
+
+        assert!(!port_in_use(closed_port().await));
+    }
+
+    #[tokio::test]
+    async fn wait_for_ready_follows_status() {
+        let state = ServerState::default();
+        let short = Duration::from_millis(50);
+        assert!(matches!(
+            state.wait_for_ready(short).await,
+            Err(ServerError::Timeout)
+        ));
+
+        state.status.send_replace(ServerStatus::Starting);
+        let waiting = state.wait_for_ready(Duration::from_secs(5));
+        let (result, _) = tokio::join!(waiting, async {
+            tokio::time::sleep(short).await;
+            state.status.send_replace(ServerStatus::Ready);
+        });
+        assert!(result.is_ok());
+        // Already ready: resolves without waiting.
+        assert!(state.wait_for_ready(Duration::ZERO).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn stop_during_startup_ends_readiness_loop() {
+        let state = ServerState::default();
+        state.record_port(closed_port().await);
+        state.status.send_replace(ServerStatus::Starting);
+
+        let started = Instant::now();
+        let mut attempts = 0;
+        let polling = state.poll_until_ready(Duration::from_secs(30), |_| attempts += 1);
+        let (polled, _) = tokio::join!(polling, async {
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            state.status.send_replace(ServerStatus::Stopping);
+        });
+        assert_eq!(polled, None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(attempts < 5);
+
+        // Not starting at all: nothing to wait for.
+        assert_eq!(
+            state.poll_until_ready(Duration::from_secs(30), |_| {}).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn loading_reports_are_parsed() {
+        let state = ServerState::default();
+        let (port, _server) = loading_server(10).await;
+        state.record_port(port);
+        assert!(matches!(state.check_health(true).await, Ok(false)));
+        assert_eq!(
+            state.loading(),
+            Some(LoadingProgress {
+                progress: Some(0.1),
+                stage: Some("loading voice models".into()),
+            })
+        );
+        assert_eq!(
+            loading_message_for(&state.loading().unwrap(), "Loading engine…"),
+            "Loading voice models… 10%"
+        );
+
+        // A bare 503 is just unhealthy.
+        let (port, _server) = mock_server("503 Service Unavailable").await;
+        state.record_port(port);
+        assert!(matches!(state.check_health(true).await, Ok(false)));
+        assert_eq!(state.loading(), None);
+    }
+
+    #[tokio::test]
+    async fn loading_progress_holds_off_the_startup_timeout() {
+        let state = ServerState::default();
+        // Ready after about 2s of loading, well past the 1s timeout.
+        let (port, _server) = loading_server(5).await;
+        state.record_port(port);
+        state.status.send_replace(ServerStatus::Starting);
+
+        let mut reports = 0;
+        let polled = state
+            .poll_until_ready(Duration::from_secs(1), |event| {
+                if let Readiness::Loading(_) = event {
+                    reports += 1;
+                }
+            })
+            .await;
+        assert_eq!(polled, Some(true));
+        // The prewarm request counts as the first.
+        assert_eq!(reports, 3);
+
+        // Unreachable, the timeout runs as before.
+        state.record_port(closed_port().await);
+        let started = Instant::now();
+        let polled = state.poll_until_ready(Duration::from_secs(1), |_| {}).await;
+        assert_eq!(polled, Some(false));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn listening_port_lines() {
+        assert_eq!(listening_port("Listening on 3042"), Some(3042));
+        assert_eq!(listening_port("server listening on port 8080\n"), Some(8080));
+        assert_eq!(listening_port("📍 HTTP: http://localhost:3001"), Some(3001));
+        assert_eq!(listening_port("Listening on http://127.0.0.1:4000"), Some(4000));
+        assert_eq!(listening_port("🔧 Health: http://localhost:3001/api/health"), None);
+        assert_eq!(listening_port("Listening on 99999"), None);
+        assert_eq!(listening_port("🚀 AIYOU Backend Server started"), None);
+    }
+
+    #[tokio::test]
+    async fn heartbeats_are_recorded_up_to_a_limit() {
+        let state = ServerState::default();
+        let (port, _server) = mock_server("503 Service Unavailable").await;
+        state.record_port(port);
+
+        // Plain checks aren't heartbeats.
+        assert!(matches!(state.check_health(true).await, Ok(false)));
+        assert!(state.health_history().is_empty());
+
+        assert!(matches!(state.heartbeat().await, Ok(false)));
+        let sample = state.health_history()[0].clone();
+        assert!(sample.reachable);
+        assert_eq!(sample.status_code, Some(503));
+        assert!(sample.response_ms.is_some());
+
+        state.record_port(closed_port().await);
+        for _ in 0..HEALTH_HISTORY_LEN {
+            let _ = state.heartbeat().await;
+        }
+        let history = state.health_history();
+        assert_eq!(history.len(), HEALTH_HISTORY_LEN);
+        // The first, reachable sample has been pushed out.
+        assert!(history.iter().all(|s| !s.reachable && s.status_code.is_none()));
+    }
+
+    #[test]
+    fn steady_timeout_applies_once_ready() {
+        let state = ServerState::default();
+        let config = state.config();
+        let startup = Duration::from_millis(config.startup_health_timeout_ms);
+        let steady = Duration::from_millis(config.steady_health_timeout_ms);
+        assert_eq!(state.health_timeout(), startup);
+        state.note_status(ServerStatus::Ready);
+        assert_eq!(state.health_timeout(), steady);
+        // Unreachable keeps the steady timeout; a restart doesn't.
+        state.note_status(ServerStatus::Unreachable);
+        assert_eq!(state.health_timeout(), steady);
+        state.note_status(ServerStatus::Starting);
+        assert_eq!(state.health_timeout(), startup);
+    }
+
+    #[test]
+    fn restarts_are_put_down_to_the_failure_the_monitor_saw() {
+        let state = ServerState::default();
+        // The webview can't claim a crash that didn't happen.
+        assert_eq!(state.note_restart(RestartReason::Crash), RestartReason::Manual);
+        state.note_status(ServerStatus::Crashed);
+        assert_eq!(state.note_restart(RestartReason::Manual), RestartReason::Crash);
+        // Each failure explains one restart.
+        assert_eq!(state.note_restart(RestartReason::Manual), RestartReason::Manual);
+
+        state.note_status(ServerStatus::Unreachable);
+        assert_eq!(state.note_restart(RestartReason::Crash), RestartReason::Watchdog);
+        // Recovering on its own, or being stopped, clears it.
+        state.note_status(ServerStatus::Unhealthy);
+        state.note_status(ServerStatus::Ready);
+        assert_eq!(state.note_restart(RestartReason::Watchdog), RestartReason::Manual);
+        state.note_status(ServerStatus::Crashed);
+        state.note_status(ServerStatus::Stopped);
+        assert_eq!(state.note_restart(RestartReason::Manual), RestartReason::Manual);
+
+        // Other reasons are kept as given.
+        state.note_status(ServerStatus::Crashed);
+        assert_eq!(state.note_restart(RestartReason::Restore), RestartReason::Restore);
+        assert_eq!(state.restart_count(), 7);
+        assert_eq!(state.last_restart_reason(), Some(RestartReason::Restore));
+    }
+
+    /// Stands in for a big project export: synchronous writes for longer
+    /// than any health check should take.
+    fn fake_export(path: &std::path::Path, run_for: Duration) -> std::io::Result<u64> {
+        let mut file = std::fs::File::create(path)?;
+        let chunk = vec![0u8; 1 << 20];
+        let started = Instant::now();
+        let mut written = 0;
+        while started.elapsed() < run_for {
+            file.write_all(&chunk)?;
+            file.sync_data()?;
+            written += chunk.len() as u64;
+        }
+        Ok(written)
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn health_checks_answer_during_a_blocking_export() {
+        let state = ServerState::default();
+        let (port, _server) = mock_server("200 OK").await;
+        state.record_port(port);
+
+        let path = std::env::temp_dir().join(format!("aiyou-export-{}.bin", std::process::id()));
+        let export_path = path.clone();
+        // One runtime thread: had the export run on it, nothing else would.
+        let export = tokio::spawn(crate::blocking::watched(
+            "export_project",
+            crate::blocking::run(move || fake_export(&export_path, Duration::from_millis(1500))),
+        ));
+
+        let started = Instant::now();
+        assert!(matches!(state.check_health(true).await, Ok(true)));
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(!export.is_finished());
+
+        let written = export.await.unwrap().unwrap().unwrap();
+        assert!(written > 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn server_mode_from_flags() {
+        let mut args = vec![
+            "--server-url".to_owned(),
+            "http://localhost:5174".to_owned(),
+            "--batch".to_owned(),
+        ];
+        let mode = take_server_mode_arg(&mut args).unwrap();
+        assert_eq!(
+            mode,
+            Some(ServerMode::External {
+                base_url: "http://localhost:5174/".into()
+            })
+        );
+        assert_eq!(args, ["--batch"]);
+
+        let mut args = vec!["--server-mode=custom:node server/index.js".to_owned()];
+        assert_eq!(
+            take_server_mode_arg(&mut args).unwrap(),
+            Some(ServerMode::CustomCommand {
+                program: "node".into(),
+                args: vec!["server/index.js".into()],
+            })
+        );
+        assert!(args.is_empty());
+
+        assert!(ServerMode::parse("external:ftp://example.com").is_err());
+        assert!(ServerMode::parse("custom:").is_err());
+        assert!(ServerMode::parse("sideways").is_err());
+        assert_eq!(ServerMode::parse(" bundled "), Ok(ServerMode::Bundled));
+    }
+}
@@ -0,0 +1,181 @@
+//! Sidecar output for the frontend's log view.
+//!
+//! Lines are emitted in `server-log-batch` events, but only once the
+//! frontend has called `frontend_ready`; until then they are only kept, so
+//! startup errors printed before its listener existed aren't lost.
+//! `frontend_ready` returns what was kept, and every later line arrives in
+//! a batch. A batch collects the lines printed within
+//! `Settings::log_batch_ms` of the first, so a sidecar logging thousands of
+//! lines a second costs the webview a few events rather than thousands.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::crash;
+use crate::settings::SettingsState;
+use crate::time::now_ms;
+
+/// Lines kept for `frontend_ready`.
+const BUFFER_LEN: usize = 500;
+/// Bounds on `Settings::log_batch_ms`.
+const MIN_BATCH_WINDOW: Duration = Duration::from_millis(10);
+const MAX_BATCH_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    /// Increases by one per line for the app's lifetime, so the frontend
+    /// can spot a gap.
+    pub seq: u64,
+    /// Unix time in milliseconds.
+    pub at: u64,
+    pub stream: Stream,
+    /// Already redacted.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogBatch {
+    /// Oldest first.
+    lines: Vec<LogLine>,
+}
+
+#[derive(Default)]
+struct Buffer {
+    lines: VecDeque<LogLine>,
+    next_seq: u64,
+    frontend_ready: bool,
+    /// Lines for the next batch. Only non-empty while a flush is due.
+    pending: Vec<LogLine>,
+}
+
+#[derive(Default)]
+pub struct ServerLogState {
+    buffer: Mutex<Buffer>,
+}
+
+impl ServerLogState {
+    /// Keeps `text`, and queues it for the next batch if the frontend is
+    /// listening. True if it starts a batch, which the caller then flushes.
+    fn push(&self, stream: Stream, text: String) -> bool {
+        let mut buffer = self.buffer.lock().unwrap();
+        let line = LogLine {
+            seq: buffer.next_seq,
+            at: now_ms(),
+            stream,
+            text,
+        };
+        buffer.next_seq += 1;
+        if buffer.lines.len() == BUFFER_LEN {
+            buffer.lines.pop_front();
+        }
+        buffer.lines.push_back(line.clone());
+        if !buffer.frontend_ready {
+            return false;
+        }
+        buffer.pending.push(line);
+        buffer.pending.len() == 1
+    }
+
+    /// The lines queued since the last batch.
+    fn take_batch(&self) -> Vec<LogLine> {
+        std::mem::take(&mut self.buffer.lock().unwrap().pending)
+    }
+
+    /// Marks the frontend as listening and returns the kept lines. Done
+    /// under one lock, so every line is either returned here or emitted.
+    fn replay(&self) -> Vec<LogLine> {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.frontend_ready = true;
+        buffer.lines.iter().cloned().collect()
+    }
+}
+
+fn batch_window(app: &AppHandle) -> Duration {
+    let ms = app.state::<SettingsState>().get().log_batch_ms;
+    Duration::from_millis(ms).clamp(MIN_BATCH_WINDOW, MAX_BATCH_WINDOW)
+}
+
+/// Emits the queued lines as one `server-log-batch`, if there are any.
+pub fn flush(app: &AppHandle) {
+    let lines = app.state::<ServerLogState>().take_batch();
+    if !lines.is_empty() {
+        let _ = app.emit("server-log-batch", LogBatch { lines });
+    }
+}
+
+/// Records a line of sidecar output, for the next `server-log-batch` once
+/// the frontend is ready for it.
+pub fn push(app: &AppHandle, stream: Stream, text: &str) {
+    let state = app.state::<ServerLogState>();
+    if state.push(stream, text.to_owned()) {
+        let handle = app.clone();
+        let window = batch_window(app);
+        crash::spawn("server-log", async move {
+            tokio::time::sleep(window).await;
+            flush(&handle);
+        });
+    }
+}
+
+/// Sends the last partial batch before the app exits.
+pub fn on_exit(app: &AppHandle) {
+    flush(app);
+}
+
+/// Called by the frontend once its `server-log-batch` listener is
+/// registered.
+/// Returns the lines printed so far, oldest first.
+#[tauri::command]
+pub fn frontend_ready(state: tauri::State<'_, ServerLogState>) -> Vec<LogLine> {
+    state.replay()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn early_lines_are_replayed_once() {
+        let state = ServerLogState::default();
+        assert!(!state.push(Stream::Stderr, "config.json is invalid".into()));
+        assert!(!state.push(Stream::Stdout, "Listening on 3001".into()));
+        assert!(state.take_batch().is_empty());
+
+        let replayed = state.replay();
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[0].text, "config.json is invalid");
+        assert_eq!(replayed[0].stream, Stream::Stderr);
+
+        // Batched from now on, numbered after the replayed ones. Only the
+        // first line of a batch asks for a flush.
+        assert!(state.push(Stream::Stdout, "ready".into()));
+        assert!(!state.push(Stream::Stderr, "slow request".into()));
+        let batch = state.take_batch();
+        assert_eq!(batch.iter().map(|l| l.seq).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(batch[1].stream, Stream::Stderr);
+        assert!(state.push(Stream::Stdout, "next".into()));
+    }
+
+    #[test]
+    fn buffer_keeps_the_newest_lines() {
+        let state = ServerLogState::default();
+        for i in 0..BUFFER_LEN + 10 {
+            state.push(Stream::Stdout, i.to_string());
+        }
+        let replayed = state.replay();
+        assert_eq!(replayed.len(), BUFFER_LEN);
+        assert_eq!(replayed[0].seq, 10);
+    }
+}
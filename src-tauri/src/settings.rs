@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::bandwidth::BandwidthLimit;
+use crate::error::ServerError;
+use crate::proxy::{ProxyLimits, ProxyRetry};
+use crate::render_queue::RenderSchedule;
+use crate::updater::UpdateCheck;
+use crate::window_state::WindowGeometry;
+
+/// User preferences persisted to `settings.json` in the app config dir.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    /// Show native notifications for finished jobs while the window is unfocused.
+    pub notifications: bool,
+    /// Last main-window geometry, restored on launch.
+    pub window: Option<WindowGeometry>,
+    /// Last preview-window geometry, shared by all scene previews.
+    pub preview_window: Option<WindowGeometry>,
+    /// Global hotkey that pauses and resumes the render queue.
+    pub pause_shortcut: String,
+    /// When to check for app updates automatically.
+    pub update_check: UpdateCheck,
+    /// Unix time in seconds of the last update check.
+    pub last_update_check: Option<u64>,
+    /// Hours between automatic project backups; 0 turns them off.
+    pub backup_interval_hours: u32,
+    /// Backups kept per project.
+    pub backups_to_keep: usize,
+    /// Render slots the queue aims for; a 4K job takes two. Automatic, a
+    /// quarter of the CPU cores between 1 and 4, until one is chosen. The
+    /// render governor runs fewer while the machine is busy.
+    pub render_parallelism: Option<usize>,
+    /// Generations proxied to the server at once; more are refused as busy.
+    pub max_concurrent_generations: usize,
+    /// Extra attempts for a generation start that failed on the way to the
+    /// server. Capped at 5.
+    pub generation_retries: u32,
+    /// Requests `api_request` sends to the server at once, per category.
+    pub proxy_limits: ProxyLimits,
+    /// When `api_request` retries a request the restarting server failed.
+    pub proxy_retry: ProxyRetry,
+    /// Pause the render queue while every AI provider is unreachable, and
+    /// resume it when they are back.
+    pub pause_queue_when_offline: bool,
+    /// Send anonymous usage events. Off until the user opts in.
+    pub telemetry: bool,
+    /// `tracing` filter for the app log, such as `info` or `debug`.
+    pub log_level: String,
+    /// Days a paid license keeps working without reaching the license
+    /// server. Capped at 30.
+    pub license_grace_days: u32,
+    /// Milliseconds between job status polls when the server has no event
+    /// stream. Kept between 250 and 10000.
+    pub job_poll_interval_ms: u64,
+    /// Upload and download speed caps shared by all transfers.
+    pub bandwidth_limit: BandwidthLimit,
+    /// When queued renders may start.
+    pub render_schedule: RenderSchedule,
+    /// Language of menus, the tray and notifications, such as `zh-CN`.
+    /// Follows the OS until one is chosen.
+    pub locale: Option<String>,
+    /// Images pasted from the clipboard with more pixels than this are
+    /// scaled down to it before they are saved.
+    pub clipboard_image_max_pixels: u64,
+    /// Milliseconds of sidecar output sent to the log view per event.
+    /// Kept between 10 and 2000.
+    pub log_batch_ms: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            notifications: true,
+            window: None,
+            preview_window: None,
+            pause_shortcut: "Ctrl+Alt+P".into(),
+            update_check: UpdateCheck::default(),
+            last_update_check: None,
+            backup_interval_hours: 6,
+            backups_to_keep: 10,
+            render_parallelism: None,
+            max_concurrent_generations: 3,
+            generation_retries: 2,
+            proxy_limits: ProxyLimits::default(),
+            proxy_retry: ProxyRetry::default(),
+            pause_queue_when_offline: true,
+            telemetry: false,
+            log_level: "info".into(),
+            license_grace_days: 14,
+            job_poll_interval_ms: 1000,
+            bandwidth_limit: BandwidthLimit::default(),
+            render_schedule: RenderSchedule::default(),
+            locale: None,
+            clipboard_image_max_pixels: 4096 * 4096,
+            log_batch_ms: 100,
+        }
+    }
+}
+
+pub struct SettingsState {
+    path: Option<PathBuf>,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsState {
+    /// Loads settings from disk, falling back to defaults if the file is
+    /// missing or unreadable.
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join("settings.json"));
+
+        let settings = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(settings) => Some(settings),
+                Err(e) => {
+                    warn!(error = %e, "Ignoring invalid settings file");
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: Settings) -> Result<(), ServerError> {
+        self.update(|s| *s = settings)
+    }
+
+    /// Applies `f` to the current settings and writes the result to disk.
+    pub fn update(&self, f: impl FnOnce(&mut Settings)) -> Result<(), ServerError> {
+        let mut settings = self.settings.lock().unwrap();
+        let mut updated = settings.clone();
+        f(&mut updated);
+
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let json = serde_json::to_string_pretty(&updated)
+                .map_err(|e| ServerError::Io(e.to_string()))?;
+            std::fs::write(path, json)?;
+        }
+        *settings = updated;
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub fn get_settings(state: tauri::State<'_, SettingsState>) -> Settings {
+    state.get()
+}
+
+#[tauri::command]
+pub fn set_settings(
+    state: tauri::State<'_, SettingsState>,
+    settings: Settings,
+) -> Result<(), ServerError> {
+    state.set(settings)
+}
@@ -0,0 +1,104 @@
+//! System-wide hotkey that pauses and resumes the render queue.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+use tracing::warn;
+
+use crate::api;
+use crate::error::ServerError;
+use crate::i18n;
+use crate::notify::{self, NotificationKind};
+use crate::server::ServerState;
+use crate::settings::SettingsState;
+
+#[derive(Default)]
+pub struct PauseShortcutState {
+    current: Mutex<Option<Shortcut>>,
+    /// Whether the last toggle paused the queue.
+    paused: AtomicBool,
+}
+
+fn on_pressed(app: &AppHandle, _shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state != ShortcutState::Pressed {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<PauseShortcutState>();
+        let pause = !state.paused.load(Ordering::SeqCst);
+        let result = api::set_queue_paused(&app.state::<ServerState>(), pause).await;
+        let (title, body, kind) = match result {
+            Ok(()) => {
+                state.paused.store(pause, Ordering::SeqCst);
+                let key = if pause {
+                    "notify.queuePaused"
+                } else {
+                    "notify.queueResumed"
+                };
+                (i18n::t(&app, key), String::new(), NotificationKind::Info)
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to toggle render queue");
+                let title = i18n::t(&app, "notify.queueToggleFailed");
+                (title, e.to_string(), NotificationKind::Error)
+            }
+        };
+        if let Err(e) = notify::send(&app, &title, &body, kind, None) {
+            warn!(error = %e, "Failed to show notification");
+        }
+    });
+}
+
+/// Swaps the registered hotkey for `accelerator`. If the new one can't be
+/// registered (e.g. another app owns it) the previous one is restored.
+fn register(app: &AppHandle, accelerator: &str) -> Result<(), ServerError> {
+    let shortcut: Shortcut = accelerator.parse().map_err(|e| {
+        ServerError::Shortcut(format!("invalid shortcut {:?}: {}", accelerator, e))
+    })?;
+    let state = app.state::<PauseShortcutState>();
+    let mut current = state.current.lock().unwrap();
+    if *current == Some(shortcut) {
+        return Ok(());
+    }
+
+    let shortcuts = app.global_shortcut();
+    if let Some(previous) = *current {
+        let _ = shortcuts.unregister(previous);
+    }
+    if let Err(e) = shortcuts.on_shortcut(shortcut, on_pressed) {
+        if let Some(previous) = *current {
+            if shortcuts.on_shortcut(previous, on_pressed).is_err() {
+                *current = None;
+            }
+        }
+        return Err(ServerError::Shortcut(format!(
+            "couldn't register {}: {}",
+            accelerator, e
+        )));
+    }
+    *current = Some(shortcut);
+    Ok(())
+}
+
+/// Registers the saved pause/resume hotkey.
+pub fn init(app: &AppHandle) {
+    let accelerator = app.state::<SettingsState>().get().pause_shortcut;
+    if let Err(e) = register(app, &accelerator) {
+        warn!(%accelerator, error = %e, "Failed to register pause shortcut");
+    }
+}
+
+pub fn unregister(app: &AppHandle) {
+    let _ = app.global_shortcut().unregister_all();
+}
+
+/// Rebinds the pause/resume hotkey and saves it. Fails, keeping the old
+/// binding, if the combination is invalid or already taken.
+#[tauri::command]
+pub fn set_global_shortcut(app: AppHandle, accelerator: String) -> Result<(), ServerError> {
+    register(&app, &accelerator)?;
+    app.state::<SettingsState>()
+        .update(|s| s.pause_shortcut = accelerator)
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+use tauri::{AppHandle, Listener, Manager, WebviewUrl, WebviewWindowBuilder};
+use tracing::warn;
+
+use crate::first_run::FirstRunProgress;
+use crate::migrations::MigrationProgress;
+use crate::server::{ServerState, ServerStatus, StartupProgress};
+use crate::system_prefs;
+
+const SPLASH_LABEL: &str = "splash";
+/// The splash never outlives this, even if the status never settles.
+const HARD_CAP: Duration = Duration::from_secs(90);
+
+pub fn is_splash(label: &str) -> bool {
+    label == SPLASH_LABEL
+}
+
+/// Shows the splash window and swaps it for the main window once the
+/// server is ready or has failed to start.
+pub fn open(app: &AppHandle) -> tauri::Result<()> {
+    let splash = WebviewWindowBuilder::new(app, SPLASH_LABEL, WebviewUrl::App("splash.html".into()))
+        .title("AIYOU")
+        .inner_size(420.0, 260.0)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .center()
+        .background_color(system_prefs::background(app))
+        .build()?;
+
+    let show = move |message: &str| {
+        let message = serde_json::to_string(message).unwrap_or_default();
+        let _ = splash.eval(format!(
+            "window.setSplashProgress && window.setSplashProgress({})",
+            message
+        ));
+    };
+    let startup = show.clone();
+    let progress = app.listen("server-startup-progress", move |event| {
+        if let Ok(progress) = serde_json::from_str::<StartupProgress>(event.payload()) {
+            startup(&progress.message);
+        }
+    });
+    // Migrations and first-run setup report here before the server is
+    // spawned.
+    let migrating = show.clone();
+    let migration = app.listen("migration-progress", move |event| {
+        if let Ok(progress) = serde_json::from_str::<MigrationProgress>(event.payload()) {
+            migrating(&progress.message);
+        }
+    });
+    let setup = app.listen("first-run-progress", move |event| {
+        if let Ok(progress) = serde_json::from_str::<FirstRunProgress>(event.payload()) {
+            show(&progress.message);
+        }
+    });
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut rx = handle.state::<ServerState>().subscribe();
+        let settled = rx.wait_for(|s| {
+            matches!(
+                s,
+                ServerStatus::Ready | ServerStatus::Failed | ServerStatus::Crashed
+            )
+        });
+        if tokio::time::timeout(HARD_CAP, settled).await.is_err() {
+            warn!("Server did not settle, closing splash anyway");
+        }
+        handle.unlisten(progress);
+        handle.unlisten(migration);
+        handle.unlisten(setup);
+        close(&handle);
+    });
+
+    Ok(())
+}
+
+/// Closes the splash (if open) and reveals the main window.
+pub fn close(app: &AppHandle) {
+    if let Some(main) = app.get_webview_window("main") {
+        let _ = main.show();
+        let _ = main.set_focus();
+    }
+    if let Some(splash) = app.get_webview_window(SPLASH_LABEL) {
+        let _ = splash.close();
+    }
+}
@@ -0,0 +1,185 @@
+//! Per-project statistics for the dashboard and the storage overview.
+//!
+//! Content and usage figures come from the sidecar; media size is measured
+//! on disk here and cached until the media directory's mtime changes.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::api;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::server::ServerState;
+
+/// Projects fetched at once by `get_all_projects_stats`.
+const PARALLEL_FETCHES: usize = 8;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectStats {
+    pub project_id: String,
+    pub scene_count: u32,
+    pub rendered_duration_secs: f64,
+    pub dialogue_words: u64,
+    pub api_calls: BTreeMap<String, u64>,
+    pub estimated_tokens: BTreeMap<String, u64>,
+    /// `None` if the project has no media directory.
+    pub media_bytes: Option<u64>,
+    pub created_at: Option<String>,
+    pub modified_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsTotals {
+    pub project_count: usize,
+    pub scene_count: u64,
+    pub rendered_duration_secs: f64,
+    pub dialogue_words: u64,
+    pub api_calls: BTreeMap<String, u64>,
+    pub estimated_tokens: BTreeMap<String, u64>,
+    pub media_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllProjectsStats {
+    pub projects: Vec<ProjectStats>,
+    pub totals: StatsTotals,
+    /// Projects whose stats couldn't be fetched; left out of the totals.
+    pub failed: Vec<String>,
+}
+
+/// Media sizes by directory, with the mtime they were measured at.
+#[derive(Default)]
+pub struct StatsState {
+    sizes: Mutex<HashMap<PathBuf, (SystemTime, u64)>>,
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            // Symlinks aren't followed, so nothing is counted twice.
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Size of `dir`, walked again only if its mtime moved since last time.
+async fn media_size(app: &AppHandle, dir: PathBuf) -> Option<u64> {
+    let mtime = tokio::fs::metadata(&dir).await.ok()?.modified().ok()?;
+    let state = app.state::<StatsState>();
+    if let Some(&(cached_at, size)) = state.sizes.lock().unwrap().get(&dir) {
+        if cached_at == mtime {
+            return Some(size);
+        }
+    }
+    let walked = dir.clone();
+    let size = blocking::run(move || dir_size(&walked)).await.ok()?;
+    state.sizes.lock().unwrap().insert(dir, (mtime, size));
+    Some(size)
+}
+
+async fn project_stats(app: &AppHandle, project_id: &str) -> Result<ProjectStats, ServerError> {
+    let server = api::get_project_stats(&app.state::<ServerState>(), project_id).await?;
+    let media_bytes = match server.media_dir {
+        Some(dir) => media_size(app, PathBuf::from(dir)).await,
+        None => None,
+    };
+    Ok(ProjectStats {
+        project_id: project_id.to_owned(),
+        scene_count: server.scene_count,
+        rendered_duration_secs: server.rendered_duration_secs,
+        dialogue_words: server.dialogue_words,
+        api_calls: server.api_calls,
+        estimated_tokens: server.estimated_tokens,
+        media_bytes,
+        created_at: server.created_at,
+        modified_at: server.updated_at,
+    })
+}
+
+fn add_counts(into: &mut BTreeMap<String, u64>, from: &BTreeMap<String, u64>) {
+    for (provider, count) in from {
+        *into.entry(provider.clone()).or_default() += count;
+    }
+}
+
+#[tauri::command]
+pub async fn get_project_stats(
+    app: AppHandle,
+    project_id: String,
+) -> Result<ProjectStats, ServerError> {
+    blocking::watched("get_project_stats", async move {
+        project_stats(&app, &project_id).await
+    })
+    .await
+}
+
+/// Stats for every project plus totals. Projects are fetched a few at a
+/// time; one failing doesn't fail the rest.
+#[tauri::command]
+pub async fn get_all_projects_stats(app: AppHandle) -> Result<AllProjectsStats, ServerError> {
+    blocking::watched("get_all_projects_stats", async move {
+        let projects = api::list_projects(&app.state::<ServerState>()).await?;
+        let limit = Arc::new(Semaphore::new(PARALLEL_FETCHES));
+        let mut tasks = tokio::task::JoinSet::new();
+        for (index, project) in projects.into_iter().enumerate() {
+            let app = app.clone();
+            let limit = limit.clone();
+            tasks.spawn(async move {
+                let _permit = limit.acquire_owned().await;
+                let stats = project_stats(&app, &project.id).await;
+                (index, project.id, stats)
+            });
+        }
+
+        let mut results = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok(result) = joined {
+                results.push(result);
+            }
+        }
+        // Keep the server's project order.
+        results.sort_by_key(|(index, _, _)| *index);
+
+        let mut all = AllProjectsStats {
+            projects: Vec::new(),
+            totals: StatsTotals::default(),
+            failed: Vec::new(),
+        };
+        for (_, id, stats) in results {
+            let stats = match stats {
+                Ok(stats) => stats,
+                Err(e) => {
+                    warn!(project_id = %id, error = %e, "Failed to get project stats");
+                    all.failed.push(id);
+                    continue;
+                }
+            };
+            let totals = &mut all.totals;
+            totals.project_count += 1;
+            totals.scene_count += u64::from(stats.scene_count);
+            totals.rendered_duration_secs += stats.rendered_duration_secs;
+            totals.dialogue_words += stats.dialogue_words;
+            add_counts(&mut totals.api_calls, &stats.api_calls);
+            add_counts(&mut totals.estimated_tokens, &stats.estimated_tokens);
+            totals.media_bytes += stats.media_bytes.unwrap_or(0);
+            all.projects.push(stats);
+        }
+        Ok(all)
+    })
+    .await
+}
@@ -0,0 +1,360 @@
+//! SRT and WebVTT export from TTS-aligned dialogue timing.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use crate::api;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::server::ServerState;
+use crate::tempfiles;
+
+/// Default line width in columns; CJK characters take two.
+const DEFAULT_WRAP_WIDTH: usize = 42;
+/// Zero-length cues are stretched to this so players show them at all.
+const MIN_CUE_MS: u64 = 500;
+const UTF8_BOM: &str = "\u{feff}";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimedLine {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+/// Display width of `c`: 2 for wide (mostly CJK) characters, 1 otherwise.
+fn char_width(c: char) -> usize {
+    match c {
+        '\u{1100}'..='\u{115f}'
+        | '\u{2e80}'..='\u{a4cf}'
+        | '\u{ac00}'..='\u{d7a3}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{fe30}'..='\u{fe4f}'
+        | '\u{ff00}'..='\u{ff60}'
+        | '\u{ffe0}'..='\u{ffe6}'
+        | '\u{20000}'..='\u{3fffd}' => 2,
+        _ => 1,
+    }
+}
+
+fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Greedy wrap to `width` columns. Latin text breaks at spaces; wide
+/// characters can break anywhere, and so can words too long for a line.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    // Words, with each wide character its own word.
+    let mut words: Vec<String> = Vec::new();
+    for chunk in text.split_whitespace() {
+        let mut word = String::new();
+        for c in chunk.chars() {
+            if char_width(c) == 2 {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                words.push(c.to_string());
+            } else {
+                word.push(c);
+            }
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+        // An empty word marks the whitespace between chunks.
+        words.push(String::new());
+    }
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut spaced = false;
+    for word in words {
+        if word.is_empty() {
+            spaced = true;
+            continue;
+        }
+        // Spaces from the source are kept; wide characters split out of
+        // one chunk get none.
+        let sep = if spaced && !line.is_empty() { " " } else { "" };
+        spaced = false;
+        if str_width(&line) + sep.len() + str_width(&word) <= width {
+            line.push_str(sep);
+            line.push_str(&word);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            if !line.is_empty() && str_width(&line) + char_width(c) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(c);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Sorts lines, stretches zero-length ones and merges any that overlap,
+/// wrapping each line's text to `width`.
+fn prepare(lines: &[TimedLine], width: usize) -> Vec<TimedLine> {
+    let mut lines: Vec<TimedLine> = lines
+        .iter()
+        .filter(|l| !l.text.trim().is_empty())
+        .cloned()
+        .collect();
+    lines.sort_by_key(|l| (l.start_ms, l.end_ms));
+
+    for i in 0..lines.len() {
+        if lines[i].end_ms <= lines[i].start_ms {
+            let start = lines[i].start_ms;
+            let next = lines.get(i + 1).map(|l| l.start_ms).filter(|&n| n > start);
+            lines[i].end_ms = (start + MIN_CUE_MS).min(next.unwrap_or(u64::MAX));
+        }
+    }
+
+    let mut cues: Vec<TimedLine> = Vec::with_capacity(lines.len());
+    for line in lines {
+        let text = wrap(&line.text, width).join("\n");
+        match cues.last_mut() {
+            Some(prev) if line.start_ms < prev.end_ms || line.start_ms == prev.start_ms => {
+                prev.end_ms = prev.end_ms.max(line.end_ms);
+                prev.text.push('\n');
+                prev.text.push_str(&text);
+            }
+            _ => cues.push(TimedLine { text, ..line }),
+        }
+    }
+    cues
+}
+
+/// `HH:MM:SS,mmm` for SRT, `HH:MM:SS.mmm` for WebVTT.
+fn timestamp(ms: u64, format: SubtitleFormat) -> String {
+    let sep = match format {
+        SubtitleFormat::Srt => ',',
+        SubtitleFormat::Vtt => '.',
+    };
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        sep,
+        ms % 1000
+    )
+}
+
+/// Cue text can't contain the timing arrow, and WebVTT treats `&` and `<`
+/// as markup.
+fn escape(text: &str, format: SubtitleFormat) -> String {
+    match format {
+        SubtitleFormat::Srt => text.replace("-->", "->"),
+        SubtitleFormat::Vtt => text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;"),
+    }
+}
+
+/// Returns the file content and the number of cues in it.
+fn render(lines: &[TimedLine], format: SubtitleFormat, width: usize, bom: bool) -> (String, usize) {
+    let mut out = String::new();
+    match format {
+        SubtitleFormat::Srt if bom => out.push_str(UTF8_BOM),
+        SubtitleFormat::Srt => {}
+        SubtitleFormat::Vtt => out.push_str("WEBVTT\n\n"),
+    }
+    let cues = prepare(lines, width);
+    for (i, cue) in cues.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            i + 1,
+            timestamp(cue.start_ms, format),
+            timestamp(cue.end_ms, format),
+            escape(&cue.text, format)
+        ));
+    }
+    (out, cues.len())
+}
+
+/// Writes subtitles for `project_id`'s dialogue, or for `timed_lines` if
+/// given, to `dest_path`. `bom` only applies to SRT. Returns the number of
+/// cues written.
+#[tauri::command]
+pub async fn export_subtitles(
+    app: AppHandle,
+    project_id: Option<String>,
+    timed_lines: Option<Vec<TimedLine>>,
+    format: SubtitleFormat,
+    dest_path: String,
+    wrap_width: Option<usize>,
+    bom: Option<bool>,
+) -> Result<usize, ServerError> {
+    blocking::watched("export_subtitles", async move {
+        let lines = match (timed_lines, project_id) {
+            (Some(lines), _) => lines,
+            (None, Some(id)) => api::get_timed_lines(&app.state::<ServerState>(), &id).await?,
+            (None, None) => {
+                return Err(ServerError::Subtitles(
+                    "either a project or timed lines are required".into(),
+                ))
+            }
+        };
+        let width = wrap_width.unwrap_or(DEFAULT_WRAP_WIDTH);
+        if width < 2 {
+            return Err(ServerError::Subtitles(
+                "wrap width must be at least 2".into(),
+            ));
+        }
+
+        let (content, count) = render(&lines, format, width, bom.unwrap_or(false));
+        let dest = PathBuf::from(dest_path);
+        if let Some(dir) = dest.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp = tempfiles::create_temp_path(&app, "exports", "part")?;
+        if let Err(e) = tokio::fs::write(&tmp, &content).await {
+            tempfiles::discard(&app, &tmp).await;
+            return Err(e.into());
+        }
+        tempfiles::commit(&app, &tmp, &dest).await?;
+        Ok(count)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_timestamp(s: &str) -> u64 {
+        let (hms, ms) = s.split_once([',', '.']).unwrap();
+        let parts: Vec<u64> = hms.split(':').map(|p| p.parse().unwrap()).collect();
+        (parts[0] * 3600 + parts[1] * 60 + parts[2]) * 1000 + ms.parse::<u64>().unwrap()
+    }
+
+    /// Reads back what `render` wrote, checking indices as it goes.
+    fn parse(content: &str, format: SubtitleFormat) -> Vec<TimedLine> {
+        let body = match format {
+            SubtitleFormat::Srt => content.strip_prefix(UTF8_BOM).unwrap_or(content),
+            SubtitleFormat::Vtt => content.strip_prefix("WEBVTT\n\n").unwrap(),
+        };
+        body.split("\n\n")
+            .filter(|block| !block.is_empty())
+            .enumerate()
+            .map(|(i, block)| {
+                let mut lines = block.lines();
+                assert_eq!(lines.next().unwrap(), (i + 1).to_string());
+                let (start, end) = lines.next().unwrap().split_once(" --> ").unwrap();
+                let text = lines.collect::<Vec<_>>().join("\n");
+                let text = match format {
+                    SubtitleFormat::Srt => text,
+                    SubtitleFormat::Vtt => text
+                        .replace("&lt;", "<")
+                        .replace("&gt;", ">")
+                        .replace("&amp;", "&"),
+                };
+                TimedLine {
+                    start_ms: parse_timestamp(start),
+                    end_ms: parse_timestamp(end),
+                    text,
+                }
+            })
+            .collect()
+    }
+
+    fn line(start_ms: u64, end_ms: u64, text: &str) -> TimedLine {
+        TimedLine {
+            start_ms,
+            end_ms,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn timestamps_per_format() {
+        assert_eq!(timestamp(3_723_004, SubtitleFormat::Srt), "01:02:03,004");
+        assert_eq!(timestamp(3_723_004, SubtitleFormat::Vtt), "01:02:03.004");
+    }
+
+    #[test]
+    fn round_trip_merges_overlapping_cues() {
+        let lines = vec![
+            line(3000, 4000, "Third"),
+            line(0, 1500, "First"),
+            line(1000, 2000, "Overlaps <first> & more"),
+        ];
+        for format in [SubtitleFormat::Srt, SubtitleFormat::Vtt] {
+            let parsed = parse(&render(&lines, format, 42, false).0, format);
+            assert_eq!(
+                parsed,
+                vec![
+                    line(0, 2000, "First\nOverlaps <first> & more"),
+                    line(3000, 4000, "Third"),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip_stretches_zero_duration_cues() {
+        let lines = vec![
+            line(0, 0, "Blink"),
+            line(200, 1000, "Next"),
+            line(5000, 5000, "Alone"),
+            line(8000, 8000, "Same"),
+            line(8000, 9000, "Start"),
+        ];
+        let parsed = parse(
+            &render(&lines, SubtitleFormat::Srt, 42, true).0,
+            SubtitleFormat::Srt,
+        );
+        assert_eq!(
+            parsed,
+            vec![
+                line(0, 200, "Blink"),
+                line(200, 1000, "Next"),
+                line(5000, 5000 + MIN_CUE_MS, "Alone"),
+                line(8000, 9000, "Same\nStart"),
+            ]
+        );
+    }
+
+    #[test]
+    fn srt_bom_is_optional() {
+        let lines = [line(0, 1000, "Hi")];
+        assert!(render(&lines, SubtitleFormat::Srt, 42, true)
+            .0
+            .starts_with(UTF8_BOM));
+        assert!(render(&lines, SubtitleFormat::Srt, 42, false)
+            .0
+            .starts_with("1\n"));
+        assert!(render(&lines, SubtitleFormat::Vtt, 42, true)
+            .0
+            .starts_with("WEBVTT\n\n1\n"));
+    }
+
+    #[test]
+    fn wraps_by_display_width() {
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+        // Ten wide characters are twenty columns.
+        assert_eq!(
+            wrap("你好世界你好世界你好", 10),
+            ["你好世界你", "好世界你好"]
+        );
+        assert_eq!(wrap("他说 hello 世界", 7), ["他说", "hello", "世界"]);
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    }
+}
@@ -0,0 +1,288 @@
+//! OS appearance and accessibility preferences: reduce motion, light or
+//! dark, accent color and UI scale.
+//!
+//! Theme and scale changes arrive as window events. Reduce motion and the
+//! accent color have no window event, so they are watched with the
+//! platform's own notifications (AppKit on macOS, `WM_SETTINGCHANGE` on
+//! Windows) and otherwise re-read whenever the main window regains focus,
+//! which is when the user comes back from the system settings.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::window::Color;
+use tauri::{AppHandle, Emitter, Manager, Theme, Window, WindowEvent};
+use tracing::debug;
+
+/// Window backgrounds matching the splash and app styles, so a window
+/// isn't painted white before its page loads.
+const DARK_BACKGROUND: Color = Color(0x02, 0x02, 0x02, 0xff);
+const LIGHT_BACKGROUND: Color = Color(0xf5, 0xf5, 0xf7, 0xff);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemPreferences {
+    pub reduce_motion: bool,
+    pub color_scheme: ColorScheme,
+    /// `#rrggbb`, when the OS has an accent color.
+    pub accent_color: Option<String>,
+    pub scale_factor: f64,
+}
+
+/// The preferences last sent to the frontend.
+#[derive(Default)]
+pub struct SystemPrefsState {
+    last: Mutex<Option<SystemPreferences>>,
+}
+
+fn color_scheme(app: &AppHandle) -> ColorScheme {
+    let theme = app
+        .get_webview_window("main")
+        .and_then(|w| w.theme().ok())
+        .or_else(|| app.webview_windows().values().find_map(|w| w.theme().ok()));
+    match theme {
+        Some(Theme::Dark) => ColorScheme::Dark,
+        _ => ColorScheme::Light,
+    }
+}
+
+fn scale_factor(app: &AppHandle) -> f64 {
+    app.get_webview_window("main")
+        .and_then(|w| w.scale_factor().ok())
+        .or_else(|| app.primary_monitor().ok().flatten().map(|m| m.scale_factor()))
+        .unwrap_or(1.0)
+}
+
+fn read(app: &AppHandle) -> SystemPreferences {
+    SystemPreferences {
+        reduce_motion: platform_reduce_motion(),
+        color_scheme: color_scheme(app),
+        accent_color: platform_accent_color(),
+        scale_factor: scale_factor(app),
+    }
+}
+
+/// Background for a new window, so it opens in the OS color scheme.
+pub fn background(app: &AppHandle) -> Color {
+    match color_scheme(app) {
+        ColorScheme::Dark => DARK_BACKGROUND,
+        ColorScheme::Light => LIGHT_BACKGROUND,
+    }
+}
+
+/// Re-reads the preferences and emits `system-preferences-changed` if
+/// anything moved. Open windows follow a color scheme change.
+fn refresh(app: &AppHandle) {
+    let prefs = read(app);
+    let state = app.state::<SystemPrefsState>();
+    let mut last = state.last.lock().unwrap();
+    if last.as_ref() == Some(&prefs) {
+        return;
+    }
+    let scheme_changed = last.as_ref().map(|p| p.color_scheme) != Some(prefs.color_scheme);
+    *last = Some(prefs.clone());
+    drop(last);
+
+    debug!(?prefs, "System preferences changed");
+    if scheme_changed {
+        let color = background(app);
+        for window in app.webview_windows().values() {
+            let _ = window.set_background_color(Some(color));
+        }
+    }
+    let _ = app.emit("system-preferences-changed", prefs);
+}
+
+/// Records the starting preferences, paints the main window in the OS color
+/// scheme and starts the platform watchers.
+pub fn init(app: &AppHandle) {
+    *app.state::<SystemPrefsState>().last.lock().unwrap() = Some(read(app));
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.set_background_color(Some(background(app)));
+    }
+    watch_platform(app);
+}
+
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if window.label() != "main" {
+        return;
+    }
+    match event {
+        WindowEvent::ThemeChanged(_)
+        | WindowEvent::ScaleFactorChanged { .. }
+        | WindowEvent::Focused(true) => refresh(window.app_handle()),
+        _ => {}
+    }
+}
+
+#[tauri::command]
+pub fn get_system_preferences(app: AppHandle) -> SystemPreferences {
+    read(&app)
+}
+
+#[cfg(target_os = "macos")]
+fn platform_reduce_motion() -> bool {
+    objc2_app_kit::NSWorkspace::sharedWorkspace().accessibilityDisplayShouldReduceMotion()
+}
+
+#[cfg(target_os = "macos")]
+fn platform_accent_color() -> Option<String> {
+    use objc2_app_kit::{NSColor, NSColorSpace};
+
+    // The accent is a dynamic color; it has components only once resolved
+    // into a concrete color space.
+    let color = NSColor::controlAccentColor().colorUsingColorSpace(&NSColorSpace::sRGBColorSpace())?;
+    let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Some(format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.redComponent()),
+        channel(color.greenComponent()),
+        channel(color.blueComponent())
+    ))
+}
+
+/// Observes accessibility display options (reduce motion) on the workspace
+/// center and system color changes (accent) on the default center, for the
+/// app's lifetime.
+#[cfg(target_os = "macos")]
+fn watch_platform(app: &AppHandle) {
+    use block2::RcBlock;
+    use objc2_app_kit::{
+        NSSystemColorsDidChangeNotification, NSWorkspace,
+        NSWorkspaceAccessibilityDisplayOptionsDidChangeNotification,
+    };
+    use objc2_foundation::{NSNotification, NSNotificationCenter};
+    use std::ptr::NonNull;
+
+    let handle = app.clone();
+    let block = RcBlock::new(move |_: NonNull<NSNotification>| refresh(&handle));
+    // SAFETY: no sender object is given, and with no queue the block runs on
+    // the posting thread; `refresh` is safe to call from any thread.
+    unsafe {
+        NSWorkspace::sharedWorkspace()
+            .notificationCenter()
+            .addObserverForName_object_queue_usingBlock(
+                Some(NSWorkspaceAccessibilityDisplayOptionsDidChangeNotification),
+                None,
+                None,
+                &block,
+            );
+        NSNotificationCenter::defaultCenter().addObserverForName_object_queue_usingBlock(
+            Some(NSSystemColorsDidChangeNotification),
+            None,
+            None,
+            &block,
+        );
+    }
+}
+
+/// Off when "Animation effects" is turned off in the accessibility settings.
+#[cfg(windows)]
+fn platform_reduce_motion() -> bool {
+    use windows::core::BOOL;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+    };
+
+    let mut animate = BOOL(1);
+    // SAFETY: SPI_GETCLIENTAREAANIMATION writes a single BOOL.
+    let read = unsafe {
+        SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            Some(&mut animate as *mut BOOL as *mut _),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    };
+    read.is_ok() && !animate.as_bool()
+}
+
+#[cfg(windows)]
+fn platform_accent_color() -> Option<String> {
+    use windows::core::BOOL;
+    use windows::Win32::Graphics::Dwm::DwmGetColorizationColor;
+
+    let mut argb = 0u32;
+    let mut opaque = BOOL(0);
+    // SAFETY: both out-pointers are valid for the call.
+    unsafe { DwmGetColorizationColor(&mut argb, &mut opaque) }.ok()?;
+    Some(format!("#{:06x}", argb & 0x00ff_ffff))
+}
+
+/// Subclasses the main window for `WM_SETTINGCHANGE`, which Windows
+/// broadcasts when animation, accent or theme settings change.
+#[cfg(windows)]
+fn watch_platform(app: &AppHandle) {
+    use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::UI::Shell::{DefSubclassProc, SetWindowSubclass};
+    use windows::Win32::UI::WindowsAndMessaging::WM_SETTINGCHANGE;
+
+    unsafe extern "system" fn on_message(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        data: usize,
+    ) -> LRESULT {
+        if msg == WM_SETTINGCHANGE {
+            // SAFETY: `data` is the handle leaked in `watch_platform`.
+            refresh(unsafe { &*(data as *const AppHandle) });
+        }
+        unsafe { DefSubclassProc(hwnd, msg, wparam, lparam) }
+    }
+
+    let Some(hwnd) = app.get_webview_window("main").and_then(|w| w.hwnd().ok()) else {
+        return;
+    };
+    // Lives as long as the window's subclass, i.e. the app.
+    let data = Box::into_raw(Box::new(app.clone())) as usize;
+    // SAFETY: called on the main thread, which owns the window.
+    unsafe { SetWindowSubclass(HWND(hwnd.0), Some(on_message), 1, data) };
+}
+
+/// GNOME's setting; other desktops read as animations on.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn platform_reduce_motion() -> bool {
+    gsetting("enable-animations").as_deref() == Some("false")
+}
+
+/// GNOME 47's named accents, as libadwaita draws them.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn platform_accent_color() -> Option<String> {
+    let hex = match gsetting("accent-color")?.trim_matches('\'') {
+        "blue" => "#3584e4",
+        "teal" => "#2190a4",
+        "green" => "#3a944a",
+        "yellow" => "#c88800",
+        "orange" => "#ed5b00",
+        "red" => "#e62d42",
+        "pink" => "#d56199",
+        "purple" => "#9141ac",
+        "slate" => "#6f8396",
+        _ => return None,
+    };
+    Some(hex.to_owned())
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn gsetting(key: &str) -> Option<String> {
+    let output = std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", key])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Nothing to subscribe to; focus changes cover it.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn watch_platform(_app: &AppHandle) {}
@@ -0,0 +1,192 @@
+//! Taskbar (Windows) / dock (macOS) progress for long renders.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Manager};
+
+use crate::crash;
+use crate::server::{ServerState, ServerStatus};
+
+struct JobProgress {
+    done: f64,
+    total: f64,
+    /// `done`/`total` are seconds of output rather than a percentage.
+    timed: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    jobs: HashMap<String, JobProgress>,
+    /// Fraction set by the frontend; overrides job progress while set.
+    manual: Option<f64>,
+    /// The last job to end failed; shown until new progress arrives.
+    failed: bool,
+}
+
+impl Inner {
+    /// Aggregate progress across jobs as a fraction. Uses total output time
+    /// over total duration when every job reports timing, otherwise the mean
+    /// of per-job fractions.
+    fn fraction(&self) -> Option<f64> {
+        if let Some(manual) = self.manual {
+            return Some(manual);
+        }
+        if self.jobs.is_empty() {
+            return None;
+        }
+        let fraction = if self.jobs.values().all(|j| j.timed) {
+            let done: f64 = self.jobs.values().map(|j| j.done).sum();
+            let total: f64 = self.jobs.values().map(|j| j.total).sum();
+            done / total
+        } else {
+            let sum: f64 = self.jobs.values().map(|j| j.done / j.total).sum();
+            sum / self.jobs.len() as f64
+        };
+        Some(fraction.clamp(0.0, 1.0))
+    }
+}
+
+#[derive(Default)]
+pub struct TaskbarState {
+    inner: Mutex<Inner>,
+}
+
+fn apply(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let taskbar = app.state::<TaskbarState>();
+    let inner = taskbar.inner.lock().unwrap();
+    let fraction = inner.fraction();
+    let state = match (fraction, inner.failed) {
+        (Some(f), failed) => ProgressBarState {
+            status: Some(if failed {
+                ProgressBarStatus::Error
+            } else {
+                ProgressBarStatus::Normal
+            }),
+            progress: Some((f * 100.0).round() as u64),
+        },
+        (None, true) => ProgressBarState {
+            status: Some(ProgressBarStatus::Error),
+            progress: Some(100),
+        },
+        (None, false) => ProgressBarState {
+            status: Some(ProgressBarStatus::None),
+            progress: None,
+        },
+    };
+    let _ = window.set_progress_bar(state);
+}
+
+/// Clears job progress when the sidecar goes away, since no completion
+/// events will follow. Flags an error if it died mid-render.
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("taskbar", async move {
+        let mut rx = handle.state::<ServerState>().subscribe();
+        while rx.changed().await.is_ok() {
+            let status = *rx.borrow_and_update();
+            if !matches!(
+                status,
+                ServerStatus::Stopped | ServerStatus::Failed | ServerStatus::Crashed
+            ) {
+                continue;
+            }
+            {
+                let taskbar = handle.state::<TaskbarState>();
+                let mut inner = taskbar.inner.lock().unwrap();
+                if inner.jobs.is_empty() {
+                    continue;
+                }
+                inner.jobs.clear();
+                inner.failed = status == ServerStatus::Crashed;
+            }
+            apply(&handle);
+        }
+    });
+}
+
+/// Drops jobs that ended without an event, such as cancelled ones, so they
+/// don't hold the aggregate back.
+pub fn forget_jobs(app: &AppHandle, job_ids: &[String]) {
+    {
+        let taskbar = app.state::<TaskbarState>();
+        let mut inner = taskbar.inner.lock().unwrap();
+        let before = inner.jobs.len();
+        inner.jobs.retain(|id, _| !job_ids.contains(id));
+        if inner.jobs.len() == before {
+            return;
+        }
+    }
+    apply(app);
+}
+
+fn number(payload: &serde_json::Value, keys: &[&str]) -> Option<f64> {
+    keys.iter()
+        .find_map(|k| payload.get(*k))
+        .and_then(|v| v.as_f64())
+}
+
+/// Called by the event bridge for every relayed server event.
+pub fn on_server_event(app: &AppHandle, event: &str, payload: &serde_json::Value) {
+    let Some(job_id) = payload
+        .get("jobId")
+        .or_else(|| payload.get("id"))
+        .and_then(|v| v.as_str())
+    else {
+        return;
+    };
+
+    {
+        let taskbar = app.state::<TaskbarState>();
+        let mut inner = taskbar.inner.lock().unwrap();
+        match event {
+            "generation-progress" | "render-progress" => {
+                let timed = number(payload, &["outTime", "out_time"])
+                    .zip(number(payload, &["duration"]))
+                    .filter(|(_, total)| *total > 0.0);
+                let progress = match timed {
+                    Some((done, total)) => JobProgress {
+                        done,
+                        total,
+                        timed: true,
+                    },
+                    None => match number(payload, &["progress"]) {
+                        Some(percent) => JobProgress {
+                            done: percent,
+                            total: 100.0,
+                            timed: false,
+                        },
+                        None => return,
+                    },
+                };
+                inner.jobs.insert(job_id.to_owned(), progress);
+                inner.failed = false;
+            }
+            "generation-complete" | "job-complete" | "export-finished" => {
+                inner.jobs.remove(job_id);
+                inner.failed = false;
+            }
+            "generation-error" => {
+                inner.jobs.remove(job_id);
+                inner.failed = true;
+            }
+            _ => return,
+        }
+    }
+    apply(app);
+}
+
+/// Sets the taskbar progress from the frontend, as a fraction from 0 to 1.
+/// Pass `null` to hand control back to render progress.
+#[tauri::command]
+pub fn set_taskbar_progress(app: AppHandle, value: Option<f64>) {
+    {
+        let taskbar = app.state::<TaskbarState>();
+        let mut inner = taskbar.inner.lock().unwrap();
+        inner.manual = value.map(|v| v.clamp(0.0, 1.0));
+    }
+    apply(&app);
+}
@@ -0,0 +1,338 @@
+//! Opt-in anonymous usage events, buffered in `app_data_dir()/telemetry.jsonl`
+//! and sent in batches while the user has it enabled and we're online.
+//!
+//! Only allow-listed event names and properties are recorded, so nothing
+//! like a file path, project name or prompt can end up in the buffer even
+//! if the frontend passes it along.
+
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+use crate::crash;
+use crate::error::ServerError;
+use crate::network::NetworkState;
+use crate::server::ServerState;
+use crate::settings::SettingsState;
+use crate::time::now_ms;
+
+/// Where batches go. Builds without one keep events buffered locally.
+const ENDPOINT: Option<&str> = option_env!("AIYOU_TELEMETRY_URL");
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(15);
+/// Events sent per request.
+const BATCH_SIZE: usize = 100;
+/// A buffer that grows past this is dropped rather than sent.
+const MAX_BUFFER_BYTES: u64 = 512 * 1024;
+
+const EVENTS: &[&str] = &[
+    "app_opened",
+    "render_started",
+    "render_succeeded",
+    "render_failed",
+];
+
+#[derive(Clone, Copy)]
+enum PropKind {
+    Number,
+    Bool,
+    /// A short identifier such as an error kind; never free text.
+    Code,
+}
+
+const PROPS: &[(&str, PropKind)] = &[
+    ("durationMs", PropKind::Number),
+    ("sceneCount", PropKind::Number),
+    ("jobCount", PropKind::Number),
+    ("retried", PropKind::Bool),
+    ("errorKind", PropKind::Code),
+    ("resolution", PropKind::Code),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    pub queued: usize,
+    /// Unix time in milliseconds of the last batch the endpoint accepted.
+    pub last_flush: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Event<'a> {
+    name: &'a str,
+    props: serde_json::Map<String, serde_json::Value>,
+    /// Unix time in milliseconds.
+    at: u64,
+    app_version: &'a str,
+    os: &'static str,
+}
+
+pub struct TelemetryState {
+    path: Option<PathBuf>,
+    queued: AtomicUsize,
+    last_flush: Mutex<Option<u64>>,
+    /// Serializes buffer reads and writes.
+    busy: tokio::sync::Mutex<()>,
+}
+
+impl TelemetryState {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_data_dir()
+            .ok()
+            .map(|dir| dir.join("telemetry.jsonl"));
+        let queued = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .map(|s| s.lines().filter(|l| !l.is_empty()).count())
+            .unwrap_or(0);
+        Self {
+            path,
+            queued: AtomicUsize::new(queued),
+            last_flush: Mutex::new(None),
+            busy: tokio::sync::Mutex::new(()),
+        }
+    }
+}
+
+fn is_code(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 32
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Keeps the allow-listed properties whose values have the expected type.
+fn sanitize(
+    props: serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Map<String, serde_json::Value> {
+    props
+        .into_iter()
+        .filter(|(key, value)| {
+            let Some((_, kind)) = PROPS.iter().find(|(name, _)| name == key) else {
+                return false;
+            };
+            match kind {
+                PropKind::Number => value.is_number(),
+                PropKind::Bool => value.is_boolean(),
+                PropKind::Code => value.as_str().is_some_and(is_code),
+            }
+        })
+        .collect()
+}
+
+async fn remove_buffer(state: &TelemetryState) -> Result<(), ServerError> {
+    if let Some(path) = &state.path {
+        match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    state.queued.store(0, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Appends an event to the buffer if telemetry is enabled.
+pub async fn track(
+    app: &AppHandle,
+    name: &str,
+    props: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), ServerError> {
+    let Some(name) = EVENTS.iter().find(|e| **e == name) else {
+        return Err(ServerError::Telemetry(format!("unknown event: {}", name)));
+    };
+    if !app.state::<SettingsState>().get().telemetry {
+        return Ok(());
+    }
+    let state = app.state::<TelemetryState>();
+    let Some(path) = &state.path else {
+        return Ok(());
+    };
+    let version = app.package_info().version.to_string();
+    let event = Event {
+        name,
+        props: sanitize(props),
+        at: now_ms(),
+        app_version: &version,
+        os: std::env::consts::OS,
+    };
+    let mut line = serde_json::to_string(&event).map_err(|e| ServerError::Io(e.to_string()))?;
+    line.push('\n');
+
+    let _busy = state.busy.lock().await;
+    let size = tokio::fs::metadata(path).await.map(|m| m.len()).unwrap_or(0);
+    if size + line.len() as u64 > MAX_BUFFER_BYTES {
+        warn!(
+            limit_bytes = MAX_BUFFER_BYTES,
+            dropped = state.queued.load(Ordering::SeqCst),
+            "Telemetry buffer is full; dropping it"
+        );
+        remove_buffer(&state).await?;
+    }
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    tokio::io::AsyncWriteExt::write_all(&mut file, line.as_bytes()).await?;
+    state.queued.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Sends buffered events in batches, keeping whatever wasn't accepted.
+async fn flush(app: &AppHandle, endpoint: &str) -> Result<(), ServerError> {
+    let state = app.state::<TelemetryState>();
+    let Some(path) = &state.path else {
+        return Ok(());
+    };
+    let _busy = state.busy.lock().await;
+    let data = match tokio::fs::read_to_string(path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut events: Vec<serde_json::Value> = data
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect();
+
+    let client = app.state::<ServerState>().client();
+    let mut result = Ok(());
+    while !events.is_empty() {
+        let batch: Vec<_> = events.iter().take(BATCH_SIZE).collect();
+        let sent = client
+            .post(endpoint)
+            .timeout(FLUSH_TIMEOUT)
+            .json(&serde_json::json!({ "events": batch }))
+            .send()
+            .await;
+        match sent {
+            Ok(resp) if resp.status().is_success() => {
+                let count = batch.len();
+                events.drain(..count);
+                *state.last_flush.lock().unwrap() = Some(now_ms());
+            }
+            Ok(resp) => {
+                result = Err(ServerError::BadStatus(resp.status().as_u16()));
+                break;
+            }
+            Err(e) => {
+                result = Err(e.into());
+                break;
+            }
+        }
+    }
+
+    if events.is_empty() {
+        remove_buffer(&state).await?;
+    } else {
+        let mut rest = String::new();
+        for event in &events {
+            rest.push_str(&event.to_string());
+            rest.push('\n');
+        }
+        let tmp = path.with_extension("jsonl.tmp");
+        tokio::fs::write(&tmp, rest).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        state.queued.store(events.len(), Ordering::SeqCst);
+    }
+    result
+}
+
+/// Records the launch and starts the background flusher.
+pub fn init(app: &AppHandle) {
+    let handle = app.clone();
+    crash::spawn("telemetry", async move {
+        if let Err(e) = track(&handle, "app_opened", serde_json::Map::new()).await {
+            warn!(error = %e, "Failed to record telemetry event");
+        }
+        let Some(endpoint) = ENDPOINT else {
+            return;
+        };
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            let enabled = handle.state::<SettingsState>().get().telemetry;
+            if !enabled || !handle.state::<NetworkState>().is_online() {
+                continue;
+            }
+            if let Err(e) = flush(&handle, endpoint).await {
+                warn!(error = %e, "Failed to send telemetry");
+            }
+        }
+    });
+}
+
+/// Turning telemetry off also drops anything still buffered.
+#[tauri::command]
+pub async fn set_telemetry_enabled(app: AppHandle, enabled: bool) -> Result<(), ServerError> {
+    app.state::<SettingsState>()
+        .update(|s| s.telemetry = enabled)?;
+    if !enabled {
+        let state = app.state::<TelemetryState>();
+        let _busy = state.busy.lock().await;
+        remove_buffer(&state).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn track_event(
+    app: AppHandle,
+    name: String,
+    props: Option<serde_json::Map<String, serde_json::Value>>,
+) -> Result<(), ServerError> {
+    track(&app, &name, props.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub fn get_telemetry_status(app: AppHandle) -> TelemetryStatus {
+    let state = app.state::<TelemetryState>();
+    TelemetryStatus {
+        enabled: app.state::<SettingsState>().get().telemetry,
+        queued: state.queued.load(Ordering::SeqCst),
+        last_flush: *state.last_flush.lock().unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sanitize_keeps_only_allowed_props() {
+        let props = json!({
+            "durationMs": 1234,
+            "retried": true,
+            "errorKind": "timeout",
+            "resolution": "1080p",
+            "sceneCount": "12",
+            "projectName": "My drama",
+            "path": "/home/me/drama.aiyou",
+            "prompt": "a cat on a roof",
+        });
+        let serde_json::Value::Object(props) = props else {
+            unreachable!()
+        };
+        let kept = sanitize(props);
+        let mut keys: Vec<_> = kept.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["durationMs", "errorKind", "resolution", "retried"]);
+
+        let props = json!({ "errorKind": "failed to open /home/me/x.mp4" });
+        let serde_json::Value::Object(props) = props else {
+            unreachable!()
+        };
+        assert!(sanitize(props).is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+//! Temporary files for work that finishes with a rename: ffmpeg output,
+//! exports, backup zips, library copies and downloads.
+//!
+//! Paths are handed out under `app_data_dir()/tmp/<category>/` and listed
+//! in `tmp/manifest.json` until [`commit`] moves them into place or
+//! [`discard`] deletes them. Whatever a cancelled operation or a dead app
+//! leaves behind is swept on the next launch: everything still in the
+//! manifest, and anything else in the tree older than a day.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+use crate::error::ServerError;
+
+/// Unlisted files in the tmp tree younger than this may belong to another
+/// running instance and are left alone.
+const ORPHAN_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Default, Serialize, Deserialize)]
+struct Manifest {
+    paths: BTreeSet<PathBuf>,
+}
+
+pub struct TempFilesState {
+    /// `None` when the app data dir can't be resolved; temporary paths
+    /// can't be handed out then.
+    root: Option<PathBuf>,
+    manifest: Mutex<Manifest>,
+    /// Destinations [`commit`] is still copying into, across volumes.
+    committing: Mutex<BTreeSet<PathBuf>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TempUsage {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TempReport {
+    pub dir: String,
+    pub total: TempUsage,
+    pub by_category: BTreeMap<String, TempUsage>,
+}
+
+fn manifest_path(root: &Path) -> PathBuf {
+    root.join("manifest.json")
+}
+
+fn valid_category(category: &str) -> bool {
+    !category.is_empty()
+        && category
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Deletes listed files and old unlisted ones under `root`. Returns how
+/// many files and bytes were reclaimed.
+fn sweep(root: &Path, listed: &BTreeSet<PathBuf>, now: SystemTime) -> (usize, u64) {
+    let mut reclaimed = (0, 0);
+    let mut remove = |path: &Path, size: u64| {
+        if std::fs::remove_file(path).is_ok() {
+            reclaimed.0 += 1;
+            reclaimed.1 += size;
+        }
+    };
+    for path in listed.iter().filter(|p| p.starts_with(root)) {
+        if let Ok(meta) = std::fs::metadata(path) {
+            remove(path, meta.len());
+        }
+    }
+    let Ok(categories) = std::fs::read_dir(root) else {
+        return reclaimed;
+    };
+    for dir in categories.flatten().filter(|e| e.file_type().is_ok_and(|t| t.is_dir())) {
+        let Ok(entries) = std::fs::read_dir(dir.path()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let old = meta
+                .modified()
+                .ok()
+                .and_then(|m| now.duration_since(m).ok())
+                .is_some_and(|age| age > ORPHAN_AGE);
+            if meta.is_file() && old {
+                remove(&entry.path(), meta.len());
+            }
+        }
+    }
+    reclaimed
+}
+
+impl TempFilesState {
+    /// Sweeps what earlier sessions left behind and starts an empty
+    /// manifest. Runs before any command can hand out a path.
+    pub fn load(app: &AppHandle) -> Self {
+        let root = app.path().app_data_dir().ok().map(|d| d.join("tmp"));
+        if let Some(root) = &root {
+            let leftover: Manifest = std::fs::read(manifest_path(root))
+                .ok()
+                .and_then(|data| serde_json::from_slice(&data).ok())
+                .unwrap_or_default();
+            let (files, bytes) = sweep(root, &leftover.paths, SystemTime::now());
+            if files > 0 {
+                info!(files, bytes, "Reclaimed temporary files from earlier sessions");
+            }
+        }
+        let state = Self {
+            root,
+            manifest: Mutex::new(Manifest::default()),
+            committing: Mutex::new(BTreeSet::new()),
+        };
+        state.save(&state.manifest.lock().unwrap());
+        state
+    }
+
+    fn save(&self, manifest: &Manifest) {
+        let Some(root) = &self.root else {
+            return;
+        };
+        let written = serde_json::to_vec(manifest)
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                std::fs::create_dir_all(root)?;
+                let tmp = root.join("manifest.json.tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, manifest_path(root))
+            });
+        if let Err(e) = written {
+            warn!(error = %e, "Failed to save temporary file manifest");
+        }
+    }
+
+    fn register(&self, category: &str, name: String) -> Result<PathBuf, ServerError> {
+        if !valid_category(category) {
+            return Err(ServerError::Io(format!("invalid temp category: {}", category)));
+        }
+        let root = self
+            .root
+            .as_ref()
+            .ok_or_else(|| ServerError::Io("no app data dir for temporary files".into()))?;
+        let dir = root.join(category);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        let mut manifest = self.manifest.lock().unwrap();
+        if manifest.paths.insert(path.clone()) {
+            self.save(&manifest);
+        }
+        Ok(path)
+    }
+
+    fn unregister(&self, path: &Path) {
+        let mut manifest = self.manifest.lock().unwrap();
+        if manifest.paths.remove(path) {
+            self.save(&manifest);
+        }
+    }
+}
+
+/// A new, unique path under `tmp/<category>/` ending in `.<extension>`.
+/// Tools such as ffmpeg pick the format from the extension.
+pub fn create_temp_path(
+    app: &AppHandle,
+    category: &str,
+    extension: &str,
+) -> Result<PathBuf, ServerError> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let name = format!(
+        "{:x}-{:x}-{:x}.{}",
+        nanos,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        extension
+    );
+    app.state::<TempFilesState>().register(category, name)
+}
+
+/// The same path under `tmp/<category>/` every time for `key`, so an
+/// interrupted operation (a download, say) can pick up where it stopped.
+pub fn keyed_temp_path(
+    app: &AppHandle,
+    category: &str,
+    key: &str,
+    extension: &str,
+) -> Result<PathBuf, ServerError> {
+    let digest = Sha256::digest(key.as_bytes());
+    let hex: String = digest[..12].iter().map(|b| format!("{:02x}", b)).collect();
+    app.state::<TempFilesState>()
+        .register(category, format!("{}.{}", hex, extension))
+}
+
+/// Moves a finished temporary file to `dest` and drops it from the
+/// manifest. Falls back to copying when `dest` is on another volume.
+pub async fn commit(app: &AppHandle, tmp: &Path, dest: &Path) -> Result<(), ServerError> {
+    let state = app.state::<TempFilesState>();
+    if tokio::fs::rename(tmp, dest).await.is_err() {
+        state.committing.lock().unwrap().insert(dest.to_owned());
+        let copied = tokio::fs::copy(tmp, dest).await;
+        state.committing.lock().unwrap().remove(dest);
+        copied?;
+        let _ = tokio::fs::remove_file(tmp).await;
+    }
+    state.unregister(tmp);
+    Ok(())
+}
+
+/// Whether `path` is a temporary file or one [`commit`] is still writing,
+/// so watchers can leave it alone until it is finished.
+pub fn in_progress(app: &AppHandle, path: &Path) -> bool {
+    let state = app.state::<TempFilesState>();
+    state.root.as_ref().is_some_and(|root| path.starts_with(root))
+        || state.manifest.lock().unwrap().paths.contains(path)
+        || state.committing.lock().unwrap().contains(path)
+}
+
+/// Deletes a temporary file that won't be used and drops it from the
+/// manifest.
+pub async fn discard(app: &AppHandle, tmp: &Path) {
+    let _ = tokio::fs::remove_file(tmp).await;
+    app.state::<TempFilesState>().unregister(tmp);
+}
+
+/// What the tmp tree holds right now, by category.
+#[tauri::command]
+pub async fn get_temp_usage(app: AppHandle) -> Result<TempReport, ServerError> {
+    let root = app
+        .state::<TempFilesState>()
+        .root
+        .clone()
+        .ok_or_else(|| ServerError::Io("no app data dir for temporary files".into()))?;
+    let dir = root.to_string_lossy().into_owned();
+    let by_category = crate::blocking::run(move || {
+        let mut by_category: BTreeMap<String, TempUsage> = BTreeMap::new();
+        let Ok(categories) = std::fs::read_dir(&root) else {
+            return by_category;
+        };
+        for category in categories.flatten().filter(|e| e.file_type().is_ok_and(|t| t.is_dir())) {
+            let usage = by_category
+                .entry(category.file_name().to_string_lossy().into_owned())
+                .or_default();
+            for meta in std::fs::read_dir(category.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .filter(|m| m.is_file())
+            {
+                usage.files += 1;
+                usage.bytes += meta.len();
+            }
+        }
+        by_category
+    })
+    .await
+    .map_err(ServerError::Io)?;
+    let total = by_category.values().fold(TempUsage::default(), |total, c| TempUsage {
+        files: total.files + c.files,
+        bytes: total.bytes + c.bytes,
+    });
+    Ok(TempReport {
+        dir,
+        total,
+        by_category,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_takes_listed_and_old_files() {
+        let root = std::env::temp_dir().join(format!("aiyou-tmp-{}", std::process::id()));
+        let dir = root.join("frames");
+        std::fs::create_dir_all(&dir).unwrap();
+        let listed = dir.join("listed.png");
+        let fresh = dir.join("fresh.png");
+        std::fs::write(&listed, b"abc").unwrap();
+        std::fs::write(&fresh, b"abcd").unwrap();
+
+        let (files, bytes) = sweep(&root, &BTreeSet::from([listed.clone()]), SystemTime::now());
+        assert_eq!((files, bytes), (1, 3));
+        assert!(!listed.exists());
+        // Unlisted and recent: may be another instance's.
+        assert!(fresh.exists());
+
+        let tomorrow = SystemTime::now() + ORPHAN_AGE + Duration::from_secs(60);
+        assert_eq!(sweep(&root, &BTreeSet::new(), tomorrow), (1, 4));
+        assert!(!fresh.exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
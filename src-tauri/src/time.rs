@@ -0,0 +1,17 @@
+//! Wall-clock timestamps for persisted records and events.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Unix time in milliseconds; 0 if the clock is before 1970.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Unix time in seconds; 0 if the clock is before 1970.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,147 @@
+use tauri::image::Image;
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_opener::OpenerExt;
+use tracing::warn;
+
+use crate::crash;
+use crate::focus_main_window;
+use crate::i18n;
+use crate::server::{self, ServerState, ServerStatus};
+
+const ICON_SIZE: u32 = 32;
+
+/// Draws a filled status dot: green when ready, yellow while starting,
+/// loading or stopping, red otherwise.
+fn status_icon(status: ServerStatus) -> Image<'static> {
+    let [r, g, b] = match status {
+        ServerStatus::Ready => [0x22, 0xc5, 0x5e],
+        ServerStatus::Starting | ServerStatus::Stopping | ServerStatus::Loading => {
+            [0xea, 0xb3, 0x08]
+        }
+        _ => [0xef, 0x44, 0x44],
+    };
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 * 0.4;
+
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let (dx, dy) = (x as f32 - center, y as f32 - center);
+            let alpha = if dx * dx + dy * dy <= radius * radius { 0xff } else { 0 };
+            rgba.extend_from_slice(&[r, g, b, alpha]);
+        }
+    }
+    Image::new_owned(rgba, ICON_SIZE, ICON_SIZE)
+}
+
+fn tooltip(app: &AppHandle, status: ServerStatus) -> String {
+    let key = match status {
+        ServerStatus::Stopped => "tray.status.stopped",
+        ServerStatus::Starting => "tray.status.starting",
+        ServerStatus::Ready => "tray.status.ready",
+        ServerStatus::Failed => "tray.status.failed",
+        ServerStatus::Stopping => "tray.status.stopping",
+        ServerStatus::Crashed => "tray.status.crashed",
+        ServerStatus::Unreachable => "tray.status.unreachable",
+        ServerStatus::Loading => "tray.status.loading",
+        ServerStatus::Unhealthy => "tray.status.unhealthy",
+    };
+    i18n::t_with(app, "tray.tooltip", &[("status", &i18n::t(app, key))])
+}
+
+fn menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let item = |id: &str, key: &str| {
+        MenuItem::with_id(app, id, i18n::t(app, key), true, None::<&str>)
+    };
+    Menu::with_items(
+        app,
+        &[
+            &item("tray-restart-server", "tray.restartServer")?,
+            &item("tray-stop-server", "tray.stopServer")?,
+            &item("tray-open-logs", "tray.openLogs")?,
+        ],
+    )
+}
+
+fn on_menu_event(app: &AppHandle, id: &str) {
+    match id {
+        "tray-restart-server" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = server::restart(&app, server::RestartReason::Manual).await {
+                    warn!(error = %e, "Failed to restart server");
+                }
+            });
+        }
+        "tray-stop-server" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = server::stop(&app).await {
+                    warn!(error = %e, "Failed to stop server");
+                }
+            });
+        }
+        "tray-open-logs" => {
+            let opened = app
+                .path()
+                .app_log_dir()
+                .map_err(|e| e.to_string())
+                .and_then(|dir| {
+                    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                    app.opener()
+                        .open_path(dir.to_string_lossy(), None::<&str>)
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(e) = opened {
+                warn!(error = %e, "Failed to open logs");
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Creates the tray icon and keeps it in sync with the server status.
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let state = app.state::<ServerState>();
+    let status = state.status();
+    let tray = TrayIconBuilder::with_id("main")
+        .icon(status_icon(status))
+        .tooltip(tooltip(app, status))
+        .menu(&menu(app)?)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| on_menu_event(app, event.id().as_ref()))
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                focus_main_window(tray.app_handle());
+            }
+        })
+        .build(app)?;
+
+    let mut rx = state.subscribe();
+    crash::spawn("tray", async move {
+        while rx.changed().await.is_ok() {
+            let status = *rx.borrow_and_update();
+            let _ = tray.set_icon(Some(status_icon(status)));
+            let _ = tray.set_tooltip(Some(tooltip(tray.app_handle(), status)));
+        }
+    });
+
+    Ok(())
+}
+
+/// Rebuilds the tray menu and tooltip in the current language.
+pub fn relabel(app: &AppHandle) -> tauri::Result<()> {
+    let Some(tray) = app.tray_by_id("main") else {
+        return Ok(());
+    };
+    tray.set_menu(Some(menu(app)?))?;
+    let status = app.state::<ServerState>().status();
+    tray.set_tooltip(Some(tooltip(app, status)))
+}
@@ -0,0 +1,389 @@
+//! Reporting on and pruning the sidecar's TTS audio cache.
+//!
+//! Every regenerated line leaves its previous audio behind. The sidecar
+//! says where the cache is and which files current scenes still use; those
+//! are never pruned, and neither is anything of a project being rendered.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{info, warn};
+
+use crate::api;
+use crate::backups::BackupState;
+use crate::blocking;
+use crate::error::ServerError;
+use crate::server::ServerState;
+
+/// Files deleted between `tts-prune-progress` events.
+const PROGRESS_EVERY: usize = 50;
+
+/// Serializes prunes.
+#[derive(Default)]
+pub struct TtsCacheState {
+    busy: tokio::sync::Mutex<()>,
+}
+
+struct CachedAudio {
+    path: PathBuf,
+    /// Relative to the cache dir, with `/` separators.
+    key: String,
+    provider: String,
+    voice: String,
+    project: String,
+    line: String,
+    size: u64,
+    modified: SystemTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PruneStrategy {
+    /// Files no current scene uses.
+    Orphans,
+    /// Files last written more than `days` ago.
+    OlderThan { days: u32 },
+    /// All but the newest file of each line.
+    KeepNewestPerLine,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheUsage {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl CacheUsage {
+    fn add(&mut self, size: u64) {
+        self.files += 1;
+        self.bytes += size;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceUsage {
+    pub provider: String,
+    pub voice: String,
+    #[serde(flatten)]
+    pub usage: CacheUsage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectUsage {
+    pub project_id: String,
+    #[serde(flatten)]
+    pub usage: CacheUsage,
+    /// The part no current scene uses.
+    pub orphaned: CacheUsage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TtsCacheReport {
+    pub dir: String,
+    pub total: CacheUsage,
+    pub orphaned: CacheUsage,
+    /// Largest first.
+    pub by_voice: Vec<VoiceUsage>,
+    /// Largest first.
+    pub by_project: Vec<ProjectUsage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrunedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PruneResult {
+    pub dry_run: bool,
+    /// Deleted, or with `dry_run` the files that would be.
+    pub files: Vec<PrunedFile>,
+    pub freed_bytes: u64,
+    /// Files that matched but belong to a project being rendered.
+    pub skipped_rendering: usize,
+    /// Files that couldn't be deleted.
+    pub failed: usize,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PruneProgress {
+    done: usize,
+    total: usize,
+}
+
+fn walk(root: &Path, dir: &Path, found: &mut Vec<CachedAudio>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => walk(root, &entry.path(), found),
+            Ok(t) if t.is_file() => found.extend(cached_audio(root, &entry)),
+            // Symlinks aren't followed or deleted.
+            _ => {}
+        }
+    }
+}
+
+/// Files outside the `<provider>/<voice>/<project>/<line>/<file>` layout
+/// are left alone.
+fn cached_audio(root: &Path, entry: &std::fs::DirEntry) -> Option<CachedAudio> {
+    let path = entry.path();
+    let parts: Vec<&str> = path
+        .strip_prefix(root)
+        .ok()?
+        .iter()
+        .map(|c| c.to_str())
+        .collect::<Option<_>>()?;
+    let [provider, voice, project, line, _] = parts.as_slice() else {
+        return None;
+    };
+    let meta = entry.metadata().ok()?;
+    Some(CachedAudio {
+        key: parts.join("/"),
+        provider: (*provider).to_owned(),
+        voice: (*voice).to_owned(),
+        project: (*project).to_owned(),
+        line: (*line).to_owned(),
+        size: meta.len(),
+        modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        path,
+    })
+}
+
+/// The cache as the sidecar describes it, scanned on the blocking pool.
+async fn scan(app: &AppHandle) -> Result<(String, Vec<CachedAudio>, HashSet<String>), ServerError> {
+    let info = api::get_tts_cache(&app.state::<ServerState>()).await?;
+    let root = PathBuf::from(&info.dir);
+    let files = blocking::run(move || {
+        let mut found = Vec::new();
+        walk(&root, &root, &mut found);
+        found
+    })
+    .await
+    .map_err(ServerError::TtsCache)?;
+    Ok((info.dir, files, info.referenced.into_iter().collect()))
+}
+
+/// Indices of the files `strategy` would delete. Referenced files are
+/// never picked.
+fn select(
+    files: &[CachedAudio],
+    referenced: &HashSet<String>,
+    strategy: PruneStrategy,
+    now: SystemTime,
+) -> Vec<usize> {
+    let unused = |i: &usize| !referenced.contains(&files[*i].key);
+    match strategy {
+        PruneStrategy::Orphans => (0..files.len()).filter(unused).collect(),
+        PruneStrategy::OlderThan { days } => {
+            let cutoff = now
+                .checked_sub(Duration::from_secs(u64::from(days) * 86_400))
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (0..files.len())
+                .filter(unused)
+                .filter(|i| files[*i].modified < cutoff)
+                .collect()
+        }
+        PruneStrategy::KeepNewestPerLine => {
+            let mut newest: HashMap<(&str, &str), usize> = HashMap::new();
+            for (i, file) in files.iter().enumerate() {
+                let slot = newest.entry((file.project.as_str(), file.line.as_str())).or_insert(i);
+                if file.modified > files[*slot].modified {
+                    *slot = i;
+                }
+            }
+            let keep: HashSet<usize> = newest.into_values().collect();
+            (0..files.len())
+                .filter(|i| !keep.contains(i))
+                .filter(unused)
+                .collect()
+        }
+    }
+}
+
+/// Cached audio grouped by voice and by project, with how much of it no
+/// current scene uses.
+#[tauri::command]
+pub async fn get_tts_cache_report(app: AppHandle) -> Result<TtsCacheReport, ServerError> {
+    blocking::watched("get_tts_cache_report", async move {
+        let (dir, files, referenced) = scan(&app).await?;
+        let mut total = CacheUsage::default();
+        let mut orphaned = CacheUsage::default();
+        let mut by_voice: BTreeMap<(String, String), CacheUsage> = BTreeMap::new();
+        let mut by_project: BTreeMap<String, (CacheUsage, CacheUsage)> = BTreeMap::new();
+        for file in &files {
+            total.add(file.size);
+            by_voice
+                .entry((file.provider.clone(), file.voice.clone()))
+                .or_default()
+                .add(file.size);
+            let project = by_project.entry(file.project.clone()).or_default();
+            project.0.add(file.size);
+            if !referenced.contains(&file.key) {
+                orphaned.add(file.size);
+                project.1.add(file.size);
+            }
+        }
+
+        let mut by_voice: Vec<_> = by_voice
+            .into_iter()
+            .map(|((provider, voice), usage)| VoiceUsage {
+                provider,
+                voice,
+                usage,
+            })
+            .collect();
+        by_voice.sort_by_key(|v| std::cmp::Reverse(v.usage.bytes));
+        let mut by_project: Vec<_> = by_project
+            .into_iter()
+            .map(|(project_id, (usage, orphaned))| ProjectUsage {
+                project_id,
+                usage,
+                orphaned,
+            })
+            .collect();
+        by_project.sort_by_key(|p| std::cmp::Reverse(p.usage.bytes));
+        Ok(TtsCacheReport {
+            dir,
+            total,
+            orphaned,
+            by_voice,
+            by_project,
+        })
+    })
+    .await
+}
+
+/// Deletes cached audio picked by `strategy`, emitting
+/// `tts-prune-progress` as it goes. With `dry_run` nothing is deleted and
+/// the result lists what would have been.
+#[tauri::command]
+pub async fn prune_tts_cache(
+    app: AppHandle,
+    strategy: PruneStrategy,
+    dry_run: Option<bool>,
+) -> Result<PruneResult, ServerError> {
+    blocking::watched("prune_tts_cache", async move {
+        let dry_run = dry_run.unwrap_or(false);
+        let state = app.state::<TtsCacheState>();
+        let _busy = state.busy.lock().await;
+        let (_, files, referenced) = scan(&app).await?;
+
+        let backups = app.state::<BackupState>();
+        let mut skipped_rendering = 0;
+        let picked: Vec<PrunedFile> = select(&files, &referenced, strategy, SystemTime::now())
+            .into_iter()
+            .map(|i| &files[i])
+            .filter(|file| {
+                let rendering = backups.rendering(&file.project);
+                skipped_rendering += usize::from(rendering);
+                !rendering
+            })
+            .map(|file| PrunedFile {
+                path: file.path.to_string_lossy().into_owned(),
+                size: file.size,
+            })
+            .collect();
+
+        if dry_run {
+            let freed_bytes = picked.iter().map(|f| f.size).sum();
+            return Ok(PruneResult {
+                dry_run,
+                files: picked,
+                freed_bytes,
+                skipped_rendering,
+                failed: 0,
+            });
+        }
+
+        let handle = app.clone();
+        let (files, failed) = blocking::run(move || {
+            let total = picked.len();
+            let mut deleted = Vec::with_capacity(total);
+            let mut failed = 0;
+            for (done, file) in picked.into_iter().enumerate() {
+                match std::fs::remove_file(&file.path) {
+                    Ok(()) => deleted.push(file),
+                    Err(e) => {
+                        warn!(path = %file.path, error = %e, "Failed to delete cached audio");
+                        failed += 1;
+                    }
+                }
+                if (done + 1) % PROGRESS_EVERY == 0 || done + 1 == total {
+                    let _ = handle.emit(
+                        "tts-prune-progress",
+                        PruneProgress {
+                            done: done + 1,
+                            total,
+                        },
+                    );
+                }
+            }
+            (deleted, failed)
+        })
+        .await
+        .map_err(ServerError::TtsCache)?;
+
+        let freed_bytes = files.iter().map(|f| f.size).sum();
+        info!(?strategy, files = files.len(), freed_bytes, "Pruned TTS cache");
+        Ok(PruneResult {
+            dry_run,
+            files,
+            freed_bytes,
+            skipped_rendering,
+            failed,
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(project: &str, line: &str, file: &str, age_days: u64, now: SystemTime) -> CachedAudio {
+        CachedAudio {
+            path: PathBuf::from(file),
+            key: format!("p/v/{}/{}/{}", project, line, file),
+            provider: "p".into(),
+            voice: "v".into(),
+            project: project.into(),
+            line: line.into(),
+            size: 1,
+            modified: now - Duration::from_secs(age_days * 86_400),
+        }
+    }
+
+    #[test]
+    fn referenced_files_are_never_selected() {
+        let now = SystemTime::now();
+        let files = vec![
+            audio("a", "1", "old.wav", 30, now),
+            audio("a", "1", "mid.wav", 10, now),
+            audio("a", "1", "new.wav", 1, now),
+            audio("b", "1", "only.wav", 40, now),
+        ];
+        // The scene still points at the middle take.
+        let referenced = HashSet::from(["p/v/a/1/mid.wav".to_owned()]);
+
+        let orphans = select(&files, &referenced, PruneStrategy::Orphans, now);
+        assert_eq!(orphans, vec![0, 2, 3]);
+
+        let old = select(&files, &referenced, PruneStrategy::OlderThan { days: 7 }, now);
+        assert_eq!(old, vec![0, 3]);
+
+        let superseded = select(&files, &referenced, PruneStrategy::KeepNewestPerLine, now);
+        assert_eq!(superseded, vec![0]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Checks for and installs app updates. Installing always stops the sidecar
+//! first so an old `aiyou-server` never keeps running against the new app,
+//! and freezes it so nothing starts it again while it is being replaced.
+//!
+//! The signing key in `tauri.conf.json` is left empty in the repo; release
+//! builds supply it through `TAURI_CONFIG`. Without one the updater plugin
+//! isn't registered, nothing is checked automatically and the commands
+//! fail with `updatesNotConfigured`.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use tracing::{info, warn};
+
+use crate::crash;
+use crate::error::ServerError;
+use crate::power::PowerState;
+use crate::server::{self, RestartReason, ServerState};
+use crate::settings::SettingsState;
+use crate::time::now_secs;
+use crate::ws;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often a long-running app re-evaluates the daily check.
+const DAILY_POLL: Duration = Duration::from_secs(60 * 60);
+
+/// When to look for updates automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateCheck {
+    #[default]
+    OnLaunch,
+    Daily,
+    Never,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub available: bool,
+    pub version: Option<String>,
+    pub notes: Option<String>,
+    /// Download size in bytes, if the server reports it.
+    pub size: Option<u64>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateProgress {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+/// The update found by the last check, installed by
+/// `download_and_install_update`.
+#[derive(Default)]
+pub struct UpdaterState {
+    pending: tokio::sync::Mutex<Option<Update>>,
+}
+
+/// Whether the config has a signing key and an endpoint to check. Every
+/// signature check fails without a key.
+pub fn configured(config: &tauri::Config) -> bool {
+    let Some(updater) = config.plugins.0.get("updater") else {
+        return false;
+    };
+    let pubkey = updater.get("pubkey").and_then(|k| k.as_str());
+    let endpoints = updater.get("endpoints").and_then(|e| e.as_array());
+    pubkey.is_some_and(|k| !k.trim().is_empty()) && endpoints.is_some_and(|e| !e.is_empty())
+}
+
+fn ensure_configured(app: &AppHandle) -> Result<(), ServerError> {
+    if configured(app.config()) {
+        Ok(())
+    } else {
+        Err(ServerError::UpdatesNotConfigured)
+    }
+}
+
+async fn check(app: &AppHandle) -> Result<UpdateInfo, ServerError> {
+    ensure_configured(app)?;
+    let update = app.updater()?.check().await?;
+    let _ = app
+        .state::<SettingsState>()
+        .update(|s| s.last_update_check = Some(now_secs()));
+
+    let info = match &update {
+        Some(update) => {
+            // The manifest doesn't carry the size; ask the download host.
+            let size = app
+                .state::<ServerState>()
+                .client()
+                .head(update.download_url.clone())
+                .send()
+                .await
+                .ok()
+                .and_then(|resp| resp.content_length());
+            UpdateInfo {
+                available: true,
+                version: Some(update.version.clone()),
+                notes: update.body.clone(),
+                size,
+            }
+        }
+        None => UpdateInfo::default(),
+    };
+    *app.state::<UpdaterState>().pending.lock().await = update;
+    Ok(info)
+}
+
+async fn check_and_announce(app: &AppHandle) {
+    match check(app).await {
+        Ok(info) if info.available => {
+            info!(version = ?info.version, "Update available");
+            let _ = app.emit("update-available", info);
+        }
+        Ok(_) => {}
+        Err(e) => warn!(error = %e, "Update check failed"),
+    }
+}
+
+fn daily_check_due(app: &AppHandle) -> bool {
+    let last = app.state::<SettingsState>().get().last_update_check;
+    last.is_none_or(|last| now_secs().saturating_sub(last) >= DAY.as_secs())
+}
+
+/// Runs the automatic check configured in settings. Only announces updates;
+/// installing always waits for the user.
+pub fn init(app: &AppHandle) {
+    if !configured(app.config()) {
+        info!("Updates aren't configured; skipping update checks");
+        return;
+    }
+    let handle = app.clone();
+    crash::spawn("updater", async move {
+        match handle.state::<SettingsState>().get().update_check {
+            UpdateCheck::Never => {}
+            UpdateCheck::OnLaunch => check_and_announce(&handle).await,
+            UpdateCheck::Daily => loop {
+                if handle.state::<SettingsState>().get().update_check != UpdateCheck::Daily {
+                    return;
+                }
+                if daily_check_due(&handle) {
+                    check_and_announce(&handle).await;
+                }
+                tokio::time::sleep(DAILY_POLL).await;
+            },
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn check_for_update(app: AppHandle) -> Result<UpdateInfo, ServerError> {
+    check(&app).await
+}
+
+/// Downloads the pending update (checking first if needed), verifies its
+/// signature, shuts the sidecar down, installs and relaunches. Progress is
+/// emitted as `update-download-progress`.
+#[tauri::command]
+pub async fn download_and_install_update(app: AppHandle) -> Result<(), ServerError> {
+    ensure_configured(&app)?;
+    let state = app.state::<UpdaterState>();
+    let mut pending = state.pending.lock().await;
+    if pending.is_none() {
+        *pending = app.updater()?.check().await?;
+    }
+    let update = pending.as_ref().ok_or(ServerError::NoUpdate)?;
+
+    let mut downloaded = 0u64;
+    let mut last_emit = Instant::now();
+    let bytes = update
+        .download(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                if last_emit.elapsed() >= PROGRESS_INTERVAL {
+                    last_emit = Instant::now();
+                    let _ = app.emit("update-download-progress", UpdateProgress { downloaded, total });
+                }
+            },
+            || {},
+        )
+        .await?;
+
+    // Same shutdown path as quitting: the installer may replace the sidecar
+    // binary, and it must not outlive the old app. Frozen first, so nothing
+    // starts it again between the stop and the install.
+    server::freeze(&app);
+    ws::close(&app);
+    match server::stop(&app).await {
+        Ok(_) | Err(ServerError::NotRunning) => {}
+        Err(e) => warn!(error = %e, "Failed to stop server before update"),
+    }
+    app.state::<PowerState>().release_all();
+
+    if let Err(e) = update.install(bytes) {
+        // The old binary is still in place; bring the backend back up.
+        server::unfreeze(&app);
+        app.state::<ServerState>().note_restart(RestartReason::Update);
+        if let Err(start) = server::start(&app).await {
+            warn!(error = %start, "Failed to restart server after failed update");
+        }
+        return Err(e.into());
+    }
+    app.restart();
+}
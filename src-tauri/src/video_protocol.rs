@@ -0,0 +1,237 @@
+//! `aiyou-video://` URLs for playing generated videos in the webview.
+//!
+//! A data URL holds the whole file in memory, several times over for a
+//! long drama. This scheme answers the `<video>` element's range requests
+//! with just the bytes asked for, so it can start and seek anywhere. Only
+//! files inside the server's videos directory are served.
+
+use std::io::SeekFrom;
+use std::path::{Component, Path, PathBuf};
+use tauri::http::{header, Method, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, UriSchemeContext, UriSchemeResponder, Wry};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{debug, warn};
+
+use crate::error::ServerError;
+
+pub const SCHEME: &str = "aiyou-video";
+/// Most bytes sent for one request. The player asks for the rest as it
+/// goes.
+const MAX_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// Where the sidecar saves generated videos; passed to it as
+/// `AIYOU_VIDEOS_DIR`.
+pub fn videos_dir(app: &AppHandle) -> Result<PathBuf, ServerError> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| ServerError::Io(e.to_string()))?;
+    Ok(dir.join("videos"))
+}
+
+/// The inclusive byte range a `Range` header asks for in a file of `len`
+/// bytes. `Ok(None)` means the whole file; `Err` means the range can't be
+/// satisfied. Only the first of several ranges is honoured.
+fn parse_range(header: Option<&str>, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return Ok(None);
+    };
+    let first = spec.split(',').next().unwrap_or("").trim();
+    let (start, end) = first.split_once('-').ok_or(())?;
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) => (start, end.min(len.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        // `bytes=-500` is the last 500 bytes.
+        (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        _ => return Err(()),
+    };
+    if len == 0 || start > end || start >= len {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+fn decode_path(path: &str) -> Option<PathBuf> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    let relative = PathBuf::from(String::from_utf8(decoded).ok()?.trim_start_matches('/'));
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(relative)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext.to_ascii_lowercase().as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        _ => "application/octet-stream",
+    }
+}
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+    Response::builder().status(code).body(Vec::new()).unwrap_or_default()
+}
+
+async fn serve(app: &AppHandle, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let head = request.method() == Method::HEAD;
+    if !head && request.method() != Method::GET {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let Some(relative) = decode_path(request.uri().path()) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    let dir = videos_dir(app).and_then(|d| d.canonicalize().map_err(ServerError::from));
+    let Ok(dir) = dir else {
+        return status(StatusCode::NOT_FOUND);
+    };
+    // Symlinks out of the directory are caught here.
+    let path = match dir.join(&relative).canonicalize() {
+        Ok(path) if path.starts_with(&dir) => path,
+        Ok(_) => return status(StatusCode::FORBIDDEN),
+        Err(_) => return status(StatusCode::NOT_FOUND),
+    };
+    let Ok(mut file) = tokio::fs::File::open(&path).await else {
+        return status(StatusCode::NOT_FOUND);
+    };
+    let len = match file.metadata().await {
+        Ok(meta) if meta.is_file() => meta.len(),
+        _ => return status(StatusCode::NOT_FOUND),
+    };
+
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok());
+    let (start, end, partial) = match parse_range(range, len) {
+        Ok(Some((start, end))) => (start, end.min(start + MAX_CHUNK - 1), true),
+        // Small files whole; a large one only in ranges, starting with the
+        // first chunk.
+        Ok(None) if len <= MAX_CHUNK => (0, len.saturating_sub(1), false),
+        Ok(None) => (0, MAX_CHUNK - 1, true),
+        Err(()) => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Vec::new())
+                .unwrap_or_default();
+        }
+    };
+    let size = if len == 0 { 0 } else { end - start + 1 };
+
+    let mut body = Vec::new();
+    if !head && size > 0 {
+        body = vec![0; size as usize];
+        let read = async {
+            file.seek(SeekFrom::Start(start)).await?;
+            file.read_exact(&mut body).await
+        };
+        if let Err(e) = read.await {
+            warn!(path = %path.display(), error = %e, "Failed to read video");
+            return status(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type(&path))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, size);
+    response = if partial {
+        response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+    } else {
+        response.status(StatusCode::OK)
+    };
+    response.body(body).unwrap_or_default()
+}
+
+/// Handler registered for [`SCHEME`].
+pub fn handle(
+    ctx: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let response = serve(&app, &request).await;
+        debug!(uri = %request.uri(), status = %response.status(), "Served video");
+        responder.respond(response);
+    });
+}
+
+/// The URL a `<video>` element can play `path` from. The file must be in
+/// the videos directory.
+#[tauri::command]
+pub fn get_video_url(app: AppHandle, path: String) -> Result<String, ServerError> {
+    let dir = videos_dir(&app)?.canonicalize()?;
+    let path = PathBuf::from(path).canonicalize()?;
+    let relative = path.strip_prefix(&dir).map_err(|_| {
+        ServerError::PermissionDenied(format!("{} is outside the videos folder", path.display()))
+    })?;
+    let encoded: Vec<String> = relative
+        .components()
+        .map(|c| {
+            c.as_os_str()
+                .to_string_lossy()
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                        (b as char).to_string()
+                    }
+                    _ => format!("%{:02X}", b),
+                })
+                .collect()
+        })
+        .collect();
+    // Windows and Android webviews only load custom schemes as
+    // `http://<scheme>.localhost`.
+    let base = if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    };
+    Ok(format!("{}/{}", base, encoded.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_and_paths() {
+        assert_eq!(parse_range(None, 1000), Ok(None));
+        assert_eq!(parse_range(Some("bytes=0-"), 1000), Ok(Some((0, 999))));
+        assert_eq!(parse_range(Some("bytes=100-199, 300-"), 1000), Ok(Some((100, 199))));
+        assert_eq!(parse_range(Some("bytes=900-5000"), 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range(Some("bytes=-100"), 1000), Ok(Some((900, 999))));
+        assert_eq!(parse_range(Some("bytes=1000-"), 1000), Err(()));
+        assert_eq!(parse_range(Some("bytes=abc"), 1000), Err(()));
+
+        assert_eq!(
+            decode_path("/episode%201/final%E5%89%A7.mp4"),
+            Some(PathBuf::from("episode 1/final剧.mp4"))
+        );
+        assert_eq!(decode_path("/../settings.json"), None);
+        assert_eq!(decode_path("/a/%2E%2E/%2E%2E/b"), None);
+    }
+}
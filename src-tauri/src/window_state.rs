@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{
+    AppHandle, LogicalSize, Manager, PhysicalPosition, PhysicalSize, Runtime, WebviewWindow,
+    Window, WindowEvent,
+};
+use tracing::warn;
+
+use crate::error::ServerError;
+use crate::preview;
+use crate::settings::{Settings, SettingsState};
+
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+/// How much of the window (px) must overlap a monitor to count as on-screen.
+const MIN_VISIBLE: u32 = 100;
+const DEFAULT_SIZE: LogicalSize<f64> = LogicalSize::new(1400.0, 900.0);
+
+/// Window geometry in physical pixels. Position and size are the
+/// un-maximized bounds, so restoring a maximized window still has a sane
+/// size to return to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    pub fullscreen: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    fn overlap(&self, other: &Rect) -> (u32, u32) {
+        let x0 = self.x.max(other.x);
+        let y0 = self.y.max(other.y);
+        let x1 = (self.x + self.width as i32).min(other.x + other.width as i32);
+        let y1 = (self.y + self.height as i32).min(other.y + other.height as i32);
+        ((x1 - x0).max(0) as u32, (y1 - y0).max(0) as u32)
+    }
+
+    fn center(&self) -> (i64, i64) {
+        (
+            self.x as i64 + self.width as i64 / 2,
+            self.y as i64 + self.height as i64 / 2,
+        )
+    }
+}
+
+/// Debounce counters for geometry saves, per window label; only the latest
+/// scheduled save for a window runs.
+#[derive(Default)]
+pub struct WindowStateTracker {
+    generations: Mutex<HashMap<String, u64>>,
+}
+
+/// Where a window's geometry is saved: the main window has its own slot and
+/// all preview windows share another. Other windows aren't tracked.
+fn slot<'a>(settings: &'a mut Settings, label: &str) -> Option<&'a mut Option<WindowGeometry>> {
+    if label == "main" {
+        Some(&mut settings.window)
+    } else if preview::is_preview(label) {
+        Some(&mut settings.preview_window)
+    } else {
+        None
+    }
+}
+
+/// Keeps the saved bounds on a connected monitor. If no monitor shows enough
+/// of the window, moves (and if needed shrinks) it onto the nearest one.
+fn clamp_to_monitors(geom: WindowGeometry, monitors: &[Rect]) -> WindowGeometry {
+    let window = Rect {
+        x: geom.x,
+        y: geom.y,
+        width: geom.width,
+        height: geom.height,
+    };
+    let visible = monitors.iter().any(|m| {
+        let (w, h) = window.overlap(m);
+        w >= MIN_VISIBLE.min(window.width) && h >= MIN_VISIBLE.min(window.height)
+    });
+    if visible {
+        return geom;
+    }
+
+    let (cx, cy) = window.center();
+    let Some(nearest) = monitors.iter().min_by_key(|m| {
+        let (mx, my) = m.center();
+        (mx - cx).pow(2) + (my - cy).pow(2)
+    }) else {
+        return geom;
+    };
+
+    let width = geom.width.min(nearest.width);
+    let height = geom.height.min(nearest.height);
+    WindowGeometry {
+        x: geom
+            .x
+            .clamp(nearest.x, nearest.x + (nearest.width - width) as i32),
+        y: geom
+            .y
+            .clamp(nearest.y, nearest.y + (nearest.height - height) as i32),
+        width,
+        height,
+        ..geom
+    }
+}
+
+fn monitor_rects<R: Runtime>(window: &WebviewWindow<R>) -> Vec<Rect> {
+    window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|m| Rect {
+            x: m.position().x,
+            y: m.position().y,
+            width: m.size().width,
+            height: m.size().height,
+        })
+        .collect()
+}
+
+/// Applies saved geometry to a (still hidden) window.
+pub fn restore<R: Runtime>(window: &WebviewWindow<R>, geom: WindowGeometry) {
+    let geom = clamp_to_monitors(geom, &monitor_rects(window));
+    let _ = window.set_size(PhysicalSize::new(geom.width, geom.height));
+    let _ = window.set_position(PhysicalPosition::new(geom.x, geom.y));
+    if geom.maximized {
+        let _ = window.maximize();
+    }
+    if geom.fullscreen {
+        let _ = window.set_fullscreen(true);
+    }
+}
+
+/// Reads the window's current geometry. While maximized or fullscreen the
+/// previous normal bounds are kept.
+fn capture(window: &Window, previous: Option<WindowGeometry>) -> Option<WindowGeometry> {
+    let maximized = window.is_maximized().ok()?;
+    let fullscreen = window.is_fullscreen().ok()?;
+    if window.is_minimized().unwrap_or(false) {
+        return previous;
+    }
+
+    if maximized || fullscreen {
+        let prev = previous?;
+        return Some(WindowGeometry {
+            maximized,
+            fullscreen,
+            ..prev
+        });
+    }
+
+    let position = window.outer_position().ok()?;
+    let size = window.inner_size().ok()?;
+    Some(WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized,
+        fullscreen,
+    })
+}
+
+fn save(window: &Window) {
+    let settings = window.state::<SettingsState>();
+    let mut current = settings.get();
+    let Some(previous) = slot(&mut current, window.label()) else {
+        return;
+    };
+    let Some(geom) = capture(window, *previous) else {
+        return;
+    };
+    let saved = settings.update(|s| {
+        if let Some(slot) = slot(s, window.label()) {
+            *slot = Some(geom);
+        }
+    });
+    if let Err(e) = saved {
+        warn!(error = %e, "Failed to save window state");
+    }
+}
+
+fn schedule_save(window: &Window) {
+    let tracker = window.state::<WindowStateTracker>();
+    let generation = {
+        let mut generations = tracker.generations.lock().unwrap();
+        let generation = generations.entry(window.label().to_owned()).or_default();
+        *generation += 1;
+        *generation
+    };
+    let window = window.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SAVE_DEBOUNCE).await;
+        let tracker = window.state::<WindowStateTracker>();
+        let latest = tracker.generations.lock().unwrap().get(window.label()).copied();
+        if latest == Some(generation) {
+            save(&window);
+        }
+    });
+}
+
+/// Saved geometry for the window with this label, if it is tracked.
+pub fn saved(app: &AppHandle, label: &str) -> Option<WindowGeometry> {
+    let mut settings = app.state::<SettingsState>().get();
+    slot(&mut settings, label).and_then(|g| *g)
+}
+
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    let label = window.label();
+    if label != "main" && !preview::is_preview(label) {
+        return;
+    }
+    match event {
+        WindowEvent::Moved(_) | WindowEvent::Resized(_) => schedule_save(window),
+        WindowEvent::CloseRequested { .. } => save(window),
+        _ => {}
+    }
+}
+
+/// Forgets the saved geometry and puts the main window back at its default
+/// size in the center of the screen.
+#[tauri::command]
+pub fn reset_window_state(app: AppHandle) -> Result<(), ServerError> {
+    app.state::<SettingsState>().update(|s| s.window = None)?;
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.set_fullscreen(false);
+        let _ = window.unmaximize();
+        let _ = window.set_size(DEFAULT_SIZE);
+        let _ = window.center();
+    }
+    Ok(())
+}
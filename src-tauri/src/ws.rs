@@ -0,0 +1,190 @@
+//! WebSocket channel to the sidecar's `/ws` endpoint. Incoming messages are
+//! emitted as `ws-message` (`ws-binary` for binary frames); the frontend
+//! sends by emitting `ws-send`, whose payload goes out as a JSON text frame.
+
+use futures_util::{SinkExt, StreamExt};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, EventId, Listener, Manager};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::Connector;
+use tracing::{info, warn};
+
+use crate::error::ServerError;
+use crate::server::{self, ServerState, ServerStatus};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+struct Channel {
+    shutdown: oneshot::Sender<()>,
+    listener: EventId,
+}
+
+#[derive(Default)]
+pub struct WsState {
+    channel: Mutex<Option<Channel>>,
+}
+
+/// How a connected session ended.
+enum Ended {
+    /// The channel was closed from our side.
+    Shutdown,
+    /// The socket dropped or the server left the ready state.
+    Disconnected,
+}
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Connects with the same scheme and certificate settings as the HTTP
+/// client.
+async fn connect(app: &AppHandle) -> Result<Socket, tungstenite::Error> {
+    let state = app.state::<ServerState>();
+    let url = state.url("/ws").replacen("http", "ws", 1);
+    let connector = if state.config().danger_accept_invalid_certs {
+        let tls = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| tungstenite::Error::Tls(e.into()))?;
+        Some(Connector::NativeTls(tls))
+    } else {
+        None
+    };
+    let (socket, _) =
+        tokio_tungstenite::connect_async_tls_with_config(url, None, false, connector).await?;
+    Ok(socket)
+}
+
+/// Connects whenever the server becomes ready and relays until the channel
+/// is closed.
+async fn run(
+    app: AppHandle,
+    mut outgoing: mpsc::UnboundedReceiver<String>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let mut status = app.state::<ServerState>().subscribe();
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => return,
+            ready = status.wait_for(|s| *s == ServerStatus::Ready) => {
+                if ready.is_err() {
+                    return;
+                }
+            }
+        }
+
+        match connect(&app).await {
+            Ok(socket) => {
+                info!("Connected");
+                let _ = app.emit("ws-connected", ());
+                let ended = session(&app, socket, &mut outgoing, &mut shutdown, &mut status).await;
+                let _ = app.emit("ws-disconnected", ());
+                if let Ended::Shutdown = ended {
+                    return;
+                }
+                info!("Disconnected, reconnecting");
+            }
+            Err(tungstenite::Error::Http(resp)) if resp.status() == 404 => {
+                info!("Server has no WebSocket endpoint, channel disabled");
+                return;
+            }
+            Err(e) => warn!(error = %e, "Failed to connect"),
+        }
+
+        tokio::select! {
+            _ = &mut shutdown => return,
+            _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+        }
+    }
+}
+
+async fn session(
+    app: &AppHandle,
+    socket: Socket,
+    outgoing: &mut mpsc::UnboundedReceiver<String>,
+    shutdown: &mut oneshot::Receiver<()>,
+    status: &mut watch::Receiver<ServerStatus>,
+) -> Ended {
+    let (mut sink, mut stream) = socket.split();
+    loop {
+        tokio::select! {
+            _ = &mut *shutdown => {
+                let _ = sink.send(Message::Close(None)).await;
+                return Ended::Shutdown;
+            }
+            message = outgoing.recv() => {
+                let Some(text) = message else {
+                    let _ = sink.send(Message::Close(None)).await;
+                    return Ended::Shutdown;
+                };
+                if let Err(e) = sink.send(Message::text(text)).await {
+                    warn!(error = %e, "Failed to send");
+                    return Ended::Disconnected;
+                }
+            }
+            changed = status.changed() => {
+                if changed.is_err() || *status.borrow_and_update() != ServerStatus::Ready {
+                    return Ended::Disconnected;
+                }
+            }
+            incoming = stream.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    let payload = serde_json::from_str(&text)
+                        .unwrap_or_else(|_| serde_json::Value::String(text.to_string()));
+                    let _ = app.emit("ws-message", payload);
+                }
+                Some(Ok(Message::Binary(data))) => {
+                    let _ = app.emit("ws-binary", data.to_vec());
+                }
+                Some(Ok(Message::Close(_))) | None => return Ended::Disconnected,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    warn!(error = %e, "Connection failed");
+                    return Ended::Disconnected;
+                }
+            },
+        }
+    }
+}
+
+/// Closes the channel if it is open, sending a close frame to the server.
+pub fn close(app: &AppHandle) {
+    let state = app.state::<WsState>();
+    let channel = state.channel.lock().unwrap().take();
+    if let Some(channel) = channel {
+        app.unlisten(channel.listener);
+        let _ = channel.shutdown.send(());
+    }
+}
+
+/// Opens the WebSocket channel. It connects once the server is ready and
+/// reconnects each time it becomes ready again, until `stop_server` or
+/// `close_ws_channel`. Does nothing if the channel is already open.
+#[tauri::command]
+pub fn open_ws_channel(app: AppHandle) -> Result<(), ServerError> {
+    server::ensure_unfrozen(&app.state::<ServerState>())?;
+    let state = app.state::<WsState>();
+    let mut channel = state.channel.lock().unwrap();
+    if channel.is_some() {
+        return Ok(());
+    }
+
+    let (outgoing_tx, outgoing_rx) = mpsc::unbounded_channel();
+    let listener = app.listen("ws-send", move |event| {
+        let _ = outgoing_tx.send(event.payload().to_owned());
+    });
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    tauri::async_runtime::spawn(run(app.clone(), outgoing_rx, shutdown_rx));
+
+    *channel = Some(Channel {
+        shutdown: shutdown_tx,
+        listener,
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn close_ws_channel(app: AppHandle) {
+    close(&app);
+}